@@ -0,0 +1,22 @@
+//! Link SQLCipher in place of SQLite with the `sqlcipher` feature
+//!
+//! SQLite itself is linked by the `sqlite3` default feature, the installed
+//! library or else a bundled one. Both define the same symbols, so only one
+//! of them may be linked.
+
+use std::env;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if env::var_os("CARGO_FEATURE_SQLCIPHER").is_none() {
+        return;
+    }
+    if env::var_os("CARGO_FEATURE_SQLITE3").is_some() {
+        panic!("The `sqlcipher` feature replaces SQLite, build with `--no-default-features --features sqlcipher`");
+    }
+
+    // Library and search path as given by `sqlcipher.pc`
+    if let Err(e) = pkg_config::probe_library("sqlcipher") {
+        panic!("SQLCipher not found: {}", e);
+    }
+}
@@ -0,0 +1,387 @@
+//! Detection of the user activity on the system
+//!
+//! Used to suggest a check-in time when stamping was forgotten at the
+//! beginning of the work: the system being up, or the user back at it, since
+//! a while is a good hint that work started then.
+//!
+//! `wtime daemon` also checks out when the user is away, i.e. the session is
+//! locked or there is no input for `idle_after`, and back in on return. The
+//! input and lock are read from the Windows API, or with `xprintidle` and
+//! `loginctl` on Linux.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, SubsecRound, TimeZone, Utc};
+use std::fs;
+#[cfg(not(windows))]
+use std::process::Command;
+
+/// Compute the boot time from the content of `/proc/uptime`
+///
+/// # Arguments
+///
+/// * `uptime` - Content of `/proc/uptime`, i.e. the uptime and idle time in seconds
+/// * `now` - Date/Time the content was read
+pub fn boot_time(uptime: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let seconds = uptime.split_whitespace().next()?.parse::<f64>().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some((now - Duration::seconds(seconds as i64)).trunc_subsecs(0))
+}
+
+/// Read the end of the last idleness out of `loginctl show-session -p IdleHint -p IdleSinceHint`
+///
+/// logind moves `IdleSinceHint` whenever the session goes idle or comes back,
+/// e.g. at the lock screen after a resume and at the unlock. None if the
+/// session is idle, or the hint is not set.
+///
+/// # Arguments
+///
+/// * `show_session` - Output of `loginctl show-session`, as `Key=value` lines
+pub fn idle_end(show_session: &str) -> Option<DateTime<Utc>> {
+    let mut idle = None;
+    let mut since = None;
+    for line in show_session.lines() {
+        match line.split_once('=') {
+            Some(("IdleHint", value)) => idle = Some(value.trim() == "yes"),
+            Some(("IdleSinceHint", value)) => since = value.trim().parse::<i64>().ok(),
+            _ => (),
+        }
+    }
+    match (idle, since) {
+        (Some(false), Some(micros)) if micros > 0 => {
+            Utc.timestamp_opt(micros / 1_000_000, 0).single()
+        }
+        _ => None,
+    }
+}
+
+/// Start of the activity of the day
+///
+/// The uptime counts the time suspended, so the boot time may be days ago:
+/// the end of the last idleness is preferred, and the start of the day is the
+/// earliest.
+///
+/// # Arguments
+///
+/// * `boot` - Boot time of the system, see [boot_time]
+/// * `idle_end` - End of the last idleness of the user, see [idle_end]
+/// * `start_of_day` - Start of the current day
+pub fn activity_start(
+    boot: DateTime<Utc>,
+    idle_end: Option<DateTime<Utc>>,
+    start_of_day: DateTime<Utc>,
+) -> DateTime<Utc> {
+    idle_end.map_or(boot, |end| end.max(boot)).max(start_of_day)
+}
+
+/// Get the date/time the user is active since on the current day, see [activity_start]
+///
+/// # Arguments
+///
+/// * `start_of_day` - Start of the current day
+pub fn active_since(start_of_day: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow!("Activity detection is only supported on Linux"));
+    }
+
+    let uptime = fs::read_to_string("/proc/uptime").context("Reading system uptime")?;
+    let boot = boot_time(&uptime, Utc::now())
+        .ok_or_else(|| anyhow!("Invalid system uptime `{}`", uptime))?;
+    Ok(activity_start(boot, logind_idle_end(), start_of_day))
+}
+
+/// Get the end of the last idleness of the logind session, None if unknown
+#[cfg(not(windows))]
+fn logind_idle_end() -> Option<DateTime<Utc>> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".into());
+    let output = Command::new("loginctl")
+        .args([
+            "show-session",
+            &session,
+            "-p",
+            "IdleHint",
+            "-p",
+            "IdleSinceHint",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    idle_end(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(windows)]
+fn logind_idle_end() -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Change of presence of the user, as decided by an [IdleWatcher]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Presence {
+    /// Away since the given date/time, to check out then
+    Away(DateTime<Utc>),
+    /// Back after being away, to check in again
+    Back,
+}
+
+/// State of the user input, turning idleness and session locks into
+/// [Presence] changes
+#[derive(Debug, Clone)]
+pub struct IdleWatcher {
+    /// How long without input the user is away
+    idle_after: Duration,
+    /// Whether the user is away since the watcher checked out
+    away: bool,
+}
+
+impl IdleWatcher {
+    /// Create a watcher, as if the user was present
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_after` - How long without input the user is away
+    pub fn new(idle_after: Duration) -> Self {
+        Self {
+            idle_after,
+            away: false,
+        }
+    }
+
+    /// Take a new reading of the user input into account
+    ///
+    /// Only sessions checked in are closed when leaving, and only the ones
+    /// the watcher closed are resumed, so that stamps made by hand in between
+    /// are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle` - Time since the last input
+    /// * `locked` - Whether the session is locked
+    /// * `checked_in` - Whether the work-log is checked in
+    /// * `now` - Date/time of the reading
+    pub fn update(
+        &mut self,
+        idle: Duration,
+        locked: bool,
+        checked_in: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Presence> {
+        let away = locked || idle >= self.idle_after;
+        match (self.away, away) {
+            (false, true) if checked_in => {
+                self.away = true;
+                Some(Presence::Away((now - idle).trunc_subsecs(0)))
+            }
+            (true, false) => {
+                self.away = false;
+                (!checked_in).then_some(Presence::Back)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read the idle time out of the output of `xprintidle`, in milliseconds
+pub fn parse_xprintidle(output: &str) -> Option<Duration> {
+    let milliseconds = output.trim().parse::<i64>().ok()?;
+    (milliseconds >= 0).then(|| Duration::milliseconds(milliseconds))
+}
+
+/// Get the time since the last input of the user
+pub fn idle_time() -> Result<Duration> {
+    #[cfg(windows)]
+    return windows::idle_time().ok_or_else(|| anyhow!("Reading the last input time failed"));
+
+    #[cfg(not(windows))]
+    {
+        let output = Command::new("xprintidle")
+            .output()
+            .context("Running xprintidle")?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_xprintidle(&stdout)
+            .filter(|_| output.status.success())
+            .ok_or_else(|| anyhow!("Invalid xprintidle output `{}`", stdout.trim()))
+    }
+}
+
+/// Get whether the session of the user is locked
+///
+/// On Linux, the `LockedHint` of the logind session is read, the session
+/// being given by `XDG_SESSION_ID`.
+pub fn is_locked() -> Result<bool> {
+    #[cfg(windows)]
+    return Ok(windows::is_locked());
+
+    #[cfg(not(windows))]
+    {
+        let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".into());
+        let output = Command::new("loginctl")
+            .args(["show-session", &session, "-p", "LockedHint", "--value"])
+            .output()
+            .context("Running loginctl")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "loginctl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim() == "yes")
+    }
+}
+
+/// Bindings of the Windows API reading the user input and session lock
+#[cfg(windows)]
+mod windows {
+    use chrono::Duration;
+    use std::ffi::c_void;
+
+    /// `LASTINPUTINFO` structure of `GetLastInputInfo`
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+
+    /// Access right to switch to a desktop, which fails when locked
+    const DESKTOP_SWITCHDESKTOP: u32 = 0x0100;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+        fn OpenInputDesktop(flags: u32, inherit: i32, access: u32) -> *mut c_void;
+        fn SwitchDesktop(desktop: *mut c_void) -> i32;
+        fn CloseDesktop(desktop: *mut c_void) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    /// Time since the last input of the session, None if it cannot be read
+    pub fn idle_time() -> Option<Duration> {
+        let mut info = LastInputInfo {
+            size: std::mem::size_of::<LastInputInfo>() as u32,
+            time: 0,
+        };
+        // SAFETY: the structure is initialized with its size as required
+        if unsafe { GetLastInputInfo(&mut info) } == 0 {
+            return None;
+        }
+        // SAFETY: no arguments. Both counters wrap around after 49.7 days
+        let now = unsafe { GetTickCount() };
+        Some(Duration::milliseconds(now.wrapping_sub(info.time).into()))
+    }
+
+    /// Whether the session is locked, i.e. its input desktop is the one of
+    /// the lock screen
+    pub fn is_locked() -> bool {
+        // SAFETY: the desktop handle is checked, then closed once used
+        unsafe {
+            let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+            if desktop.is_null() {
+                return true;
+            }
+            let locked = SwitchDesktop(desktop) == 0;
+            CloseDesktop(desktop);
+            locked
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{activity_start, boot_time, idle_end, parse_xprintidle, IdleWatcher, Presence};
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn uptime() {
+        let now = DateTime::<Utc>::from_str("2020-01-01T09:00:00.5Z").unwrap();
+
+        assert_eq!(
+            boot_time("3600.25 7000.10\n", now),
+            Some(DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap())
+        );
+        assert_eq!(boot_time("", now), None);
+        assert_eq!(boot_time("soon", now), None);
+        assert_eq!(boot_time("-5 0", now), None);
+    }
+
+    #[test]
+    fn activity() {
+        let date = |s| DateTime::<Utc>::from_str(s).unwrap();
+        let midnight = date("2020-01-02T00:00:00Z");
+
+        // Booted yesterday, suspended overnight
+        let boot = date("2020-01-01T17:30:00Z");
+        assert_eq!(activity_start(boot, None, midnight), midnight);
+        let back = idle_end("IdleHint=no\nIdleSinceHint=1577951100000000\n");
+        assert_eq!(back, Some(date("2020-01-02T07:45:00Z")));
+        assert_eq!(activity_start(boot, back, midnight), back.unwrap());
+
+        // Booted this morning, never idle
+        let boot = date("2020-01-02T08:10:00Z");
+        assert_eq!(activity_start(boot, None, midnight), boot);
+        assert_eq!(
+            activity_start(boot, idle_end("IdleHint=no\nIdleSinceHint=0"), midnight),
+            boot
+        );
+
+        // Away right now
+        assert_eq!(
+            idle_end("IdleHint=yes\nIdleSinceHint=1577951100000000"),
+            None
+        );
+    }
+
+    #[test]
+    fn idle() {
+        let mut watcher = IdleWatcher::new(Duration::minutes(15));
+        let start = DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+
+        assert_eq!(
+            watcher.update(Duration::minutes(5), false, true, at(10)),
+            None
+        );
+        // Away since the last input
+        assert_eq!(
+            watcher.update(Duration::minutes(15), false, true, at(20)),
+            Some(Presence::Away(at(5)))
+        );
+        assert_eq!(
+            watcher.update(Duration::minutes(25), false, false, at(30)),
+            None
+        );
+        assert_eq!(
+            watcher.update(Duration::seconds(3), false, false, at(40)),
+            Some(Presence::Back)
+        );
+
+        // Locked right away
+        assert_eq!(
+            watcher.update(Duration::seconds(10), true, true, at(50)),
+            Some(Presence::Away(at(50) - Duration::seconds(10)))
+        );
+        // Already checked in by hand
+        assert_eq!(watcher.update(Duration::zero(), false, true, at(60)), None);
+
+        // Not checked in, nothing to close nor resume
+        assert_eq!(
+            watcher.update(Duration::hours(1), false, false, at(70)),
+            None
+        );
+        assert_eq!(watcher.update(Duration::zero(), false, false, at(80)), None);
+    }
+
+    #[test]
+    fn xprintidle() {
+        assert_eq!(
+            parse_xprintidle("90500\n"),
+            Some(Duration::milliseconds(90500))
+        );
+        assert_eq!(parse_xprintidle(""), None);
+        assert_eq!(parse_xprintidle("-1"), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Installation of `wtime daemon` as a background agent of the user
+//!
+//! `wtime daemon install` starts the daemon at every logon, with the same
+//! arguments: as a scheduled task on Windows, run in the session of the user
+//! so that its input and lock are seen, or as a systemd user service on
+//! Linux. `wtime daemon uninstall` removes it.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the scheduled task or systemd service
+pub const NAME: &str = "wtime-daemon";
+
+/// Quote an argument of a command line, if needed
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '"', '\'', '\\']) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Content of the systemd user service running the daemon
+///
+/// # Arguments
+///
+/// * `exe` - Path of the wtime executable
+/// * `args` - Arguments of the daemon, `daemon` included
+pub fn systemd_unit(exe: &Path, args: &[String]) -> String {
+    let command: Vec<String> = [exe.to_string_lossy().into_owned()]
+        .iter()
+        .chain(args)
+        .map(|arg| quote(arg))
+        .collect();
+    format!(
+        "[Unit]
+Description=wtime check-in and check-out daemon
+
+[Service]
+ExecStart={}
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+",
+        command.join(" ")
+    )
+}
+
+/// Arguments of `schtasks` creating the task running the daemon at logon
+///
+/// The task only runs while the user is logged on, in their session.
+///
+/// # Arguments
+///
+/// * `exe` - Path of the wtime executable
+/// * `args` - Arguments of the daemon, `daemon` included
+/// * `user` - Account the task runs as, e.g. `DOMAIN\me`
+pub fn schtasks_create(exe: &Path, args: &[String], user: &str) -> Vec<String> {
+    // Windows splits the arguments itself, only the spaces need quoting
+    let command: Vec<String> = [exe.to_string_lossy().into_owned()]
+        .iter()
+        .chain(args)
+        .map(|arg| match arg.contains(' ') {
+            true => format!("\"{}\"", arg),
+            false => arg.clone(),
+        })
+        .collect();
+    [
+        "/Create", "/F", "/TN", NAME, "/SC", "ONLOGON", "/RL", "LIMITED", "/IT", "/RU", user, "/TR",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .chain([command.join(" ")])
+    .collect()
+}
+
+/// Run a command, failing with its error output
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Running {}", program))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Path of the systemd user service file
+fn unit_path() -> Result<std::path::PathBuf> {
+    let dirs = directories::BaseDirs::new().ok_or_else(|| anyhow!("No home directory"))?;
+    Ok(dirs
+        .config_dir()
+        .join("systemd/user")
+        .join(format!("{}.service", NAME)))
+}
+
+/// Start the daemon at every logon, and now on Linux
+///
+/// # Arguments
+///
+/// * `args` - Arguments of the daemon, `daemon` included
+///
+/// # Return
+///
+/// Where the agent is installed, i.e. the task name or the service file
+pub fn install(args: &[String]) -> Result<String> {
+    let exe = std::env::current_exe().context("Locating the wtime executable")?;
+
+    if cfg!(windows) {
+        let user = std::env::var("USERNAME").context("Reading USERNAME")?;
+        let user = match std::env::var("USERDOMAIN") {
+            Ok(domain) => format!("{}\\{}", domain, user),
+            Err(_) => user,
+        };
+        let create = schtasks_create(&exe, args, &user);
+        run(
+            "schtasks",
+            &create.iter().map(String::as_str).collect::<Vec<_>>(),
+        )?;
+        return Ok(NAME.to_string());
+    }
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow!(
+            "Installing the daemon is only supported on Windows and Linux"
+        ));
+    }
+
+    let path = unit_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+    }
+    std::fs::write(&path, systemd_unit(&exe, args))
+        .with_context(|| format!("Writing {}", path.display()))?;
+    run("systemctl", &["--user", "daemon-reload"])?;
+    run(
+        "systemctl",
+        &["--user", "enable", "--now", &format!("{}.service", NAME)],
+    )?;
+    Ok(path.display().to_string())
+}
+
+/// Stop starting the daemon at logon, and stop it on Linux
+pub fn uninstall() -> Result<()> {
+    if cfg!(windows) {
+        return run("schtasks", &["/Delete", "/F", "/TN", NAME]);
+    }
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow!(
+            "Installing the daemon is only supported on Windows and Linux"
+        ));
+    }
+
+    let path = unit_path()?;
+    if !path.exists() {
+        return Err(anyhow!(
+            "The daemon is not installed, {} is missing",
+            path.display()
+        ));
+    }
+    run(
+        "systemctl",
+        &["--user", "disable", "--now", &format!("{}.service", NAME)],
+    )?;
+    std::fs::remove_file(&path).with_context(|| format!("Removing {}", path.display()))?;
+    run("systemctl", &["--user", "daemon-reload"])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{schtasks_create, systemd_unit};
+    use std::path::Path;
+
+    #[test]
+    fn systemd() {
+        let unit = systemd_unit(
+            Path::new("/home/me/.cargo/bin/wtime"),
+            &["daemon".into(), "--user".into(), "Jane Doe".into()],
+        );
+        assert!(unit.contains("\nExecStart=/home/me/.cargo/bin/wtime daemon --user \"Jane Doe\"\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+    }
+
+    #[test]
+    fn schtasks() {
+        let args = schtasks_create(
+            Path::new(r"C:\Program Files\wtime\wtime.exe"),
+            &["daemon".into(), "--interval".into(), "30".into()],
+            r"ACME\me",
+        );
+        assert_eq!(args[..4], ["/Create", "/F", "/TN", "wtime-daemon"]);
+        assert_eq!(
+            args[args.len() - 2..],
+            [
+                "/TR",
+                r#""C:\Program Files\wtime\wtime.exe" daemon --interval 30"#
+            ]
+        );
+        assert!(args.windows(2).any(|pair| pair == ["/RU", r"ACME\me"]));
+    }
+}
@@ -0,0 +1,199 @@
+//! Command aliases of the configuration
+//!
+//! The `[aliases]` table of the configuration names command lines, expanded
+//! before the arguments are parsed:
+//!
+//! ```toml
+//! [aliases]
+//! in = "checkin --project default"
+//! b = "report --bars"
+//! ```
+//!
+//! `wtime in --tag meeting` then runs
+//! `wtime checkin --project default --tag meeting`. An alias may use another
+//! one, but never hides a command of wtime.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use thiserror::Error;
+
+/// Invalid alias
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AliasError {
+    /// Quote opened but not closed
+    #[error("Alias `{0}`: unterminated quote")]
+    Quote(String),
+    /// Alias expanding to nothing
+    #[error("Alias `{0}` is empty")]
+    Empty(String),
+    /// Alias expanding, directly or not, to itself
+    #[error("Alias `{0}` expands to itself")]
+    Loop(String),
+}
+
+/// Split the command line of an alias into arguments
+///
+/// Arguments are separated by whitespace, unless quoted with `"` or `'`.
+///
+/// # Arguments
+///
+/// * `name` - Name of the alias, for the errors
+/// * `text` - Command line of the alias
+pub fn split(name: &str, text: &str) -> Result<Vec<String>, AliasError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                // Quotes may delimit an empty argument
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(AliasError::Quote(name.to_string()));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Position of the command in the arguments, after the global options
+fn command_position(args: &[OsString]) -> Option<usize> {
+    let mut position = 1;
+    while let Some(arg) = args.get(position) {
+        match arg.to_str() {
+            // Global option taking a value
+            Some("--user") => position += 2,
+            Some(arg) if arg.starts_with('-') => position += 1,
+            _ => return Some(position),
+        }
+    }
+    None
+}
+
+/// Expand the alias the command line starts with, if any
+///
+/// # Arguments
+///
+/// * `aliases` - Command line of each alias name
+/// * `commands` - Names of the commands of wtime, never expanded
+/// * `args` - Arguments of the program, starting with its name
+///
+/// # Return
+///
+/// The arguments with the alias replaced by its command line.
+pub fn expand(
+    aliases: &BTreeMap<String, String>,
+    commands: &[String],
+    mut args: Vec<OsString>,
+) -> Result<Vec<OsString>, AliasError> {
+    let Some(position) = command_position(&args) else {
+        return Ok(args);
+    };
+
+    let mut expanded = BTreeSet::new();
+    while let Some(name) = args[position].to_str().map(str::to_string) {
+        if commands.contains(&name) {
+            break;
+        }
+        let Some(text) = aliases.get(&name) else {
+            break;
+        };
+        if !expanded.insert(name.clone()) {
+            return Err(AliasError::Loop(name));
+        }
+        let words = split(&name, text)?;
+        if words.is_empty() {
+            return Err(AliasError::Empty(name));
+        }
+        args.splice(position..=position, words.into_iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expand, split, AliasError};
+    use std::collections::BTreeMap;
+    use std::ffi::OsString;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            split("in", "checkin  --project default").unwrap(),
+            ["checkin", "--project", "default"]
+        );
+        assert_eq!(
+            split(
+                "s",
+                r#"search --note-contains "code review" --project '' x"#
+            )
+            .unwrap(),
+            [
+                "search",
+                "--note-contains",
+                "code review",
+                "--project",
+                "",
+                "x"
+            ]
+        );
+        assert!(split("e", "").unwrap().is_empty());
+        assert_eq!(
+            split("q", "search \"review"),
+            Err(AliasError::Quote("q".to_string()))
+        );
+    }
+
+    #[test]
+    fn expand_aliases() {
+        let aliases = BTreeMap::from([
+            ("in".to_string(), "checkin --project default".to_string()),
+            ("w".to_string(), "report --week".to_string()),
+            ("ww".to_string(), "w --project acme".to_string()),
+            ("checkout".to_string(), "checkin".to_string()),
+            ("loop".to_string(), "again".to_string()),
+            ("again".to_string(), "loop".to_string()),
+            ("empty".to_string(), " ".to_string()),
+        ]);
+        let commands = ["checkin".to_string(), "checkout".to_string()];
+        let args = |line: &str| -> Vec<OsString> { line.split(' ').map(OsString::from).collect() };
+
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime -v --user bob in --tag x")).unwrap(),
+            args("wtime -v --user bob checkin --project default --tag x")
+        );
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime ww")).unwrap(),
+            args("wtime report --week --project acme")
+        );
+        // Commands are not hidden, nor arguments expanded
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime checkout")).unwrap(),
+            args("wtime checkout")
+        );
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime checkin in")).unwrap(),
+            args("wtime checkin in")
+        );
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime -v")).unwrap(),
+            args("wtime -v")
+        );
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime loop")),
+            Err(AliasError::Loop("loop".to_string()))
+        );
+        assert_eq!(
+            expand(&aliases, &commands, args("wtime empty")),
+            Err(AliasError::Empty("empty".to_string()))
+        );
+    }
+}
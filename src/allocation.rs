@@ -0,0 +1,150 @@
+//! Allocation of a session's time to issues or projects
+//!
+//! A finished session can be split across several targets (issues, tickets,
+//! projects), by percentage or by duration, e.g. `60% PROJ-1, 40% PROJ-7` or
+//! `1h30m PROJ-1, 45m PROJ-7`.
+
+use chrono::Duration;
+use thiserror::Error;
+
+/// Type for errors in a split specification
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseSplitError {
+    /// A part is not made of an amount followed by a target
+    #[error("Expected `<amount> <target>`, got `{0}`")]
+    InvalidPart(String),
+
+    /// Amount is neither a percentage nor a duration
+    #[error("Invalid amount `{0}` (expected e.g. `60%`, `45m` or `1h30m`)")]
+    InvalidAmount(String),
+
+    /// Parts sum to more than the session
+    #[error("Split exceeds the session duration")]
+    TooLarge,
+}
+
+/// Parse a duration written as hours and/or minutes, e.g. `1h30m`, `2h` or `45m`
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut has_unit = false;
+
+    for c in s.trim().chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' if !number.is_empty() => {
+                let value = number.parse::<i64>().ok()?;
+                total = total
+                    + if c == 'h' {
+                        Duration::hours(value)
+                    } else {
+                        Duration::minutes(value)
+                    };
+                number.clear();
+                has_unit = true;
+            }
+            _ => return None,
+        }
+    }
+
+    if has_unit && number.is_empty() {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+/// Parse a split specification into targets and fraction of the session
+///
+/// # Arguments
+///
+/// * `spec` - Comma separated parts, each an amount followed by a target
+/// * `session` - Duration of the split session
+///
+/// # Returns
+///
+/// Targets with the fraction of the session allocated to them. Fractions may
+/// sum to less than one, the remainder is left unallocated.
+pub fn parse_split(spec: &str, session: Duration) -> Result<Vec<(String, f64)>, ParseSplitError> {
+    let mut parts = Vec::new();
+
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (amount, target) = part
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| ParseSplitError::InvalidPart(part.into()))?;
+        let target = target.trim();
+
+        let fraction = if let Some(percent) = amount.strip_suffix('%') {
+            percent
+                .parse::<f64>()
+                .map_err(|_| ParseSplitError::InvalidAmount(amount.into()))?
+                / 100.0
+        } else {
+            let duration = parse_duration(amount)
+                .ok_or_else(|| ParseSplitError::InvalidAmount(amount.into()))?;
+            if session.is_zero() {
+                return Err(ParseSplitError::TooLarge);
+            }
+            duration.num_seconds() as f64 / session.num_seconds() as f64
+        };
+
+        if fraction < 0.0 {
+            return Err(ParseSplitError::InvalidAmount(amount.into()));
+        }
+        parts.push((target.to_string(), fraction));
+    }
+
+    // Tolerate rounding of the amounts
+    if parts.iter().map(|(_, f)| f).sum::<f64>() > 1.0 + 1e-6 {
+        return Err(ParseSplitError::TooLarge);
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_duration, parse_split, ParseSplitError};
+    use chrono::Duration;
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("45m"), Some(Duration::minutes(45)));
+        assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1x"), None);
+    }
+
+    #[test]
+    fn split() {
+        let session = Duration::hours(2);
+
+        assert_eq!(
+            parse_split("60% PROJ-1, 40% PROJ-7", session),
+            Ok(vec![("PROJ-1".into(), 0.6), ("PROJ-7".into(), 0.4)])
+        );
+        assert_eq!(
+            parse_split("1h30m PROJ-1,30m PROJ-7", session),
+            Ok(vec![("PROJ-1".into(), 0.75), ("PROJ-7".into(), 0.25)])
+        );
+        assert_eq!(
+            parse_split("30m PROJ-1", session),
+            Ok(vec![("PROJ-1".into(), 0.25)])
+        );
+
+        assert_eq!(
+            parse_split("60%", session),
+            Err(ParseSplitError::InvalidPart("60%".into()))
+        );
+        assert_eq!(
+            parse_split("lots PROJ-1", session),
+            Err(ParseSplitError::InvalidAmount("lots".into()))
+        );
+        assert_eq!(
+            parse_split("60% PROJ-1, 1h30m PROJ-7", session),
+            Err(ParseSplitError::TooLarge)
+        );
+    }
+}
@@ -1,130 +1,4587 @@
-use crate::db::InOut::{In, Out};
-use crate::db::{InOut, Stamp};
+use crate::config::{Config, DateRange};
+use crate::db::{
+    self, Adjustment, Allocation, Audit, Closeout, DbError, Estimate, InOut, Reference, Setting,
+    Source, Stamp, StampFilter, StampId, Tag,
+};
+use crate::storage::Storage;
+use crate::{
+    activity, agent, allocation, chart, clipboard, compliance, doctor, edit, export, format, hooks,
+    i18n, import, pick, project, push, render, report, serve, site, stats, statusbar, sync,
+    taskwarrior, timesheet, tr,
+};
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, Duration, Utc};
-use now::DateTimeNow;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::cell::{RefCell, RefMut};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
 use std::path::Path;
+use tracing::instrument;
+
+/// Minutes of system activity before a check-in for suggesting `--since-active`
+const ACTIVITY_HINT_MINUTES: i64 = 15;
+
+/// Format a day with its translated weekday, e.g. `Mon 2024-02-12`
+fn format_day(day: NaiveDate) -> String {
+    day.format(&i18n::localize("%a %Y-%m-%d", &day)).to_string()
+}
+
+/// Translated direction of a stamp, e.g. `In`
+fn direction(in_out: InOut) -> String {
+    match in_out {
+        InOut::In => tr!("direction-in"),
+        InOut::Out => tr!("direction-out"),
+    }
+}
+
+/// Share of a duration in a total, in percent
+fn percent(part: &Duration, total: &Duration) -> f64 {
+    if total.is_zero() {
+        0.0
+    } else {
+        part.num_seconds() as f64 * 100.0 / total.num_seconds() as f64
+    }
+}
+
+/// Write the worked time and share of the total of every issue, project or tag
+fn shares_table(
+    out: &mut impl Write,
+    totals: &BTreeMap<String, Duration>,
+    total: &Duration,
+) -> io::Result<()> {
+    let width = totals.keys().map(|k| k.len()).max().unwrap_or(0);
+    for (name, name_total) in totals {
+        writeln!(
+            out,
+            "{:<width$}  {:>8}  {:>3.0}%",
+            name,
+            format::duration(name_total),
+            percent(name_total, total)
+        )?;
+    }
+    Ok(())
+}
+
+/// Label of a project in a tree, its name under its parent indented by its
+/// depth, its whole path at the top, see [report::project_tree]
+fn tree_label(depth: usize, project: &str, parents: &report::Parents) -> String {
+    let name = parents
+        .get(project)
+        .filter(|_| depth > 0)
+        .and_then(|parent| project.strip_prefix(parent.as_str())?.strip_prefix('/'))
+        .unwrap_or(project);
+    format!("{}{}", "  ".repeat(depth), name)
+}
+
+/// Print estimates with their actual time and variance, under a heading
+/// naming their kind
+fn estimates_table(
+    out: &mut impl Write,
+    heading: &str,
+    actuals: &[report::Actual],
+) -> io::Result<()> {
+    let width = actuals
+        .iter()
+        .map(|a| a.estimate.name.len())
+        .chain([heading.len()])
+        .max()
+        .unwrap_or(0);
+    writeln!(
+        out,
+        "{:<width$}  {:>8}  {:>8}  {:>8}",
+        heading,
+        tr!("estimate"),
+        tr!("actual"),
+        tr!("variance")
+    )?;
+    for actual in actuals {
+        writeln!(
+            out,
+            "{:<width$}  {:>8}  {:>8}  {:>8}",
+            actual.estimate.name,
+            format::duration(&actual.estimate.estimate),
+            format::duration(&actual.actual),
+            format::delta(&actual.variance())
+        )?;
+    }
+    Ok(())
+}
+
+/// (De)serialize a duration as a number of seconds
+mod seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(d.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        i64::deserialize(deserializer).map(Duration::seconds)
+    }
+}
+
+/// Worked time of the current day and week
+///
+/// Durations are serialized as seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resume {
+    /// Start of the current day
+    pub begin_of_day: DateTime<Utc>,
+    /// Worked time since `begin_of_day`
+    #[serde(with = "seconds")]
+    pub day_total: Duration,
+    /// Start of the current week
+    pub begin_of_week: DateTime<Utc>,
+    /// Worked time since `begin_of_week`
+    #[serde(with = "seconds")]
+    pub week_total: Duration,
+}
+
+/// Worked time of a period, see [App::summary]
+///
+/// Durations are serialized as seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkSummary {
+    /// Start of the period (included)
+    pub from: DateTime<Utc>,
+    /// End of the period (excluded)
+    pub to: DateTime<Utc>,
+    /// Time of the sessions started in the period
+    #[serde(with = "seconds")]
+    pub stamped: Duration,
+    /// Sum of the adjustments applying to the period
+    #[serde(with = "seconds")]
+    pub adjusted: Duration,
+}
+
+impl WorkSummary {
+    /// Worked time, stamped and adjusted
+    pub fn total(&self) -> Duration {
+        self.stamped + self.adjusted
+    }
+}
+
+/// Outcome of a check-out, see [App::checkout]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckoutSummary {
+    /// The check-out stamp
+    pub checkout: Stamp,
+    /// The session finished by the check-out, None without check-in
+    pub session: Option<report::Session>,
+    /// Allocations of the session, if split
+    pub allocations: Vec<Allocation>,
+    /// Worked time of the day and week, the session included
+    pub resume: Resume,
+    /// Whether the day or the week exceeds its target by more than the
+    /// `overtime_threshold`
+    #[serde(default)]
+    pub overtime: bool,
+}
+
+/// State of the work-log, see [App::status]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    /// Whether the last stamp is a check-in
+    pub checked_in: bool,
+    /// The last stamp, None if there is no stamp at all
+    pub last_stamp: Option<Stamp>,
+    /// Worked time of the day and week
+    pub resume: Resume,
+    /// Working-time limits exceeded today, see [App::violations]
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// Error returned by the application commands
+pub type AppError = anyhow::Error;
 
 /// Datacontainer for application live variables
-pub struct App {
-    /// Database connection
-    conn: sqlite::Connection,
+pub struct App<S: Storage = sqlite::Connection> {
+    /// Work-log storage backend
+    storage: S,
+    /// User settings
+    config: Config,
+    /// Where the commands print their results, stdout by default
+    out: RefCell<Box<dyn Write>>,
+    /// Where the warnings and hints are printed, stderr by default
+    err: RefCell<Box<dyn Write>>,
+    /// Project the reported totals are restricted to, if any
+    project: Option<String>,
+    /// Accept new stamps dated before the last one, see [App::with_force]
+    force: bool,
+    /// Entry point the new stamps are made through, see [App::with_source]
+    source: Source,
+    /// Source the reported sessions are restricted to, if any
+    source_filter: Option<Source>,
+}
+
+/// Environment variable holding the database passphrase
+#[cfg(feature = "sqlcipher")]
+pub const PASSPHRASE_VAR: &str = "WTIME_PASSPHRASE";
+
+/// Get the database passphrase
+///
+/// It is read from [PASSPHRASE_VAR], or else looked up in the desktop keyring
+/// with `secret-tool lookup application wtime`. None if neither is set.
+#[cfg(feature = "sqlcipher")]
+fn passphrase() -> Option<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return Some(passphrase);
+    }
+
+    keyring_lookup(&[])
 }
 
-impl App {
+/// Look up a secret of wtime in the desktop keyring
+///
+/// The secret is found with `secret-tool lookup application wtime`, followed
+/// by the given attribute pairs. None if there is no such secret.
+fn keyring_lookup(attributes: &[(&str, &str)]) -> Option<String> {
+    let output = std::process::Command::new("secret-tool")
+        .args(["lookup", "application", env!("CARGO_PKG_NAME")])
+        .args(attributes.iter().flat_map(|(key, value)| [key, value]))
+        .output()
+        .ok()?;
+    let secret = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !secret.is_empty()).then_some(secret)
+}
+
+impl App<sqlite::Connection> {
+    /// Create an application working on a database file
+    ///
+    /// With the `sqlcipher` feature, the file is encrypted when a passphrase
+    /// is available in `WTIME_PASSPHRASE` or the keyring.
     pub fn new(db_file: &Path) -> Result<Self> {
-        let must_init = !db_file.exists();
-        let conn = sqlite::open(db_file)?;
+        #[cfg(feature = "sqlcipher")]
+        if let Some(passphrase) = passphrase() {
+            return Self::with_connection(
+                db::open_encrypted(db_file, &passphrase).context("Open encrypted database")?,
+            );
+        }
+
+        Self::with_connection(db::open(db_file).context("Open database")?)
+    }
+
+    /// Create an application working on a private in-memory database
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(db::open_memory().context("Open database")?)
+    }
 
-        if must_init {
-            Stamp::create(&conn).context("Crate Stamp table")?;
+    fn with_connection(conn: sqlite::Connection) -> Result<Self> {
+        db::init(&conn).context("Create tables")?;
+        Ok(Self::with_storage(conn))
+    }
+
+    /// Save a consistent snapshot of the work-log into a new file
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Backup file, must not exist
+    #[instrument(skip(self))]
+    pub fn do_backup(&self, file: &Path) -> Result<()> {
+        if file.exists() {
+            return Err(anyhow!("{:?} already exists, choose another file", file));
         }
-        Ok(Self { conn })
+
+        let target = sqlite::open(file).context("Create backup file")?;
+        db::backup(&self.storage, &target).context("Backing up database")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("backup-saved", file = format!("{:?}", file))
+        )?;
+        Ok(())
     }
 
-    /// Get total worked time since given date `from`.
-    fn get_total_from(&self, from: &DateTime<Utc>) -> Duration {
-        let mut total = Duration::zero();
-        let mut possible_last: Option<Stamp> = None;
+    /// Move the stamps dated before `before` to the archive table
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - First day that stays in the current work-log
+    #[instrument(skip(self))]
+    pub fn do_archive(&self, before: &DateTime<Utc>) -> Result<()> {
+        let count = Stamp::archive(&self.storage, before).context("Archiving stamps")?;
 
-        // Get first stamp after given date, it there is none, return Zero duration
-        let first = if let Ok(s) = Stamp::get_after(&self.conn, from) {
-            s
-        } else {
-            return Duration::zero();
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "stamps-archived",
+                count = count,
+                before = before.format("%Y-%m-%d").to_string()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Delete the stamps dated before `before`, for good
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - First day that stays in the work-log
+    #[instrument(skip(self))]
+    pub fn do_purge(&self, before: &DateTime<Utc>) -> Result<()> {
+        let count = Stamp::delete_range(&self.storage, &DateTime::<Utc>::MIN_UTC, before)
+            .context("Purging stamps")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "stamps-purged",
+                count = count,
+                before = before.format("%Y-%m-%d").to_string()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Delete a stamp, with the allocations, tags and worklogs of its session
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Stamp to delete
+    #[instrument(skip(self))]
+    pub fn do_delete(&self, id: StampId) -> Result<()> {
+        let stamp = Stamp::get(&self.storage, id).context("Reading stamp")?;
+        db::transaction(&self.storage, |tx| {
+            stamp.delete(tx)?;
+            if stamp.in_out == InOut::In {
+                Stamp::delete_session_data(tx, stamp.id)?;
+            }
+            Ok::<_, DbError>(())
+        })
+        .context("Deleting stamp")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "stamp-deleted",
+                id = stamp.id.0,
+                date = stamp.date.format("%Y-%m-%d %H:%M").to_string()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Edit the stamps of a range as text, see [edit]
+    ///
+    /// The changes are checked, then applied in a single transaction and the
+    /// stamps renumbered in date order.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Stamps to edit
+    /// * `editor` - Let the user edit the text, e.g. [edit::open_editor]
+    #[instrument(skip(self, editor))]
+    pub fn do_edit(
+        &self,
+        range: edit::Range,
+        editor: impl FnOnce(&str) -> Result<String>,
+    ) -> Result<()> {
+        let (from, to) = range.window(&self.config, &Utc::now());
+        let filter = StampFilter {
+            from: Some(from),
+            to: Some(to),
+            ..StampFilter::default()
+        };
+        let stamps = Stamp::search(&self.storage, &filter).context("Reading stamps")?;
+        let text = edit::dump(&stamps, (&from, &to), &self.config.timezone);
+
+        let edited = editor(&text)?;
+        let lines = edit::parse(&edited, &self.config.timezone)?;
+        let neighbour = |stamp: Result<Stamp, DbError>| match stamp {
+            Ok(stamp) => Ok(Some(stamp.in_out)),
+            Err(DbError::NoSuchEntry) => Ok(None),
+            Err(e) => Err(e),
         };
+        let before = neighbour(Stamp::get_before(&self.storage, &from))?;
+        let after = neighbour(Stamp::get_after(&self.storage, &to))?;
+        let mut changes = edit::changes(&stamps, &lines, (&from, &to), before, after)?;
+        if changes.is_empty() {
+            writeln!(self.out(), "{}", tr!("nothing-changed"))?;
+            return Ok(());
+        }
 
-        // Iterate on all stamps from there and sum the total
-        for stamp in first.iter(&self.conn) {
-            if let Some(l) = possible_last {
-                if l.in_out == In && stamp.in_out == Out {
-                    total = total + (stamp.date - l.date);
+        db::transaction(&self.storage, |tx| {
+            for stamp in &changes.deleted {
+                stamp.delete(tx)?;
+                if stamp.in_out == InOut::In {
+                    Stamp::delete_session_data(tx, stamp.id)?;
                 }
             }
-            possible_last = Some(stamp);
+            for stamp in &mut changes.updated {
+                stamp.update(tx)?;
+            }
+            for stamp in &mut changes.inserted {
+                stamp.source = Some(self.source);
+            }
+            Stamp::insert_many(tx, &mut changes.inserted)?;
+            if !Stamp::is_chained(tx)? {
+                Stamp::renumber(tx)?;
+            }
+            Ok::<_, DbError>(())
+        })
+        .context("Applying the changes")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "stamps-edited",
+                inserted = changes.inserted.len(),
+                updated = changes.updated.len(),
+                deleted = changes.deleted.len()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Replace the work-log by the content of a backup file
+    ///
+    /// The backup is checked to be a sound wtime database first, and the
+    /// current work-log is saved before being replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Backup file to restore
+    /// * `save_to` - New file where the current work-log is saved
+    #[instrument(skip(self))]
+    pub fn do_restore(&self, file: &Path, save_to: &Path) -> Result<()> {
+        let source =
+            sqlite::Connection::open_with_flags(file, sqlite::OpenFlags::new().set_read_only())
+                .with_context(|| format!("Opening {:?}", file))?;
+
+        if let Some(problem) = db::integrity_check(&source)? {
+            return Err(anyhow!("{:?} is corrupted: {}", file, problem));
+        }
+        if !db::table_exists(&source, "Stamp")? {
+            return Err(anyhow!("{:?} is not a wtime database", file));
+        }
+        if db::schema_version(&source)? > db::SCHEMA_VERSION {
+            return Err(anyhow!(
+                "{:?} was made by a newer version of wtime, upgrade first",
+                file
+            ));
+        }
+
+        self.do_backup(save_to)
+            .context("Saving the current work-log")?;
+        db::backup(&source, &self.storage).context("Restoring database")?;
+        db::init(&self.storage).context("Upgrade restored database")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("backup-restored", file = format!("{:?}", file))
+        )?;
+        Ok(())
+    }
+
+    /// Write the stamps to a sync bundle, see [sync]
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Bundle file to create, printed if None
+    #[instrument(skip(self))]
+    pub fn do_sync_export(&self, file: Option<&Path>) -> Result<()> {
+        let bundle = sync::export(&self.storage, None).context("Reading stamps")?;
+
+        match file {
+            Some(file) => {
+                let output =
+                    std::fs::File::create(file).with_context(|| format!("Creating {:?}", file))?;
+                serde_json::to_writer_pretty(output, &bundle)?;
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!(
+                        "sync-exported",
+                        count = bundle.stamps.len(),
+                        file = format!("{:?}", file)
+                    )
+                )?;
+            }
+            None => {
+                serde_json::to_writer_pretty(&mut *self.out(), &bundle)?;
+                writeln!(self.out())?;
+            }
         }
+        Ok(())
+    }
+
+    /// Exchange the stamps updated since the last synchronization with a
+    /// wtime server, see [sync::pull] and [sync::push]
+    ///
+    /// The server is reached with the `api_token` of the configuration. The
+    /// cursors of the last exchange are kept per server URL in the settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Base URL of the server, e.g. `https://host:8080`
+    #[instrument(skip(self))]
+    pub fn do_sync_remote(&self, url: &str) -> Result<()> {
+        let token = self.config.api_token.as_deref().ok_or_else(|| {
+            anyhow!("Set `api_token` in the configuration file to sync with a server")
+        })?;
 
-        // Return total duration
-        total
+        self.set_command(&format!("sync --remote {}", url))?;
+        self.sync_with(
+            url,
+            |since| Ok(sync::pull(url, token, since)?),
+            |bundle| Ok(sync::push(url, token, bundle)?),
+        )
     }
 
-    fn print_resume(&self) {
-        // Print worked time
-        let now = Utc::now();
+    /// Exchange the stamps with a server reached by `pull` and `push`, see [App::do_sync_remote]
+    ///
+    /// The stamps just pulled are not pushed back, unless the local version
+    /// was kept by the merge.
+    fn sync_with(
+        &self,
+        url: &str,
+        pull: impl FnOnce(Option<&DateTime<Utc>>) -> Result<sync::Pull>,
+        push: impl FnOnce(&sync::Bundle) -> Result<sync::Merge>,
+    ) -> Result<()> {
+        let pull_key = format!("sync_pull:{}", url);
+        let push_key = format!("sync_push:{}", url);
+        let cursor = |key: &str| -> Result<Option<DateTime<Utc>>> {
+            Setting::get(&self.storage, key)?
+                .map(|value| DateTime::parse_from_rfc3339(&value).map(|d| d.with_timezone(&Utc)))
+                .transpose()
+                .with_context(|| format!("Invalid {} setting", key))
+        };
+
+        let pull = pull(cursor(&pull_key)?.as_ref()).context("Pulling stamps")?;
+        let pulled = sync::merge(&self.storage, &pull.bundle).context("Merging stamps")?;
+
+        // Stamps changed from now on are pushed next time
+        let push_from = Utc::now();
+        let mut bundle =
+            sync::export(&self.storage, cursor(&push_key)?.as_ref()).context("Reading stamps")?;
+        // Merged stamps are updated now, but the server already has them
+        bundle.stamps.retain(|stamp| {
+            !pull.bundle.stamps.iter().any(|remote| {
+                remote.uid == stamp.uid
+                    && remote.date == stamp.date
+                    && remote.in_out == stamp.in_out
+            })
+        });
+        let pushed = push(&bundle).context("Pushing stamps")?;
+
+        Setting::set(&self.storage, &pull_key, &pull.cursor.to_rfc3339())?;
+        Setting::set(&self.storage, &push_key, &push_from.to_rfc3339())?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "sync-pulled",
+                count = pull.bundle.stamps.len(),
+                added = pulled.added,
+                updated = pulled.updated
+            )
+        )?;
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "sync-pushed",
+                count = bundle.stamps.len(),
+                added = pushed.added,
+                updated = pushed.updated
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Check in or out as Taskwarrior tasks are started or stopped, see
+    /// [taskwarrior]
+    ///
+    /// Starting a task while checked in switches to it: the running session
+    /// is checked out, allocated to the project of the previous task. The
+    /// started task is kept in the settings, so that stopping another task
+    /// leaves the session running.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Standard input of the hook
+    #[instrument(skip(self, input))]
+    pub fn do_hook_taskwarrior(&self, input: &str) -> Result<()> {
+        const TASK_KEY: &str = "taskwarrior_task";
+        const PROJECT_KEY: &str = "taskwarrior_project";
+
+        let (change, task_line) = taskwarrior::change(input)?;
+        // Taskwarrior needs the task back, whatever happens next
+        writeln!(self.out(), "{}", task_line)?;
+
+        let checked_in = self.status()?.checked_in;
+        let check_out = || -> Result<()> {
+            let project = Setting::get(&self.storage, PROJECT_KEY)?.filter(|p| !p.is_empty());
+            let split = project.map(|project| format!("100% {}", project));
+            let summary = self.checkout(split.as_deref())?;
+            if let Some(session) = summary.session {
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!(
+                        "taskwarrior-checked-out",
+                        worked = format::duration(&session.duration())
+                    )
+                )?;
+            }
+            Ok(())
+        };
+
+        match change {
+            taskwarrior::Change::Started(task) => {
+                if checked_in {
+                    check_out()?;
+                }
+                let stamp = self.checkin(false, None)?;
+                Setting::set(&self.storage, TASK_KEY, &task.uuid)?;
+                Setting::set(
+                    &self.storage,
+                    PROJECT_KEY,
+                    task.project.as_deref().unwrap_or_default(),
+                )?;
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!(
+                        "taskwarrior-checked-in",
+                        time = stamp.date.format("%H:%M").to_string(),
+                        task = task.description.as_str()
+                    )
+                )?;
+            }
+            taskwarrior::Change::Stopped(task) => {
+                let current = Setting::get(&self.storage, TASK_KEY)?;
+                if checked_in && current.as_deref() == Some(task.uuid.as_str()) {
+                    check_out()?;
+                    Setting::set(&self.storage, TASK_KEY, "")?;
+                    Setting::set(&self.storage, PROJECT_KEY, "")?;
+                }
+            }
+            taskwarrior::Change::Other => (),
+        }
+        Ok(())
+    }
+
+    /// Merge the stamps of a sync bundle made on another device
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Bundle file, see [App::do_sync_export]
+    #[instrument(skip(self))]
+    pub fn do_sync_merge(&self, file: &Path) -> Result<()> {
+        let input = std::fs::read_to_string(file).with_context(|| format!("Reading {:?}", file))?;
+        let bundle: sync::Bundle = serde_json::from_str(&input).context("Invalid sync bundle")?;
+        let merge = sync::merge(&self.storage, &bundle).context("Merging stamps")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "sync-merged",
+                added = merge.added,
+                updated = merge.updated,
+                known = merge.known
+            )
+        )?;
+        if merge.renumbered > 0 {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("sync-renumbered", count = merge.renumbered)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Storage> App<S> {
+    /// Create an application on top of any storage backend
+    pub fn with_storage(storage: S) -> Self {
+        Self {
+            storage,
+            config: Config::default(),
+            out: RefCell::new(Box::new(io::stdout())),
+            err: RefCell::new(Box::new(io::stderr())),
+            project: None,
+            force: false,
+            source: Source::Manual,
+            source_filter: None,
+        }
+    }
+
+    /// Use given settings instead of the defaults
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Print the results of the commands to `out` instead of stdout
+    ///
+    /// Warnings and hints are still printed to stderr, see [App::with_errors].
+    pub fn with_output(mut self, out: impl Write + 'static) -> Self {
+        self.out = RefCell::new(Box::new(out));
+        self
+    }
+
+    /// Print the warnings and hints to `err` instead of stderr
+    pub fn with_errors(mut self, err: impl Write + 'static) -> Self {
+        self.err = RefCell::new(Box::new(err));
+        self
+    }
+
+    /// Restrict the reported totals to the time allocated to a project
+    ///
+    /// See [report::Report::for_project]. No restriction with None.
+    pub fn with_project(mut self, project: Option<String>) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Accept new stamps dated before the last stored one
+    ///
+    /// Such a stamp comes from a wrong system clock and is refused, unless
+    /// forced: it is then only warned about.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Record the new stamps as made through an entry point
+    ///
+    /// The stamps are [Source::Manual] by default.
+    pub fn with_source(mut self, source: Source) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Restrict the reported sessions to the ones stamped through a source
+    ///
+    /// See [report::retain_source]. No restriction with None.
+    pub fn with_source_filter(mut self, source: Option<Source>) -> Self {
+        self.source_filter = source;
+        self
+    }
+
+    /// Check that a new stamp is not dated before the last stored one
+    ///
+    /// # Arguments
+    ///
+    /// * `stamp` - The new stamp
+    /// * `last` - The last stored stamp, if any
+    fn check_clock(&self, stamp: &Stamp, last: Option<&Stamp>) -> Result<()> {
+        let last = match last {
+            Some(last) if last.date > stamp.date => last,
+            _ => return Ok(()),
+        };
 
-        let begin_of_day = now.beginning_of_day();
-        let day_total = self.get_total_from(&begin_of_day);
-        println!(
-            "You worked {} hours, {} minutes and {} seconds today (since {})",
-            day_total.num_hours(),
-            day_total.num_minutes() % 60,
-            day_total.num_seconds() % 60,
-            begin_of_day
+        let (new, last) = (
+            stamp.date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            last.date.format("%Y-%m-%d %H:%M:%S").to_string(),
         );
+        if !self.force {
+            return Err(anyhow!(
+                "The new stamp at {} is dated before the last one at {}, check the system clock (or use --force)",
+                new,
+                last
+            ));
+        }
+        self.warn(tr!("warning-clock-backwards", new = new, last = last));
+        Ok(())
+    }
+
+    /// Warn when the system clock is off the time of the NTP server
+    ///
+    /// Nothing is checked without `ntp_server` in the configuration, nor
+    /// without the `ntp` feature.
+    fn check_ntp(&self) {
+        #[cfg(feature = "ntp")]
+        if let Some(server) = &self.config.ntp_server {
+            match crate::ntp::clock_offset(server, std::time::Duration::from_secs(2)) {
+                Ok(offset) if offset > self.config.max_clock_skew => self.warn(tr!(
+                    "warning-clock-ahead",
+                    seconds = offset.num_seconds(),
+                    server = server.as_str()
+                )),
+                Ok(offset) if -offset > self.config.max_clock_skew => self.warn(tr!(
+                    "warning-clock-behind",
+                    seconds = -offset.num_seconds(),
+                    server = server.as_str()
+                )),
+                Ok(_) => (),
+                Err(e) => self.warn(tr!("clock-error", error = format!("{:#}", e))),
+            }
+        }
+    }
+
+    /// Get the writer the commands print their results to
+    fn out(&self) -> RefMut<'_, Box<dyn Write>> {
+        self.out.borrow_mut()
+    }
+
+    /// Print a warning or a hint, see [App::with_errors]
+    ///
+    /// A failing writer is ignored, the warning not being the result of the
+    /// command.
+    fn warn(&self, message: impl std::fmt::Display) {
+        let _ = writeln!(self.err.borrow_mut(), "{}", message);
+    }
+
+    /// Name of the user the stamps are of
+    ///
+    /// The default user for backends without users.
+    fn user_name(&self) -> Result<String> {
+        match self.storage.current_user() {
+            Ok(user) => Ok(user.name),
+            Err(db::DbError::Unsupported(_)) => Ok(db::DEFAULT_USER.to_string()),
+            Err(e) => Err(e).context("Reading user"),
+        }
+    }
+
+    /// Run the configured hook of an event, if any
+    ///
+    /// A failing hook is only reported, the event having already happened.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - Event that happened
+    /// * `vars` - Environment variables of the event, see [hooks]
+    fn hook(&self, event: hooks::Event, vars: &[(&str, String)]) {
+        let command = match event {
+            hooks::Event::CheckIn => &self.config.on_checkin,
+            hooks::Event::CheckOut => &self.config.on_checkout,
+            hooks::Event::Error => &self.config.on_error,
+        };
+        if let Some(command) = command {
+            if let Err(e) = hooks::run(command, event, vars) {
+                self.warn(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Record `command` in the audit log with the following modifications
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Command line being run, e.g. `checkout --split "PROJ-1"`
+    pub fn set_command(&self, command: &str) -> Result<()> {
+        self.storage
+            .set_audit_command(Some(command))
+            .context("Set audited command")
+    }
+
+    /// Work on the stamps of another person than the default user
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the user, created on first use
+    pub fn select_user(&self, name: &str) -> Result<()> {
+        self.storage
+            .select_user(name)
+            .with_context(|| format!("Selecting user {}", name))?;
+        Ok(())
+    }
+
+    /// Get total worked time since given date `from`.
+    fn get_total_from(&self, from: &DateTime<Utc>) -> Result<Duration> {
+        let stamped = self
+            .storage
+            .stamp_total_from(from)
+            .context("Computing total worked time")?;
+        let adjusted = self
+            .storage
+            .adjustment_total_from(from)
+            .context("Computing total adjustments")?;
+        Ok(stamped + adjusted)
+    }
+
+    /// Get the worked time of a period
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Period, sessions are counted in the period they start
+    #[instrument(skip(self))]
+    pub fn summary(&self, range: Range<DateTime<Utc>>) -> Result<WorkSummary> {
+        let stamped = |from| {
+            self.storage
+                .stamp_total_from(from)
+                .context("Computing total worked time")
+        };
+        let adjusted = |from| {
+            self.storage
+                .adjustment_total_from(from)
+                .context("Computing total adjustments")
+        };
+
+        Ok(WorkSummary {
+            stamped: stamped(&range.start)? - stamped(&range.end)?,
+            adjusted: adjusted(&range.start)? - adjusted(&range.end)?,
+            from: range.start,
+            to: range.end,
+        })
+    }
+
+    /// Get the current day, in the configured timezone
+    pub fn today(&self) -> NaiveDate {
+        self.config.day_of(&Utc::now())
+    }
+
+    /// Get a date/time in the configured timezone, for display
+    pub fn local(&self, date: &DateTime<Utc>) -> DateTime<Tz> {
+        date.with_timezone(&self.config.timezone)
+    }
+
+    /// Start of a day, see [Config::start_of_day]
+    pub fn start_of_day(&self, day: NaiveDate) -> DateTime<Utc> {
+        self.config.start_of_day(day)
+    }
+
+    /// Months of the reports, see [Config::month]
+    pub fn month(&self) -> report::Period {
+        self.config.month()
+    }
+
+    /// Get a date window of the `[ranges]` of the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the range
+    pub fn range(&self, name: &str) -> Result<&DateRange> {
+        self.config.ranges.get(name).ok_or_else(|| {
+            anyhow!(
+                "Unknown range `{}`, see the [ranges] of the configuration",
+                name
+            )
+        })
+    }
+
+    /// Get the worked time of the current day and week
+    pub fn resume(&self) -> Result<Resume> {
+        let now = Utc::now();
+        let begin_of_day = self.config.beginning_of_day(&now);
+        let begin_of_week = self.config.beginning_of_week(&now);
+
+        Ok(Resume {
+            begin_of_day,
+            day_total: self.get_total_from(&begin_of_day)?,
+            begin_of_week,
+            week_total: self.get_total_from(&begin_of_week)?,
+        })
+    }
+
+    fn print_resume(&self) -> Result<()> {
+        // Print worked time
+        let resume = self.resume()?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "worked-today",
+                worked = format::duration(&resume.day_total),
+                since = resume.begin_of_day.to_string()
+            )
+        )?;
 
         // Don't show week total on mondays
-        let begin_of_week = now.beginning_of_week();
-        let week_total = self.get_total_from(&begin_of_week);
-        if week_total != day_total {
-            println!(
-                "You worked {} hours, {} minutes and {} seconds this week (since {})",
-                week_total.num_hours(),
-                week_total.num_minutes() % 60,
-                week_total.num_seconds() % 60,
-                begin_of_week
-            );
+        if resume.week_total != resume.day_total {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!(
+                    "worked-week",
+                    worked = format::duration(&resume.week_total),
+                    since = resume.begin_of_week.to_string()
+                )
+            )?;
+        }
+
+        self.warn_violations()
+    }
+
+    /// Line of [App::do_watch]: the time of the session running, if any, and
+    /// the worked time of today
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Date/Time the times run until
+    pub fn watch_line(&self, now: &DateTime<Utc>) -> Result<String> {
+        let begin_of_day = self.config.beginning_of_day(now);
+        let today = self.get_total_from(&begin_of_day)?;
+        Ok(match self.storage.last_stamp()? {
+            Some(last) if last.in_out == InOut::In => {
+                // Sessions count on the day they start, as in the totals
+                let today = if last.date >= begin_of_day {
+                    today + (*now - last.date)
+                } else {
+                    today
+                };
+                tr!(
+                    "watch-running",
+                    session = format::timer(&(*now - last.date)),
+                    today = format::duration(&today)
+                )
+            }
+            _ => tr!("watch-stopped", today = format::duration(&today)),
+        })
+    }
+
+    /// Get what a menu or status bar shows of the work-log, see [statusbar]
+    ///
+    /// Like the totals, today only counts the sessions started today.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Date/Time the times run until
+    pub fn status_bar(&self, now: &DateTime<Utc>) -> Result<statusbar::Bar> {
+        let begin_of_day = self.config.beginning_of_day(now);
+        let resume = self.resume()?;
+        let sessions =
+            report::sessions(&self.storage, &begin_of_day, now).context("Reading sessions")?;
+        let running = self
+            .storage
+            .last_stamp()?
+            .filter(|last| last.in_out == InOut::In)
+            .map(|last| last.date);
+
+        let (mut today, mut week) = (resume.day_total, resume.week_total);
+        if let Some(start) = running {
+            if start >= begin_of_day {
+                today = today + (*now - start);
+            }
+            if start >= resume.begin_of_week {
+                week = week + (*now - start);
+            }
+        }
+        Ok(statusbar::Bar {
+            now: *now,
+            running,
+            sessions,
+            today,
+            week,
+        })
+    }
+
+    /// Print the status of the work-log, see [statusbar]
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Text as printed by `wtime`, or for a bar
+    #[instrument(skip(self))]
+    pub fn do_status(&self, format: statusbar::StatusFormat) -> Result<()> {
+        match format {
+            statusbar::StatusFormat::Text => self.print_resume(),
+            statusbar::StatusFormat::Xbar => {
+                let bar = self.status_bar(&Utc::now())?;
+                let exe = std::env::current_exe().context("Locating the wtime executable")?;
+                writeln!(
+                    self.out(),
+                    "{}",
+                    statusbar::xbar(&bar, &self.config.timezone, &exe.to_string_lossy())
+                )?;
+                Ok(())
+            }
+            statusbar::StatusFormat::Waybar => {
+                let bar = self.status_bar(&Utc::now())?;
+                writeln!(
+                    self.out(),
+                    "{}",
+                    statusbar::waybar(&bar, &self.config.timezone)
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Get what a bug report needs to know of the installation
+    ///
+    /// The secrets of the configuration are only told to be set. The labels
+    /// are translated, except the configuration keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `database` - Path of the sqlite file, None for a database server
+    /// * `config_file` - Path of the configuration file, used or not
+    ///
+    /// # Return
+    ///
+    /// Name and value of every item, in printing order.
+    pub fn info(
+        &self,
+        database: Option<&Path>,
+        config_file: &Path,
+    ) -> Result<Vec<(String, String)>> {
+        let features: Vec<&str> = [
+            ("postgres", cfg!(feature = "postgres")),
+            ("sqlcipher", cfg!(feature = "sqlcipher")),
+            ("tls", cfg!(feature = "tls")),
+            ("dbus", cfg!(feature = "dbus")),
+            ("wifi", cfg!(feature = "wifi")),
+            ("ntp", cfg!(feature = "ntp")),
+            ("clipboard", cfg!(feature = "clipboard")),
+        ]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+        let date = |stamp: Option<Stamp>| match stamp {
+            Some(stamp) => format!(
+                "{} ({})",
+                stamp
+                    .date
+                    .with_timezone(&self.config.timezone)
+                    .format("%Y-%m-%d %H:%M:%S %Z"),
+                stamp.in_out
+            ),
+            None => "-".to_string(),
+        };
+        let list = |items: Vec<&str>| match items.is_empty() {
+            true => "-".to_string(),
+            false => items.join(", "),
+        };
+        let set = |value: &Option<String>| match value {
+            Some(_) => tr!("info-set"),
+            None => tr!("info-unset"),
+        };
+
+        let mut info = vec![
+            (tr!("info-version"), env!("CARGO_PKG_VERSION").to_string()),
+            (tr!("info-features"), list(features)),
+        ];
+        match database {
+            Some(path) => {
+                info.push((tr!("info-database"), path.display().to_string()));
+                let size = fs::metadata(path)
+                    .with_context(|| format!("Reading the size of {:?}", path))?
+                    .len();
+                info.push((tr!("info-file-size"), tr!("info-bytes", bytes = size)));
+            }
+            None => info.push((tr!("info-database"), tr!("info-database-server"))),
+        }
+        info.push((
+            tr!("info-schema-version"),
+            match self.storage.schema_version() {
+                Err(DbError::Unsupported(_)) => "-".to_string(),
+                other => other.context("Reading the schema version")?.to_string(),
+            },
+        ));
+        info.extend([
+            (
+                tr!("info-stamps"),
+                self.storage
+                    .stamp_count()
+                    .context("Counting the stamps")?
+                    .to_string(),
+            ),
+            (tr!("info-first-stamp"), date(self.storage.first_stamp()?)),
+            (tr!("info-last-stamp"), date(self.storage.last_stamp()?)),
+            (
+                tr!("info-timezone"),
+                self.config.timezone.name().to_string(),
+            ),
+            (
+                tr!("info-language"),
+                format!(
+                    "{:?}",
+                    self.config
+                        .language
+                        .or_else(i18n::Language::from_env)
+                        .unwrap_or_default()
+                ),
+            ),
+            (
+                tr!("info-config-file"),
+                match config_file.exists() {
+                    true => config_file.display().to_string(),
+                    false => tr!(
+                        "info-config-missing",
+                        file = config_file.display().to_string()
+                    ),
+                },
+            ),
+            (
+                tr!("info-daily-target"),
+                format::duration(&self.config.daily_target),
+            ),
+            (
+                tr!("info-weekly-target"),
+                format::duration(&self.config.weekly_target),
+            ),
+            (
+                tr!("info-month-start-day"),
+                self.config.month_start_day.to_string(),
+            ),
+            ("database_url".to_string(), set(&self.config.database_url)),
+            ("api_token".to_string(), set(&self.config.api_token)),
+            ("jira_token".to_string(), set(&self.config.jira_token)),
+            ("gitlab_token".to_string(), set(&self.config.gitlab_token)),
+            (
+                tr!("info-hooks"),
+                list(
+                    [
+                        ("on_checkin", &self.config.on_checkin),
+                        ("on_checkout", &self.config.on_checkout),
+                        ("on_error", &self.config.on_error),
+                    ]
+                    .iter()
+                    .filter(|(_, command)| command.is_some())
+                    .map(|(name, _)| *name)
+                    .collect(),
+                ),
+            ),
+            (tr!("info-wifi-rules"), self.config.wifi.len().to_string()),
+            (
+                "idle_after".to_string(),
+                self.config
+                    .idle_after
+                    .map_or("-".to_string(), |idle| format::duration(&idle)),
+            ),
+            (tr!("info-aliases"), self.config.aliases.len().to_string()),
+        ]);
+        Ok(info)
+    }
+
+    /// Print what a bug report needs to know of the installation, see [App::info]
+    ///
+    /// # Arguments
+    ///
+    /// * `database` - Path of the sqlite file, None for a database server
+    /// * `config_file` - Path of the configuration file, used or not
+    #[instrument(skip(self))]
+    pub fn do_info(&self, database: Option<&Path>, config_file: &Path) -> Result<()> {
+        let info = self.info(database, config_file)?;
+        let width = info
+            .iter()
+            .map(|(name, _)| name.chars().count() + 1)
+            .max()
+            .unwrap_or_default();
+        for (name, value) in info {
+            writeln!(self.out(), "{:<width$} {}", format!("{}:", name), value)?;
+        }
+        Ok(())
+    }
+
+    /// Print the time of the running session and of today every second,
+    /// until interrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `overwrite` - Print every line over the previous one, for terminals
+    #[instrument(skip(self))]
+    pub fn do_watch(&self, overwrite: bool) -> Result<()> {
+        loop {
+            let now = Utc::now();
+            let line = self.watch_line(&now)?;
+            if overwrite {
+                // Back to the line start, then clear the previous line
+                write!(self.out(), "\r\x1b[K{}", line)?;
+            } else {
+                writeln!(self.out(), "{}", line)?;
+            }
+            self.out().flush()?;
+
+            // Tick on the next second, as a clock does
+            let elapsed = now.timestamp_subsec_millis().min(999);
+            std::thread::sleep(std::time::Duration::from_millis(u64::from(1000 - elapsed)));
+        }
+    }
+
+    /// Check-in and print the time, see [App::checkin]
+    ///
+    /// Without project, the project of the current git repository is taken
+    /// when `detect_project` is set in the configuration, see [project].
+    ///
+    /// # Arguments
+    ///
+    /// * `since_active` - Check-in when the system became active instead of now
+    /// * `project` - Project the new session is allocated to, if any
+    /// * `tags` - Tags of the new session, e.g. `meeting`
+    /// * `references` - Tickets the new session is about, e.g. `ABC-123`, see
+    ///   [Reference]
+    /// * `close_previous_at` - Close the session still open at this time first, see [App::close_previous]
+    #[instrument(skip(self))]
+    pub fn do_checkin(
+        &self,
+        since_active: bool,
+        project: Option<&str>,
+        tags: &[String],
+        references: &[String],
+        close_previous_at: Option<&str>,
+    ) -> Result<()> {
+        let detected = match project {
+            None if self.config.detect_project => {
+                project::detect(&std::env::current_dir()?).context("Detecting project")?
+            }
+            _ => None,
+        };
+        let project = project.or(detected.as_deref());
+
+        // The previous session stays open if the check-in fails
+        let (checkout, stamp) = self.storage.transaction(|| -> Result<_> {
+            let checkout = close_previous_at
+                .map(|at| self.close_previous(at))
+                .transpose()?;
+            let stamp = self.checkin(since_active, project)?;
+            // Each tag once
+            let tags: BTreeSet<&String> = tags.iter().collect();
+            for tag in tags {
+                self.storage
+                    .insert_tag(&mut Tag::new(stamp.id, tag))
+                    .context("Inserting tag")?;
+            }
+            let references: BTreeSet<&String> = references.iter().collect();
+            for reference in references {
+                self.storage
+                    .insert_reference(&mut Reference::new(stamp.id, reference))
+                    .context("Inserting reference")?;
+            }
+            Ok((checkout, stamp))
+        })?;
+
+        if let Some(checkout) = checkout {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!(
+                    "previous-closed",
+                    date = checkout.date.format("%Y-%m-%d %H:%M").to_string()
+                )
+            )?;
         }
+        let time = stamp.date.format("%H:%M").to_string();
+        match project {
+            Some(project) => writeln!(
+                self.out(),
+                "{}",
+                tr!("checked-in-on", time = time, project = project)
+            )?,
+            None => writeln!(self.out(), "{}", tr!("checked-in", time = time))?,
+        }
+        Ok(())
+    }
+
+    /// Get the check-in of a session left open since a previous day
+    pub fn stale_checkin(&self) -> Result<Option<Stamp>> {
+        let today = self.config.beginning_of_day(&Utc::now());
+        Ok(self
+            .storage
+            .last_stamp()?
+            .filter(|s| s.in_out == InOut::In && s.date < today))
+    }
+
+    /// Suggested end of a session left open: after the daily target, or now
+    ///
+    /// # Arguments
+    ///
+    /// * `checkin` - The check-in of the open session
+    pub fn suggested_checkout(&self, checkin: &Stamp) -> DateTime<Utc> {
+        (checkin.date + self.config.daily_target).min(Utc::now())
+    }
+
+    /// Close the session left open by a forgotten check-out
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - Local time of the check-out, `HH:MM` on the day of the
+    ///   check-in or `YYYY-MM-DD HH:MM`
+    ///
+    /// # Return
+    ///
+    /// The inserted check-out stamp
+    #[instrument(skip(self))]
+    pub fn close_previous(&self, at: &str) -> Result<Stamp> {
+        let checkin = self
+            .storage
+            .last_stamp()?
+            .filter(|s| s.in_out == InOut::In)
+            .ok_or_else(|| anyhow!("Not checked in, there is no session to close"))?;
+        let day = self.config.day_of(&checkin.date);
+        let date = doctor::parse_time(at, day, &self.config.timezone)
+            .ok_or_else(|| anyhow!("Invalid time {:?}, expected HH:MM", at))?;
+        self.close_session(&checkin, date)
+    }
+
+    /// Close the open session at a past date/time
+    ///
+    /// # Arguments
+    ///
+    /// * `checkin` - The check-in of the open session
+    /// * `date` - Date/time of the check-out, between the check-in and now
+    ///
+    /// # Return
+    ///
+    /// The inserted check-out stamp
+    fn close_session(&self, checkin: &Stamp, date: DateTime<Utc>) -> Result<Stamp> {
+        let mut checkout = doctor::Resolution::Cap(date)
+            .stamps(checkin, &Utc::now())
+            .and_then(|stamps| stamps.into_iter().next())
+            .ok_or_else(|| {
+                anyhow!(
+                    "The session must be closed after its check-in at {} and before now",
+                    checkin.date.format("%Y-%m-%d %H:%M")
+                )
+            })?;
+
+        checkout.source = Some(self.source);
+        self.storage
+            .insert_next_stamp(&mut checkout)
+            .context("Inserting check-out stamp")?;
+        self.hook(
+            hooks::Event::CheckOut,
+            &[
+                ("WTIME_DATE", checkout.date.to_rfc3339()),
+                ("WTIME_START", checkin.date.to_rfc3339()),
+                (
+                    "WTIME_DURATION",
+                    (checkout.date - checkin.date).num_seconds().to_string(),
+                ),
+            ],
+        );
+        Ok(checkout)
     }
 
-    pub fn do_checkin(&self) -> Result<()> {
+    /// Check-in, optionally backdated to the start of the system activity
+    ///
+    /// The session is allocated whole to the project, until split at
+    /// check-out.
+    ///
+    /// # Arguments
+    ///
+    /// * `since_active` - Check-in when the system became active instead of now
+    /// * `project` - Project the new session is allocated to, if any
+    ///
+    /// # Return
+    ///
+    /// The inserted check-in stamp
+    #[instrument(skip(self))]
+    pub fn checkin(&self, since_active: bool, project: Option<&str>) -> Result<Stamp> {
         // check that we are actually out
-        if let Some(last_stamp) = Stamp::last(&self.conn) {
+        let last_stamp = self.storage.last_stamp()?;
+        if let Some(last_stamp) = &last_stamp {
             if last_stamp.in_out == InOut::In {
                 return Err(anyhow!(
-                    "Already checked in ! (Do you meant to check-out ?)"
+                    "Already checked in since {} ! (Do you meant to check-out, or `checkin --close-previous-at <HH:MM>` ?)",
+                    last_stamp.date.format("%Y-%m-%d %H:%M")
                 ));
             }
         }
 
         // Creat teh checking stamp
+        self.check_ntp();
         let mut stamp = Stamp::check_in();
-        stamp.insert(&self.conn).context("Inserting new stamp")?;
-
-        println!("Checked in at {}", stamp.date.format("%H:%M"));
-        Ok(())
-    }
-
-    pub fn do_checkout(&self) -> Result<()> {
-        // Check that last stamp is check-in
-        if let Some(last_stamp) = Stamp::last(&self.conn) {
-            if last_stamp.in_out == InOut::Out {
-                return Err(anyhow!(
-                    "Already checked out ! (Do you meant to check-in ?)"
+        stamp.source = Some(self.source);
+        match activity::active_since(self.config.beginning_of_day(&stamp.date)) {
+            Ok(active) if since_active => {
+                // Never overlap the previous session
+                let last_out = last_stamp.as_ref().map(|s| s.date);
+                stamp.date = last_out.map_or(active, |out| out.max(active));
+            }
+            Ok(active) if stamp.date - active > Duration::minutes(ACTIVITY_HINT_MINUTES) => {
+                self.warn(tr!(
+                    "active-since",
+                    time = active.format("%H:%M").to_string()
                 ));
             }
+            Err(e) if since_active => return Err(e),
+            _ => (),
         }
+        self.check_clock(&stamp, last_stamp.as_ref())?;
 
-        // Create the checkout stamps
-        let mut stamp = Stamp::check_out();
-        stamp.insert(&self.conn).context("Inserting new stamp")?;
-
-        println!("Checked out at {}", stamp.date.format("%H:%M"));
-
-        if let Some(checkin) = stamp.previous(&self.conn) {
-            let work_time = checkin.delta(&stamp);
-            println!(
-                "You worked {} hours, {} minutes and {} seconds",
-                work_time.num_hours(),
-                work_time.num_minutes() % 60,
-                work_time.num_seconds() & 60
-            );
+        self.storage
+            .insert_next_stamp(&mut stamp)
+            .context("Inserting new stamp")?;
+        if let Some(project) = project {
+            self.storage
+                .insert_allocation(&mut Allocation::new(stamp.id, project, 1.0))
+                .context("Inserting allocation")?;
         }
 
-        Ok(())
+        let mut vars = vec![("WTIME_DATE", stamp.date.to_rfc3339())];
+        vars.extend(project.map(|project| ("WTIME_PROJECT", project.to_string())));
+        self.hook(hooks::Event::CheckIn, &vars);
+
+        Ok(stamp)
     }
 
-    pub fn do_list(&self) -> Result<()> {
-        self.print_resume();
+    /// Get the current state of the work-log
+    #[instrument(skip(self))]
+    pub fn status(&self) -> Result<Status> {
+        let last_stamp = self.storage.last_stamp()?;
+
+        Ok(Status {
+            checked_in: last_stamp.as_ref().is_some_and(|s| s.in_out == InOut::In),
+            last_stamp,
+            resume: self.resume()?,
+            warnings: self
+                .violations(&self.config.beginning_of_day(&Utc::now()))?
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        })
+    }
+
+    /// Get the working-time limits exceeded since a date
+    ///
+    /// A session still open counts until now.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Date/Time of the first violations returned
+    pub fn violations(&self, since: &DateTime<Utc>) -> Result<Vec<compliance::Violation>> {
+        if !self.config.limits.any() {
+            return Ok(Vec::new());
+        }
+
+        // The rest before the first day needs the day before
+        let from = *since - Duration::days(1);
+        let now = Utc::now();
+        let mut sessions = report::sessions(&self.storage, &from, &(now + Duration::days(1)))
+            .context("Reading sessions")?;
+        if let Some(last) = self.storage.last_stamp()? {
+            if last.in_out == InOut::In && last.date >= from {
+                sessions.push(report::Session {
+                    id: last.id,
+                    start: last.date,
+                    end: now,
+                });
+            }
+        }
+
+        Ok(compliance::check(&sessions, &self.config)
+            .into_iter()
+            .filter(|violation| violation.date() >= *since)
+            .collect())
+    }
+
+    /// Print the working-time limits exceeded today on stderr
+    fn warn_violations(&self) -> Result<()> {
+        for violation in self.violations(&self.config.beginning_of_day(&Utc::now()))? {
+            self.warn(tr!("warning", message = violation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Print the budgets of the given projects, or of their ancestors,
+    /// consumed beyond [report::BUDGET_WARNING] on stderr
+    ///
+    /// # Arguments
+    ///
+    /// * `projects` - Issues and projects to check the budget of
+    fn warn_budgets<'a>(&self, projects: impl IntoIterator<Item = &'a String>) -> Result<()> {
+        if self.config.budgets.is_empty() {
+            return Ok(());
+        }
+        let parents = report::project_parents(&self.storage).context("Reading projects")?;
+        let budgets: BTreeMap<String, Duration> = projects
+            .into_iter()
+            .flat_map(|project| {
+                std::iter::once(project.as_str()).chain(report::ancestors(project, &parents))
+            })
+            .filter_map(|project| {
+                let budget = self.config.budgets.get(project)?;
+                Some((project.to_string(), *budget))
+            })
+            .collect();
+        for budget in report::budget_use(&self.storage, &budgets).context("Reading budgets")? {
+            if budget.remaining() < Duration::zero() {
+                self.warn(tr!(
+                    "warning-budget-exceeded",
+                    project = budget.project.as_str(),
+                    budget = format::duration(&budget.budget),
+                    over = format::duration(&-budget.remaining())
+                ));
+            } else if budget.warned() {
+                self.warn(tr!(
+                    "warning-budget",
+                    project = budget.project.as_str(),
+                    percent = format!("{:.0}", budget.ratio() * 100.0),
+                    budget = format::duration(&budget.budget),
+                    remaining = format::duration(&budget.remaining())
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check-out and print the worked time, see [App::checkout]
+    ///
+    /// The overtime and the nearly exhausted project budgets are warned
+    /// about on stderr.
+    ///
+    /// # Arguments
+    ///
+    /// * `split` - Split specification, see [allocation::parse_split]
+    ///
+    /// # Return
+    ///
+    /// Whether a target is exceeded, see [CheckoutSummary::overtime]
+    #[instrument(skip(self))]
+    pub fn do_checkout(&self, split: Option<&str>) -> Result<bool> {
+        let summary = self.checkout(split)?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "checked-out",
+                time = summary.checkout.date.format("%H:%M").to_string()
+            )
+        )?;
+
+        if let Some(session) = &summary.session {
+            let work_time = session.duration();
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("worked-session", worked = format::duration(&work_time))
+            )?;
+
+            for allocation in &summary.allocations {
+                let allocated = Duration::seconds(
+                    (work_time.num_seconds() as f64 * allocation.fraction) as i64,
+                );
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!(
+                        "allocated",
+                        worked = format::duration(&allocated),
+                        target = allocation.target.as_str(),
+                        percent = format!("{:.0}", allocation.fraction * 100.0)
+                    )
+                )?;
+            }
+        }
+
+        // Quick stats against the targets
+        let resume = summary.resume;
+        let day_target = self
+            .config
+            .day_target(self.config.day_of(&resume.begin_of_day));
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "checkout-totals",
+                day = format::duration(&resume.day_total),
+                day_delta = format::delta(&(resume.day_total - day_target)),
+                week = format::duration(&resume.week_total),
+                week_delta = format::delta(&(resume.week_total - self.config.weekly_target)),
+            )
+        )?;
+
+        let (day, week) = self.overtime(&resume);
+        if let Some(over) = day {
+            self.warn(tr!("warning-day-overtime", over = format::duration(&over)));
+        }
+        if let Some(over) = week {
+            self.warn(tr!("warning-week-overtime", over = format::duration(&over)));
+        }
+        self.warn_violations()?;
+        if let Some(session) = &summary.session {
+            let projects = report::allocated_totals(&self.storage, std::slice::from_ref(session))
+                .context("Reading allocations")?;
+            self.warn_budgets(projects.keys())?;
+        }
+
+        Ok(summary.overtime)
+    }
+
+    /// Check-out, optionally splitting the finished session across issues
+    ///
+    /// # Arguments
+    ///
+    /// * `split` - Split specification, see [allocation::parse_split]
+    #[instrument(skip(self))]
+    pub fn checkout(&self, split: Option<&str>) -> Result<CheckoutSummary> {
+        // Check that last stamp is check-in
+        let last_stamp = self.storage.last_stamp()?;
+        if let Some(last_stamp) = &last_stamp {
+            if last_stamp.in_out == InOut::Out {
+                return Err(anyhow!(
+                    "Already checked out ! (Do you meant to check-in ?)"
+                ));
+            }
+        }
+
+        // Create the checkout stamps
+        self.check_ntp();
+        let mut checkout = Stamp::check_out();
+        checkout.source = Some(self.source);
+        self.check_clock(&checkout, last_stamp.as_ref())?;
+
+        let session = last_stamp.map(|checkin| report::Session {
+            id: checkin.id,
+            start: checkin.date,
+            end: checkout.date,
+        });
+        // An invalid split leaves the session open
+        let parts = match (&session, split) {
+            (Some(session), Some(spec)) => {
+                Some(allocation::parse_split(spec, session.duration()).context("Parsing split")?)
+            }
+            _ => None,
+        };
+        let allocations = self.storage.transaction(|| -> Result<_> {
+            self.storage
+                .insert_next_stamp(&mut checkout)
+                .context("Inserting new stamp")?;
+            match (&session, parts) {
+                (Some(session), Some(parts)) => self.allocate(session, parts),
+                _ => Ok(Vec::new()),
+            }
+        })?;
+
+        let mut vars = vec![("WTIME_DATE", checkout.date.to_rfc3339())];
+        if let Some(session) = &session {
+            let targets: Vec<String> = self
+                .storage
+                .allocation_range(session.id, checkout.id)?
+                .into_iter()
+                .map(|a| a.target)
+                .collect();
+            vars.push(("WTIME_START", session.start.to_rfc3339()));
+            vars.push((
+                "WTIME_DURATION",
+                session.duration().num_seconds().to_string(),
+            ));
+            if !targets.is_empty() {
+                vars.push(("WTIME_PROJECT", targets.join(",")));
+            }
+        }
+        self.hook(hooks::Event::CheckOut, &vars);
+
+        let resume = self.resume()?;
+        let (day, week) = self.overtime(&resume);
+        Ok(CheckoutSummary {
+            checkout,
+            session,
+            allocations,
+            overtime: day.is_some() || week.is_some(),
+            resume,
+        })
+    }
+
+    /// Check-out of the current session and check-in on another project
+    ///
+    /// Both stamps are dated the same and inserted in one transaction, so
+    /// that no time is lost between the sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `project` - Project the new session is allocated to
+    ///
+    /// # Return
+    ///
+    /// The finished session and the new check-in stamp
+    #[instrument(skip(self))]
+    pub fn switch(&self, project: &str) -> Result<(report::Session, Stamp)> {
+        let last_stamp = self.storage.last_stamp()?;
+        let checkin = match last_stamp {
+            Some(stamp) if stamp.in_out == InOut::In => stamp,
+            _ => {
+                return Err(anyhow!(
+                    "Not checked in ! (Do you meant `checkin --project {}` ?)",
+                    project
+                ))
+            }
+        };
+
+        self.check_ntp();
+        let mut stamps = [Stamp::check_out(), Stamp::check_in()];
+        stamps[1].date = stamps[0].date;
+        for stamp in &mut stamps {
+            stamp.source = Some(self.source);
+        }
+        self.check_clock(&stamps[0], Some(&checkin))?;
+        self.storage.transaction(|| -> Result<()> {
+            // Out before In, the pairing follows the ids
+            self.storage
+                .insert_stamps(&mut stamps)
+                .context("Inserting new stamps")?;
+            self.storage
+                .insert_allocation(&mut Allocation::new(stamps[1].id, project, 1.0))
+                .context("Inserting allocation")?;
+            Ok(())
+        })?;
+        let [checkout, stamp] = stamps;
+
+        let session = report::Session {
+            id: checkin.id,
+            start: checkin.date,
+            end: checkout.date,
+        };
+        let targets: Vec<String> = self
+            .storage
+            .allocation_range(session.id, checkout.id)?
+            .into_iter()
+            .map(|a| a.target)
+            .collect();
+        let mut vars = vec![
+            ("WTIME_DATE", checkout.date.to_rfc3339()),
+            ("WTIME_START", session.start.to_rfc3339()),
+            (
+                "WTIME_DURATION",
+                session.duration().num_seconds().to_string(),
+            ),
+        ];
+        if !targets.is_empty() {
+            vars.push(("WTIME_PROJECT", targets.join(",")));
+        }
+        self.hook(hooks::Event::CheckOut, &vars);
+        self.hook(
+            hooks::Event::CheckIn,
+            &[
+                ("WTIME_DATE", stamp.date.to_rfc3339()),
+                ("WTIME_PROJECT", project.to_string()),
+            ],
+        );
+
+        Ok((session, stamp))
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_switch(&self, project: &str) -> Result<()> {
+        let (session, stamp) = self.switch(project)?;
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "switched",
+                worked = format::duration(&session.duration()),
+                time = stamp.date.format("%H:%M").to_string(),
+                project = project
+            )
+        )?;
+        self.warn_violations()
+    }
+
+    /// Get the time worked beyond the day and week targets
+    ///
+    /// The day target is the one of today, see [Config::day_target].
+    ///
+    /// # Arguments
+    ///
+    /// * `resume` - Worked time of the day and week
+    ///
+    /// # Return
+    ///
+    /// The overtime of the day and of the week, each None unless it exceeds
+    /// the `overtime_threshold`.
+    fn overtime(&self, resume: &Resume) -> (Option<Duration>, Option<Duration>) {
+        let beyond = |total: Duration, target: Duration| {
+            Some(total - target).filter(|over| *over > self.config.overtime_threshold)
+        };
+        (
+            beyond(
+                resume.day_total,
+                self.config
+                    .day_target(self.config.day_of(&resume.begin_of_day)),
+            ),
+            beyond(resume.week_total, self.config.weekly_target),
+        )
+    }
+
+    /// Split a session across issues
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - Session to split
+    /// * `parts` - Targets with their fraction, see [allocation::parse_split]
+    fn allocate(
+        &self,
+        session: &report::Session,
+        parts: Vec<(String, f64)>,
+    ) -> Result<Vec<Allocation>> {
+        // The split replaces the project given at check-in
+        self.storage
+            .delete_allocations(session.id)
+            .context("Deleting allocations")?;
+        let mut allocations = Vec::new();
+        for (target, fraction) in parts {
+            let mut allocation = Allocation::new(session.id, &target, fraction);
+            self.storage
+                .insert_allocation(&mut allocation)
+                .context("Inserting allocation")?;
+            allocations.push(allocation);
+        }
+
+        Ok(allocations)
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_list(&self) -> Result<()> {
+        self.print_resume()
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_close(&self, until: &DateTime<Utc>) -> Result<()> {
+        // A session still open before the closing date would be cut in two
+        if let Some(last_stamp) = self.storage.last_stamp()? {
+            if last_stamp.in_out == InOut::In && last_stamp.date < *until {
+                return Err(anyhow!(
+                    "Still checked in since {} ! (Check-out before closing)",
+                    last_stamp.date.format("%Y-%m-%d %H:%M")
+                ));
+            }
+        }
+
+        self.storage
+            .insert_closeout(&mut Closeout::new(*until))
+            .context("Inserting closeout")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("closed", until = until.format("%Y-%m-%d").to_string())
+        )?;
+        Ok(())
+    }
+
+    /// Reopen the period closed by the latest closeout, see [Closeout::delete_last]
+    ///
+    /// The previous closeout, if any, applies again.
+    #[instrument(skip(self))]
+    pub fn do_reopen(&self) -> Result<()> {
+        let closeout = self
+            .storage
+            .delete_last_closeout()
+            .context("Removing closeout")?
+            .ok_or_else(|| anyhow!("The work-log was never closed"))?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "reopened",
+                until = closeout.until.format("%Y-%m-%d").to_string()
+            )
+        )?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_adjust(
+        &self,
+        amount: Duration,
+        reason: &str,
+        target: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        if reason.trim().is_empty() {
+            return Err(anyhow!("An adjustment needs a reason"));
+        }
+
+        let mut adjustment = Adjustment::new(amount, reason, target);
+        self.storage
+            .insert_adjustment(&mut adjustment)
+            .context("Inserting adjustment")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "adjusted",
+                day = adjustment.target.format("%Y-%m-%d").to_string(),
+                minutes = adjustment.amount.num_minutes(),
+                reason = adjustment.reason.as_str()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Record the estimate of a project or tag, replacing the previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - Whether `name` is a tag rather than a project
+    /// * `name` - Name of the project or tag
+    /// * `estimate` - Expected time
+    #[instrument(skip(self))]
+    pub fn do_estimate(&self, tag: bool, name: &str, estimate: Duration) -> Result<()> {
+        self.storage
+            .save_estimate(&mut Estimate::new(tag, name, estimate))
+            .context("Saving estimate")?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "estimate-recorded",
+                name = name,
+                estimate = format::duration(&estimate)
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Remove the estimate of a project or tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - Whether `name` is a tag rather than a project
+    /// * `name` - Name of the project or tag
+    #[instrument(skip(self))]
+    pub fn do_estimate_remove(&self, tag: bool, name: &str) -> Result<()> {
+        if !self
+            .storage
+            .delete_estimate(tag, name)
+            .context("Removing estimate")?
+        {
+            return Err(anyhow!("No estimate of {}", name));
+        }
+
+        writeln!(self.out(), "{}", tr!("estimate-removed", name = name))?;
+        Ok(())
+    }
+
+    /// Print the estimates with the actual time spent and its variance
+    ///
+    /// The actual time covers the whole work-log, archived sessions
+    /// included, see [report::estimate_actuals].
+    #[instrument(skip(self))]
+    pub fn do_estimates(&self) -> Result<()> {
+        let estimates = self.storage.estimates().context("Reading estimates")?;
+        let actuals =
+            report::estimate_actuals(&self.storage, estimates).context("Reading sessions")?;
+        if actuals.is_empty() {
+            writeln!(self.out(), "{}", tr!("no-estimates"))?;
+            return Ok(());
+        }
+
+        let (tags, projects): (Vec<_>, Vec<_>) =
+            actuals.into_iter().partition(|actual| actual.estimate.tag);
+        let mut out = self.out();
+        if !projects.is_empty() {
+            estimates_table(&mut *out, &tr!("estimate-project"), &projects)?;
+        }
+        if !tags.is_empty() {
+            if !projects.is_empty() {
+                writeln!(out)?;
+            }
+            estimates_table(&mut *out, &tr!("estimate-tag"), &tags)?;
+        }
+        Ok(())
+    }
+
+    /// Get the actual time of the estimated projects or tags among the
+    /// reported ones
+    ///
+    /// Storage backends without estimates have none.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - Whether to get the estimates of tags rather than projects
+    /// * `reported` - Totals of the reported projects or tags
+    fn reported_estimates(
+        &self,
+        tag: bool,
+        reported: &BTreeMap<String, impl Sized>,
+    ) -> Result<Vec<report::Actual>> {
+        let estimates = match self.storage.estimates() {
+            Ok(estimates) => estimates,
+            Err(DbError::Unsupported(_)) => Vec::new(),
+            Err(e) => return Err(e).context("Reading estimates"),
+        };
+        let estimates = estimates
+            .into_iter()
+            .filter(|estimate| estimate.tag == tag && reported.contains_key(&estimate.name))
+            .collect();
+        report::estimate_actuals(&self.storage, estimates).context("Reading sessions")
+    }
+
+    /// Import the sessions exported by another tool
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Sessions to import, see [import::read]
+    #[instrument(skip(self, records))]
+    pub fn do_import(&self, mut records: Vec<import::Record>) -> Result<()> {
+        records.sort_by_key(|r| r.start);
+
+        // Imported stamps would be taken for the end of the current session
+        let last_stamp = self.storage.last_stamp()?;
+        if let Some(last_stamp) = &last_stamp {
+            if last_stamp.in_out == InOut::In {
+                return Err(anyhow!("Checked in ! (Check-out before importing)"));
+            }
+        }
+
+        // Worked time is never counted twice, nor ahead
+        let now = Utc::now();
+        let mut worked = match (records.first(), records.iter().map(|r| r.end).max()) {
+            (Some(first), Some(end)) => {
+                let (from, to) = (first.start - Duration::days(1), end + Duration::days(1));
+                report::sessions(&self.storage, &from, &to).context("Reading sessions")?
+            }
+            _ => Vec::new(),
+        };
+        let count = records.len();
+        records.retain(|record| {
+            let overlaps = worked
+                .iter()
+                .any(|s| s.start < record.end && record.start < s.end);
+            if overlaps || record.end > now {
+                return false;
+            }
+            worked.push(report::Session {
+                id: StampId::default(),
+                start: record.start,
+                end: record.end,
+            });
+            true
+        });
+        let skipped = count - records.len();
+
+        // Stamps are paired in id order, so they must be moved to their place
+        let in_past = match (&last_stamp, records.first()) {
+            (Some(last_stamp), Some(first)) => first.start < last_stamp.date,
+            _ => false,
+        };
+        if in_past {
+            // Also tells whether the storage supports it, before any insertion
+            self.storage
+                .renumber_stamps()
+                .context("Importing before the last stamp")?;
+        }
+
+        // All the sessions or none
+        self.storage.transaction(|| -> Result<()> {
+            let mut stamps: Vec<Stamp> = records
+                .iter()
+                .flat_map(|record| {
+                    [
+                        Stamp::new(StampId::default(), record.start, InOut::In),
+                        Stamp::new(StampId::default(), record.end, InOut::Out),
+                    ]
+                })
+                .collect();
+            for stamp in &mut stamps {
+                stamp.source = Some(self.source);
+            }
+            self.storage
+                .insert_stamps(&mut stamps)
+                .context("Importing sessions")?;
+
+            for (record, checkin) in records.iter().zip(stamps.iter().step_by(2)) {
+                if let Some(project) = &record.project {
+                    self.storage
+                        .insert_allocation(&mut Allocation::new(checkin.id, project, 1.0))
+                        .context("Inserting allocation")?;
+                }
+            }
+
+            if in_past {
+                self.storage
+                    .renumber_stamps()
+                    .context("Renumbering stamps")?;
+            }
+            Ok(())
+        })?;
+
+        writeln!(self.out(), "{}", tr!("imported", count = records.len()))?;
+        if skipped > 0 {
+            writeln!(self.out(), "{}", tr!("import-skipped", count = skipped))?;
+        }
+        Ok(())
+    }
+
+    /// List the stamps matching a filter, see [Stamp::search]
+    ///
+    /// Each stamp is given with the entry point it was made through, the
+    /// stamps recorded before the sources were being listed as manual, and
+    /// the check-ins with the tickets their session references.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Criteria the stamps must all match
+    #[instrument(skip(self))]
+    pub fn do_search(&self, filter: &StampFilter) -> Result<()> {
+        let stamps = self
+            .storage
+            .search_stamps(filter)
+            .context("Searching stamps")?;
+        let references = match (stamps.first(), stamps.last()) {
+            (Some(first), Some(last)) => match self.storage.reference_range(first.id, last.id) {
+                Err(DbError::Unsupported(_)) => Vec::new(),
+                other => other.context("Reading references")?,
+            },
+            _ => Vec::new(),
+        };
+
+        for stamp in &stamps {
+            let mut line = format!(
+                "{:>6}  {}  {:<7}  {}",
+                stamp.id.0,
+                stamp.date.format("%Y-%m-%d %H:%M"),
+                direction(stamp.in_out),
+                stamp.source.unwrap_or_default()
+            );
+            for reference in references.iter().filter(|r| r.session == stamp.id) {
+                line.push_str(&format!("  {}", reference.name));
+            }
+            writeln!(self.out(), "{}", line)?;
+        }
+        writeln!(self.out(), "{}", tr!("stamps-found", count = stamps.len()))?;
+        if filter.limit == Some(stamps.len()) {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("search-more", offset = filter.offset + stamps.len())
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Let the user pick a stamp with the fuzzy finder, see [pick]
+    ///
+    /// The stamps are listed with their projects and tags, latest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `ask` - Ask the user a question and return the answer
+    #[instrument(skip(self, ask))]
+    pub fn pick_stamp(&self, ask: impl Fn(&str) -> Result<String>) -> Result<Stamp> {
+        const SHOWN: usize = 10;
+
+        let stamps = self
+            .storage
+            .search_stamps(&StampFilter::default())
+            .context("Reading stamps")?;
+        let (Some(first), Some(last)) = (stamps.first(), stamps.last()) else {
+            return Err(anyhow!("There is no stamp to pick"));
+        };
+        let allocations = self.storage.allocation_range(first.id, last.id)?;
+        let tags = self.storage.tag_range(first.id, last.id)?;
+        let references = self.storage.reference_range(first.id, last.id)?;
+        let lines: Vec<String> = stamps
+            .iter()
+            .map(|stamp| {
+                let date = stamp.date.with_timezone(&self.config.timezone);
+                let mut line = format!(
+                    "{} {}",
+                    date.format(&i18n::localize("%a %Y-%m-%d %H:%M", &date)),
+                    direction(stamp.in_out).to_lowercase()
+                );
+                for allocation in allocations.iter().filter(|a| a.session == stamp.id) {
+                    line.push_str(&format!(" {}", allocation.target));
+                }
+                for tag in tags.iter().filter(|t| t.session == stamp.id) {
+                    line.push_str(&format!(" #{}", tag.name));
+                }
+                for reference in references.iter().filter(|r| r.session == stamp.id) {
+                    line.push_str(&format!(" [{}]", reference.name));
+                }
+                line.push_str(&format!(" ({})", tr!("pick-stamp", id = stamp.id.0)));
+                line
+            })
+            .collect();
+
+        let mut query = ask(&format!("{} ", tr!("pick-search")))?;
+        loop {
+            let found: Vec<usize> = pick::matches(&query, &lines)
+                .into_iter()
+                .take(SHOWN)
+                .collect();
+            if found.is_empty() {
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!("pick-none", query = format!("{:?}", query))
+                )?;
+            }
+            for (number, index) in found.iter().enumerate() {
+                writeln!(self.out(), "{:>3}) {}", number + 1, lines[*index])?;
+            }
+
+            let answer = ask(&format!("{} ", tr!("pick-number")))?;
+            if answer.is_empty() {
+                return Err(anyhow!("No stamp picked"));
+            }
+            match answer.parse::<usize>() {
+                Ok(number) if (1..=found.len()).contains(&number) => {
+                    return Ok(stamps[found[number - 1]].clone())
+                }
+                _ => query = answer,
+            }
+        }
+    }
+
+    /// Export the sessions of a date window, see [export::sessions]
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - First day of the sessions, defaults to the beginning of the week
+    /// * `to` - Day after the sessions, defaults to tomorrow
+    /// * `archived` - Include the archived stamps
+    /// * `format` - Output format
+    #[instrument(skip(self))]
+    pub fn do_export(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        archived: bool,
+        format: export::SessionFormat,
+    ) -> Result<()> {
+        let report = self.report(from, to, archived)?;
+        let allocations = report::session_allocations(&self.storage, &report.sessions)
+            .context("Reading allocations")?;
+        let user = self.user_name()?;
+        export::sessions(
+            &mut *self.out(),
+            &report.sessions,
+            &allocations,
+            &user,
+            &self.config,
+            format,
+        )
+    }
+
+    /// Setting of the last session written by a periodic report or export,
+    /// kept apart for every user and project the totals are restricted to
+    fn last_export_key(&self, key: &str) -> Result<String> {
+        let user = self.user_name()?;
+        Ok(match &self.project {
+            Some(project) => format!("last_export:{}:{} --project {}", user, key, project),
+            None => format!("last_export:{}:{}", user, key),
+        })
+    }
+
+    /// Start of the sessions not reported yet by a periodic report or export,
+    /// see [App::mark_exported]
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Name of the report or export, e.g. `export harvest`
+    ///
+    /// # Return
+    ///
+    /// The end of the last session written, the Unix epoch before the first
+    /// time.
+    pub fn last_export(&self, key: &str) -> Result<DateTime<Utc>> {
+        let key = self.last_export_key(key)?;
+        Ok(self
+            .storage
+            .setting(&key)?
+            .map(|value| DateTime::parse_from_rfc3339(&value).map(|d| d.with_timezone(&Utc)))
+            .transpose()
+            .with_context(|| format!("Invalid {} setting", key))?
+            .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap()))
+    }
+
+    /// Remember the end of the last session of a date window, once written by
+    /// a periodic report or export
+    ///
+    /// The open session is left for the next time; nothing is remembered
+    /// when the window has no session.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Name of the report or export, see [App::last_export]
+    /// * `from` - Start of the window, as given by [App::last_export]
+    /// * `to` - End of the window, defaults to tomorrow
+    /// * `archived` - Include the archived stamps
+    pub fn mark_exported(
+        &self,
+        key: &str,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        archived: bool,
+    ) -> Result<()> {
+        let report = self.report(Some(from), to, archived)?;
+        if let Some(last) = report.sessions.iter().map(|s| s.end).max() {
+            self.storage
+                .set_setting(&self.last_export_key(key)?, &last.to_rfc3339())?;
+        }
+        Ok(())
+    }
+
+    /// Draw the worked hours of the current week, month or year, see [chart]
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Days to draw
+    /// * `kind` - Kind of chart
+    /// * `file` - SVG file to write
+    #[instrument(skip(self))]
+    pub fn do_chart(&self, range: chart::Range, kind: chart::Kind, file: &Path) -> Result<()> {
+        let (from, to) = range.window(&self.config, &Utc::now());
+        let report = self.report(Some(from), Some(to), true)?;
+        let svg = chart::svg(
+            self.config.day_of(&from),
+            self.config.day_of(&to),
+            &report.days,
+            kind,
+            self.config.daily_target,
+        );
+        fs::write(file, svg).with_context(|| format!("Writing {:?}", file))?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("chart-written", file = format!("{:?}", file))
+        )?;
+        Ok(())
+    }
+
+    /// Write the timesheet of a month, see [timesheet]
+    ///
+    /// Archived stamps are included, the month being possibly archived.
+    ///
+    /// # Arguments
+    ///
+    /// * `month` - First day of the month
+    /// * `format` - Output format
+    /// * `file` - File to write the timesheet to
+    #[instrument(skip(self))]
+    pub fn do_timesheet(
+        &self,
+        month: NaiveDate,
+        format: timesheet::Format,
+        file: &Path,
+    ) -> Result<()> {
+        let from = self.config.start_of_day(month);
+        let to = self.config.start_of_day(month + Months::new(1));
+        let report = self.report(Some(from), Some(to), true)?;
+
+        let document = match format {
+            timesheet::Format::Pdf => timesheet::pdf(&self.user_name()?, month, &report.days),
+        };
+        fs::write(file, document).with_context(|| format!("Writing {:?}", file))?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "timesheet-written",
+                month = month.format(&i18n::localize("%B %Y", &month)).to_string(),
+                total = format::duration(&report.total),
+                file = format!("{:?}", file)
+            )
+        )?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_audit_export(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        format: export::Format,
+    ) -> Result<()> {
+        let records = self.storage.audit_log().context("Reading audit log")?;
+        export::audit(&mut *self.out(), &records, from, to, format)
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_audit_verify(&self) -> Result<()> {
+        let records = self.storage.audit_log().context("Reading audit log")?;
+
+        match Audit::verify(&records) {
+            Ok(count) => {
+                writeln!(self.out(), "{}", tr!("audit-valid", count = count))?;
+                Ok(())
+            }
+            Err(id) => Err(anyhow!("Audit log is broken at record {} !", id)),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_chain_enable(&self) -> Result<()> {
+        if self.storage.is_chained()? {
+            return Err(anyhow!("Stamps are already hash-chained"));
+        }
+
+        self.storage.enable_chain().context("Enabling hash chain")?;
+
+        writeln!(self.out(), "{}", tr!("chain-enabled"))?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_verify_chain(&self) -> Result<()> {
+        if !self.storage.is_chained()? {
+            return Err(anyhow!(
+                "Stamps are not hash-chained ! (Enable it with `chain enable`)"
+            ));
+        }
+
+        match self
+            .storage
+            .verify_chain()
+            .context("Verifying hash chain")?
+        {
+            Ok(count) => {
+                writeln!(self.out(), "{}", tr!("chain-valid", count = count))?;
+                Ok(())
+            }
+            Err(id) => Err(anyhow!("Hash chain is broken at stamp {} !", id)),
+        }
+    }
+
+    /// Check the consistency of the work-log
+    ///
+    /// # Arguments
+    ///
+    /// * `explain` - Also print the commands fixing each problem
+    /// * `fix` - Split the sessions running past midnight first
+    #[instrument(skip(self))]
+    pub fn do_doctor(&self, explain: bool, fix: bool) -> Result<()> {
+        let mut problems = doctor::diagnose(&self.storage, &Utc::now(), &self.config)
+            .context("Checking the work-log")?;
+
+        let splits: Vec<_> = problems
+            .iter()
+            .filter_map(|problem| match problem {
+                doctor::Problem::OverMidnight { checkin, checkout } if fix => Some((
+                    checkin.clone(),
+                    doctor::midnights(&checkin.date, checkout, &self.config),
+                )),
+                _ => None,
+            })
+            .collect();
+        if !splits.is_empty() {
+            let count = self
+                .storage
+                .split_sessions(&splits)
+                .context("Splitting sessions at midnight")?;
+            writeln!(self.out(), "{}", tr!("doctor-split", count = count))?;
+            problems = doctor::diagnose(&self.storage, &Utc::now(), &self.config)
+                .context("Checking the work-log")?;
+        }
+
+        if let Some(first) = self.storage.first_stamp()? {
+            for violation in self.violations(&first.date)? {
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!("warning", message = violation.to_string())
+                )?;
+            }
+        }
+        if problems.is_empty() {
+            writeln!(self.out(), "{}", tr!("doctor-ok"))?;
+            return Ok(());
+        }
+
+        for problem in &problems {
+            writeln!(self.out(), "{}", problem)?;
+            if explain {
+                for fix in problem.fixes(self.config.daily_target) {
+                    writeln!(self.out(), "    {}", fix)?;
+                }
+            }
+        }
+
+        if !explain {
+            writeln!(self.out(), "{}", tr!("doctor-explain"))?;
+        }
+        Err(anyhow!("{} problem(s) found", problems.len()))
+    }
+
+    /// Check the work-log and fix the session left open, asking how
+    ///
+    /// The open session is kept, capped at a time or split by a break; the
+    /// stamps of the fix are inserted at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `ask` - Print a question and read the answer of the user
+    #[instrument(skip(self, ask))]
+    pub fn do_doctor_interactive(&self, ask: impl Fn(&str) -> Result<String>) -> Result<()> {
+        let now = Utc::now();
+        let problems =
+            doctor::diagnose(&self.storage, &now, &self.config).context("Checking the work-log")?;
+        if problems.is_empty() {
+            writeln!(self.out(), "{}", tr!("doctor-ok"))?;
+            return Ok(());
+        }
+
+        let mut unsolved = 0;
+        for problem in &problems {
+            writeln!(self.out(), "{}", problem)?;
+            let checkin = match problem {
+                doctor::Problem::OpenSession { checkin } => checkin,
+                _ => {
+                    for fix in problem.fixes(self.config.daily_target) {
+                        writeln!(self.out(), "    {}", fix)?;
+                    }
+                    unsolved += 1;
+                    continue;
+                }
+            };
+            self.out().flush()?;
+
+            let day = self.config.day_of(&checkin.date);
+            let time = |question: &str| -> Result<DateTime<Utc>> {
+                let answer = ask(question)?;
+                doctor::parse_time(&answer, day, &self.config.timezone)
+                    .ok_or_else(|| anyhow!("Invalid time {:?}, expected HH:MM", answer))
+            };
+            let question = |id: &str| format!("{} ", i18n::catalog().message(id, None));
+            let resolution = match ask(&question("doctor-resolution"))?.as_str() {
+                "c" => doctor::Resolution::Cap(time(&question("doctor-checkout-time"))?),
+                "b" => doctor::Resolution::Break(
+                    time(&question("doctor-break-start"))?,
+                    time(&question("doctor-break-end"))?,
+                ),
+                _ => doctor::Resolution::Keep,
+            };
+            let mut stamps = resolution
+                .stamps(checkin, &now)
+                .ok_or_else(|| anyhow!("The times must be within the open session"))?;
+            if stamps.is_empty() {
+                continue;
+            }
+
+            for stamp in &mut stamps {
+                stamp.source = Some(self.source);
+            }
+            self.storage
+                .insert_stamps(&mut stamps)
+                .context("Inserting the stamps")?;
+            for stamp in &stamps {
+                let date = stamp.date.format("%Y-%m-%d %H:%M").to_string();
+                let line = match stamp.in_out {
+                    InOut::In => tr!("doctor-checked-in", date = date, id = stamp.id.0),
+                    InOut::Out => tr!("doctor-checked-out", date = date, id = stamp.id.0),
+                };
+                writeln!(self.out(), "{}", line)?;
+            }
+        }
+
+        if unsolved > 0 {
+            writeln!(self.out(), "{}", tr!("doctor-unsolved"))?;
+            return Err(anyhow!("{} problem(s) left", unsolved));
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn do_site_build(&self, out_dir: &Path) -> Result<()> {
+        let sessions = match self.storage.first_stamp()? {
+            Some(first) => report::sessions(
+                &self.storage,
+                &first.date,
+                &(Utc::now() + Duration::days(1)),
+            )
+            .context("Reading sessions")?,
+            None => Vec::new(),
+        };
+
+        let projects = report::allocated_days(&self.storage, &sessions, &self.config)
+            .context("Reading allocations")?;
+        let count = site::build(&sessions, &projects, &self.config, out_dir)?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "site-written",
+                count = count,
+                dir = format!("{:?}", out_dir)
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Get the stamps dated in a window, in id order
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - Lower bound (included) of the stamps dates
+    /// * `to` - Upper bound (excluded) of the stamps dates
+    #[instrument(skip(self))]
+    pub fn stamps(&self, from: &DateTime<Utc>, to: &DateTime<Utc>) -> Result<Vec<Stamp>> {
+        self.storage
+            .stamp_range(from, to)
+            .and_then(|stamps| stamps.collect())
+            .context("Reading stamps")
+    }
+
+    /// Change the date and direction of a stamp
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Stamp to change
+    /// * `date` - New date of the stamp
+    /// * `in_out` - New direction of the stamp
+    ///
+    /// # Return
+    ///
+    /// The stamp as stored after the change
+    #[instrument(skip(self))]
+    pub fn edit_stamp(&self, id: StampId, date: DateTime<Utc>, in_out: InOut) -> Result<Stamp> {
+        let mut stamp = self.storage.get_stamp(id).context("Reading stamp")?;
+        stamp.date = date;
+        stamp.in_out = in_out;
+        self.storage
+            .update_stamp(&mut stamp)
+            .context("Updating stamp")?;
+
+        self.storage.get_stamp(id).context("Reading stamp")
+    }
+
+    /// Get the stamps updated since a previous pull, see [sync::pull]
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Cursor of the previous pull, None to get all the stamps
+    #[instrument(skip(self))]
+    pub fn sync_pull(&self, since: Option<&DateTime<Utc>>) -> Result<sync::Pull> {
+        // Taken before reading, so that concurrent changes are pulled next time
+        let cursor = Utc::now();
+        let bundle = sync::export(&self.storage, since).context("Reading stamps")?;
+
+        Ok(sync::Pull { cursor, bundle })
+    }
+
+    /// Merge the stamps pushed by another device, see [sync::push]
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle` - Stamps updated on the other device
+    #[instrument(skip(self, bundle))]
+    pub fn sync_push(&self, bundle: &sync::Bundle) -> Result<sync::Merge> {
+        sync::merge(&self.storage, bundle).context("Merging stamps")
+    }
+
+    /// Answer the HTTP API requests until the server fails, see [serve]
+    ///
+    /// # Arguments
+    ///
+    /// * `listen` - Address to listen on, e.g. `127.0.0.1:8080`
+    /// * `tls` - Certificate and private key files (PEM) to serve HTTPS, HTTP if None
+    #[instrument(skip(self))]
+    pub fn do_serve(&self, listen: &str, tls: Option<(&Path, &Path)>) -> Result<()> {
+        let token =
+            self.config.api_token.as_deref().ok_or_else(|| {
+                anyhow!("Set `api_token` in the configuration file to serve the API")
+            })?;
+        serve::check_token(token)?;
+
+        let server = match tls {
+            None => tiny_http::Server::http(listen),
+            #[cfg(feature = "tls")]
+            Some((certificate, private_key)) => tiny_http::Server::https(
+                listen,
+                tiny_http::SslConfig {
+                    certificate: std::fs::read(certificate).context("Reading certificate")?,
+                    private_key: std::fs::read(private_key).context("Reading private key")?,
+                },
+            ),
+            #[cfg(not(feature = "tls"))]
+            Some(_) => {
+                return Err(anyhow!(
+                    "HTTPS is requested, but this build lacks the `tls` feature"
+                ))
+            }
+        }
+        .map_err(|e| anyhow!("Listening on {}: {}", listen, e))?;
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "api-serving",
+                url = format!(
+                    "{}://{}",
+                    if tls.is_some() { "https" } else { "http" },
+                    listen
+                )
+            )
+        )?;
+        self.out().flush()?;
+        serve::run(self, token, &server)
+    }
+
+    /// Check in and out as the Wi-Fi network changes, see [crate::wifi], and
+    /// as the user leaves and comes back, see [crate::activity]
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between two readings of the network and input
+    /// * `metrics` - Address to answer the Prometheus metrics on, see
+    ///   [crate::metrics], with the `api_token` if configured
+    #[instrument(skip(self))]
+    pub fn do_daemon(&self, interval: Duration, metrics: Option<&str>) -> Result<()> {
+        use crate::activity::{IdleWatcher, Presence};
+        use crate::wifi::{self, Action};
+
+        if self.config.wifi.is_empty() && self.config.idle_after.is_none() {
+            return Err(anyhow!(
+                "Add `[[wifi]]` rules or `idle_after` to the configuration file to run the daemon"
+            ));
+        }
+        if !self.config.wifi.is_empty() && !cfg!(feature = "wifi") {
+            return Err(anyhow!(
+                "This build lacks the `wifi` feature of the `[[wifi]]` rules"
+            ));
+        }
+        let server = match metrics {
+            Some(listen) => Some(
+                tiny_http::Server::http(listen)
+                    .map_err(|e| anyhow!("Listening on {}: {}", listen, e))?,
+            ),
+            None => None,
+        };
+        let mut watcher = wifi::Watcher::new(self.config.wifi.clone());
+        let mut idle_watcher = self.config.idle_after.map(IdleWatcher::new);
+        if !self.config.wifi.is_empty() {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("wifi-watching", count = self.config.wifi.len())
+            )?;
+        }
+        if let Some(idle_after) = self.config.idle_after {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("idle-watching", after = format::duration(&idle_after))
+            )?;
+        }
+        if let Some(server) = &server {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!(
+                    "metrics-serving",
+                    url = format!("http://{}{}", server.server_addr(), crate::metrics::PATH)
+                )
+            )?;
+        }
+        self.out().flush()?;
+
+        // Check-in of the open session already warned about
+        let mut warned = None;
+        // Project of the session closed when leaving, resumed on return
+        let mut left_project: Option<String> = None;
+        loop {
+            self.wait_answering_metrics(server.as_ref(), interval)?;
+            let status = self.status()?;
+            if let Some(last) = status.last_stamp.filter(|s| s.in_out == InOut::In) {
+                if Utc::now() - last.date > Duration::hours(doctor::MAX_OPEN_HOURS)
+                    && warned != Some(last.id)
+                {
+                    self.warn(tr!(
+                        "wifi-open-session",
+                        date = last.date.format("%Y-%m-%d %H:%M").to_string()
+                    ));
+                    warned = Some(last.id);
+                }
+            }
+
+            if !self.config.wifi.is_empty() {
+                match wifi::current_ssid() {
+                    // Read again next time
+                    Err(e) => self.warn(tr!("wifi-error", error = format!("{:#}", e))),
+                    Ok(ssid) => {
+                        match watcher.update(ssid.as_deref(), status.checked_in, Utc::now()) {
+                            Some(Action::CheckIn(project)) => {
+                                self.set_command(&format!(
+                                    "daemon: connected to {}",
+                                    ssid.unwrap_or_default()
+                                ))?;
+                                self.do_checkin(false, project.as_deref(), &[], &[], None)?;
+                            }
+                            Some(Action::CheckOut) => {
+                                self.set_command("daemon: disconnected")?;
+                                self.do_checkout(None)?;
+                            }
+                            None => (),
+                        }
+                    }
+                }
+            }
+
+            if let Some(idle_watcher) = &mut idle_watcher {
+                let idle = match activity::idle_time() {
+                    Ok(idle) => idle,
+                    Err(e) => {
+                        self.warn(tr!("idle-error", error = format!("{:#}", e)));
+                        self.out().flush()?;
+                        continue;
+                    }
+                };
+                // Only idleness counts where the lock cannot be read
+                let locked = activity::is_locked().unwrap_or(false);
+                // The network may have just stamped
+                let status = self.status()?;
+                match idle_watcher.update(idle, locked, status.checked_in, Utc::now()) {
+                    Some(Presence::Away(since)) => {
+                        self.set_command("daemon: away")?;
+                        let checkin = status.last_stamp.filter(|s| s.in_out == InOut::In);
+                        left_project = match &checkin {
+                            Some(checkin) => self.whole_project(checkin)?,
+                            None => None,
+                        };
+                        let checkout = match checkin {
+                            Some(checkin) if since > checkin.date => {
+                                self.close_session(&checkin, since)?
+                            }
+                            // Away since before the check-in, e.g. made remotely
+                            _ => self.checkout(None)?.checkout,
+                        };
+                        writeln!(
+                            self.out(),
+                            "{}",
+                            tr!(
+                                "idle-checked-out",
+                                time = checkout.date.format("%H:%M").to_string()
+                            )
+                        )?;
+                    }
+                    Some(Presence::Back) => {
+                        self.set_command("daemon: back")?;
+                        self.do_checkin(false, left_project.take().as_deref(), &[], &[], None)?;
+                    }
+                    None => (),
+                }
+            }
+            self.out().flush()?;
+        }
+    }
+
+    /// Wait for some time, answering the metrics requests meanwhile
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - Server receiving the metrics requests, just sleep if None
+    /// * `time` - Time to wait
+    fn wait_answering_metrics(
+        &self,
+        server: Option<&tiny_http::Server>,
+        time: Duration,
+    ) -> Result<()> {
+        let until = std::time::Instant::now() + time.to_std()?;
+        let server = match server {
+            Some(server) => server,
+            None => {
+                std::thread::sleep(time.to_std()?);
+                return Ok(());
+            }
+        };
+        while let Some(left) = until.checked_duration_since(std::time::Instant::now()) {
+            if let Some(request) = server.recv_timeout(left).context("Receiving request")? {
+                serve::answer_metrics(self, self.config.api_token.as_deref(), request);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the daemon at every logon, see [crate::agent]
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between two readings of the daemon
+    /// * `metrics` - Address the daemon answers the metrics on, if any
+    /// * `user` - User the daemon tracks the time of, the default one if None
+    #[instrument(skip(self))]
+    pub fn do_daemon_install(
+        &self,
+        interval: Duration,
+        metrics: Option<&str>,
+        user: Option<&str>,
+    ) -> Result<()> {
+        let mut args = vec![
+            "daemon".to_string(),
+            "--interval".to_string(),
+            interval.num_seconds().to_string(),
+        ];
+        if let Some(metrics) = metrics {
+            args.extend(["--metrics".to_string(), metrics.to_string()]);
+        }
+        if let Some(user) = user {
+            args.extend(["--user".to_string(), user.to_string()]);
+        }
+        let location = agent::install(&args).context("Installing the daemon")?;
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("daemon-installed", location = location)
+        )?;
+        Ok(())
+    }
+
+    /// Stop starting the daemon at logon, see [crate::agent]
+    #[instrument(skip(self))]
+    pub fn do_daemon_uninstall(&self) -> Result<()> {
+        agent::uninstall().context("Uninstalling the daemon")?;
+        writeln!(self.out(), "{}", tr!("daemon-uninstalled"))?;
+        Ok(())
+    }
+
+    /// Get the project a session is allocated to whole, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `checkin` - The check-in of the session
+    fn whole_project(&self, checkin: &Stamp) -> Result<Option<String>> {
+        let allocations = match self.storage.allocation_range(checkin.id, checkin.id) {
+            Err(DbError::Unsupported(_)) => Vec::new(),
+            other => other.context("Reading allocations")?,
+        };
+        Ok(match allocations.as_slice() {
+            [allocation] if allocation.fraction >= 1.0 => Some(allocation.target.clone()),
+            _ => None,
+        })
+    }
+
+    /// Answer the D-Bus method calls, see [crate::dbus]
+    #[cfg(feature = "dbus")]
+    #[instrument(skip(self))]
+    pub fn do_dbus(&self) -> Result<()> {
+        writeln!(
+            self.out(),
+            "{}",
+            tr!("dbus-serving", name = crate::dbus::NAME)
+        )?;
+        self.out().flush()?;
+        crate::dbus::run(self)
+    }
+
+    /// Get the worked time per day in a date window
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - First day of the report, defaults to the beginning of the week
+    /// * `to` - Day after the report, defaults to tomorrow
+    /// * `archived` - Include the archived stamps
+    #[instrument(skip(self))]
+    pub fn report(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        archived: bool,
+    ) -> Result<report::Report> {
+        let now = Utc::now();
+        let from = from.unwrap_or_else(|| self.config.beginning_of_week(&now));
+        let to = to.unwrap_or_else(|| {
+            self.config
+                .start_of_day(self.config.day_of(&now) + Duration::days(1))
+        });
+
+        let mut sessions = if archived {
+            report::sessions_with_archive(&self.storage, &from, &to)
+        } else {
+            report::sessions(&self.storage, &from, &to)
+        }
+        .context("Reading sessions")?;
+        if let Some(source) = self.source_filter {
+            let stamps = if archived {
+                self.storage.stamp_range_with_archive(&from, &to)
+            } else {
+                self.storage.stamp_range(&from, &to)
+            };
+            report::retain_source(&mut sessions, stamps?, source)
+                .context("Reading the stamp sources")?;
+        }
+
+        let report = report::Report::new(from, to, sessions, &self.config);
+        match &self.project {
+            Some(project) => report
+                .for_project(&self.storage, project, &self.config)
+                .context("Reading allocations"),
+            None => Ok(report),
+        }
+    }
+
+    /// Log the sessions of a date window on a Jira issue, see [push]
+    ///
+    /// The server is given by `jira_url`, the credentials by `jira_user` and
+    /// `jira_token` in the configuration, the token being otherwise looked up
+    /// in the keyring with `secret-tool lookup application wtime service jira`.
+    ///
+    /// # Arguments
+    ///
+    /// * `issue` - Issue key, e.g. `PROJ-1`
+    /// * `from` - First day of the sessions, defaults to the beginning of the week
+    /// * `to` - Day after the sessions, defaults to tomorrow
+    #[instrument(skip(self))]
+    pub fn do_push_jira(
+        &self,
+        issue: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let url =
+            self.config.jira_url.as_deref().ok_or_else(|| {
+                anyhow!("Set `jira_url` in the configuration file to push to Jira")
+            })?;
+        let token = self
+            .config
+            .jira_token
+            .clone()
+            .or_else(|| keyring_lookup(&[("service", push::JIRA)]))
+            .ok_or_else(|| {
+                anyhow!("Set `jira_token` in the configuration file or the keyring to push to Jira")
+            })?;
+        let jira = push::Jira::new(url, self.config.jira_user.as_deref(), token.trim());
+
+        let report = self.report(from, to, false)?;
+        let worklogs = push::jira(&self.storage, &jira, issue, &report.sessions)
+            .with_context(|| format!("Pushing to {}", issue))?;
+
+        let total = worklogs.iter().fold(Duration::zero(), |total, w| {
+            total + Duration::seconds(w.seconds)
+        });
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "pushed-jira",
+                count = worklogs.len(),
+                issue = issue,
+                total = format::duration(&total)
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Log the sessions of a date window on a GitLab issue, see [push]
+    ///
+    /// The token is given by `gitlab_token` in the configuration, otherwise
+    /// looked up in the keyring with
+    /// `secret-tool lookup application wtime service gitlab`.
+    ///
+    /// # Arguments
+    ///
+    /// * `issue_url` - Web URL of the issue, e.g. `https://gitlab.com/group/project/-/issues/42`
+    /// * `from` - First day of the sessions, defaults to the beginning of the week
+    /// * `to` - Day after the sessions, defaults to tomorrow
+    #[instrument(skip(self))]
+    pub fn do_push_gitlab(
+        &self,
+        issue_url: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let token = self
+            .config
+            .gitlab_token
+            .clone()
+            .or_else(|| keyring_lookup(&[("service", push::GITLAB)]))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Set `gitlab_token` in the configuration file or the keyring to push to GitLab"
+                )
+            })?;
+        let issue = push::GitLab::new(issue_url, token.trim())?;
+
+        let report = self.report(from, to, false)?;
+        let worklogs = push::gitlab(&self.storage, &issue, &report.sessions)
+            .with_context(|| format!("Pushing to {}", issue.reference()))?;
+
+        let total = worklogs.iter().fold(Duration::zero(), |total, w| {
+            total + Duration::seconds(w.seconds)
+        });
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "pushed-gitlab",
+                count = worklogs.len(),
+                issue = issue.reference(),
+                total = format::duration(&total)
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Get the worked time of every user in a date window, see [App::report]
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - First day of the report, defaults to the beginning of the week
+    /// * `to` - Day after the report, defaults to tomorrow
+    /// * `archived` - Include the archived stamps
+    #[instrument(skip(self))]
+    pub fn team_report(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        archived: bool,
+    ) -> Result<report::TeamReport> {
+        let selected = self.storage.current_user().context("Reading users")?;
+        let users = self.storage.users().context("Reading users")?;
+
+        let mut reports = Vec::new();
+        for user in users {
+            self.storage
+                .select_user(&user.name)
+                .context("Selecting user")?;
+            let report = self.report(from, to, archived);
+            reports.push((user, report));
+        }
+        // Back to the user of the command, even after a failure
+        self.storage
+            .select_user(&selected.name)
+            .context("Selecting user")?;
+
+        let users = reports
+            .into_iter()
+            .map(|(user, report)| Ok((user, report?)))
+            .collect::<Result<_>>()?;
+        Ok(report::TeamReport { users })
+    }
+
+    /// Print the worked time per user and per day in a date window
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - First day of the report, defaults to the beginning of the week
+    /// * `to` - Day after the report, defaults to tomorrow
+    /// * `archived` - Include the archived stamps
+    #[instrument(skip(self))]
+    pub fn do_team_report(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        archived: bool,
+    ) -> Result<()> {
+        let team = self.team_report(from, to, archived)?;
+        let widths: Vec<_> = team
+            .users
+            .iter()
+            .map(|(user, _)| user.name.len().max(8))
+            .collect();
+
+        write!(self.out(), "{:<14}", "")?;
+        for ((user, _), width) in team.users.iter().zip(&widths) {
+            write!(self.out(), "  {:>width$}", user.name)?;
+        }
+        writeln!(self.out(), "  {:>8}", tr!("total"))?;
+
+        for day in team.days() {
+            write!(self.out(), "{}", format_day(day))?;
+            for ((_, report), width) in team.users.iter().zip(&widths) {
+                let total = report
+                    .days
+                    .get(&day)
+                    .copied()
+                    .unwrap_or_else(Duration::zero);
+                write!(self.out(), "  {:>width$}", format::duration(&total))?;
+            }
+            writeln!(
+                self.out(),
+                "  {:>8}",
+                format::duration(&team.day_total(&day))
+            )?;
+        }
+
+        write!(self.out(), "{:<14}", tr!("total"))?;
+        for ((_, report), width) in team.users.iter().zip(&widths) {
+            write!(self.out(), "  {:>width$}", format::duration(&report.total))?;
+        }
+        writeln!(self.out(), "  {:>8}", format::duration(&team.total()))?;
+        Ok(())
+    }
+
+    /// Print the timesheet grid of a week
+    ///
+    /// Every day gets its first check-in, last check-out, breaks, worked time
+    /// and overtime against the daily target, see [Config::day_target]. The
+    /// week total is compared to the weekly target.
+    ///
+    /// # Arguments
+    ///
+    /// * `monday` - First day of the ISO week
+    #[instrument(skip(self))]
+    pub fn do_week(&self, monday: NaiveDate) -> Result<()> {
+        let from = self.config.start_of_day(monday);
+        let to = self.config.start_of_day(monday + Duration::days(7));
+        let report = self.report(Some(from), Some(to), true)?;
+        let spans = report::day_spans(&report.sessions, &self.config);
+
+        writeln!(
+            self.out(),
+            "{:<14}  {:>5}  {:>5}  {:>8}  {:>8}  {:>8}",
+            tr!("week", week = monday.format("%G-W%V").to_string()),
+            tr!("start"),
+            tr!("end"),
+            tr!("breaks"),
+            tr!("total"),
+            tr!("overtime")
+        )?;
+        let mut breaks = Duration::zero();
+        for day in monday.iter_days().take(7) {
+            let target = self.config.day_target(day);
+            let Some(span) = spans.get(&day) else {
+                writeln!(
+                    self.out(),
+                    "{}  {:>5}  {:>5}  {:>8}  {:>8}  {:>8}",
+                    format_day(day),
+                    "-",
+                    "-",
+                    "-",
+                    "-",
+                    format::delta(&-target)
+                )?;
+                continue;
+            };
+            let worked = report.days[&day];
+            breaks = breaks + span.breaks();
+            writeln!(
+                self.out(),
+                "{}  {:>5}  {:>5}  {:>8}  {:>8}  {:>8}",
+                format_day(day),
+                span.start.format("%H:%M"),
+                span.end.format("%H:%M"),
+                format::duration(&span.breaks()),
+                format::duration(&worked),
+                format::delta(&(worked - target))
+            )?;
+        }
+        writeln!(
+            self.out(),
+            "{:<14}  {:>5}  {:>5}  {:>8}  {:>8}  {:>8}",
+            tr!("total"),
+            "",
+            "",
+            format::duration(&breaks),
+            format::duration(&report.total),
+            format::delta(&(report.total - self.config.weekly_target))
+        )?;
+        Ok(())
+    }
+
+    /// Print the calendar of a month, with the worked hours of every day
+    ///
+    /// With colors, the days reaching their target are green, the days
+    /// short of it yellow and the past days not worked red. Saturday, Sunday
+    /// and the holidays are dimmed.
+    ///
+    /// # Arguments
+    ///
+    /// * `month` - First day of the month
+    /// * `color` - Color the days with ANSI escape codes
+    #[instrument(skip(self))]
+    pub fn do_month(&self, month: NaiveDate, color: bool) -> Result<()> {
+        const GREEN: &str = "\x1b[32m";
+        const YELLOW: &str = "\x1b[33m";
+        const RED: &str = "\x1b[31m";
+        const DIM: &str = "\x1b[2m";
+
+        let from = self.config.start_of_day(month);
+        let to = self.config.start_of_day(month + Months::new(1));
+        let report = self.report(Some(from), Some(to), true)?;
+        let today = self.today();
+        let paint = |text: String, code: &str| {
+            if color {
+                format!("{}{}\x1b[0m", code, text)
+            } else {
+                text
+            }
+        };
+
+        let title = format!(
+            "{:^49}",
+            month.format(&i18n::localize("%B %Y", &month)).to_string()
+        );
+        writeln!(self.out(), "{}", title.trim_end())?;
+        let weekdays: String = (1..=7)
+            .map(|number| format!("{:>7}", tr!("weekday-short", number = number)))
+            .collect();
+        writeln!(self.out(), "{}", weekdays)?;
+        let last = month + Months::new(1) - Duration::days(1);
+        let mut monday = month - Duration::days(month.weekday().num_days_from_monday().into());
+        let mut target = Duration::zero();
+        while monday <= last {
+            let mut numbers = String::new();
+            let mut hours = String::new();
+            for day in monday.iter_days().take(7) {
+                if day.month() != month.month() {
+                    numbers.push_str("       ");
+                    hours.push_str("       ");
+                    continue;
+                }
+
+                let day_target = self.config.day_target(day);
+                target = target + day_target;
+                let worked = report
+                    .days
+                    .get(&day)
+                    .copied()
+                    .unwrap_or_else(Duration::zero);
+                let cell = if !worked.is_zero() {
+                    format!("{:>6.1}h", worked.num_minutes() as f64 / 60.0)
+                } else if self.config.holidays.contains(&day) {
+                    format!("{:>7}", tr!("calendar-off"))
+                } else if day_target.is_zero() || day > today {
+                    " ".repeat(7)
+                } else {
+                    format!("{:>7}", "-")
+                };
+                let code = if day_target.is_zero() {
+                    DIM
+                } else if worked >= day_target {
+                    GREEN
+                } else if !worked.is_zero() {
+                    YELLOW
+                } else if day <= today {
+                    RED
+                } else {
+                    ""
+                };
+                numbers.push_str(&paint(format!("{:>7}", day.day()), code));
+                hours.push_str(&paint(cell, code));
+            }
+            writeln!(self.out(), "{}", numbers.trim_end())?;
+            writeln!(self.out(), "{}", hours.trim_end())?;
+            monday += Duration::days(7);
+        }
+
+        writeln!(
+            self.out(),
+            "{}",
+            tr!(
+                "calendar-total",
+                total = format::duration(&report.total),
+                delta = format::delta(&(report.total - target))
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Print the summary of a year of work, see [stats::year]
+    ///
+    /// # Arguments
+    ///
+    /// * `year` - Year to sum up
+    #[instrument(skip(self))]
+    pub fn do_stats_year(&self, year: i32) -> Result<()> {
+        let from = Utc
+            .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
+            .single()
+            .context("Invalid year")?;
+        let report = self.report(Some(from), Some(from + Months::new(12)), true)?;
+        let stats = stats::year(year, &report, &self.config);
+
+        writeln!(self.out(), "{}", tr!("stats-title", year = year))?;
+        writeln!(
+            self.out(),
+            "  {}",
+            tr!(
+                "stats-total",
+                total = format::duration(&stats.total),
+                days = stats.days
+            )
+        )?;
+        if let Some((week, total)) = &stats.busiest_week {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "stats-busiest-week",
+                    week = week.as_str(),
+                    total = format::duration(total)
+                )
+            )?;
+        }
+        if let (Some(start), Some(end)) = (stats.average_start, stats.average_end) {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "stats-average-day",
+                    start = start.format("%H:%M").to_string(),
+                    end = end.format("%H:%M").to_string()
+                )
+            )?;
+        }
+        if let Some(longest) = &stats.longest {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "stats-longest",
+                    worked = format::duration(&longest.duration()),
+                    day = format_day(longest.day(&self.config))
+                )
+            )?;
+        }
+        writeln!(
+            self.out(),
+            "  {}",
+            tr!("stats-days-off", count = stats.days_off)
+        )?;
+        Ok(())
+    }
+
+    /// Print the habits of the last weeks, see [stats::habits]
+    ///
+    /// # Arguments
+    ///
+    /// * `weeks` - Number of weeks ending today
+    #[instrument(skip(self))]
+    pub fn do_stats_habits(&self, weeks: u32) -> Result<()> {
+        let today = self.today();
+        let first = today - Duration::weeks(weeks.into()) + Duration::days(1);
+        let from = self.config.start_of_day(first);
+        let to = self.config.start_of_day(today + Duration::days(1));
+        let report = self.report(Some(from), Some(to), true)?;
+        let habits = stats::habits(&report, first, today, &self.config);
+
+        writeln!(self.out(), "{}", tr!("habits-title", weeks = weeks))?;
+        writeln!(
+            self.out(),
+            "  {}",
+            tr!("habits-current-streak", days = habits.current_streak)
+        )?;
+        if let Some((start, end, length)) = habits.longest_streak {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "habits-longest-streak",
+                    days = length,
+                    from = format_day(start),
+                    to = format_day(end)
+                )
+            )?;
+            if length == habits.current_streak && length > 1 {
+                writeln!(self.out(), "  {}", tr!("habits-record"))?;
+            }
+        }
+        if let Some((day, time)) = habits.earliest_checkin {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "habits-earliest",
+                    time = time.format("%H:%M").to_string(),
+                    day = format_day(day)
+                )
+            )?;
+        }
+        if let Some((day, time)) = habits.latest_checkin {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "habits-latest",
+                    time = time.format("%H:%M").to_string(),
+                    day = format_day(day)
+                )
+            )?;
+        }
+        if habits.weeks > 0 {
+            writeln!(
+                self.out(),
+                "  {}",
+                tr!(
+                    "habits-steady-weeks",
+                    count = habits.steady_weeks,
+                    weeks = habits.weeks
+                )
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the 7 and 30 days rolling averages of the last weeks
+    ///
+    /// A line is printed per week, ending today, flagged when the 7 days
+    /// average starts rising or falling against the 30 days one, see
+    /// [stats::Trend].
+    ///
+    /// # Arguments
+    ///
+    /// * `weeks` - Number of weeks to print
+    #[instrument(skip(self))]
+    pub fn do_stats_trend(&self, weeks: u32) -> Result<()> {
+        let today = self.today();
+        let first = today - Duration::weeks(weeks.into()) + Duration::days(1);
+        let from = self.config.start_of_day(first - Duration::days(30));
+        let to = self.config.start_of_day(today + Duration::days(1));
+        let report = self.report(Some(from), Some(to), true)?;
+        let working = |day| !self.config.day_target(day).is_zero();
+
+        writeln!(
+            self.out(),
+            "{:<14}  {:>8}  {:>8}",
+            tr!("trend-week-ending"),
+            tr!("trend-days", days = 7),
+            tr!("trend-days", days = 30)
+        )?;
+        let mut previous = stats::Trend::Steady;
+        let mut averages = (Duration::zero(), Duration::zero());
+        for week in (0..weeks).rev() {
+            let last = today - Duration::weeks(week.into());
+            averages = (
+                stats::rolling_average(&report.days, last, 7, working),
+                stats::rolling_average(&report.days, last, 30, working),
+            );
+            let trend = stats::Trend::of(averages.0, averages.1);
+            let flag = match trend {
+                _ if trend == previous => String::new(),
+                stats::Trend::Rising => format!("  {}", tr!("trend-rising")),
+                stats::Trend::Falling => format!("  {}", tr!("trend-falling")),
+                stats::Trend::Steady => format!("  {}", tr!("trend-steady")),
+            };
+            previous = trend;
+            writeln!(
+                self.out(),
+                "{}  {:>8}  {:>8}{}",
+                format_day(last),
+                format::duration(&averages.0),
+                format::duration(&averages.1),
+                flag
+            )?;
+        }
+
+        let target = self.config.daily_target;
+        if averages.0 > target && averages.1 > target {
+            writeln!(
+                self.out(),
+                "{}",
+                tr!("trend-creeping", target = format::duration(&target))
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the worked time per day in a date window
+    ///
+    /// The nearly exhausted budgets of the reported projects are warned
+    /// about on stderr.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - First day of the report, defaults to the beginning of the week
+    /// * `to` - Day after the report, defaults to tomorrow
+    /// * `by_allocation` - Also print the worked time per allocated issue
+    /// * `by_project` - Also print the worked time per project over these
+    ///   periods, with the share of every project
+    /// * `by_tag` - Also print the worked time per tag, see [report::tagged_totals]
+    /// * `archived` - Include the archived stamps
+    /// * `bars` - Draw the text report days as bars and the weeks as
+    ///   sparklines with these characters
+    /// * `format` - Output format, see [render]
+    /// * `file` - File to write the report to instead of printing it
+    /// * `copy` - Copy the report to the clipboard instead of printing it
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self))]
+    pub fn do_report(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        by_allocation: bool,
+        by_project: Option<report::Period>,
+        by_tag: bool,
+        archived: bool,
+        bars: Option<render::Glyphs>,
+        format: render::ReportFormat,
+        file: Option<&Path>,
+        copy: bool,
+    ) -> Result<()> {
+        let report = self.report(from, to, archived)?;
+        let mut document = Vec::new();
+        match format {
+            render::ReportFormat::Text => self.text_report(
+                &mut document,
+                &report,
+                by_allocation,
+                by_project,
+                by_tag,
+                bars,
+            )?,
+            render::ReportFormat::Html => {
+                let projects = report
+                    .allocated_days(&self.storage, &self.config)
+                    .context("Reading allocations")?;
+                let html = render::html(&self.user_name()?, &report, &projects, &self.config);
+                document.extend(html.into_bytes());
+            }
+            render::ReportFormat::Markdown => {
+                let projects = report
+                    .allocated_days(&self.storage, &self.config)
+                    .context("Reading allocations")?;
+                let notes = self
+                    .storage
+                    .adjustment_range(&report.from, &report.to)
+                    .context("Reading adjustments")?;
+                let markdown =
+                    render::markdown(&self.user_name()?, &report, &projects, &notes, &self.config);
+                document.extend(markdown.into_bytes());
+            }
+        }
+
+        match file {
+            Some(file) => {
+                fs::write(file, document).with_context(|| format!("Writing {:?}", file))?;
+                writeln!(
+                    self.out(),
+                    "{}",
+                    tr!("report-written", file = format!("{:?}", file))
+                )?;
+            }
+            None if copy => {
+                clipboard::copy(&String::from_utf8_lossy(&document))?;
+                writeln!(self.out(), "{}", tr!("report-copied"))?;
+            }
+            None => self.out().write_all(&document)?,
+        }
+
+        if !self.config.budgets.is_empty() {
+            let projects = report
+                .allocated(&self.storage)
+                .context("Reading allocations")?;
+            self.warn_budgets(projects.keys())?;
+        }
+        Ok(())
+    }
+
+    /// Write the plain text report of [App::do_report]
+    fn text_report(
+        &self,
+        out: &mut impl Write,
+        report: &report::Report,
+        by_allocation: bool,
+        by_project: Option<report::Period>,
+        by_tag: bool,
+        bars: Option<render::Glyphs>,
+    ) -> Result<()> {
+        for (day, day_total) in &report.days {
+            write!(
+                out,
+                "{}  {:>8}",
+                format_day(*day),
+                format::duration(day_total)
+            )?;
+            match bars {
+                Some(glyphs) => writeln!(out, "  {}", glyphs.bar(day_total))?,
+                None => writeln!(out)?,
+            }
+        }
+        writeln!(
+            out,
+            "{:<14}  {:>8}",
+            tr!("total"),
+            format::duration(&report.total)
+        )?;
+
+        if let Some(glyphs) = bars {
+            // Days of every week from Monday, for a sparkline per week
+            let mut weeks: BTreeMap<String, [Duration; 7]> = BTreeMap::new();
+            for (day, day_total) in &report.days {
+                let week = weeks
+                    .entry(day.format("%G-W%V").to_string())
+                    .or_insert([Duration::zero(); 7]);
+                week[day.weekday().num_days_from_monday() as usize] = *day_total;
+            }
+
+            writeln!(out)?;
+            // Same scale for all the weeks
+            let days: Vec<Duration> = weeks.values().flatten().copied().collect();
+            let sparkline: Vec<char> = glyphs.sparkline(&days).chars().collect();
+            for ((week, days), line) in weeks.iter().zip(sparkline.chunks(7)) {
+                let total = days.iter().fold(Duration::zero(), |total, d| total + *d);
+                writeln!(
+                    out,
+                    "{:<14}  {:>8}  {}",
+                    tr!("week", week = week.as_str()),
+                    format::duration(&total),
+                    line.iter().collect::<String>().trim_end()
+                )?;
+            }
+        }
+
+        if by_allocation {
+            writeln!(out)?;
+            let totals = report
+                .allocated(&self.storage)
+                .context("Reading allocations")?;
+            shares_table(out, &totals, &report.total)?;
+        }
+
+        if by_tag {
+            writeln!(out)?;
+            let totals =
+                report::tagged_totals(&self.storage, &report.sessions).context("Reading tags")?;
+            shares_table(out, &totals, &report.total)?;
+
+            let actuals = self.reported_estimates(true, &totals)?;
+            if !actuals.is_empty() {
+                writeln!(out)?;
+                estimates_table(out, &tr!("estimate-tag"), &actuals)?;
+            }
+        }
+
+        if let Some(period) = by_project {
+            let days = report
+                .allocated_days(&self.storage, &self.config)
+                .context("Reading allocations")?;
+            let parents = report::project_parents(&self.storage).context("Reading projects")?;
+            // The ancestors of the project a report is restricted to are left out
+            let within = |project: &String| {
+                report
+                    .project
+                    .as_ref()
+                    .is_none_or(|restricted| report::is_within(project, restricted, &parents))
+            };
+            let mut projects = report::roll_up(
+                &report
+                    .allocated(&self.storage)
+                    .context("Reading allocations")?,
+                &parents,
+            );
+            projects.retain(|project, _| within(project));
+            let width = report::project_tree(projects.keys(), &parents)
+                .into_iter()
+                .map(|(depth, project)| tree_label(depth, project, &parents).len())
+                .max()
+                .unwrap_or(0);
+            for (start, totals) in report::period_totals(&days, period) {
+                let period_total = totals
+                    .values()
+                    .fold(Duration::zero(), |total, d| total + *d);
+                let mut totals = report::roll_up(&totals, &parents);
+                totals.retain(|project, _| within(project));
+                writeln!(out)?;
+                writeln!(
+                    out,
+                    "{:<w$}  {:>8}",
+                    period.label(start),
+                    format::duration(&period_total),
+                    w = width + 2
+                )?;
+                for (depth, project) in report::project_tree(totals.keys(), &parents) {
+                    writeln!(
+                        out,
+                        "  {:<width$}  {:>8}  {:>3.0}%",
+                        tree_label(depth, project, &parents),
+                        format::duration(&totals[project]),
+                        percent(&totals[project], &period_total)
+                    )?;
+                }
+            }
+
+            let actuals = self.reported_estimates(false, &projects)?;
+            if !actuals.is_empty() {
+                writeln!(out)?;
+                estimates_table(out, &tr!("estimate-project"), &actuals)?;
+            }
+
+            let budgets: BTreeMap<String, Duration> = self
+                .config
+                .budgets
+                .iter()
+                .filter(|(project, _)| projects.contains_key(*project))
+                .map(|(project, budget)| (project.clone(), *budget))
+                .collect();
+            let budgets = report::budget_use(&self.storage, &budgets).context("Reading budgets")?;
+            if !budgets.is_empty() {
+                writeln!(out)?;
+                writeln!(out, "{}", tr!("budgets"))?;
+            }
+            for budget in budgets {
+                let line = if budget.remaining() < Duration::zero() {
+                    tr!(
+                        "budget-exceeded",
+                        used = format::duration(&budget.used),
+                        budget = format::duration(&budget.budget),
+                        over = format::duration(&-budget.remaining())
+                    )
+                } else {
+                    tr!(
+                        "budget-remaining",
+                        used = format::duration(&budget.used),
+                        budget = format::duration(&budget.budget),
+                        remaining = format::duration(&budget.remaining())
+                    )
+                };
+                writeln!(out, "  {:<width$}  {}", budget.project, line)?;
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::App;
+    use crate::config::Config;
+    use crate::db::{Adjustment, Allocation, InOut, Stamp, StampId, Tag};
+    use crate::storage::Storage;
+    use crate::{import, report};
+    use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+    use now::DateTimeNow;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn checkin_checkout() {
+        let app = App::in_memory().unwrap();
+
+        // Can't check-in twice
+        app.do_checkin(false, None, &[], &[], None).unwrap();
+        assert!(app.do_checkin(false, None, &[], &[], None).is_err());
+
+        // Nor check-out twice
+        app.do_checkout(None).unwrap();
+        assert!(app.do_checkout(None).is_err());
+
+        app.do_list().unwrap();
+    }
+
+    /// Writer whose content can be read while owned by an [App]
+    #[derive(Clone, Default)]
+    struct Captured(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn week_grid() {
+        let captured = Captured::default();
+        let app = App::in_memory().unwrap().with_output(captured.clone());
+        for (date, in_out) in [
+            ("2024-02-12T08:00:00Z", InOut::In),
+            ("2024-02-12T12:00:00Z", InOut::Out),
+            ("2024-02-12T13:00:00Z", InOut::In),
+            ("2024-02-12T17:30:00Z", InOut::Out),
+        ] {
+            app.storage
+                .insert_stamp(&mut Stamp::new(
+                    StampId(0),
+                    DateTime::<Utc>::from_str(date).unwrap(),
+                    in_out,
+                ))
+                .unwrap();
+        }
+
+        app.do_week(NaiveDate::from_ymd_opt(2024, 2, 12).unwrap())
+            .unwrap();
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with("Week 2024-W07"));
+        assert_eq!(
+            lines[1],
+            "Mon 2024-02-12  08:00  17:30    1h 00m    8h 30m   +0h 30m"
+        );
+        assert!(lines[2].ends_with("-8h 00m"));
+        assert!(lines[7].ends_with("+0h 00m"));
+        assert!(lines[8].ends_with("-31h 30m"));
+    }
+
+    #[test]
+    fn watch_line() {
+        let app = App::in_memory().unwrap();
+        let date = |date: &str| DateTime::<Utc>::from_str(date).unwrap();
+        for (stamp, in_out) in [
+            ("2024-02-11T22:00:00Z", InOut::In),
+            ("2024-02-12T02:00:00Z", InOut::Out),
+            ("2024-02-12T13:00:00Z", InOut::In),
+        ] {
+            app.storage
+                .insert_stamp(&mut Stamp::new(StampId(0), date(stamp), in_out))
+                .unwrap();
+        }
+
+        assert_eq!(
+            app.watch_line(&date("2024-02-12T13:30:05Z")).unwrap(),
+            "Session 0:30:05, today 0h 30m"
+        );
+
+        app.storage
+            .insert_stamp(&mut Stamp::new(
+                StampId(0),
+                date("2024-02-12T14:00:00Z"),
+                InOut::Out,
+            ))
+            .unwrap();
+        assert_eq!(
+            app.watch_line(&date("2024-02-12T15:00:00Z")).unwrap(),
+            "Checked out, today 1h 00m"
+        );
+    }
+
+    #[test]
+    fn month_calendar() {
+        let captured = Captured::default();
+        let config = Config {
+            holidays: vec![NaiveDate::from_ymd_opt(2024, 2, 14).unwrap()],
+            ..Config::default()
+        };
+        let app = App::in_memory()
+            .unwrap()
+            .with_config(config)
+            .with_output(captured.clone());
+        for (date, in_out) in [
+            ("2024-02-01T08:00:00Z", InOut::In),
+            ("2024-02-01T16:30:00Z", InOut::Out),
+        ] {
+            app.storage
+                .insert_stamp(&mut Stamp::new(
+                    StampId(0),
+                    DateTime::<Utc>::from_str(date).unwrap(),
+                    in_out,
+                ))
+                .unwrap();
+        }
+
+        app.do_month(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(), false)
+            .unwrap();
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0].trim(), "February 2024");
+        // February 2024 starts on a Thursday, over 5 weeks
+        assert_eq!(lines.len(), 2 + 5 * 2 + 1);
+        assert_eq!(lines[2], format!("{:>28}      2      3      4", 1));
+        assert_eq!(lines[3], format!("{:>28}      -", "8.5h"));
+        assert!(lines[7].ends_with("    off      -      -"));
+        // 20 working days besides the holiday
+        assert_eq!(lines[12], "Total: 8h 30m (-151h 30m to target)");
+    }
+
+    #[test]
+    fn output() {
+        let captured = Captured::default();
+        let app = App::in_memory().unwrap().with_output(captured.clone());
+
+        app.do_checkin(false, None, &[], &[], None).unwrap();
+        app.do_checkout(None).unwrap();
+
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        assert!(output.starts_with("Checked in at "));
+        assert!(output.contains("Checked out at "));
+    }
+
+    #[test]
+    fn summaries() {
+        let app = App::in_memory().unwrap();
+        let mut checkin = Stamp::check_in();
+        checkin.date -= Duration::hours(1);
+        app.storage.insert_stamp(&mut checkin).unwrap();
+
+        // Still checked in after an invalid split
+        assert!(app.checkout(Some("75 PROJ-1")).is_err());
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), checkin);
+
+        let summary = app.checkout(Some("75% PROJ-1")).unwrap();
+        let session = summary.session.unwrap();
+        assert_eq!(session.id, checkin.id);
+        assert_eq!(session.duration(), Duration::hours(1));
+        assert_eq!(summary.allocations.len(), 1);
+        assert_eq!(summary.allocations[0].target, "PROJ-1");
+        assert!(summary.resume.week_total >= Duration::hours(1));
+
+        let work = app
+            .summary(checkin.date..checkin.date + Duration::minutes(1))
+            .unwrap();
+        assert_eq!(work.total(), Duration::hours(1));
+        let work = app
+            .summary(checkin.date - Duration::days(1)..checkin.date)
+            .unwrap();
+        assert_eq!(work.total(), Duration::zero());
+
+        // A back-dated correction counts in the period it corrects
+        let mut adjustment = Adjustment::new(
+            Duration::minutes(30),
+            "Call not stamped",
+            Some(checkin.date - Duration::hours(12)),
+        );
+        app.storage.insert_adjustment(&mut adjustment).unwrap();
+        let work = app
+            .summary(checkin.date - Duration::days(1)..checkin.date)
+            .unwrap();
+        assert_eq!(work.adjusted, Duration::minutes(30));
+        let work = app
+            .summary(checkin.date..checkin.date + Duration::minutes(1))
+            .unwrap();
+        assert_eq!(work.total(), Duration::hours(1));
+    }
+
+    #[test]
+    fn overtime() {
+        let config = Config {
+            daily_target: Duration::zero(),
+            weekly_target: Duration::zero(),
+            overtime_threshold: Duration::hours(24),
+            ..Config::default()
+        };
+        let app = App::in_memory().unwrap().with_config(config.clone());
+        let mut checkin = Stamp::check_in();
+        checkin.date = Utc::now().beginning_of_day();
+        app.storage.insert_stamp(&mut checkin).unwrap();
+        assert!(!app.checkout(None).unwrap().overtime);
+
+        // Anything beyond the targets
+        let config = Config {
+            overtime_threshold: Duration::zero(),
+            ..config
+        };
+        let app = App::in_memory().unwrap().with_config(config);
+        let mut checkin = Stamp::check_in();
+        checkin.date = Utc::now().beginning_of_day();
+        app.storage.insert_stamp(&mut checkin).unwrap();
+        let summary = app.checkout(None).unwrap();
+        assert!(summary.overtime);
+        assert!(serde_json::to_string(&summary)
+            .unwrap()
+            .contains("\"overtime\":true"));
+    }
+
+    #[test]
+    fn status_warnings() {
+        let mut config = Config::default();
+        config.limits.max_without_break = Some(Duration::hours(6));
+        let app = App::in_memory().unwrap().with_config(config);
+        let mut checkin = Stamp::check_in();
+        checkin.date -= Duration::hours(7);
+        app.storage.insert_stamp(&mut checkin).unwrap();
+
+        // The open session counts until now
+        let status = app.status().unwrap();
+        assert_eq!(status.warnings.len(), 1);
+        assert!(status.warnings[0].starts_with("7h 00m worked without a break"));
+
+        assert!(App::in_memory()
+            .unwrap()
+            .status()
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn doctor_interactive() {
+        let app = App::in_memory().unwrap().with_output(io::sink());
+        let mut checkin = Stamp::check_in();
+        checkin.date = (Utc::now() - Duration::hours(30))
+            .with_second(0)
+            .unwrap()
+            .with_nanosecond(0)
+            .unwrap();
+        app.storage.insert_stamp(&mut checkin).unwrap();
+        let answers = |answers: Vec<String>| {
+            let answers = RefCell::new(answers.into_iter());
+            move |_: &str| Ok(answers.borrow_mut().next().unwrap())
+        };
+        let end = checkin.date + Duration::hours(8);
+
+        // Times out of the session are refused
+        let before = (checkin.date - Duration::hours(1)).format("%Y-%m-%d %H:%M");
+        assert!(app
+            .do_doctor_interactive(answers(vec!["c".to_string(), before.to_string()]))
+            .is_err());
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), checkin);
+
+        // Kept open
+        app.do_doctor_interactive(answers(vec!["k".to_string()]))
+            .unwrap();
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), checkin);
+
+        app.do_doctor_interactive(answers(vec![
+            "c".to_string(),
+            end.format("%Y-%m-%d %H:%M").to_string(),
+        ]))
+        .unwrap();
+        let last = app.storage.last_stamp().unwrap().unwrap();
+        assert_eq!(last.in_out, InOut::Out);
+        assert_eq!(last.date, end);
+        assert!(!app.status().unwrap().checked_in);
+    }
+
+    #[test]
+    fn pick_and_delete() {
+        let app = App::in_memory().unwrap().with_output(io::sink());
+        let answers = |answers: &[&str]| {
+            let answers: Vec<String> = answers.iter().map(|a| a.to_string()).collect();
+            let answers = RefCell::new(answers.into_iter());
+            move |_: &str| Ok(answers.borrow_mut().next().unwrap())
+        };
+        assert!(app.pick_stamp(answers(&[""])).is_err());
+
+        let mut checkin = Stamp::check_in();
+        app.storage.insert_stamp(&mut checkin).unwrap();
+        app.storage
+            .insert_allocation(&mut Allocation::new(checkin.id, "acme", 1.0))
+            .unwrap();
+        app.storage
+            .insert_tag(&mut Tag::new(checkin.id, "review"))
+            .unwrap();
+        let mut checkout = Stamp::check_out();
+        app.storage.insert_stamp(&mut checkout).unwrap();
+
+        assert_eq!(app.pick_stamp(answers(&["", "1"])).unwrap(), checkout);
+        // Search again, then pick
+        assert_eq!(
+            app.pick_stamp(answers(&["initech", "acm #rev", "1"]))
+                .unwrap(),
+            checkin
+        );
+        assert!(app.pick_stamp(answers(&["acme", ""])).is_err());
+
+        app.do_delete(checkin.id).unwrap();
+        assert_eq!(app.storage.first_stamp().unwrap().unwrap(), checkout);
+        assert!(app
+            .storage
+            .allocation_range(checkin.id, checkin.id)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn close_previous() {
+        let app = App::in_memory().unwrap().with_output(io::sink());
+        assert!(app.close_previous("17:00").is_err());
+        let mut checkin = Stamp::check_in();
+        checkin.date = (Utc::now() - Duration::days(2)).beginning_of_day() + Duration::hours(8);
+        app.storage.insert_stamp(&mut checkin).unwrap();
+        assert_eq!(app.stale_checkin().unwrap(), Some(checkin.clone()));
+        assert_eq!(
+            app.suggested_checkout(&checkin),
+            checkin.date + Duration::hours(8)
+        );
+
+        // Before the check-in, or not a time
+        assert!(app
+            .do_checkin(false, None, &[], &[], Some("07:00"))
+            .is_err());
+        assert!(app.do_checkin(false, None, &[], &[], Some("5pm")).is_err());
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), checkin);
+
+        // Still open if the check-in fails
+        app.storage
+            .execute(
+                "CREATE TEMP TRIGGER broken BEFORE INSERT ON Reference
+                BEGIN SELECT RAISE(ABORT, 'broken'); END;",
+            )
+            .unwrap();
+        assert!(app
+            .do_checkin(false, None, &[], &["ABC-1".into()], Some("17:30"))
+            .is_err());
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), checkin);
+        app.storage.execute("DROP TRIGGER broken;").unwrap();
+
+        app.do_checkin(false, None, &[], &[], Some("17:30"))
+            .unwrap();
+        let stamps = app
+            .storage
+            .stamp_range(&checkin.date, &(Utc::now() + Duration::days(1)))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(stamps.len(), 3);
+        assert_eq!(stamps[1].in_out, InOut::Out);
+        assert_eq!(stamps[1].date, checkin.date + Duration::minutes(570));
+        assert_eq!(stamps[2].in_out, InOut::In);
+        assert_eq!(app.stale_checkin().unwrap(), None);
+    }
+
+    #[test]
+    fn close_previous_local_time() {
+        let config = Config {
+            timezone: chrono_tz::America::New_York,
+            ..Config::default()
+        };
+        let app = App::in_memory()
+            .unwrap()
+            .with_config(config)
+            .with_output(io::sink());
+        // Evening of the previous day in New York
+        let mut checkin = Stamp::check_in();
+        checkin.date = (Utc::now() - Duration::days(2)).beginning_of_day() + Duration::hours(2);
+        app.storage.insert_stamp(&mut checkin).unwrap();
+
+        let checkout = app.close_previous("23:45").unwrap();
+        let local = app.local(&checkout.date);
+        assert_eq!(local.date_naive(), app.local(&checkin.date).date_naive());
+        assert_eq!(local.format("%H:%M").to_string(), "23:45");
+        assert!(checkout.date > checkin.date);
+    }
+
+    #[test]
+    fn sync_with() {
+        let server = App::in_memory().unwrap();
+        let mut checkin = Stamp::check_in();
+        checkin.date -= Duration::hours(2);
+        server.storage.insert_stamp(&mut checkin).unwrap();
+
+        let client = App::in_memory().unwrap().with_output(io::sink());
+        let pushed = RefCell::new(Vec::new());
+        let sync = || {
+            client
+                .sync_with(
+                    "test",
+                    |since| server.sync_pull(since),
+                    |bundle| {
+                        pushed.borrow_mut().push(bundle.stamps.len());
+                        server.sync_push(bundle)
+                    },
+                )
+                .unwrap()
+        };
+
+        // The pulled stamp is not pushed back
+        sync();
+        assert_eq!(
+            client.storage.last_stamp().unwrap().unwrap().uid,
+            checkin.uid
+        );
+        assert_eq!(*pushed.borrow(), [0]);
+
+        // Nor the next time, only the new ones
+        let mut checkout = Stamp::check_out();
+        checkout.date -= Duration::hours(1);
+        client.storage.insert_stamp(&mut checkout).unwrap();
+        sync();
+        assert_eq!(*pushed.borrow(), [0, 1]);
+        assert_eq!(
+            server.storage.last_stamp().unwrap().unwrap().uid,
+            checkout.uid
+        );
+    }
+
+    #[test]
+    fn reopen() {
+        let captured = Captured::default();
+        let app = App::in_memory().unwrap().with_output(captured.clone());
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        assert!(app.do_reopen().is_err());
+
+        app.do_close(&date("2024-01-01T00:00:00Z")).unwrap();
+        app.do_close(&date("2024-02-01T00:00:00Z")).unwrap();
+        app.do_reopen().unwrap();
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        assert!(output.contains("2024-02-01"));
+
+        // The previous closeout applies again
+        let mut stamp = Stamp::new(StampId(0), date("2024-01-15T08:00:00Z"), InOut::In);
+        app.storage.insert_stamp(&mut stamp).unwrap();
+        let mut stamp = Stamp::new(StampId(0), date("2023-12-15T08:00:00Z"), InOut::In);
+        assert!(app.storage.insert_stamp(&mut stamp).is_err());
+        app.do_reopen().unwrap();
+        assert!(app.do_reopen().is_err());
+    }
+
+    #[test]
+    fn backwards_clock() {
+        let app = App::in_memory().unwrap().with_output(io::sink());
+        // Stamped by a machine whose clock is ahead
+        let mut ahead = Stamp::check_out();
+        ahead.date += Duration::hours(2);
+        app.storage.insert_stamp(&mut ahead).unwrap();
+
+        let error = app.do_checkin(false, None, &[], &[], None).unwrap_err();
+        assert!(error.to_string().contains("check the system clock"));
+        assert_eq!(app.storage.last_stamp().unwrap().unwrap(), ahead);
+
+        let warnings = Captured::default();
+        let app = app.with_force(true).with_errors(warnings.clone());
+        app.do_checkin(false, None, &[], &[], None).unwrap();
+        assert!(app.status().unwrap().checked_in);
+        let warned = String::from_utf8(warnings.0.borrow().clone()).unwrap();
+        assert!(warned.contains(&ahead.date.format("%Y-%m-%d %H:%M:%S").to_string()));
+        // Now before the check-in too
+        app.storage
+            .update_stamp(&mut {
+                let mut checkin = app.storage.last_stamp().unwrap().unwrap();
+                checkin.date += Duration::hours(4);
+                checkin
+            })
+            .unwrap();
+        assert!(app.with_force(false).checkout(None).is_err());
+    }
+
+    #[test]
+    fn checkin_project() {
+        let app = App::in_memory().unwrap();
+
+        let first = app.checkin(false, Some("website")).unwrap();
+        let summary = app.checkout(None).unwrap();
+        assert!(summary.allocations.is_empty());
+
+        // A split replaces the project of the check-in
+        app.checkin(false, Some("website")).unwrap();
+        let summary = app.checkout(Some("50% PROJ-1")).unwrap();
+        assert_eq!(summary.allocations.len(), 1);
+
+        let allocations = app
+            .storage
+            .allocation_range(first.id, summary.checkout.id)
+            .unwrap();
+        let targets: Vec<&str> = allocations.iter().map(|a| a.target.as_str()).collect();
+        assert_eq!(targets, ["website", "PROJ-1"]);
+    }
+
+    #[test]
+    fn switch() {
+        let app = App::in_memory().unwrap();
+        assert!(app.switch("globex").is_err());
+
+        let checkin = app.checkin(false, Some("acme")).unwrap();
+        let (session, stamp) = app.switch("globex").unwrap();
+        assert_eq!(session.id, checkin.id);
+        assert_eq!(session.end, stamp.date);
+
+        // The finished session is closed, the new one open on the project
+        let last = app.storage.last_stamp().unwrap().unwrap();
+        assert_eq!(last.id, stamp.id);
+        assert_eq!(last.in_out, InOut::In);
+        let checkout = app.storage.previous_stamp(&last).unwrap().unwrap();
+        assert_eq!(checkout.in_out, InOut::Out);
+        assert_eq!(checkout.date, stamp.date);
+        let allocations = app.storage.allocation_range(checkin.id, stamp.id).unwrap();
+        let targets: Vec<&str> = allocations.iter().map(|a| a.target.as_str()).collect();
+        assert_eq!(targets, ["acme", "globex"]);
+
+        app.do_switch("initech").unwrap();
+        app.do_checkout(None).unwrap();
+        assert!(app.do_switch("acme").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks() {
+        let file = std::env::temp_dir().join("wtime-app-hooks-test.txt");
+        let _ = fs::remove_file(&file);
+        let config = Config {
+            on_checkout: Some(format!(
+                "echo \"$WTIME_PROJECT $WTIME_DURATION\" > {}",
+                file.to_string_lossy()
+            )),
+            ..Config::default()
+        };
+        let app = App::in_memory().unwrap().with_config(config);
+
+        app.checkin(false, Some("website")).unwrap();
+        assert!(!file.exists());
+        app.checkout(None).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "website 0\n");
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn import_in_past() {
+        let captured = Captured::default();
+        let app = App::in_memory().unwrap().with_output(captured.clone());
+        let record = |start: &str, hours: i64, project: &str| {
+            let start = DateTime::<Utc>::from_str(start).unwrap();
+            import::Record {
+                start,
+                end: start + Duration::hours(hours),
+                project: Some(project.to_string()),
+            }
+        };
+
+        app.do_import(vec![
+            record("2020-01-01T08:00:00Z", 2, "dev"),
+            record("2020-01-01T13:00:00Z", 4, "dev"),
+        ])
+        .unwrap();
+        app.do_import(vec![
+            record("2020-01-01T11:00:00Z", 1, "meeting"),
+            record("2020-01-01T16:00:00Z", 1, "meeting"),
+            record("2100-01-01T08:00:00Z", 1, "meeting"),
+        ])
+        .unwrap();
+        // Importing again adds nothing
+        app.do_import(vec![record("2020-01-01T13:00:00Z", 4, "dev")])
+            .unwrap();
+
+        let from = DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap();
+        let sessions = report::sessions(&app.storage, &from, &(from + Duration::days(1))).unwrap();
+        let starts: Vec<u32> = sessions.iter().map(|s| s.start.hour()).collect();
+        assert_eq!(starts, [8, 11, 13]);
+        let totals = report::allocated_totals(&app.storage, &sessions).unwrap();
+        assert_eq!(totals["meeting"], Duration::hours(1));
+
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        assert!(output.contains(
+            "Imported 1 session(s)\n2 session(s) skipped, overlapping worked time or in the future\n"
+        ));
+        assert!(output.ends_with("Imported 0 session(s)\n1 session(s) skipped, overlapping worked time or in the future\n"));
+    }
+
+    #[test]
+    fn team_report() {
+        let captured = Captured::default();
+        let app = App::in_memory().unwrap().with_output(captured.clone());
+        let from = DateTime::<Utc>::from_str("2020-01-06T00:00:00Z").unwrap();
+        let stamp = |hours, in_out| {
+            let mut stamp = Stamp::new(StampId::default(), from + Duration::hours(hours), in_out);
+            app.storage.insert_stamp(&mut stamp).unwrap();
+        };
+
+        stamp(8, InOut::In);
+        stamp(12, InOut::Out);
+        app.select_user("alice").unwrap();
+        stamp(9, InOut::In);
+        app.select_user("default").unwrap();
+        stamp(24 + 8, InOut::In);
+        app.select_user("alice").unwrap();
+        stamp(10, InOut::Out);
+
+        let team = app
+            .team_report(Some(from), Some(from + Duration::days(7)), false)
+            .unwrap();
+        assert_eq!(team.users.len(), 2);
+        assert_eq!(team.users[1].0.name, "alice");
+        assert_eq!(team.users[1].1.total, Duration::hours(1));
+        assert_eq!(team.days().len(), 1);
+        assert_eq!(team.total(), Duration::hours(5));
+        // Still working as alice
+        assert_eq!(app.storage.current_user().unwrap().name, "alice");
+
+        app.do_team_report(Some(from), Some(from + Duration::days(7)), false)
+            .unwrap();
+        let output = String::from_utf8(captured.0.borrow().clone()).unwrap();
+        assert!(output.contains("Mon 2020-01-06    4h 00m    1h 00m    5h 00m"));
+    }
+
+    #[test]
+    fn backup_restore() {
+        let dir = std::env::temp_dir().join("wtime-backup-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let backup = dir.join("backup.sqlite");
+        let saved = dir.join("saved.sqlite");
+        let other = dir.join("other.sqlite");
+
+        let app = App::in_memory().unwrap();
+        app.do_checkin(false, None, &[], &[], None).unwrap();
+        app.do_backup(&backup).unwrap();
+        // Never overwrite a file
+        assert!(app.do_backup(&backup).is_err());
+
+        let restored = App::in_memory().unwrap();
+        restored.do_restore(&backup, &saved).unwrap();
+        assert!(restored.storage.last_stamp().unwrap().is_some());
+        assert!(saved.exists());
+
+        // Only restore wtime databases
+        sqlite::open(&other).unwrap();
+        assert!(restored.do_restore(&other, &dir.join("x")).is_err());
+        assert!(restored
+            .do_restore(&dir.join("missing"), &dir.join("x"))
+            .is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
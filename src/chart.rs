@@ -0,0 +1,278 @@
+//! Standalone SVG charts of the worked hours
+//!
+//! `wtime chart` draws the worked hours of every day of the current week,
+//! month or year as bars or as a line, with the daily target as a dashed
+//! line. The SVG file needs no external tool nor style sheet.
+
+use crate::config::Config;
+use crate::export::ParseFormatError;
+use crate::site::hours;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// Days drawn on a chart
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Range {
+    /// Current week, from Monday
+    Week,
+    /// Current month
+    Month,
+    /// Current year
+    Year,
+}
+
+impl FromStr for Range {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+impl Range {
+    /// Get the date window of the range containing a day
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration, giving the timezone
+    /// * `now` - Date/Time in the range, usually now
+    ///
+    /// # Return
+    ///
+    /// The lower (included) and upper (excluded) bounds of the window.
+    pub fn window(&self, config: &Config, now: &DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let day = config.day_of(now);
+        let (first, last) = match self {
+            Range::Week => {
+                let first = day - Duration::days(day.weekday().num_days_from_monday().into());
+                (first, first + Duration::days(7))
+            }
+            Range::Month => {
+                let first = day.with_day(1).unwrap();
+                (first, first + Months::new(1))
+            }
+            Range::Year => {
+                let first = day.with_ordinal(1).unwrap();
+                (first, first + Months::new(12))
+            }
+        };
+        (config.start_of_day(first), config.start_of_day(last))
+    }
+}
+
+/// Kind of chart
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Kind {
+    /// A bar per day
+    Bar,
+    /// A line through the days
+    Line,
+}
+
+impl FromStr for Kind {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bar" | "bars" => Ok(Self::Bar),
+            "line" => Ok(Self::Line),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Width of the plot area
+const WIDTH: f64 = 800.0;
+/// Height of the plot area
+const HEIGHT: f64 = 300.0;
+/// Space left of the plot area, for the hour labels
+const LEFT: f64 = 40.0;
+/// Space above the plot area
+const TOP: f64 = 20.0;
+/// Space below the plot area, for the day labels
+const BOTTOM: f64 = 30.0;
+
+/// Label of a day on the horizontal axis, if any
+fn day_label(day: NaiveDate, days: usize) -> Option<String> {
+    match days {
+        0..=7 => Some(day.format("%a %d").to_string()),
+        8..=31 => Some(day.day().to_string()),
+        _ => (day.day() == 1).then(|| day.format("%b").to_string()),
+    }
+}
+
+/// Draw the worked hours of days as a standalone SVG image
+///
+/// # Arguments
+///
+/// * `from` - First day drawn
+/// * `to` - Day after the last drawn day
+/// * `days` - Worked time per day, days without work may be missing
+/// * `kind` - Kind of chart
+/// * `target` - Expected worked time per day, drawn as a dashed line
+pub fn svg(
+    from: NaiveDate,
+    to: NaiveDate,
+    days: &BTreeMap<NaiveDate, Duration>,
+    kind: Kind,
+    target: Duration,
+) -> String {
+    let points: Vec<(NaiveDate, f64)> = from
+        .iter_days()
+        .take_while(|day| *day < to)
+        .map(|day| (day, days.get(&day).map_or(0.0, hours)))
+        .collect();
+    let max = points
+        .iter()
+        .map(|(_, h)| *h)
+        .fold(hours(&target), f64::max)
+        .ceil()
+        .max(1.0);
+    let step = WIDTH / points.len().max(1) as f64;
+    let y = |h: f64| TOP + HEIGHT - h / max * HEIGHT;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+        font-family=\"sans-serif\" font-size=\"10\">\n\
+        <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        LEFT + WIDTH + 10.0,
+        TOP + HEIGHT + BOTTOM
+    );
+
+    // Horizontal grid, labelled in hours
+    let every = (max / 6.0).ceil().max(1.0);
+    let mut h = 0.0;
+    while h <= max {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{LEFT}\" y1=\"{0:.1}\" x2=\"{1}\" y2=\"{0:.1}\" stroke=\"#ddd\"/>\n\
+            <text x=\"{2}\" y=\"{3:.1}\" text-anchor=\"end\">{h}h</text>",
+            y(h),
+            LEFT + WIDTH,
+            LEFT - 4.0,
+            y(h) + 3.0
+        );
+        h += every;
+    }
+
+    match kind {
+        Kind::Bar => {
+            for (i, (day, h)) in points.iter().enumerate() {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4a7ab5\">\
+                    <title>{} {:.2}h</title></rect>",
+                    LEFT + i as f64 * step + step * 0.1,
+                    y(*h),
+                    step * 0.8,
+                    TOP + HEIGHT - y(*h),
+                    day.format("%a %Y-%m-%d"),
+                    h
+                );
+            }
+        }
+        Kind::Line => {
+            let line: Vec<String> = points
+                .iter()
+                .enumerate()
+                .map(|(i, (_, h))| format!("{:.1},{:.1}", LEFT + (i as f64 + 0.5) * step, y(*h)))
+                .collect();
+            let _ = writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#4a7ab5\" stroke-width=\"2\"/>",
+                line.join(" ")
+            );
+        }
+    }
+
+    if !target.is_zero() {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{LEFT}\" y1=\"{0:.1}\" x2=\"{1}\" y2=\"{0:.1}\" stroke=\"#c33\" \
+            stroke-dasharray=\"4 4\"><title>Target {2:.2}h</title></line>",
+            y(hours(&target)),
+            LEFT + WIDTH,
+            hours(&target)
+        );
+    }
+
+    for (i, (day, _)) in points.iter().enumerate() {
+        if let Some(label) = day_label(*day, points.len()) {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+                LEFT + (i as f64 + 0.5) * step,
+                TOP + HEIGHT + 15.0,
+                label
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::{svg, Kind, Range};
+    use crate::config::Config;
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn windows() {
+        let config = Config::default();
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        let now = date("2024-02-14T12:00:00Z");
+
+        assert_eq!(
+            Range::Week.window(&config, &now),
+            (date("2024-02-12T00:00:00Z"), date("2024-02-19T00:00:00Z"))
+        );
+        assert_eq!(
+            Range::Month.window(&config, &now),
+            (date("2024-02-01T00:00:00Z"), date("2024-03-01T00:00:00Z"))
+        );
+        assert_eq!(
+            Range::Year.window(&config, &now),
+            (date("2024-01-01T00:00:00Z"), date("2025-01-01T00:00:00Z"))
+        );
+
+        let zurich = Config {
+            timezone: chrono_tz::Europe::Zurich,
+            ..Config::default()
+        };
+        assert_eq!(
+            Range::Month.window(&zurich, &date("2024-02-29T23:30:00Z")),
+            (date("2024-02-29T23:00:00Z"), date("2024-03-31T22:00:00Z"))
+        );
+        assert!(Range::from_str("day").is_err());
+    }
+
+    #[test]
+    fn charts() {
+        let from = NaiveDate::from_ymd_opt(2024, 2, 12).unwrap();
+        let to = from + Duration::days(7);
+        let days = BTreeMap::from([(from, Duration::minutes(450))]);
+
+        let bars = svg(from, to, &days, Kind::Bar, Duration::hours(8));
+        assert!(bars.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert_eq!(bars.matches("fill=\"#4a7ab5\"").count(), 7);
+        assert!(bars.contains("<title>Mon 2024-02-12 7.50h</title>"));
+        assert!(bars.contains("<title>Target 8.00h</title>"));
+        assert!(bars.contains(">Sun 18</text>"));
+
+        let line = svg(from, to, &days, Kind::Line, Duration::zero());
+        assert_eq!(line.matches("<polyline").count(), 1);
+        assert!(!line.contains("Target"));
+        assert_eq!(Kind::from_str("line"), Ok(Kind::Line));
+    }
+}
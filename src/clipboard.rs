@@ -0,0 +1,34 @@
+//! Copy of the reports to the system clipboard
+//!
+//! `wtime report --copy` places the rendered report on the clipboard rather
+//! than printing it, e.g. to paste the summary of the week into a standup
+//! message. This needs the `clipboard` cargo feature, which supports X11 on
+//! Linux, and the `clipboard-wayland` one for Wayland sessions.
+//!
+//! On Linux the clipboard is served by the program that copied; once wtime
+//! exits, the text stays available if a clipboard manager took it over.
+
+use anyhow::Result;
+
+/// Place a text on the system clipboard
+///
+/// # Arguments
+///
+/// * `text` - Text to copy
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<()> {
+    use anyhow::Context;
+
+    let mut clipboard = arboard::Clipboard::new().context("Opening the clipboard")?;
+    clipboard
+        .set_text(text)
+        .context("Copying to the clipboard")?;
+    Ok(())
+}
+
+/// Place a text on the system clipboard, impossible without the
+/// `clipboard` feature
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_text: &str) -> Result<()> {
+    Err(anyhow::anyhow!("This build lacks the `clipboard` feature"))
+}
@@ -0,0 +1,255 @@
+//! Legal working-time limits
+//!
+//! Labour laws cap the worked time of a day, require a rest between two
+//! working days and a break after some hours of work. The limits are set in
+//! the `[limits]` table of the configuration, e.g. for the European working
+//! time directive:
+//!
+//! ```toml
+//! [limits]
+//! max_day = "10h"
+//! min_rest = "11h"
+//! max_without_break = "6h"
+//! # Pauses shorter than this are not breaks, 15 minutes by default
+//! min_break = "15m"
+//! ```
+//!
+//! Exceeded limits are only warned about, by `wtime doctor`, `wtime` and
+//! `wtime checkout`.
+
+use crate::config::Config;
+use crate::format;
+use crate::report::{self, Session};
+use crate::tr;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::fmt::{self, Display, Formatter};
+
+/// Working-time limit exceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// More work on a day than `max_day`
+    LongDay {
+        /// Day worked too long
+        day: NaiveDate,
+        /// End of the day, in the configured timezone
+        end: DateTime<Utc>,
+        /// Worked time of the day
+        worked: Duration,
+        /// The `max_day` limit
+        limit: Duration,
+    },
+
+    /// Less rest between two working days than `min_rest`
+    ShortRest {
+        /// Last check-out of the first day
+        checkout: DateTime<Utc>,
+        /// First check-in of the next day
+        checkin: DateTime<Utc>,
+        /// The `min_rest` limit
+        limit: Duration,
+    },
+
+    /// More work without a break than `max_without_break`
+    NoBreak {
+        /// Start of the work without break
+        start: DateTime<Utc>,
+        /// End of the work without break
+        end: DateTime<Utc>,
+        /// The `max_without_break` limit
+        limit: Duration,
+    },
+}
+
+impl Violation {
+    /// Date/time the limit was exceeded by, the end of the faulty period
+    pub fn date(&self) -> DateTime<Utc> {
+        match self {
+            Violation::LongDay { end, .. } => *end,
+            Violation::ShortRest { checkin, .. } => *checkin,
+            Violation::NoBreak { end, .. } => *end,
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::LongDay {
+                day, worked, limit, ..
+            } => write!(
+                f,
+                "{}",
+                tr!(
+                    "violation-long-day",
+                    worked = format::duration(worked),
+                    day = day.format("%Y-%m-%d").to_string(),
+                    limit = format::duration(limit)
+                )
+            ),
+            Violation::ShortRest {
+                checkout,
+                checkin,
+                limit,
+            } => write!(
+                f,
+                "{}",
+                tr!(
+                    "violation-short-rest",
+                    rest = format::duration(&(*checkin - *checkout)),
+                    checkout = checkout.format("%Y-%m-%d %H:%M").to_string(),
+                    checkin = checkin.format("%Y-%m-%d %H:%M").to_string(),
+                    limit = format::duration(limit)
+                )
+            ),
+            Violation::NoBreak { start, end, limit } => write!(
+                f,
+                "{}",
+                tr!(
+                    "violation-no-break",
+                    worked = format::duration(&(*end - *start)),
+                    start = start.format("%Y-%m-%d %H:%M").to_string(),
+                    end = end.format("%H:%M").to_string(),
+                    limit = format::duration(limit)
+                )
+            ),
+        }
+    }
+}
+
+/// Check sessions against the working-time limits
+///
+/// # Arguments
+///
+/// * `sessions` - Sessions to check, in date order
+/// * `config` - User settings, giving the limits to check (the unset ones
+///   are skipped) and the timezone of the days
+///
+/// # Return
+///
+/// The exceeded limits, in date order.
+pub fn check(sessions: &[Session], config: &Config) -> Vec<Violation> {
+    let limits = &config.limits;
+    let mut violations = Vec::new();
+
+    if let Some(limit) = limits.max_day {
+        for (day, worked) in report::daily_totals(sessions, config) {
+            if worked > limit {
+                violations.push(Violation::LongDay {
+                    day,
+                    end: config.start_of_day(day + Duration::days(1)) - Duration::seconds(1),
+                    worked,
+                    limit,
+                });
+            }
+        }
+    }
+
+    if let Some(limit) = limits.min_rest {
+        let spans: Vec<_> = report::day_spans(sessions, config).into_values().collect();
+        for pair in spans.windows(2) {
+            if pair[1].start - pair[0].end < limit {
+                violations.push(Violation::ShortRest {
+                    checkout: pair[0].end,
+                    checkin: pair[1].start,
+                    limit,
+                });
+            }
+        }
+    }
+
+    if let Some(limit) = limits.max_without_break {
+        // Sessions separated by less than a break are worked in a row
+        let mut stretches: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for session in sessions {
+            match stretches.last_mut() {
+                Some((_, end)) if session.start - *end < limits.min_break => {
+                    *end = (*end).max(session.end)
+                }
+                _ => stretches.push((session.start, session.end)),
+            }
+        }
+        for (start, end) in stretches {
+            if end - start > limit {
+                violations.push(Violation::NoBreak { start, end, limit });
+            }
+        }
+    }
+
+    violations.sort_by_key(Violation::date);
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, Violation};
+    use crate::config::{Config, Limits};
+    use crate::db::StampId;
+    use crate::report::Session;
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::str::FromStr;
+
+    fn session(start: &str, end: &str) -> Session {
+        Session {
+            id: StampId(0),
+            start: DateTime::<Utc>::from_str(start).unwrap(),
+            end: DateTime::<Utc>::from_str(end).unwrap(),
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let sessions = [
+            // 6h30m in a row, the 10 minutes pause is no break
+            session("2024-02-12T07:00:00Z", "2024-02-12T10:00:00Z"),
+            session("2024-02-12T10:10:00Z", "2024-02-12T13:30:00Z"),
+            session("2024-02-12T14:00:00Z", "2024-02-12T19:00:00Z"),
+            // 9h rest only
+            session("2024-02-13T04:00:00Z", "2024-02-13T08:00:00Z"),
+        ];
+        let mut config = Config {
+            limits: Limits {
+                max_day: Some(Duration::hours(10)),
+                min_rest: Some(Duration::hours(11)),
+                max_without_break: Some(Duration::hours(6)),
+                ..Limits::default()
+            },
+            ..Config::default()
+        };
+
+        let violations = check(&sessions, &config);
+        assert_eq!(violations.len(), 3);
+        assert_eq!(
+            violations[0].to_string(),
+            "6h 30m worked without a break from 2024-02-12 07:00 to 13:30, more than 6h 00m"
+        );
+        assert_eq!(
+            violations[1],
+            Violation::LongDay {
+                day: NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+                end: DateTime::<Utc>::from_str("2024-02-12T23:59:59Z").unwrap(),
+                worked: Duration::minutes(11 * 60 + 20),
+                limit: Duration::hours(10),
+            }
+        );
+        assert_eq!(
+            violations[2].to_string(),
+            "Only 9h 00m rest between the check-out at 2024-02-12 19:00 and the check-in at 2024-02-13 04:00, less than 11h 00m"
+        );
+
+        // All on the same day in New York, without rest between days
+        config.timezone = chrono_tz::America::New_York;
+        let violations = check(&sessions, &config);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[1],
+            Violation::LongDay {
+                day: NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+                end: DateTime::<Utc>::from_str("2024-02-13T04:59:59Z").unwrap(),
+                worked: Duration::minutes(15 * 60 + 20),
+                limit: Duration::hours(10),
+            }
+        );
+
+        assert!(check(&sessions, &Config::default()).is_empty());
+    }
+}
@@ -4,12 +4,19 @@
 //! objects stored in database.
 
 use chrono::{prelude::*, Duration};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlite::{self};
-use std::{fmt::Formatter, str::FromStr};
+use sqlite3_sys as ffi;
+use std::ffi::{c_int, CStr};
+use std::time::Instant;
+use std::{collections::HashMap, fmt::Formatter, path::Path, str::FromStr};
 use thiserror::Error;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
 
 /// Stamping direction (checked -in or -out) used for Stamp data structure.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum InOut {
     /// Stamp is a Check-in
     In,
@@ -42,17 +49,190 @@ impl FromStr for InOut {
     }
 }
 
+/// Entry point a [Stamp] was made through
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Typed on the command line
+    #[default]
+    Manual,
+    /// Made by `wtime daemon`, e.g. on leaving a network
+    Daemon,
+    /// Requested through the HTTP or D-Bus API
+    Api,
+    /// Imported from another tool
+    Import,
+    /// Made by the hook of another tool, e.g. Taskwarrior
+    Hook,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Source::Manual => write!(f, "manual"),
+            Source::Daemon => write!(f, "daemon"),
+            Source::Api => write!(f, "api"),
+            Source::Import => write!(f, "import"),
+            Source::Hook => write!(f, "hook"),
+        }
+    }
+}
+
+/// Unit-struct for parsing error on Source enum
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseSourceError;
+
+impl FromStr for Source {
+    type Err = ParseSourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "manual" => Ok(Self::Manual),
+            "daemon" => Ok(Self::Daemon),
+            "api" => Ok(Self::Api),
+            "import" => Ok(Self::Import),
+            "hook" => Ok(Self::Hook),
+            _ => Err(ParseSourceError),
+        }
+    }
+}
+
+/// Kind of modification recorded in the [Audit] log
+#[derive(Debug, Eq, PartialEq)]
+pub enum Operation {
+    /// Row was created
+    Insert,
+    /// Row was modified
+    Update,
+    /// Row was removed
+    Delete,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Operation::Insert => write!(f, "Insert"),
+            Operation::Update => write!(f, "Update"),
+            Operation::Delete => write!(f, "Delete"),
+        }
+    }
+}
+
+/// Unit-struct for parsing error on Operation enum
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseOperationError;
+
+impl FromStr for Operation {
+    type Err = ParseOperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "insert" => Ok(Self::Insert),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            _ => Err(ParseOperationError),
+        }
+    }
+}
+
+/// Unique ID of a [Stamp] (primary-key in database)
+///
+/// Stamps not yet inserted have the default ID, 0. Serialized as a plain number.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct StampId(pub i64);
+
+impl std::fmt::Display for StampId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<i64> for StampId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<StampId> for i64 {
+    fn from(id: StampId) -> Self {
+        id.0
+    }
+}
+
+impl sqlite::BindableWithIndex for StampId {
+    fn bind<T: sqlite::ParameterIndex>(
+        self,
+        statement: &mut sqlite::Statement,
+        index: T,
+    ) -> sqlite::Result<()> {
+        self.0.bind(statement, index)
+    }
+}
+
+impl sqlite::ReadableWithIndex for StampId {
+    fn read<T: sqlite::ColumnIndex>(
+        statement: &sqlite::Statement,
+        index: T,
+    ) -> sqlite::Result<Self> {
+        i64::read(statement, index).map(Self)
+    }
+}
+
 /// Represent an "stamp", i.e. a check-out or a check-in time
-#[derive(Debug)]
+///
+/// Dates are serialized as RFC3339 strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Stamp {
     /// Stamp unique ID (primary-key in database)
-    pub id: i64,
+    pub id: StampId,
     /// Stamp timestamp, Utc
     pub date: DateTime<Utc>,
     /// Marker if Stamp was check-in or check-out
     ///
     /// See [InOut] enum.
     pub in_out: InOut,
+    /// Wall-clock time the stamp was written to the database
+    ///
+    /// None until inserted, or for stamps written before this was recorded.
+    pub created_at: Option<DateTime<Utc>>,
+    /// Wall-clock time the stamp was last modified in the database
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Globally unique ID, recognizing the stamp on every device it is synced to
+    ///
+    /// Generated on insertion if None, see [crate::sync].
+    pub uid: Option<Uuid>,
+    /// Entry point the stamp was made through
+    ///
+    /// None for stamps written before this was recorded.
+    #[serde(default)]
+    pub source: Option<Source>,
+}
+
+/// Criteria of a stamp search, see [Stamp::search]
+///
+/// Unset criteria match every stamp. The project and the note apply to the
+/// session of the stamp, so that both its check-in and check-out are found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StampFilter {
+    /// Lower bound (included) of the stamp dates
+    pub from: Option<DateTime<Utc>>,
+    /// Upper bound (excluded) of the stamp dates
+    pub to: Option<DateTime<Utc>>,
+    /// Only the check-ins or only the check-outs
+    pub direction: Option<InOut>,
+    /// Project the session is allocated to
+    pub project: Option<String>,
+    /// Text a tag of the session contains, case insensitive
+    pub note_contains: Option<String>,
+    /// Entry point the stamp was made through
+    pub source: Option<Source>,
+    /// Maximum number of stamps returned, all of them if None
+    pub limit: Option<usize>,
+    /// Number of matching stamps skipped, to read the next page
+    pub offset: usize,
 }
 
 /// Type for database related error
@@ -65,6 +245,24 @@ pub enum DbError {
         source: sqlite::Error,
     },
 
+    /// Error propagated from postgres library
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    PgError {
+        #[from]
+        source: postgres::Error,
+    },
+
+    /// Linked SQLite library does not support encryption
+    #[cfg(feature = "sqlcipher")]
+    #[error("SQLite library was not built with SQLCipher")]
+    NoCipher,
+
+    /// Passphrase does not decrypt the database file
+    #[cfg(feature = "sqlcipher")]
+    #[error("Wrong passphrase or not an encrypted database")]
+    WrongPassphrase,
+
     /// Database was not open prior to operation returning this error
     #[error("Database not opened!")]
     DbNotOpenError,
@@ -73,6 +271,34 @@ pub enum DbError {
     #[error("No such entry")]
     NoSuchEntry,
 
+    /// Operation would modify a stamp protected by the hash chain
+    #[error("Stamps are hash-chained and can not be modified")]
+    ChainedStamp,
+
+    /// Operation would modify a period closed by a [Closeout]
+    #[error("Period is closed until {0}")]
+    ClosedPeriod(DateTime<Utc>),
+
+    /// Operation is not implemented by the storage backend
+    #[error("{0} is not supported by this storage backend")]
+    Unsupported(&'static str),
+
+    /// Operation would add a stamp among the archived ones, dated until given date
+    #[error("Stamps are archived until {0}")]
+    ArchivedPeriod(DateTime<Utc>),
+
+    /// Operation would cut the session started by the given stamp
+    #[error("Session started by stamp {0} is not finished")]
+    OpenSession(StampId),
+
+    /// Stamp was modified by someone else since it was read
+    #[error("Stamp {0} was modified meanwhile, read it again")]
+    Conflict(StampId),
+
+    /// Stamp would follow a stamp of the same direction, they must alternate
+    #[error("Already checked {}", .0.to_string().to_lowercase())]
+    NotAlternating(InOut),
+
     /// ISO8601 string in database was not parsed correctly.
     #[error(transparent)]
     ParseError {
@@ -84,84 +310,934 @@ pub enum DbError {
 type StampResult<'a> = std::result::Result<&'a Stamp, DbError>;
 
 fn do_simple_query(conn: &sqlite::Connection, query: String) -> Result<(), DbError> {
-    conn.execute(query)?;
+    let start = Instant::now();
+    conn.execute(&query)?;
+    // Only the statement kind, the text may hold values not to be logged
+    let kind = query.split_whitespace().next().unwrap_or_default();
+    debug!(kind, elapsed = ?start.elapsed(), "Query executed");
+    Ok(())
+}
+
+/// Run a multi-step operation atomically
+///
+/// The operation runs in a savepoint, released if it succeeds and rolled
+/// back if it fails, so that either all its changes are written or none.
+/// Savepoints nest: an operation already running in a transaction only
+/// rolls back its own changes.
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+/// * `operation` - Operation to run, given the connection
+///
+/// # Return
+///
+/// The result of the operation.
+pub fn transaction<T, E: From<DbError>>(
+    conn: &sqlite::Connection,
+    operation: impl FnOnce(&sqlite::Connection) -> Result<T, E>,
+) -> Result<T, E> {
+    do_simple_query(conn, "SAVEPOINT wtime;".into())?;
+    let result = operation(conn);
+    match result {
+        Ok(_) => do_simple_query(conn, "RELEASE wtime;".into())?,
+        Err(_) => do_simple_query(conn, "ROLLBACK TO wtime; RELEASE wtime;".into())?,
+    }
+    result
+}
+
+/// Whether a transaction is open on the connection, see [transaction]
+fn in_transaction(conn: &sqlite::Connection) -> bool {
+    unsafe { ffi::sqlite3_get_autocommit(conn.as_raw()) == 0 }
+}
+
+fn last_insert_id(conn: &sqlite::Connection) -> Result<i64, DbError> {
+    let mut statement = conn.prepare("SELECT last_insert_rowid()")?;
+
+    match statement.next()? {
+        sqlite::State::Row => Ok(statement.read::<i64, _>(0)?),
+        sqlite::State::Done => {
+            unreachable!("SELECT last_insert_rowid() should not fail");
+        }
+    }
+}
+
+/// Time to wait for a lock held by another connection before failing, in milliseconds
+const BUSY_TIMEOUT_MS: usize = 5000;
+
+/// Open a database file, configured for concurrent access
+///
+/// The journal is set to WAL mode, so that readers do not block the writer,
+/// and a busy timeout lets concurrent writers wait for each other instead of
+/// failing with "database is locked".
+///
+/// # Arguments
+///
+/// * `path` - Path to the database file, created if it does not exist
+pub fn open(path: &Path) -> Result<sqlite::Connection, DbError> {
+    configure(sqlite::open(path)?)
+}
+
+/// Open a database file encrypted with SQLCipher
+///
+/// The SQLite library linked must be SQLCipher, otherwise the file would
+/// silently be stored in clear and [DbError::NoCipher] is returned instead.
+///
+/// # Arguments
+///
+/// * `path` - Path to the database file, created if it does not exist
+/// * `passphrase` - Passphrase the file is (or will be) encrypted with
+#[cfg(feature = "sqlcipher")]
+pub fn open_encrypted(path: &Path, passphrase: &str) -> Result<sqlite::Connection, DbError> {
+    let conn = sqlite::open(path)?;
+
+    let mut statement = conn.prepare("PRAGMA cipher_version;")?;
+    if let sqlite::State::Done = statement.next()? {
+        return Err(DbError::NoCipher);
+    }
+    drop(statement);
+
+    // PRAGMA arguments can not be bound, quote the passphrase instead. It
+    // is run directly, never to be logged.
+    conn.execute(format!(
+        "PRAGMA key = '{}';",
+        passphrase.replace('\'', "''")
+    ))?;
+    if conn.execute("SELECT count(*) FROM sqlite_master;").is_err() {
+        return Err(DbError::WrongPassphrase);
+    }
+
+    configure(conn)
+}
+
+/// Set the connection options shared by all the database files
+fn configure(mut conn: sqlite::Connection) -> Result<sqlite::Connection, DbError> {
+    conn.set_busy_timeout(BUSY_TIMEOUT_MS)?;
+    do_simple_query(
+        &conn,
+        "PRAGMA journal_mode = WAL;
+        PRAGMA synchronous = NORMAL;
+        PRAGMA foreign_keys = ON;"
+            .into(),
+    )?;
+    Ok(conn)
+}
+
+/// Open a private, in-memory database
+///
+/// Nothing is written to the filesystem and the content is lost when the
+/// connection is dropped, which suits tests and ephemeral scripting.
+pub fn open_memory() -> Result<sqlite::Connection, DbError> {
+    let conn = sqlite::open(":memory:")?;
+    do_simple_query(&conn, "PRAGMA foreign_keys = ON;".into())?;
+    Ok(conn)
+}
+
+/// Copy the whole content of a database into another one
+///
+/// SQLite online backup API is used, so the copy is a consistent snapshot
+/// even if other processes are writing to `from`. The previous content of
+/// `to` is replaced.
+///
+/// # Arguments
+///
+/// * `from` - reference to the open SQLITE database connection to copy
+/// * `to` - reference to the open SQLITE database connection to overwrite
+#[instrument(level = "debug", skip_all)]
+pub fn backup(from: &sqlite::Connection, to: &sqlite::Connection) -> Result<(), DbError> {
+    let main = c"main";
+
+    // Safety: both connections outlive the backup object, which is always finished
+    unsafe {
+        let backup =
+            ffi::sqlite3_backup_init(to.as_raw(), main.as_ptr(), from.as_raw(), main.as_ptr());
+        if backup.is_null() {
+            return Err(sqlite_error(to, ffi::sqlite3_errcode(to.as_raw())));
+        }
+
+        let mut code = ffi::sqlite3_backup_step(backup, -1);
+        for _ in 0..BUSY_TIMEOUT_MS / 100 {
+            if code != ffi::SQLITE_BUSY && code != ffi::SQLITE_LOCKED {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            code = ffi::sqlite3_backup_step(backup, -1);
+        }
+
+        let finish = ffi::sqlite3_backup_finish(backup);
+        if code != ffi::SQLITE_DONE {
+            return Err(sqlite_error(to, code));
+        }
+        if finish != ffi::SQLITE_OK {
+            return Err(sqlite_error(to, finish));
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the error of a failed low-level call
+fn sqlite_error(conn: &sqlite::Connection, code: c_int) -> DbError {
+    // Safety: the message belongs to the open connection and is copied at once
+    let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(conn.as_raw())) };
+    sqlite::Error {
+        code: Some(code as isize),
+        message: Some(message.to_string_lossy().into_owned()),
+    }
+    .into()
+}
+
+/// Check the integrity of the database file
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+///
+/// # Return
+///
+/// Return None if the database is sound, else the first problem found.
+pub fn integrity_check(conn: &sqlite::Connection) -> Result<Option<String>, DbError> {
+    let mut statement = conn.prepare("PRAGMA integrity_check;")?;
+    match statement.next()? {
+        sqlite::State::Row => {
+            let result = statement.read::<String, _>(0)?;
+            Ok((result != "ok").then_some(result))
+        }
+        sqlite::State::Done => Ok(None),
+    }
+}
+
+/// Version of the database schema, stored as sqlite `user_version`
+pub const SCHEMA_VERSION: i64 = 18;
+
+/// Columns selected to build a [Stamp]
+const STAMP_COLUMNS: &str = "id, datetime, in_out, created_at, updated_at, uid, source";
+
+/// Condition restricting a stamp query to the selected [User]
+const CURRENT_USER: &str = "user_id = (SELECT user_id FROM temp.UserContext)";
+
+/// Id of the selected [User], as inserted with the rows they own
+const CURRENT_USER_ID: &str = "(SELECT user_id FROM temp.UserContext)";
+
+/// User id of the [Setting]s of the whole work-log, e.g. [HASH_CHAIN_SETTING]
+const SHARED_USER_ID: &str = "0";
+
+/// Name of the [User] owning the stamps when none is selected
+pub const DEFAULT_USER: &str = "default";
+
+/// Hash preceding the very first stamp of the hash chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// [Setting] key telling if the stamps are hash-chained
+pub(crate) const HASH_CHAIN_SETTING: &str = "hash_chain";
+
+/// Get the schema version of the database, 0 for a new or unversioned database
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+pub fn schema_version(conn: &sqlite::Connection) -> Result<i64, DbError> {
+    let mut statement = conn.prepare("PRAGMA user_version;")?;
+    match statement.next()? {
+        sqlite::State::Row => Ok(statement.read::<i64, _>(0)?),
+        sqlite::State::Done => Ok(0),
+    }
+}
+
+pub(crate) fn table_exists(conn: &sqlite::Connection, table: &str) -> Result<bool, DbError> {
+    let mut statement =
+        conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?;")?;
+    statement.bind((1, table))?;
+    Ok(statement.next()? == sqlite::State::Row)
+}
+
+/// Apply the migration from `version` to the next schema version
+///
+/// Migrations are written against the schema of their time, not using the
+/// `create` functions that always follow the latest schema.
+#[instrument(level = "debug", skip(conn))]
+fn migrate(conn: &sqlite::Connection, version: i64) -> Result<(), DbError> {
+    match version {
+        0 => {
+            // Unversioned databases may already have some of the tables
+            let query = "CREATE TABLE IF NOT EXISTS Closeout (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    until TEXT
+                );
+                CREATE TABLE IF NOT EXISTS Adjustment (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    datetime TEXT,
+                    target TEXT,
+                    seconds INTEGER,
+                    reason TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS Audit (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    datetime TEXT,
+                    operation TEXT,
+                    tbl TEXT,
+                    row_id INTEGER,
+                    old_value TEXT,
+                    new_value TEXT
+                );
+                CREATE TABLE IF NOT EXISTS Setting (
+                    key TEXT NOT NULL PRIMARY KEY,
+                    value TEXT
+                );
+                ALTER TABLE Stamp ADD COLUMN hash TEXT;";
+            do_simple_query(conn, query.into())
+        }
+        1 => {
+            // Stamp dates from ISO8601 text to indexed Unix epoch
+            let query = "CREATE TABLE Stamp_new (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    datetime INTEGER,
+                    in_out TEXT,
+                    hash TEXT
+                );
+                INSERT INTO Stamp_new (id, datetime, in_out, hash)
+                    SELECT id, CAST(strftime('%s', datetime) AS INTEGER), in_out, hash FROM Stamp;
+                DROP TABLE Stamp;
+                ALTER TABLE Stamp_new RENAME TO Stamp;
+                CREATE INDEX Stamp_datetime ON Stamp (datetime);";
+            // The hashed content changes with the date format: the chain is
+            // only hashed again up to its first broken stamp, not to cover a
+            // tampering made before. `wtime verify` then still finds the break.
+            // Settings are not yet kept per user
+            let chained = conn
+                .prepare(format!(
+                    "SELECT 1 FROM Setting WHERE key = '{}';",
+                    HASH_CHAIN_SETTING
+                ))?
+                .next()?
+                == sqlite::State::Row;
+            let broken = if chained {
+                legacy_chain_break(conn)?
+            } else {
+                None
+            };
+            do_simple_query(conn, query.into())?;
+
+            if chained {
+                if let Some(id) = broken {
+                    warn!(%id, "Hash chain is broken, kept as is from this stamp on");
+                }
+                Stamp::hash_all(conn, broken)?;
+            }
+            Ok(())
+        }
+        2 => {
+            let query = "CREATE TABLE Allocation (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    session INTEGER NOT NULL,
+                    target TEXT NOT NULL,
+                    fraction REAL NOT NULL
+                );";
+            do_simple_query(conn, query.into())
+        }
+        3 => do_simple_query(
+            conn,
+            "CREATE INDEX Allocation_session ON Allocation (session);".into(),
+        ),
+        4 => {
+            let query = "CREATE TABLE StampArchive (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    datetime INTEGER,
+                    in_out TEXT,
+                    hash TEXT
+                );
+                CREATE INDEX StampArchive_datetime ON StampArchive (datetime);
+                CREATE VIEW AllStamps AS
+                    SELECT id, datetime, in_out, hash FROM StampArchive
+                    UNION ALL
+                    SELECT id, datetime, in_out, hash FROM Stamp;";
+            do_simple_query(conn, query.into())
+        }
+        5 => do_simple_query(conn, "ALTER TABLE Audit ADD COLUMN command TEXT;".into()),
+        6 => {
+            let query = "ALTER TABLE Stamp ADD COLUMN created_at INTEGER;
+                ALTER TABLE Stamp ADD COLUMN updated_at INTEGER;
+                ALTER TABLE StampArchive ADD COLUMN created_at INTEGER;
+                ALTER TABLE StampArchive ADD COLUMN updated_at INTEGER;
+                DROP VIEW AllStamps;
+                CREATE VIEW AllStamps AS
+                    SELECT id, datetime, in_out, hash, created_at, updated_at FROM StampArchive
+                    UNION ALL
+                    SELECT id, datetime, in_out, hash, created_at, updated_at FROM Stamp;";
+            do_simple_query(conn, query.into())
+        }
+        7 => {
+            let query = "ALTER TABLE Stamp ADD COLUMN uid TEXT;
+                ALTER TABLE StampArchive ADD COLUMN uid TEXT;
+                DROP VIEW AllStamps;
+                CREATE VIEW AllStamps AS
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid FROM StampArchive
+                    UNION ALL
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid FROM Stamp;";
+            do_simple_query(conn, query.into())?;
+
+            // Random ids can not be generated by sqlite itself
+            for table in ["Stamp", "StampArchive"] {
+                let mut ids = Vec::new();
+                let mut statement = conn.prepare(format!("SELECT id FROM {};", table))?;
+                while let sqlite::State::Row = statement.next()? {
+                    ids.push(statement.read::<i64, _>(0)?);
+                }
+
+                let mut statement =
+                    conn.prepare(format!("UPDATE {} SET uid = ? WHERE id = ?;", table))?;
+                for id in ids {
+                    statement.reset()?;
+                    statement.bind((1, Uuid::new_v4().to_string().as_str()))?;
+                    statement.bind((2, id))?;
+                    statement.next()?;
+                }
+            }
+
+            do_simple_query(
+                conn,
+                "CREATE UNIQUE INDEX Stamp_uid ON Stamp (uid);
+                CREATE UNIQUE INDEX StampArchive_uid ON StampArchive (uid);"
+                    .into(),
+            )
+        }
+        8 => {
+            // Existing stamps belong to the default user
+            let query = format!(
+                "CREATE TABLE User (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    name TEXT NOT NULL UNIQUE
+                );
+                INSERT INTO User (id, name) VALUES(1, '{}');
+                ALTER TABLE Stamp ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+                ALTER TABLE StampArchive ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+                DROP VIEW AllStamps;
+                CREATE VIEW AllStamps AS
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id
+                        FROM StampArchive
+                    UNION ALL
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id
+                        FROM Stamp;",
+                DEFAULT_USER
+            );
+            do_simple_query(conn, query)
+        }
+        9 => {
+            let query = "CREATE TABLE Worklog (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    session INTEGER NOT NULL,
+                    service TEXT NOT NULL,
+                    issue TEXT NOT NULL,
+                    seconds INTEGER NOT NULL,
+                    remote_id TEXT,
+                    datetime TEXT
+                );
+                CREATE INDEX Worklog_session ON Worklog (session);";
+            do_simple_query(conn, query.into())
+        }
+        10 => {
+            let query = "CREATE TABLE Tag (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    session INTEGER NOT NULL,
+                    name TEXT NOT NULL
+                );
+                CREATE INDEX Tag_session ON Tag (session);";
+            do_simple_query(conn, query.into())
+        }
+        11 => {
+            let query = "CREATE TABLE Estimate (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    kind TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    seconds INTEGER NOT NULL,
+                    UNIQUE (kind, name)
+                );";
+            do_simple_query(conn, query.into())
+        }
+        12 => {
+            Project::create(conn)?;
+
+            // Existing allocation targets become projects
+            let mut targets = Vec::new();
+            let mut statement = conn.prepare("SELECT DISTINCT target FROM Allocation;")?;
+            while let sqlite::State::Row = statement.next()? {
+                targets.push(statement.read::<String, _>(0)?);
+            }
+            for target in targets {
+                Project::register(conn, &target)?;
+            }
+            Ok(())
+        }
+        13 => {
+            let query = "ALTER TABLE Stamp ADD COLUMN source TEXT;
+                ALTER TABLE StampArchive ADD COLUMN source TEXT;
+                DROP VIEW AllStamps;
+                CREATE VIEW AllStamps AS
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source
+                        FROM StampArchive
+                    UNION ALL
+                    SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source
+                        FROM Stamp;";
+            do_simple_query(conn, query.into())
+        }
+        14 => {
+            let query = "CREATE TABLE Reference (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    session INTEGER NOT NULL,
+                    name TEXT NOT NULL
+                );
+                CREATE INDEX Reference_session ON Reference (session);";
+            do_simple_query(conn, query.into())
+        }
+        15 => {
+            // Corrections, closings and settings were shared by all the users,
+            // they become the default user's, but for the hash chain
+            let query = format!(
+                "ALTER TABLE Closeout ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+                ALTER TABLE Adjustment ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+                CREATE TABLE Setting_new (
+                    user_id INTEGER NOT NULL,
+                    key TEXT NOT NULL,
+                    value TEXT,
+                    PRIMARY KEY (user_id, key)
+                );
+                INSERT INTO Setting_new (user_id, key, value)
+                    SELECT CASE key WHEN '{}' THEN {} ELSE 1 END, key, value FROM Setting;
+                DROP TABLE Setting;
+                ALTER TABLE Setting_new RENAME TO Setting;",
+                HASH_CHAIN_SETTING, SHARED_USER_ID
+            );
+            do_simple_query(conn, query)
+        }
+        16 => {
+            // Sessions split or referencing tickets were pushed by share
+            let query = "ALTER TABLE Worklog ADD COLUMN share TEXT;
+                UPDATE Worklog SET share = issue
+                    WHERE session IN (SELECT session FROM Allocation)
+                    OR session IN (SELECT session FROM Reference);";
+            do_simple_query(conn, query.into())
+        }
+        17 => {
+            // The existing records are hash-chained as they are now
+            do_simple_query(conn, "ALTER TABLE Audit ADD COLUMN hash TEXT;".into())?;
+
+            let mut records = Vec::new();
+            let mut statement = conn.prepare(
+                "SELECT id, datetime, operation, tbl, row_id, old_value, new_value, command
+                FROM Audit ORDER BY id;",
+            )?;
+            while let sqlite::State::Row = statement.next()? {
+                records.push(Audit::read(&statement)?);
+            }
+
+            let mut update = conn.prepare("UPDATE Audit SET hash = ? WHERE id = ?;")?;
+            let mut prev_hash = GENESIS_HASH.to_string();
+            for record in records {
+                prev_hash = audit_hash(&prev_hash, &record);
+                update.reset()?;
+                update.bind((1, prev_hash.as_str()))?;
+                update.bind((2, record.id))?;
+                update.next()?;
+            }
+            Ok(())
+        }
+        _ => unreachable!("No migration from schema version {}", version),
+    }
+}
+
+/// Create or upgrade all the tables used by the application.
+///
+/// A new database is directly created with the latest schema. Otherwise
+/// each migration step runs in its own transaction, so a failure leaves the
+/// database at the last successfully reached version.
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+#[instrument(level = "debug", skip_all)]
+pub fn init(conn: &sqlite::Connection) -> Result<(), DbError> {
+    Audit::create_context(conn)?;
+    User::create_context(conn)?;
+    let version = schema_version(conn)?;
+
+    if version == 0 && !table_exists(conn, "Stamp")? {
+        Stamp::create(conn)?;
+        Closeout::create(conn)?;
+        Adjustment::create(conn)?;
+        Audit::create(conn)?;
+        Setting::create(conn)?;
+        Allocation::create(conn)?;
+        User::create(conn)?;
+        Worklog::create(conn)?;
+        Tag::create(conn)?;
+        Estimate::create(conn)?;
+        Project::create(conn)?;
+        Reference::create(conn)?;
+        return do_simple_query(conn, format!("PRAGMA user_version = {};", SCHEMA_VERSION));
+    }
+
+    for version in version..SCHEMA_VERSION {
+        info!(version, "Migrating database schema");
+        transaction(conn, |tx| {
+            migrate(tx, version)?;
+            do_simple_query(tx, format!("PRAGMA user_version = {};", version + 1))
+        })?;
+    }
     Ok(())
 }
 
+/// Compute the hash of a stamp as chained before the dates were stored as Unix epoch
+///
+/// The date is hashed as the ISO8601 text it was stored as.
+fn legacy_chain_hash(prev_hash: &str, id: i64, date: &str, in_out: &str) -> String {
+    let content = [prev_hash, &id.to_string(), date, in_out].join("\t");
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Check the hash chain of a schema storing the dates as ISO8601 text
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+///
+/// # Return
+///
+/// The id of the first stamp not matching its hash, None if the chain is intact.
+fn legacy_chain_break(conn: &sqlite::Connection) -> Result<Option<StampId>, DbError> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut statement =
+        conn.prepare("SELECT id, datetime, in_out, hash FROM Stamp ORDER BY id;")?;
+
+    while let sqlite::State::Row = statement.next()? {
+        let id = statement.read::<i64, _>("id")?;
+        let hash = legacy_chain_hash(
+            &prev_hash,
+            id,
+            &statement.read::<String, _>("datetime")?,
+            &statement.read::<String, _>("in_out")?,
+        );
+        if statement.read::<Option<String>, _>("hash")? != Some(hash.clone()) {
+            return Ok(Some(StampId(id)));
+        }
+        prev_hash = hash;
+    }
+    Ok(None)
+}
+
+/// Compute the hash of a stamp, chained to the hash of the previous stamp.
+///
+/// The hashed content is the previous hash followed by the stamp fields as
+/// stored in database, separated by tabulations.
+pub(crate) fn chain_hash(prev_hash: &str, id: i64, date: i64, in_out: &str) -> String {
+    let content = [prev_hash, &id.to_string(), &date.to_string(), in_out].join("\t");
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Compute the hash of an audit record, chained to the hash of the previous record.
+///
+/// The hashed content is the previous hash followed by the record fields,
+/// separated by tabulations. Missing values are hashed as empty strings,
+/// except the command that is only hashed when present, so that the records
+/// written before it was recorded keep their hash.
+pub fn audit_hash(prev_hash: &str, record: &Audit) -> String {
+    let mut content = vec![
+        prev_hash.to_string(),
+        record.id.to_string(),
+        record.date.to_rfc3339(),
+        record.operation.to_string(),
+        record.table.clone(),
+        record.row_id.to_string(),
+        record.old_value.clone().unwrap_or_default(),
+        record.new_value.clone().unwrap_or_default(),
+    ];
+    content.extend(record.command.clone());
+
+    format!("{:x}", Sha256::digest(content.join("\t").as_bytes()))
+}
+
 impl Stamp {
     /// Construct a new struct with exact value
-    pub fn new(id: i64, date: DateTime<Utc>, in_out: InOut) -> Self {
-        Self { id, date, in_out }
+    pub fn new(id: StampId, date: DateTime<Utc>, in_out: InOut) -> Self {
+        Self {
+            id,
+            date,
+            in_out,
+            created_at: None,
+            updated_at: None,
+            uid: None,
+            source: None,
+        }
     }
 
     /// Create a new stamp item, bearing current timestamp and check-IN direction
+    ///
+    /// Stamps are stored with a one second resolution, so is the timestamp.
     pub fn check_in() -> Self {
         Self {
-            id: 0,
-            date: Utc::now(),
+            id: StampId::default(),
+            date: Utc::now().trunc_subsecs(0),
             in_out: InOut::In,
+            created_at: None,
+            updated_at: None,
+            uid: None,
+            source: None,
         }
     }
 
     /// Create a new stamp item, bearing current timestamp and check-OUT direction
+    ///
+    /// Stamps are stored with a one second resolution, so is the timestamp.
     pub fn check_out() -> Self {
         Self {
-            id: 0,
-            date: Utc::now(),
+            id: StampId::default(),
+            date: Utc::now().trunc_subsecs(0),
             in_out: InOut::Out,
+            created_at: None,
+            updated_at: None,
+            uid: None,
+            source: None,
         }
     }
 
     /// Insert (create) stamp into given database
     ///
+    /// The creation and modification times are set to the current time, and
+    /// a new uid is generated if there is none.
+    ///
     /// # Arguments
     /// * `conn` - reference to a open SQLITE database connection
     ///
     /// # Return
     /// Return self if no error.
-    pub fn insert(&mut self, conn: &sqlite::Connection) -> StampResult {
-        let insert_query = format!(
-            "INSERT INTO Stamp ( datetime, in_out) VALUES( \"{}\", \"{}\") ",
-            self.date.to_rfc3339(),
-            self.in_out
-        );
+    #[instrument(level = "debug", skip(conn))]
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> StampResult<'_> {
+        Closeout::check_open(conn, &self.date)?;
 
-        conn.execute(insert_query)?;
+        // Ids continue after the archived stamps, even if all were archived
+        let now = Utc::now().trunc_subsecs(0);
+        let uid = self.uid.unwrap_or_else(Uuid::new_v4);
+        let mut statement = conn.prepare(
+            "INSERT INTO Stamp (id, datetime, in_out, created_at, updated_at, uid, user_id, source)
+            VALUES((SELECT COALESCE(MAX(id), 0) + 1 FROM AllStamps), ?, ?, ?, ?, ?,
+                (SELECT user_id FROM temp.UserContext), ?);",
+        )?;
+        statement.bind((1, self.date.timestamp()))?;
+        statement.bind((2, self.in_out.to_string().as_str()))?;
+        statement.bind((3, now.timestamp()))?;
+        statement.bind((4, now.timestamp()))?;
+        statement.bind((5, uid.to_string().as_str()))?;
+        statement.bind((6, self.source.map(|s| s.to_string()).as_deref()))?;
+        statement.next()?;
+        self.id = StampId(last_insert_id(conn)?);
+        self.uid = Some(uid);
+        self.created_at = Some(now);
+        self.updated_at = Some(now);
 
-        let mut statement = conn.prepare("SELECT last_insert_rowid()")?;
+        if Self::is_chained(conn)? {
+            let mut statement = conn.prepare(
+                "SELECT hash FROM AllStamps WHERE id < ? AND hash IS NOT NULL ORDER BY id DESC LIMIT 1;",
+            )?;
+            statement.bind((1, self.id))?;
+            let prev_hash = match statement.next()? {
+                sqlite::State::Row => statement.read::<String, _>(0)?,
+                sqlite::State::Done => GENESIS_HASH.to_string(),
+            };
 
-        match statement.next()? {
-            sqlite::State::Row => {
-                self.id = statement.read::<i64, _>(0)?;
+            let hash = chain_hash(
+                &prev_hash,
+                self.id.0,
+                self.date.timestamp(),
+                &self.in_out.to_string(),
+            );
+            let mut statement = conn.prepare("UPDATE Stamp SET hash = ? WHERE id = ?;")?;
+            statement.bind((1, hash.as_str()))?;
+            statement.bind((2, self.id))?;
+            statement.next()?;
+        }
+
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Stamp",
+            self.id.0,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Insert (create) stamps at once into given database
+    ///
+    /// Unlike inserting the stamps one by one, a single transaction and a
+    /// single prepared statement are used, so that importing thousands of
+    /// stamps is fast. Either all of them are inserted or none, with
+    /// consecutive ids in the given order.
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `stamps` - Stamps to insert, their ids, uids and times are set
+    ///
+    /// # Return
+    /// [DbError::ClosedPeriod] if a stamp is in the closed period.
+    #[instrument(level = "debug", skip_all, fields(count = stamps.len()))]
+    pub fn insert_many(conn: &sqlite::Connection, stamps: &mut [Stamp]) -> Result<(), DbError> {
+        transaction(conn, |tx| {
+            if let Some(first) = stamps.iter().map(|s| s.date).min() {
+                Closeout::check_open(tx, &first)?;
+            }
+
+            // Ids continue after the archived stamps, even if all were archived
+            let mut statement = tx.prepare("SELECT COALESCE(MAX(id), 0) + 1 FROM AllStamps;")?;
+            statement.next()?;
+            let first_id = statement.read::<i64, _>(0)?;
+
+            let mut prev_hash = None;
+            if Self::is_chained(tx)? {
+                let mut statement = tx.prepare(
+                    "SELECT hash FROM AllStamps WHERE hash IS NOT NULL ORDER BY id DESC LIMIT 1;",
+                )?;
+                prev_hash = Some(match statement.next()? {
+                    sqlite::State::Row => statement.read::<String, _>(0)?,
+                    sqlite::State::Done => GENESIS_HASH.to_string(),
+                });
             }
-            sqlite::State::Done => {
-                unreachable!("SELECT last_insert_rowid() should not fail");
+
+            let now = Utc::now().trunc_subsecs(0);
+            let mut insert = tx.prepare(
+                "INSERT INTO Stamp
+                    (id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source)
+                VALUES(?, ?, ?, ?, ?, ?, ?, (SELECT user_id FROM temp.UserContext), ?);",
+            )?;
+            let mut audit = Audit::writer(tx)?;
+            for (id, stamp) in (first_id..).zip(stamps.iter_mut()) {
+                let uid = stamp.uid.unwrap_or_else(Uuid::new_v4);
+                let in_out = stamp.in_out.to_string();
+                if let Some(prev) = &prev_hash {
+                    prev_hash = Some(chain_hash(prev, id, stamp.date.timestamp(), &in_out));
+                }
+
+                insert.reset()?;
+                insert.bind((1, id))?;
+                insert.bind((2, stamp.date.timestamp()))?;
+                insert.bind((3, in_out.as_str()))?;
+                insert.bind((4, prev_hash.as_deref()))?;
+                insert.bind((5, now.timestamp()))?;
+                insert.bind((6, now.timestamp()))?;
+                insert.bind((7, uid.to_string().as_str()))?;
+                insert.bind((8, stamp.source.map(|s| s.to_string()).as_deref()))?;
+                insert.next()?;
+
+                stamp.id = StampId(id);
+                stamp.uid = Some(uid);
+                stamp.created_at = Some(now);
+                stamp.updated_at = Some(now);
+                Audit::write(
+                    &mut audit,
+                    Operation::Insert,
+                    "Stamp",
+                    id,
+                    None,
+                    Some(&stamp.audit_value()),
+                )?;
             }
+            Ok(())
+        })
+    }
+
+    /// Insert the stamp after the last one, check-ins and check-outs alternating
+    ///
+    /// The last stamp is read and the new one inserted in an immediate
+    /// transaction, holding the write lock of the database: two processes
+    /// stamping at once are serialized, and the second one fails instead of
+    /// stamping twice the same direction. Within a [transaction], it is a
+    /// savepoint of it instead, so that the insertion is part of the caller's
+    /// operation.
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error, [DbError::NotAlternating] if the last stamp has the same direction.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn insert_next(&mut self, conn: &sqlite::Connection) -> StampResult<'_> {
+        let insert = |stamp: &mut Self| match Self::last(conn) {
+            Some(last) if last.in_out == stamp.in_out => Err(DbError::NotAlternating(stamp.in_out)),
+            _ => stamp.insert(conn).map(|_| ()),
+        };
+        if in_transaction(conn) {
+            transaction(conn, |_| insert(self))?;
+            return Ok(self);
         }
 
-        Ok(self)
+        do_simple_query(conn, "BEGIN IMMEDIATE;".into())?;
+        let result = insert(self);
+        match result {
+            Ok(_) => do_simple_query(conn, "COMMIT;".into())?,
+            Err(_) => do_simple_query(conn, "ROLLBACK;".into())?,
+        }
+        result.map(|_| &*self)
     }
 
     /// Update the database with in memory data for given Stamping.
     ///
+    /// The modification time is set to the current time in database, and in
+    /// memory. It always increases, so that a stamp read before an update
+    /// has an older modification time: updating it fails, instead of
+    /// silently overwriting the update. Stamps not read from the database
+    /// (without modification time) are not checked.
+    ///
     /// # Arguments
     /// * `conn` - reference to a open SQLITE database connection
     ///
     /// # Return
-    /// Return self if no error.
-    pub fn update(self: &Stamp, conn: &sqlite::Connection) -> StampResult {
-        let query = format!(
-            "UPDATE Stamp SET datetime = \"{}\", in_out = \"{}\" WHERE id = {};",
-            self.date.to_rfc3339(),
-            self.in_out,
-            self.id
-        );
-        do_simple_query(conn, query)?;
+    /// Return self if no error, [DbError::Conflict] if modified since read.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn update(self: &mut Stamp, conn: &sqlite::Connection) -> StampResult<'_> {
+        if Self::is_chained(conn)? {
+            return Err(DbError::ChainedStamp);
+        }
+
+        let old = Self::get(conn, self.id)?;
+        Closeout::check_open(conn, &old.date)?;
+        Closeout::check_open(conn, &self.date)?;
+
+        let now = Utc::now().trunc_subsecs(0);
+        let updated_at = match old.updated_at {
+            Some(last) if last >= now => last + Duration::seconds(1),
+            _ => now,
+        };
+        let mut statement = conn.prepare(
+            "UPDATE Stamp SET datetime = ?, in_out = ?, updated_at = ?
+            WHERE id = ? AND (? IS NULL OR updated_at IS ?);",
+        )?;
+        statement.bind((1, self.date.timestamp()))?;
+        statement.bind((2, self.in_out.to_string().as_str()))?;
+        statement.bind((3, updated_at.timestamp()))?;
+        statement.bind((4, self.id))?;
+        statement.bind((5, self.updated_at.map(|d| d.timestamp())))?;
+        statement.bind((6, self.updated_at.map(|d| d.timestamp())))?;
+        statement.next()?;
+        if conn.change_count() == 0 {
+            return Err(DbError::Conflict(self.id));
+        }
+        self.updated_at = Some(updated_at);
+
+        Audit::record(
+            conn,
+            Operation::Update,
+            "Stamp",
+            self.id.0,
+            Some(&old.audit_value()),
+            Some(&self.audit_value()),
+        )?;
         Ok(self)
     }
 
     /// Get the stamp previous to this one.
     ///
+    /// The ids of the other users' stamps are skipped.
+    ///
     /// # Arguments
     ///
     /// * `conn` - reference to a open SQLITE database connection
@@ -170,7 +1246,18 @@ impl Stamp {
     ///
     /// Return previous one into Some. Return None, if this the first stamp.
     pub fn previous(self: &Stamp, conn: &sqlite::Connection) -> Option<Stamp> {
-        Self::get(conn, self.id - 1).ok()
+        let mut statement = conn
+            .prepare(format!(
+                "SELECT {} FROM Stamp WHERE id < ? AND {} ORDER BY id DESC LIMIT 1;",
+                STAMP_COLUMNS, CURRENT_USER
+            ))
+            .ok()?;
+        statement.bind((1, self.id)).ok()?;
+
+        match statement.next().ok()? {
+            sqlite::State::Row => Self::read(&statement).ok(),
+            sqlite::State::Done => None,
+        }
     }
 
     /// Get the very first stamp
@@ -183,7 +1270,18 @@ impl Stamp {
     ///
     /// Return the very fist stamp into Some. Return None, if this there is no stamp at all.
     pub fn first(conn: &sqlite::Connection) -> Option<Stamp> {
-        Self::get(conn, 1).ok()
+        // The first ids may have been archived
+        let mut statement = conn
+            .prepare(format!("SELECT min(id) FROM Stamp WHERE {};", CURRENT_USER))
+            .ok()?;
+        match statement.next().ok()? {
+            sqlite::State::Row => {
+                let first_id = statement.read::<StampId, _>(0).ok()?;
+
+                Self::get(conn, first_id).ok()
+            }
+            sqlite::State::Done => None,
+        }
     }
 
     /// Get the very last stamp
@@ -197,11 +1295,13 @@ impl Stamp {
     /// Return the very last stamp into Some. Return None, if this there is no stamp at all.
     pub fn last(conn: &sqlite::Connection) -> Option<Stamp> {
         // Find the last id from the table
-        let mut statement = conn.prepare("SELECT max(id) FROM Stamp;").ok()?;
+        let mut statement = conn
+            .prepare(format!("SELECT max(id) FROM Stamp WHERE {};", CURRENT_USER))
+            .ok()?;
         match statement.next().ok()? {
             sqlite::State::Row => {
                 // Once we have it, get the Stamp entry
-                let last_id = statement.read::<i64, _>(0).ok()?;
+                let last_id = statement.read::<StampId, _>(0).ok()?;
 
                 Self::get(conn, last_id).ok()
             }
@@ -209,6 +1309,20 @@ impl Stamp {
         }
     }
 
+    /// Count the stamps, archived ones excluded
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn count(conn: &sqlite::Connection) -> Result<i64, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT COUNT(*) FROM Stamp WHERE {};",
+            CURRENT_USER
+        ))?;
+        statement.next()?;
+        Ok(statement.read::<i64, _>(0)?)
+    }
+
     /// Get the stamp with given ID.
     ///
     /// # Arguments
@@ -219,60 +1333,2389 @@ impl Stamp {
     /// # Returns
     ///
     /// Stamp object with the given id, or [DbError::NoSuchEntry] error
-    pub fn get(conn: &sqlite::Connection, id: i64) -> Result<Stamp, DbError> {
+    pub fn get(conn: &sqlite::Connection, id: StampId) -> Result<Stamp, DbError> {
         let mut statement = conn.prepare(format!(
-            "SELECT datetime, in_out FROM Stamp WHERE id = {};",
-            id
+            "SELECT {} FROM Stamp WHERE id = {} AND {};",
+            STAMP_COLUMNS, id, CURRENT_USER
         ))?;
 
         match statement.next()? {
-            sqlite::State::Row => Ok(Self {
-                id,
-                date: DateTime::parse_from_rfc3339(&statement.read::<String, _>("datetime")?)?
-                    .into(),
-                in_out: InOut::from_str(&statement.read::<String, _>("in_out")?).unwrap(),
-            }),
+            sqlite::State::Row => Self::read(&statement),
             sqlite::State::Done => Err(DbError::NoSuchEntry),
         }
     }
 
-    /// Get the stamp the very first Stamp after the given timestamp
+    /// Get the stamp with given uid
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `uid` - Globally unique ID of the stamp
+    /// * `archived` - Look for the stamp in the archive instead of the current stamps
+    ///
+    /// # Returns
+    ///
+    /// The stamp into Some, None if there is no such stamp
+    pub fn get_by_uid(
+        conn: &sqlite::Connection,
+        uid: &Uuid,
+        archived: bool,
+    ) -> Result<Option<Stamp>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM {} WHERE uid = ? AND {};",
+            STAMP_COLUMNS,
+            if archived { "StampArchive" } else { "Stamp" },
+            CURRENT_USER
+        ))?;
+        statement.bind((1, uid.to_string().as_str()))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Self::read(&statement).map(Some),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Get the stamp the very first Stamp after the given timestamp
     ///
     /// # Arguments
     ///
     /// * `conn` - reference to a open SQLITE database connection
     /// * `initial_date` - Date/Time to look after
     ///
-    /// # Returns
+    /// # Returns
+    ///
+    /// First stamp after given date or [DbError::NoSuchEntry] error
+    pub fn get_after(
+        conn: &sqlite::Connection,
+        initial_date: &DateTime<Utc>,
+    ) -> Result<Self, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM Stamp WHERE datetime >= {} AND {} ORDER BY id LIMIT 1;",
+            STAMP_COLUMNS,
+            initial_date.timestamp(),
+            CURRENT_USER
+        ))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Self::read(&statement),
+            sqlite::State::Done => Err(DbError::NoSuchEntry),
+        }
+    }
+
+    /// Get the very last stamp before the given timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `date` - Date/Time to look before
+    ///
+    /// # Returns
+    ///
+    /// Last stamp before given date or [DbError::NoSuchEntry] error
+    pub fn get_before(conn: &sqlite::Connection, date: &DateTime<Utc>) -> Result<Self, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM Stamp WHERE datetime < {} AND {} ORDER BY id DESC LIMIT 1;",
+            STAMP_COLUMNS,
+            date.timestamp(),
+            CURRENT_USER
+        ))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Self::read(&statement),
+            sqlite::State::Done => Err(DbError::NoSuchEntry),
+        }
+    }
+
+    /// Compute the total worked time from the stamps at or after given date.
+    ///
+    /// Every check-in immediately followed by a check-out counts as a worked
+    /// period. Pairing and summing is done by the database in a single query.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Date/Time to start counting from
+    ///
+    /// # Returns
+    ///
+    /// Sum of all the worked periods, zero if there is no stamp after `from`.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn total_from(
+        conn: &sqlite::Connection,
+        from: &DateTime<Utc>,
+    ) -> Result<Duration, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT COALESCE(SUM(datetime - prev_datetime), 0)
+            FROM (
+                SELECT datetime, in_out,
+                    LAG(datetime) OVER (ORDER BY id) AS prev_datetime,
+                    LAG(in_out) OVER (ORDER BY id) AS prev_in_out
+                FROM Stamp WHERE datetime >= ? AND {}
+            )
+            WHERE prev_in_out = 'In' AND in_out = 'Out';",
+            CURRENT_USER
+        ))?;
+        statement.bind((1, from.timestamp()))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Duration::seconds(statement.read::<i64, _>(0)?)),
+            sqlite::State::Done => Ok(Duration::zero()),
+        }
+    }
+
+    /// Find the stamps matching a filter, in id order
+    ///
+    /// The criteria are translated into SQL, so that only the matching
+    /// stamps are read, and only the requested page of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `filter` - Criteria the stamps must all match
+    #[instrument(level = "debug", skip(conn))]
+    pub fn search(conn: &sqlite::Connection, filter: &StampFilter) -> Result<Vec<Stamp>, DbError> {
+        let mut conditions = vec![CURRENT_USER];
+        if filter.from.is_some() {
+            conditions.push("datetime >= :from");
+        }
+        if filter.to.is_some() {
+            conditions.push("datetime < :to");
+        }
+        if filter.direction.is_some() {
+            conditions.push("in_out = :direction");
+        }
+        if filter.project.is_some() {
+            conditions.push("session IN (SELECT session FROM Allocation WHERE target = :project)");
+        }
+        if filter.note_contains.is_some() {
+            conditions.push(
+                "session IN (SELECT session FROM Tag WHERE instr(lower(name), lower(:note)) > 0)",
+            );
+        }
+        if filter.source.is_some() {
+            conditions.push("source = :source");
+        }
+
+        // A check-out belongs to the session of the check-in before it
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM (
+                SELECT *, CASE WHEN in_out = 'In' THEN id ELSE
+                    (SELECT MAX(p.id) FROM Stamp p
+                    WHERE p.id < s.id AND p.in_out = 'In' AND p.user_id = s.user_id)
+                END AS session
+                FROM Stamp s
+            )
+            WHERE {} ORDER BY id LIMIT :limit OFFSET :offset;",
+            STAMP_COLUMNS,
+            conditions.join(" AND ")
+        ))?;
+        // A negative limit is no limit
+        statement.bind((":limit", filter.limit.map_or(-1, |l| l as i64)))?;
+        statement.bind((":offset", filter.offset as i64))?;
+        if let Some(from) = &filter.from {
+            statement.bind((":from", from.timestamp()))?;
+        }
+        if let Some(to) = &filter.to {
+            statement.bind((":to", to.timestamp()))?;
+        }
+        if let Some(direction) = &filter.direction {
+            statement.bind((":direction", direction.to_string().as_str()))?;
+        }
+        if let Some(project) = &filter.project {
+            statement.bind((":project", project.as_str()))?;
+        }
+        if let Some(note) = &filter.note_contains {
+            statement.bind((":note", note.as_str()))?;
+        }
+        if let Some(source) = &filter.source {
+            statement.bind((":source", source.to_string().as_str()))?;
+        }
+
+        let mut stamps = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            stamps.push(Self::read(&statement)?);
+        }
+        Ok(stamps)
+    }
+
+    /// Delete current stamp from database
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    #[instrument(level = "debug", skip(conn))]
+    pub fn delete(self: &Stamp, conn: &sqlite::Connection) -> Result<(), DbError> {
+        if Self::is_chained(conn)? {
+            return Err(DbError::ChainedStamp);
+        }
+        Closeout::check_open(conn, &self.date)?;
+        do_simple_query(
+            conn,
+            format!(
+                "DELETE FROM Stamp WHERE ID = {} AND {};",
+                self.id, CURRENT_USER
+            ),
+        )?;
+
+        Audit::record(
+            conn,
+            Operation::Delete,
+            "Stamp",
+            self.id.0,
+            Some(&self.audit_value()),
+            None,
+        )
+    }
+
+    /// Delete the stamps of a date window, for good
+    ///
+    /// The allocations, tags and worklogs of the deleted sessions go with
+    /// them, all in a single transaction. A session can not be cut in two,
+    /// so the window must start with a check-in and end with a check-out.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Lower bound (included) of the stamps to delete
+    /// * `to` - Upper bound (excluded) of the stamps to delete
+    ///
+    /// # Return
+    ///
+    /// Number of deleted stamps, [DbError::OpenSession] if a session would be
+    /// cut, [DbError::ChainedStamp] if hash-chained.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn delete_range(
+        conn: &sqlite::Connection,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<usize, DbError> {
+        if Self::is_chained(conn)? {
+            return Err(DbError::ChainedStamp);
+        }
+        Closeout::check_open(conn, from)?;
+
+        let mut stamps = Vec::new();
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM Stamp WHERE datetime >= ? AND datetime < ? AND {} ORDER BY id;",
+            STAMP_COLUMNS, CURRENT_USER
+        ))?;
+        statement.bind((1, from.timestamp()))?;
+        statement.bind((2, to.timestamp()))?;
+        while let sqlite::State::Row = statement.next()? {
+            stamps.push(Self::read(&statement)?);
+        }
+        drop(statement);
+
+        if let Some(first) = stamps.first().filter(|s| s.in_out == InOut::Out) {
+            let checkin = first.previous(conn).map_or(first.id, |s| s.id);
+            return Err(DbError::OpenSession(checkin));
+        }
+        if let Some(last) = stamps.last().filter(|s| s.in_out == InOut::In) {
+            return Err(DbError::OpenSession(last.id));
+        }
+
+        transaction(conn, |tx| {
+            let mut delete = tx.prepare("DELETE FROM Stamp WHERE id = ?;")?;
+            let mut audit = Audit::writer(tx)?;
+            for stamp in &stamps {
+                delete.reset()?;
+                delete.bind((1, stamp.id))?;
+                delete.next()?;
+                Audit::write(
+                    &mut audit,
+                    Operation::Delete,
+                    "Stamp",
+                    stamp.id.0,
+                    Some(&stamp.audit_value()),
+                    None,
+                )?;
+
+                if stamp.in_out == InOut::In {
+                    Self::delete_session_data(tx, stamp.id)?;
+                }
+            }
+            Ok(stamps.len())
+        })
+    }
+
+    /// Delete the allocations, tags and worklogs of a session
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `session` - ID of the session check-in stamp
+    pub fn delete_session_data(conn: &sqlite::Connection, session: StampId) -> Result<(), DbError> {
+        Allocation::delete_of_session(conn, session)?;
+        for table in ["Tag", "Reference", "Worklog"] {
+            let mut statement =
+                conn.prepare(format!("DELETE FROM {} WHERE session = ?;", table))?;
+            statement.bind((1, session))?;
+            statement.next()?;
+        }
+        Ok(())
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Stamp (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                datetime INTEGER,
+                in_out TEXT,
+                hash TEXT,
+                created_at INTEGER,
+                updated_at INTEGER,
+                uid TEXT,
+                user_id INTEGER NOT NULL DEFAULT 1,
+                source TEXT
+            );
+            CREATE INDEX IF NOT EXISTS Stamp_datetime ON Stamp (datetime);
+            CREATE UNIQUE INDEX IF NOT EXISTS Stamp_uid ON Stamp (uid);
+            CREATE TABLE IF NOT EXISTS StampArchive (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                datetime INTEGER,
+                in_out TEXT,
+                hash TEXT,
+                created_at INTEGER,
+                updated_at INTEGER,
+                uid TEXT,
+                user_id INTEGER NOT NULL DEFAULT 1,
+                source TEXT
+            );
+            CREATE INDEX IF NOT EXISTS StampArchive_datetime ON StampArchive (datetime);
+            CREATE UNIQUE INDEX IF NOT EXISTS StampArchive_uid ON StampArchive (uid);
+            CREATE VIEW IF NOT EXISTS AllStamps AS
+                SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source
+                    FROM StampArchive
+                UNION ALL
+                SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source
+                    FROM Stamp;";
+
+        do_simple_query(conn, query.into())
+    }
+
+    /// Get an iterator, staring from current stamp
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn iter<'a>(&self, conn: &'a sqlite::Connection) -> Result<StampIterator<'a>, DbError> {
+        StampIterator::new(conn, false, Some(self.id), false, None, None)
+    }
+
+    /// Get an iterator walking backwards, staring from current stamp
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn iter_rev<'a>(&self, conn: &'a sqlite::Connection) -> Result<StampIterator<'a>, DbError> {
+        StampIterator::new(conn, false, Some(self.id), true, None, None)
+    }
+
+    /// Get an iterator, staring from current stamp and stopping before given date
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `until` - Upper bound (excluded) of the stamps dates
+    pub fn iter_until<'a>(
+        &self,
+        conn: &'a sqlite::Connection,
+        until: &DateTime<Utc>,
+    ) -> Result<StampIterator<'a>, DbError> {
+        StampIterator::new(conn, false, Some(self.id), false, None, Some(until))
+    }
+
+    /// Get an iterator over the stamps of a date window
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Lower bound (included) of the stamps dates
+    /// * `to` - Upper bound (excluded) of the stamps dates
+    pub fn iter_range<'a>(
+        conn: &'a sqlite::Connection,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<StampIterator<'a>, DbError> {
+        StampIterator::new(conn, false, None, false, Some(from), Some(to))
+    }
+
+    /// Get an iterator over the stamps dated in `[from, to)`, archived ones included
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Lower bound (included) of the stamps dates
+    /// * `to` - Upper bound (excluded) of the stamps dates
+    pub fn iter_range_archived<'a>(
+        conn: &'a sqlite::Connection,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<StampIterator<'a>, DbError> {
+        StampIterator::new(conn, true, None, false, Some(from), Some(to))
+    }
+
+    /// Move the stamps dated before `before` to the archive table
+    ///
+    /// Archived stamps are no longer counted, nor modifiable, but can still be
+    /// included in reports, see [Stamp::iter_range_archived]. A session can
+    /// not be cut in two, so the sessions must be finished before `before`.
+    /// Only the stamps of the selected [User] are archived.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `before` - Stamps dated before are archived
+    ///
+    /// # Return
+    ///
+    /// Number of archived stamps, [DbError::OpenSession] if a session would be cut.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn archive(conn: &sqlite::Connection, before: &DateTime<Utc>) -> Result<usize, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT s.id FROM Stamp s
+            LEFT JOIN Stamp n ON n.id =
+                (SELECT MIN(id) FROM Stamp WHERE id > s.id AND user_id = s.user_id)
+            WHERE s.in_out = 'In' AND s.datetime < :before AND s.{}
+                AND (n.id IS NULL OR n.datetime >= :before)
+            LIMIT 1;",
+            CURRENT_USER
+        ))?;
+        statement.bind((":before", before.timestamp()))?;
+        if let sqlite::State::Row = statement.next()? {
+            return Err(DbError::OpenSession(statement.read::<StampId, _>(0)?));
+        }
+        drop(statement);
+
+        transaction(conn, |tx| Self::move_to_archive(tx, before))
+    }
+
+    fn move_to_archive(
+        conn: &sqlite::Connection,
+        before: &DateTime<Utc>,
+    ) -> Result<usize, DbError> {
+        let mut stamps = Vec::new();
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM Stamp WHERE datetime < ? AND {} ORDER BY id;",
+            STAMP_COLUMNS, CURRENT_USER
+        ))?;
+        statement.bind((1, before.timestamp()))?;
+        while let sqlite::State::Row = statement.next()? {
+            stamps.push(Self::read(&statement)?);
+        }
+
+        let mut statement = conn.prepare(format!(
+            "INSERT INTO StampArchive
+                (id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source)
+            SELECT id, datetime, in_out, hash, created_at, updated_at, uid, user_id, source
+            FROM Stamp WHERE datetime < ? AND {};",
+            CURRENT_USER
+        ))?;
+        statement.bind((1, before.timestamp()))?;
+        statement.next()?;
+        let mut statement = conn.prepare(format!(
+            "DELETE FROM Stamp WHERE datetime < ? AND {};",
+            CURRENT_USER
+        ))?;
+        statement.bind((1, before.timestamp()))?;
+        statement.next()?;
+
+        for stamp in &stamps {
+            let value = stamp.audit_value();
+            Audit::record(
+                conn,
+                Operation::Delete,
+                "Stamp",
+                stamp.id.0,
+                Some(&value),
+                None,
+            )?;
+            Audit::record(
+                conn,
+                Operation::Insert,
+                "StampArchive",
+                stamp.id.0,
+                None,
+                Some(&value),
+            )?;
+        }
+
+        Ok(stamps.len())
+    }
+
+    /// Get the current stamps updated at or after given date, in id order
+    ///
+    /// Stamps written before the modification time was recorded are only
+    /// returned without date.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `since` - Lower bound (included) of the modification times, None for all stamps
+    pub fn updated_since(
+        conn: &sqlite::Connection,
+        since: Option<&DateTime<Utc>>,
+    ) -> Result<Vec<Stamp>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM Stamp WHERE (? IS NULL OR updated_at >= ?) AND {} ORDER BY id;",
+            STAMP_COLUMNS, CURRENT_USER
+        ))?;
+        statement.bind((1, since.map(|d| d.timestamp())))?;
+        statement.bind((2, since.map(|d| d.timestamp())))?;
+
+        let mut stamps = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            stamps.push(Self::read(&statement)?);
+        }
+        Ok(stamps)
+    }
+
+    /// Get the date of the last archived stamp, None if nothing is archived
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn archived_until(conn: &sqlite::Connection) -> Result<Option<DateTime<Utc>>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT MAX(datetime) FROM StampArchive WHERE {};",
+            CURRENT_USER
+        ))?;
+        statement.next()?;
+        Ok(statement
+            .read::<Option<i64>, _>(0)?
+            .map(|epoch| Utc.timestamp_opt(epoch, 0).unwrap()))
+    }
+
+    /// Renumber the stamps in date order, the uid breaking ties
+    ///
+    /// Stamps are paired into sessions in id order, so stamps inserted out of
+    /// date order must be moved to their place. A check-out comes before a
+    /// check-in of the same date, ending a session where the next one starts.
+    /// Ids keep starting at the lowest current one and the allocations,
+    /// worklogs, tags and references follow their stamp, all in a single
+    /// transaction. Past audit records are left untouched: the move is audited
+    /// as one update listing the `old->new` id pairs, see
+    /// [Audit::current_row_ids]. Archived stamps are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    ///
+    /// Number of stamps whose id changed, [DbError::ChainedStamp] if hash-chained.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn renumber(conn: &sqlite::Connection) -> Result<usize, DbError> {
+        if Self::is_chained(conn)? {
+            return Err(DbError::ChainedStamp);
+        }
+
+        // Negative ids avoid collisions while moving
+        let query = "CREATE TEMP TABLE Renumber AS
+                SELECT id AS old,
+                    (SELECT MIN(id) FROM Stamp) + ROW_NUMBER() OVER (ORDER BY datetime, in_out DESC, uid) - 1 AS new
+                FROM Stamp;
+            DELETE FROM temp.Renumber WHERE old = new;
+            UPDATE Stamp SET id = -(SELECT new FROM temp.Renumber WHERE old = Stamp.id)
+                WHERE id IN (SELECT old FROM temp.Renumber);
+            UPDATE Stamp SET id = -id WHERE id < 0;
+            UPDATE Allocation SET session = (SELECT new FROM temp.Renumber WHERE old = session)
+                WHERE session IN (SELECT old FROM temp.Renumber);
+            UPDATE Worklog SET session = (SELECT new FROM temp.Renumber WHERE old = session)
+                WHERE session IN (SELECT old FROM temp.Renumber);
+            UPDATE Tag SET session = (SELECT new FROM temp.Renumber WHERE old = session)
+                WHERE session IN (SELECT old FROM temp.Renumber);
+            UPDATE Reference SET session = (SELECT new FROM temp.Renumber WHERE old = session)
+                WHERE session IN (SELECT old FROM temp.Renumber);";
+        transaction(conn, |tx| {
+            do_simple_query(tx, query.into())?;
+
+            let mut moves = Vec::new();
+            let mut statement = tx.prepare("SELECT old, new FROM temp.Renumber ORDER BY new;")?;
+            while let sqlite::State::Row = statement.next()? {
+                moves.push((statement.read::<i64, _>(0)?, statement.read::<i64, _>(1)?));
+            }
+            drop(statement);
+
+            if let Some((_, first)) = moves.first() {
+                let pairs = moves
+                    .iter()
+                    .map(|(old, new)| format!("{}->{}", old, new))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Audit::record(tx, Operation::Update, "Stamp", *first, None, Some(&pairs))?;
+            }
+
+            do_simple_query(tx, "DROP TABLE temp.Renumber;".into())?;
+            Ok(moves.len())
+        })
+    }
+
+    /// Split sessions running past midnight into one session per day
+    ///
+    /// A check-out and a check-in are inserted at every midnight crossed, the
+    /// new sessions getting the allocations and tags of the split one. The
+    /// stamps are then renumbered in date order, see [Stamp::renumber], all in
+    /// a single transaction.
+    /// Sessions in the closed period are left as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `sessions` - Check-in of every session to split, with the midnights it crosses
+    ///
+    /// # Return
+    ///
+    /// Number of split sessions, [DbError::ChainedStamp] if hash-chained.
+    #[instrument(level = "debug", skip(conn))]
+    pub fn split(
+        conn: &sqlite::Connection,
+        sessions: &[(Stamp, Vec<DateTime<Utc>>)],
+    ) -> Result<usize, DbError> {
+        if Self::is_chained(conn)? {
+            return Err(DbError::ChainedStamp);
+        }
+
+        transaction(conn, |tx| Self::insert_splits(tx, sessions))
+    }
+
+    fn insert_splits(
+        conn: &sqlite::Connection,
+        sessions: &[(Stamp, Vec<DateTime<Utc>>)],
+    ) -> Result<usize, DbError> {
+        let mut count = 0;
+        for (checkin, midnights) in sessions {
+            match Closeout::check_open(conn, &checkin.date) {
+                Err(DbError::ClosedPeriod(_)) => continue,
+                other => other?,
+            }
+            let allocations = Allocation::of_session(conn, checkin.id)?;
+            let tags = Tag::of_session_range(conn, checkin.id, checkin.id)?;
+            let references = Reference::of_session_range(conn, checkin.id, checkin.id)?;
+
+            // The pieces of the session keep its source, allocations, tags and
+            // references
+            for midnight in midnights {
+                let mut out = Stamp::new(StampId(0), *midnight, InOut::Out);
+                out.source = checkin.source;
+                out.insert(conn)?;
+                let mut next = Stamp::new(StampId(0), *midnight, InOut::In);
+                next.source = checkin.source;
+                next.insert(conn)?;
+                for allocation in &allocations {
+                    Allocation::new(next.id, &allocation.target, allocation.fraction)
+                        .insert(conn)?;
+                }
+                for tag in &tags {
+                    Tag::new(next.id, &tag.name).insert(conn)?;
+                }
+                for reference in &references {
+                    Reference::new(next.id, &reference.name).insert(conn)?;
+                }
+            }
+            count += 1;
+        }
+
+        Self::renumber(conn)?;
+        Ok(count)
+    }
+
+    /// Build a stamp from the current row of a statement selecting [STAMP_COLUMNS]
+    fn read(statement: &sqlite::Statement) -> Result<Self, DbError> {
+        let date = |column| -> Result<_, DbError> {
+            Ok(statement
+                .read::<Option<i64>, _>(column)?
+                .map(|epoch| Utc.timestamp_opt(epoch, 0).unwrap()))
+        };
+
+        Ok(Self {
+            id: statement.read::<StampId, _>("id")?,
+            date: Utc
+                .timestamp_opt(statement.read::<i64, _>("datetime")?, 0)
+                .unwrap(),
+            in_out: InOut::from_str(&statement.read::<String, _>("in_out")?).unwrap(),
+            created_at: date("created_at")?,
+            updated_at: date("updated_at")?,
+            uid: statement
+                .read::<Option<String>, _>("uid")?
+                .and_then(|uid| Uuid::parse_str(&uid).ok()),
+            source: statement
+                .read::<Option<String>, _>("source")?
+                .and_then(|source| source.parse().ok()),
+        })
+    }
+
+    /// Time between the stamped date and the moment the stamp was written
+    ///
+    /// # Return
+    ///
+    /// Return how much the stamp was backdated, None if it was not or the
+    /// creation time is unknown.
+    pub fn backdated_by(&self) -> Option<Duration> {
+        self.created_at
+            .map(|created_at| created_at - self.date)
+            .filter(|delay| *delay > Duration::zero())
+    }
+
+    /// Delete table as a whole
+    ///
+    /// This is mainly used for testing purpose
+    pub fn drop(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "DROP TABLE Stamp";
+
+        do_simple_query(conn, query.into())
+    }
+
+    /// Tell if the stamps are hash-chained
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn is_chained(conn: &sqlite::Connection) -> Result<bool, DbError> {
+        Ok(Setting::get_shared(conn, HASH_CHAIN_SETTING)?.is_some())
+    }
+
+    /// Enable the hash-chained mode, hashing all the existing stamps.
+    ///
+    /// Once enabled, stamps can no longer be updated or deleted, and any
+    /// modification made behind the application's back is detected by
+    /// [Stamp::verify_chain].
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    #[instrument(level = "debug", skip_all)]
+    pub fn enable_chain(conn: &sqlite::Connection) -> Result<(), DbError> {
+        Self::hash_all(conn, None)?;
+        Setting::set_shared(conn, HASH_CHAIN_SETTING, "1")
+    }
+
+    /// (Re-)compute the hash chain of all the stamps
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `until` - First stamp keeping its hash, along with the following
+    ///   ones, all of them if None
+    fn hash_all(conn: &sqlite::Connection, until: Option<StampId>) -> Result<(), DbError> {
+        // Older schemas, being migrated, have no archive
+        let tables: &[&str] = if table_exists(conn, "StampArchive")? {
+            &["Stamp", "StampArchive"]
+        } else {
+            &["Stamp"]
+        };
+
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut select = conn.prepare(format!(
+            "SELECT id, datetime, in_out FROM {} ORDER BY id;",
+            if tables.len() > 1 {
+                "AllStamps"
+            } else {
+                "Stamp"
+            }
+        ))?;
+        let mut updates = tables
+            .iter()
+            .map(|table| conn.prepare(format!("UPDATE {} SET hash = ? WHERE id = ?;", table)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        while let sqlite::State::Row = select.next()? {
+            let id = select.read::<i64, _>("id")?;
+            if until.is_some_and(|until| id >= until.0) {
+                break;
+            }
+            let hash = chain_hash(
+                &prev_hash,
+                id,
+                select.read::<i64, _>("datetime")?,
+                &select.read::<String, _>("in_out")?,
+            );
+
+            for update in &mut updates {
+                update.reset()?;
+                update.bind((1, hash.as_str()))?;
+                update.bind((2, id))?;
+                update.next()?;
+            }
+
+            prev_hash = hash;
+        }
+
+        Ok(())
+    }
+
+    /// Check the hash chain of all the stamps
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    ///
+    /// Number of verified stamps, or the id of the first stamp not matching its hash.
+    #[instrument(level = "debug", skip_all)]
+    pub fn verify_chain(conn: &sqlite::Connection) -> Result<Result<usize, StampId>, DbError> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut count = 0;
+        let mut statement =
+            conn.prepare("SELECT id, datetime, in_out, hash FROM AllStamps ORDER BY id;")?;
+
+        while let sqlite::State::Row = statement.next()? {
+            let id = statement.read::<StampId, _>("id")?;
+            let hash = chain_hash(
+                &prev_hash,
+                id.0,
+                statement.read::<i64, _>("datetime")?,
+                &statement.read::<String, _>("in_out")?,
+            );
+
+            if statement.read::<Option<String>, _>("hash")? != Some(hash.clone()) {
+                return Ok(Err(id));
+            }
+
+            prev_hash = hash;
+            count += 1;
+        }
+
+        Ok(Ok(count))
+    }
+
+    /// Textual representation of the stamp content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!("{} {}", self.date.to_rfc3339(), self.in_out)
+    }
+
+    /// Compute the time delta from this stamp to another.
+    ///
+    /// * `other` - Stamp to compute time delta from
+    pub fn delta(self: &Stamp, other: &Stamp) -> Duration {
+        if other.date > self.date {
+            other.date - self.date
+        } else {
+            self.date - other.date
+        }
+    }
+}
+
+/// Closing of the work-log up to a given date.
+///
+/// Stamps before the closing date can no longer be inserted, updated or deleted.
+/// Corrections to a closed period must be booked as an [Adjustment], or the
+/// latest closeout removed, see [Closeout::delete_last].
+#[derive(Debug)]
+pub struct Closeout {
+    /// Closeout unique ID (primary-key in database)
+    pub id: i64,
+    /// Everything strictly before this date is closed
+    pub until: DateTime<Utc>,
+}
+
+impl Closeout {
+    /// Construct a new closeout, closing everything before `until`
+    pub fn new(until: DateTime<Utc>) -> Self {
+        Self { id: 0, until }
+    }
+
+    /// Insert (create) closeout into given database
+    ///
+    /// A closed period can not be reopened this way, so `until` must be after
+    /// the current closing date.
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error, [DbError::ClosedPeriod] if already closed further.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        Self::check_open(conn, &self.until)?;
+
+        do_simple_query(
+            conn,
+            format!(
+                "INSERT INTO Closeout (until, user_id) VALUES(\"{}\", {});",
+                self.until.to_rfc3339(),
+                CURRENT_USER_ID
+            ),
+        )?;
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Closeout",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Remove the latest closeout of the selected [User], reopening its period
+    ///
+    /// Only the latest closeout can be removed, the previous one closing the
+    /// work-log again. The removal is recorded in the [Audit] log.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    ///
+    /// The removed closeout, None if the work-log was never closed.
+    pub fn delete_last(conn: &sqlite::Connection) -> Result<Option<Closeout>, DbError> {
+        transaction(conn, |tx| {
+            let Some(closeout) = Self::last(tx)? else {
+                return Ok(None);
+            };
+            let mut statement = tx.prepare("DELETE FROM Closeout WHERE id = ?;")?;
+            statement.bind((1, closeout.id))?;
+            statement.next()?;
+            Audit::record(
+                tx,
+                Operation::Delete,
+                "Closeout",
+                closeout.id,
+                Some(&closeout.audit_value()),
+                None,
+            )?;
+            Ok(Some(closeout))
+        })
+    }
+
+    /// Textual representation of the closeout content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        self.until.to_rfc3339()
+    }
+
+    /// Get the latest closeout of the selected [User]
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    ///
+    /// Return the latest closeout into Some, None if the work-log was never closed.
+    pub fn last(conn: &sqlite::Connection) -> Result<Option<Closeout>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT id, until FROM Closeout WHERE {} ORDER BY id DESC LIMIT 1;",
+            CURRENT_USER
+        ))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(Self {
+                id: statement.read::<i64, _>("id")?,
+                until: DateTime::parse_from_rfc3339(&statement.read::<String, _>("until")?)?.into(),
+            })),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Check that the given date is not in a closed period
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `date` - Date/Time to check
+    ///
+    /// # Return
+    ///
+    /// [DbError::ClosedPeriod] error if `date` is before the latest closeout.
+    pub fn check_open(conn: &sqlite::Connection, date: &DateTime<Utc>) -> Result<(), DbError> {
+        match Self::last(conn)? {
+            Some(closeout) if *date < closeout.until => Err(DbError::ClosedPeriod(closeout.until)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Closeout (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                until TEXT,
+                user_id INTEGER NOT NULL DEFAULT 1
+            );";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Correction of the worked time, counted in totals along with the stamps.
+///
+/// An adjustment is booked at the time it is created, so it never modifies a
+/// closed period, but it references the date it corrects.
+#[derive(Debug)]
+pub struct Adjustment {
+    /// Adjustment unique ID (primary-key in database)
+    pub id: i64,
+    /// Booking timestamp, Utc
+    pub date: DateTime<Utc>,
+    /// Date the correction applies to
+    pub target: DateTime<Utc>,
+    /// Time to add to the total (negative to remove time)
+    pub amount: Duration,
+    /// Why the correction was needed
+    pub reason: String,
+}
+
+impl Adjustment {
+    /// Create a new adjustment, booked now
+    pub fn new(amount: Duration, reason: &str, target: Option<DateTime<Utc>>) -> Self {
+        let date = Utc::now();
+        Self {
+            id: 0,
+            date,
+            target: target.unwrap_or(date),
+            amount,
+            reason: reason.into(),
+        }
+    }
+
+    /// Insert (create) adjustment into given database
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        let mut statement = conn.prepare(format!(
+            "INSERT INTO Adjustment (datetime, target, seconds, reason, user_id)
+            VALUES(?, ?, ?, ?, {});",
+            CURRENT_USER_ID
+        ))?;
+        statement.bind((1, self.date.to_rfc3339().as_str()))?;
+        statement.bind((2, self.target.to_rfc3339().as_str()))?;
+        statement.bind((3, self.amount.num_seconds()))?;
+        statement.bind((4, self.reason.as_str()))?;
+        statement.next()?;
+
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Adjustment",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Textual representation of the adjustment content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!(
+            "{} {}s {}",
+            self.target.to_rfc3339(),
+            self.amount.num_seconds(),
+            self.reason
+        )
+    }
+
+    /// Sum of the adjustments of the selected [User] applying at or after given date.
+    ///
+    /// Adjustments count toward the date they correct, as in [Adjustment::range],
+    /// not the date they were booked.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Target date to start counting from
+    pub fn total_from(
+        conn: &sqlite::Connection,
+        from: &DateTime<Utc>,
+    ) -> Result<Duration, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT COALESCE(SUM(seconds), 0) FROM Adjustment WHERE target >= ? AND {};",
+            CURRENT_USER
+        ))?;
+        statement.bind((1, from.to_rfc3339().as_str()))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Duration::seconds(statement.read::<i64, _>(0)?)),
+            sqlite::State::Done => Ok(Duration::zero()),
+        }
+    }
+
+    /// Get the adjustments of the selected [User] applying to a date window, by target date
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `from` - Lower bound (included) of the target dates
+    /// * `to` - Upper bound (excluded) of the target dates
+    pub fn range(
+        conn: &sqlite::Connection,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Adjustment>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT id, datetime, target, seconds, reason FROM Adjustment
+            WHERE target >= ? AND target < ? AND {} ORDER BY target, id;",
+            CURRENT_USER
+        ))?;
+        statement.bind((1, from.to_rfc3339().as_str()))?;
+        statement.bind((2, to.to_rfc3339().as_str()))?;
+
+        let mut adjustments = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            adjustments.push(Self {
+                id: statement.read::<i64, _>("id")?,
+                date: DateTime::parse_from_rfc3339(&statement.read::<String, _>("datetime")?)?
+                    .into(),
+                target: DateTime::parse_from_rfc3339(&statement.read::<String, _>("target")?)?
+                    .into(),
+                amount: Duration::seconds(statement.read::<i64, _>("seconds")?),
+                reason: statement.read::<String, _>("reason")?,
+            });
+        }
+        Ok(adjustments)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Adjustment (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                datetime TEXT,
+                target TEXT,
+                seconds INTEGER,
+                reason TEXT NOT NULL,
+                user_id INTEGER NOT NULL DEFAULT 1
+            );";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Record of a modification done to the database
+#[derive(Debug)]
+pub struct Audit {
+    /// Audit record unique ID (primary-key in database)
+    pub id: i64,
+    /// Wall-clock time of the modification, Utc
+    pub date: DateTime<Utc>,
+    /// Kind of modification
+    pub operation: Operation,
+    /// Name of the modified table
+    pub table: String,
+    /// ID of the modified row
+    pub row_id: i64,
+    /// Row content before the modification, None on insert
+    pub old_value: Option<String>,
+    /// Row content after the modification, None on delete
+    pub new_value: Option<String>,
+    /// Command line of the modification, None if not run from a command
+    pub command: Option<String>,
+    /// Hash chained to the previous record, computed when written, see [audit_hash]
+    pub hash: String,
+}
+
+/// Prepared statements appending to the audit log, see [Audit::write]
+struct AuditWriter<'c> {
+    /// Reads the id and hash of the last record
+    last: sqlite::Statement<'c>,
+    /// Reads the command recorded with the modifications
+    command: sqlite::Statement<'c>,
+    insert: sqlite::Statement<'c>,
+}
+
+impl Audit {
+    /// Set the command line recorded with the following modifications
+    ///
+    /// The command is only kept by this connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `command` - Command line of the modifications, None if not run from a command
+    pub fn set_command(conn: &sqlite::Connection, command: Option<&str>) -> Result<(), DbError> {
+        do_simple_query(conn, "DELETE FROM temp.AuditContext;".into())?;
+
+        let mut statement = conn.prepare("INSERT INTO temp.AuditContext (command) VALUES(?);")?;
+        statement.bind((1, command))?;
+        statement.next()?;
+
+        Ok(())
+    }
+
+    /// Append a modification to the audit log
+    fn record(
+        conn: &sqlite::Connection,
+        operation: Operation,
+        table: &str,
+        row_id: i64,
+        old_value: Option<&str>,
+        new_value: Option<&str>,
+    ) -> Result<(), DbError> {
+        Self::write(
+            &mut Self::writer(conn)?,
+            operation,
+            table,
+            row_id,
+            old_value,
+            new_value,
+        )
+    }
+
+    /// Prepare the statements appending to the audit log, see [Audit::write]
+    fn writer(conn: &sqlite::Connection) -> Result<AuditWriter<'_>, DbError> {
+        Ok(AuditWriter {
+            last: conn.prepare("SELECT id, hash FROM Audit ORDER BY id DESC LIMIT 1;")?,
+            command: conn.prepare("SELECT command FROM temp.AuditContext;")?,
+            insert: conn.prepare(
+                "INSERT INTO Audit
+                    (id, datetime, operation, tbl, row_id, old_value, new_value, command, hash)
+                VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            )?,
+        })
+    }
+
+    /// Append a modification to the audit log with prepared statements
+    ///
+    /// The record is hash-chained to the last one, see [audit_hash].
+    fn write(
+        writer: &mut AuditWriter,
+        operation: Operation,
+        table: &str,
+        row_id: i64,
+        old_value: Option<&str>,
+        new_value: Option<&str>,
+    ) -> Result<(), DbError> {
+        let last = &mut writer.last;
+        last.reset()?;
+        let (last_id, last_hash) = match last.next()? {
+            sqlite::State::Row => (
+                last.read::<i64, _>("id")?,
+                last.read::<Option<String>, _>("hash")?.unwrap_or_default(),
+            ),
+            sqlite::State::Done => (0, GENESIS_HASH.to_string()),
+        };
+
+        let command = &mut writer.command;
+        command.reset()?;
+        let command = match command.next()? {
+            sqlite::State::Row => command.read::<Option<String>, _>(0)?,
+            sqlite::State::Done => None,
+        };
+
+        let mut record = Self {
+            id: last_id + 1,
+            date: Utc::now(),
+            operation,
+            table: table.to_string(),
+            row_id,
+            old_value: old_value.map(str::to_string),
+            new_value: new_value.map(str::to_string),
+            command,
+            hash: String::new(),
+        };
+        record.hash = audit_hash(&last_hash, &record);
+
+        let insert = &mut writer.insert;
+        insert.reset()?;
+        insert.bind((1, record.id))?;
+        insert.bind((2, record.date.to_rfc3339().as_str()))?;
+        insert.bind((3, record.operation.to_string().as_str()))?;
+        insert.bind((4, table))?;
+        insert.bind((5, row_id))?;
+        insert.bind((6, old_value))?;
+        insert.bind((7, new_value))?;
+        insert.bind((8, record.command.as_deref()))?;
+        insert.bind((9, record.hash.as_str()))?;
+        insert.next()?;
+
+        Ok(())
+    }
+
+    /// Verify the hash chain of the audit log
+    ///
+    /// Each stored hash is computed again from the previous stored one, so
+    /// that a modified, inserted or removed record breaks the chain.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Complete audit log, oldest first
+    ///
+    /// # Return
+    ///
+    /// The number of records, or the id of the first one not matching its hash.
+    pub fn verify(records: &[Audit]) -> Result<usize, i64> {
+        let mut prev_hash = GENESIS_HASH;
+        for record in records {
+            if audit_hash(prev_hash, record) != record.hash {
+                return Err(record.id);
+            }
+            prev_hash = &record.hash;
+        }
+
+        Ok(records.len())
+    }
+
+    /// Get the id pairs of a stamp renumbering record, see [Stamp::renumber]
+    ///
+    /// # Return
+    ///
+    /// The `(old, new)` id pairs, None if the record is not a renumbering.
+    pub fn renumbering(&self) -> Option<Vec<(i64, i64)>> {
+        if self.table != "Stamp" || self.operation != Operation::Update || self.old_value.is_some()
+        {
+            return None;
+        }
+
+        self.new_value
+            .as_deref()?
+            .split(',')
+            .map(|pair| {
+                let (old, new) = pair.split_once("->")?;
+                Some((old.parse().ok()?, new.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Resolve the row ids of audit records to the current stamp ids
+    ///
+    /// Records keep the id a stamp had when they were written; the later
+    /// renumberings are applied to get the id the stamp has now. Only the
+    /// records of the Stamp table are resolved, the others keep their row id.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Complete audit log, oldest first
+    ///
+    /// # Return
+    ///
+    /// Current row id of each record, in the same order.
+    pub fn current_row_ids(records: &[Audit]) -> Vec<i64> {
+        // Maps an id at the current point of the log to the final id, missing ids are unchanged
+        let mut later: HashMap<i64, i64> = HashMap::new();
+        let mut ids = vec![0; records.len()];
+
+        for (index, record) in records.iter().enumerate().rev() {
+            ids[index] = match record.table.as_str() {
+                "Stamp" => *later.get(&record.row_id).unwrap_or(&record.row_id),
+                _ => record.row_id,
+            };
+
+            if let Some(pairs) = record.renumbering() {
+                let moved: HashMap<i64, i64> = pairs.into_iter().collect();
+                let olds = moved
+                    .keys()
+                    .chain(later.keys())
+                    .copied()
+                    .collect::<Vec<_>>();
+                later = olds
+                    .into_iter()
+                    .map(|old| {
+                        let new = *moved.get(&old).unwrap_or(&old);
+                        (old, *later.get(&new).unwrap_or(&new))
+                    })
+                    .collect();
+            }
+        }
+
+        ids
+    }
+
+    /// Get all the audit records, oldest first
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn all(conn: &sqlite::Connection) -> Result<Vec<Audit>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, datetime, operation, tbl, row_id, old_value, new_value, command, hash
+            FROM Audit ORDER BY id;",
+        )?;
+
+        let mut records = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            let mut record = Self::read(&statement)?;
+            record.hash = statement
+                .read::<Option<String>, _>("hash")?
+                .unwrap_or_default();
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    /// Build a record from a row, without its hash
+    fn read(statement: &sqlite::Statement) -> Result<Self, DbError> {
+        Ok(Self {
+            id: statement.read::<i64, _>("id")?,
+            date: DateTime::parse_from_rfc3339(&statement.read::<String, _>("datetime")?)?.into(),
+            operation: Operation::from_str(&statement.read::<String, _>("operation")?).unwrap(),
+            table: statement.read::<String, _>("tbl")?,
+            row_id: statement.read::<i64, _>("row_id")?,
+            old_value: statement.read::<Option<String>, _>("old_value")?,
+            new_value: statement.read::<Option<String>, _>("new_value")?,
+            command: statement.read::<Option<String>, _>("command")?,
+            hash: String::new(),
+        })
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Audit (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                datetime TEXT,
+                operation TEXT,
+                tbl TEXT,
+                row_id INTEGER,
+                old_value TEXT,
+                new_value TEXT,
+                command TEXT,
+                hash TEXT
+            );";
+
+        do_simple_query(conn, query.into())
+    }
+
+    /// Create the connection private table holding the current command
+    fn create_context(conn: &sqlite::Connection) -> Result<(), DbError> {
+        do_simple_query(
+            conn,
+            "CREATE TEMP TABLE IF NOT EXISTS AuditContext (command TEXT);".into(),
+        )
+    }
+}
+
+/// Share of a session's time allocated to an issue or project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Allocation {
+    /// Allocation unique ID (primary-key in database)
+    pub id: i64,
+    /// Session the time is taken from, as the ID of its check-in stamp
+    pub session: StampId,
+    /// Issue or project the time is allocated to
+    pub target: String,
+    /// Share of the session, between 0 and 1
+    pub fraction: f64,
+}
+
+impl Allocation {
+    /// Construct a new allocation of a session
+    pub fn new(session: StampId, target: &str, fraction: f64) -> Self {
+        Self {
+            id: 0,
+            session,
+            target: target.into(),
+            fraction,
+        }
+    }
+
+    /// Insert (create) allocation into given database
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        Project::register(conn, &self.target)?;
+
+        let mut statement =
+            conn.prepare("INSERT INTO Allocation (session, target, fraction) VALUES(?, ?, ?);")?;
+        statement.bind((1, self.session))?;
+        statement.bind((2, self.target.as_str()))?;
+        statement.bind((3, self.fraction))?;
+        statement.next()?;
+
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Allocation",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Textual representation of the allocation content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!("{} {} {}", self.session, self.target, self.fraction)
+    }
+
+    /// Get the allocations of a session
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `session` - ID of the session check-in stamp
+    pub fn of_session(conn: &sqlite::Connection, session: StampId) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, session, target, fraction FROM Allocation WHERE session = ? ORDER BY id;",
+        )?;
+        statement.bind((1, session))?;
+
+        let mut allocations = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            allocations.push(Self::read(&statement)?);
+        }
+
+        Ok(allocations)
+    }
+
+    /// Delete the allocations of a session
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `session` - ID of the session check-in stamp
+    ///
+    /// # Return
+    ///
+    /// The deleted allocations
+    pub fn delete_of_session(
+        conn: &sqlite::Connection,
+        session: StampId,
+    ) -> Result<Vec<Self>, DbError> {
+        let allocations = Self::of_session(conn, session)?;
+        for allocation in &allocations {
+            do_simple_query(
+                conn,
+                format!("DELETE FROM Allocation WHERE id = {};", allocation.id),
+            )?;
+            Audit::record(
+                conn,
+                Operation::Delete,
+                "Allocation",
+                allocation.id,
+                Some(&allocation.audit_value()),
+                None,
+            )?;
+        }
+
+        Ok(allocations)
+    }
+
+    /// Get the allocations of all the sessions in a range of IDs
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `first` - ID of the first session check-in stamp
+    /// * `last` - ID of the last session check-in stamp (included)
+    pub fn of_session_range(
+        conn: &sqlite::Connection,
+        first: StampId,
+        last: StampId,
+    ) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, session, target, fraction FROM Allocation
+            WHERE session BETWEEN ? AND ? ORDER BY session, id;",
+        )?;
+        statement.bind((1, first))?;
+        statement.bind((2, last))?;
+
+        let mut allocations = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            allocations.push(Self::read(&statement)?);
+        }
+
+        Ok(allocations)
+    }
+
+    /// Build an allocation from the current row of a statement
+    fn read(statement: &sqlite::Statement) -> Result<Self, DbError> {
+        Ok(Self {
+            id: statement.read::<i64, _>("id")?,
+            session: statement.read::<StampId, _>("session")?,
+            target: statement.read::<String, _>("target")?,
+            fraction: statement.read::<f64, _>("fraction")?,
+        })
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Allocation (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                session INTEGER NOT NULL,
+                target TEXT NOT NULL,
+                fraction REAL NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS Allocation_session ON Allocation (session);";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Label of a session, e.g. `meeting` or `billable`
+///
+/// Unlike [Allocation], a session may carry several tags, each of them
+/// counting its whole worked time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    /// Tag unique ID (primary-key in database)
+    pub id: i64,
+    /// Session tagged, as the ID of its check-in stamp
+    pub session: StampId,
+    /// Name of the tag
+    pub name: String,
+}
+
+impl Tag {
+    /// Construct a new tag of a session
+    pub fn new(session: StampId, name: &str) -> Self {
+        Self {
+            id: 0,
+            session,
+            name: name.into(),
+        }
+    }
+
+    /// Insert (create) tag into given database
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        let mut statement = conn.prepare("INSERT INTO Tag (session, name) VALUES(?, ?);")?;
+        statement.bind((1, self.session))?;
+        statement.bind((2, self.name.as_str()))?;
+        statement.next()?;
+
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Tag",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Textual representation of the tag content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!("{} {}", self.session, self.name)
+    }
+
+    /// Get the tags of all the sessions in a range of IDs
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `first` - ID of the first session check-in stamp
+    /// * `last` - ID of the last session check-in stamp (included)
+    pub fn of_session_range(
+        conn: &sqlite::Connection,
+        first: StampId,
+        last: StampId,
+    ) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, session, name FROM Tag WHERE session BETWEEN ? AND ? ORDER BY session, id;",
+        )?;
+        statement.bind((1, first))?;
+        statement.bind((2, last))?;
+
+        let mut tags = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            tags.push(Self {
+                id: statement.read::<i64, _>("id")?,
+                session: statement.read::<StampId, _>("session")?,
+                name: statement.read::<String, _>("name")?,
+            });
+        }
+
+        Ok(tags)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Tag (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                session INTEGER NOT NULL,
+                name TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS Tag_session ON Tag (session);";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Ticket or issue of an external tracker a session is about, e.g. `ABC-123`
+///
+/// The references route the time of the session when pushing it, see
+/// [push::pending](crate::push::pending).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    /// Reference unique ID (primary-key in database)
+    pub id: i64,
+    /// Session referencing the ticket, as the ID of its check-in stamp
+    pub session: StampId,
+    /// Ticket key or issue reference, e.g. `ABC-123` or `group/project#42`
+    pub name: String,
+}
+
+impl Reference {
+    /// Construct a new reference of a session
+    pub fn new(session: StampId, name: &str) -> Self {
+        Self {
+            id: 0,
+            session,
+            name: name.into(),
+        }
+    }
+
+    /// Insert (create) reference into given database
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        let mut statement = conn.prepare("INSERT INTO Reference (session, name) VALUES(?, ?);")?;
+        statement.bind((1, self.session))?;
+        statement.bind((2, self.name.as_str()))?;
+        statement.next()?;
+
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Reference",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Textual representation of the reference content stored in the
+    /// [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!("{} {}", self.session, self.name)
+    }
+
+    /// Get the references of all the sessions in a range of IDs
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `first` - ID of the first session check-in stamp
+    /// * `last` - ID of the last session check-in stamp (included)
+    pub fn of_session_range(
+        conn: &sqlite::Connection,
+        first: StampId,
+        last: StampId,
+    ) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, session, name FROM Reference WHERE session BETWEEN ? AND ?
+                ORDER BY session, id;",
+        )?;
+        statement.bind((1, first))?;
+        statement.bind((2, last))?;
+
+        let mut references = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            references.push(Self {
+                id: statement.read::<i64, _>("id")?,
+                session: statement.read::<StampId, _>("session")?,
+                name: statement.read::<String, _>("name")?,
+            });
+        }
+
+        Ok(references)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Reference (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                session INTEGER NOT NULL,
+                name TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS Reference_session ON Reference (session);";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Issue or project time is allocated to, nested under its parent
+///
+/// Projects are named by their path, e.g. `acme/backend/api` whose parent is
+/// `acme/backend`. Every [Allocation] target is recorded as a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// Project unique ID (primary-key in database)
+    pub id: i64,
+    /// Path of the project, e.g. `acme/backend/api`
+    pub name: String,
+    /// ID of the parent project, None at the top of the hierarchy
+    pub parent: Option<i64>,
+}
+
+impl Project {
+    /// Path of the parent of a project, e.g. `acme/backend` for
+    /// `acme/backend/api`, None at the top of the hierarchy
+    pub fn parent_name(name: &str) -> Option<&str> {
+        name.rsplit_once('/')
+            .map(|(parent, _)| parent)
+            .filter(|parent| !parent.is_empty())
+    }
+
+    /// Get a project by path
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `name` - Path of the project
+    ///
+    /// # Return
+    ///
+    /// The project into Some, None if unknown.
+    pub fn get(conn: &sqlite::Connection, name: &str) -> Result<Option<Self>, DbError> {
+        let mut statement = conn.prepare("SELECT id, parent FROM Project WHERE name = ?;")?;
+        statement.bind((1, name))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(Self {
+                id: statement.read::<i64, _>("id")?,
+                name: name.into(),
+                parent: statement.read::<Option<i64>, _>("parent")?,
+            })),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Get a project by path, creating it and its missing ancestors
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `name` - Path of the project
+    pub fn register(conn: &sqlite::Connection, name: &str) -> Result<Self, DbError> {
+        if let Some(project) = Self::get(conn, name)? {
+            return Ok(project);
+        }
+
+        let parent = match Self::parent_name(name) {
+            Some(parent) => Some(Self::register(conn, parent)?.id),
+            None => None,
+        };
+        let mut statement = conn.prepare("INSERT INTO Project (name, parent) VALUES(?, ?);")?;
+        statement.bind((1, name))?;
+        statement.bind((2, parent))?;
+        statement.next()?;
+
+        let project = Self {
+            id: last_insert_id(conn)?,
+            name: name.into(),
+            parent,
+        };
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Project",
+            project.id,
+            None,
+            Some(&project.audit_value()),
+        )?;
+        Ok(project)
+    }
+
+    /// Textual representation of the project content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        match self.parent {
+            Some(parent) => format!("{} {}", self.name, parent),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Get all the projects, by path
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn all(conn: &sqlite::Connection) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare("SELECT id, name, parent FROM Project ORDER BY name;")?;
+
+        let mut projects = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            projects.push(Self {
+                id: statement.read::<i64, _>("id")?,
+                name: statement.read::<String, _>("name")?,
+                parent: statement.read::<Option<i64>, _>("parent")?,
+            });
+        }
+
+        Ok(projects)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Project (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                name TEXT NOT NULL UNIQUE,
+                parent INTEGER REFERENCES Project (id)
+            );";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Expected time of a project, or of a task tagged on its sessions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Estimate {
+    /// Estimate unique ID (primary-key in database)
+    pub id: i64,
+    /// Whether `name` is a tag rather than a project
+    pub tag: bool,
+    /// Name of the project or tag
+    pub name: String,
+    /// Expected time
+    pub estimate: Duration,
+}
+
+impl Estimate {
+    /// Construct a new estimate of a project or tag
+    pub fn new(tag: bool, name: &str, estimate: Duration) -> Self {
+        Self {
+            id: 0,
+            tag,
+            name: name.into(),
+            estimate,
+        }
+    }
+
+    /// Column value of the `tag` flag
+    fn kind(tag: bool) -> &'static str {
+        if tag {
+            "tag"
+        } else {
+            "project"
+        }
+    }
+
+    /// Textual representation of the estimate content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!(
+            "{} {} {}",
+            Self::kind(self.tag),
+            self.name,
+            self.estimate.num_seconds()
+        )
+    }
+
+    /// Get the estimate of a project or tag
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `tag` - Whether `name` is a tag rather than a project
+    /// * `name` - Name of the project or tag
+    ///
+    /// # Return
+    ///
+    /// The estimate into Some, None if none was recorded.
+    pub fn get(conn: &sqlite::Connection, tag: bool, name: &str) -> Result<Option<Self>, DbError> {
+        let mut statement =
+            conn.prepare("SELECT id, seconds FROM Estimate WHERE kind = ? AND name = ?;")?;
+        statement.bind((1, Self::kind(tag)))?;
+        statement.bind((2, name))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(Self {
+                id: statement.read::<i64, _>("id")?,
+                tag,
+                name: name.into(),
+                estimate: Duration::seconds(statement.read::<i64, _>("seconds")?),
+            })),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Record the estimate, replacing the previous one of the same project or tag
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn save(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        let old = Self::get(conn, self.tag, &self.name)?;
+
+        let mut statement = conn.prepare(
+            "INSERT INTO Estimate (kind, name, seconds) VALUES(?, ?, ?)
+            ON CONFLICT (kind, name) DO UPDATE SET seconds = excluded.seconds;",
+        )?;
+        statement.bind((1, Self::kind(self.tag)))?;
+        statement.bind((2, self.name.as_str()))?;
+        statement.bind((3, self.estimate.num_seconds()))?;
+        statement.next()?;
+
+        self.id = match &old {
+            Some(old) => old.id,
+            None => last_insert_id(conn)?,
+        };
+        Audit::record(
+            conn,
+            if old.is_some() {
+                Operation::Update
+            } else {
+                Operation::Insert
+            },
+            "Estimate",
+            self.id,
+            old.map(|old| old.audit_value()).as_deref(),
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Remove the estimate of a project or tag
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `tag` - Whether `name` is a tag rather than a project
+    /// * `name` - Name of the project or tag
+    ///
+    /// # Return
+    ///
+    /// Whether there was an estimate to remove.
+    pub fn delete(conn: &sqlite::Connection, tag: bool, name: &str) -> Result<bool, DbError> {
+        let old = match Self::get(conn, tag, name)? {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+
+        let mut statement = conn.prepare("DELETE FROM Estimate WHERE id = ?;")?;
+        statement.bind((1, old.id))?;
+        statement.next()?;
+
+        Audit::record(
+            conn,
+            Operation::Delete,
+            "Estimate",
+            old.id,
+            Some(&old.audit_value()),
+            None,
+        )?;
+        Ok(true)
+    }
+
+    /// Get all the estimates, the projects first, by name
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn all(conn: &sqlite::Connection) -> Result<Vec<Self>, DbError> {
+        let mut statement =
+            conn.prepare("SELECT id, kind, name, seconds FROM Estimate ORDER BY kind, name;")?;
+
+        let mut estimates = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            estimates.push(Self {
+                id: statement.read::<i64, _>("id")?,
+                tag: statement.read::<String, _>("kind")? == "tag",
+                name: statement.read::<String, _>("name")?,
+                estimate: Duration::seconds(statement.read::<i64, _>("seconds")?),
+            });
+        }
+
+        Ok(estimates)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
     ///
-    /// First stamp after given date or [DbError::NoSuchEntry] error
-    pub fn get_after(
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Estimate (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                seconds INTEGER NOT NULL,
+                UNIQUE (kind, name)
+            );";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Time of a session pushed to an issue tracker
+///
+/// Recorded so that the same time is not pushed twice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Worklog {
+    /// Worklog unique ID (primary-key in database)
+    pub id: i64,
+    /// Session the time is taken from, as the ID of its check-in stamp
+    pub session: StampId,
+    /// Issue tracker the time was pushed to, e.g. `jira`
+    pub service: String,
+    /// Issue the time was logged on, e.g. `PROJ-1`
+    pub issue: String,
+    /// Pushed time, in seconds
+    pub seconds: i64,
+    /// ID of the worklog in the issue tracker, if any
+    pub remote_id: Option<String>,
+    /// Wall-clock time of the push, Utc
+    pub date: DateTime<Utc>,
+    /// Allocation target or referenced ticket the time is the share of,
+    /// None for the whole session
+    pub share: Option<String>,
+}
+
+impl Worklog {
+    /// Construct a new worklog of a session, pushed now
+    pub fn new(session: StampId, service: &str, issue: &str, duration: Duration) -> Self {
+        Self {
+            id: 0,
+            session,
+            service: service.into(),
+            issue: issue.into(),
+            seconds: duration.num_seconds(),
+            remote_id: None,
+            date: Utc::now(),
+            share: None,
+        }
+    }
+
+    /// Insert (create) worklog into given database
+    ///
+    /// # Arguments
+    /// * `conn` - reference to a open SQLITE database connection
+    ///
+    /// # Return
+    /// Return self if no error.
+    pub fn insert(&mut self, conn: &sqlite::Connection) -> Result<&Self, DbError> {
+        let mut statement = conn.prepare(
+            "INSERT INTO Worklog (session, service, issue, seconds, remote_id, datetime, share)
+            VALUES(?, ?, ?, ?, ?, ?, ?);",
+        )?;
+        statement.bind((1, self.session))?;
+        statement.bind((2, self.service.as_str()))?;
+        statement.bind((3, self.issue.as_str()))?;
+        statement.bind((4, self.seconds))?;
+        statement.bind((5, self.remote_id.as_deref()))?;
+        statement.bind((6, self.date.to_rfc3339().as_str()))?;
+        statement.bind((7, self.share.as_deref()))?;
+        statement.next()?;
+
+        self.id = last_insert_id(conn)?;
+        Audit::record(
+            conn,
+            Operation::Insert,
+            "Worklog",
+            self.id,
+            None,
+            Some(&self.audit_value()),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Textual representation of the worklog content stored in the [Audit] log
+    pub(crate) fn audit_value(&self) -> String {
+        format!(
+            "{} {}:{} {}s",
+            self.session, self.service, self.issue, self.seconds
+        )
+    }
+
+    /// Get the worklogs pushed to an issue tracker from the sessions with ids in `[first, last]`
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `service` - Issue tracker, e.g. `jira`
+    /// * `first` - Check-in stamp of the first session
+    /// * `last` - Check-in stamp of the last session
+    pub fn of_sessions(
         conn: &sqlite::Connection,
-        initial_date: &DateTime<Utc>,
-    ) -> Result<Self, DbError> {
-        let mut statement = conn.prepare(format!(
-            "SELECT id, datetime, in_out FROM Stamp WHERE datetime >= '{}';",
-            initial_date.to_rfc3339()
-        ))?;
+        service: &str,
+        first: StampId,
+        last: StampId,
+    ) -> Result<Vec<Self>, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, session, service, issue, seconds, remote_id, datetime, share
+            FROM Worklog WHERE service = ? AND session BETWEEN ? AND ? ORDER BY id;",
+        )?;
+        statement.bind((1, service))?;
+        statement.bind((2, first))?;
+        statement.bind((3, last))?;
 
-        match statement.next()? {
-            sqlite::State::Row => Ok(Self {
+        let mut worklogs = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            worklogs.push(Self {
                 id: statement.read::<i64, _>("id")?,
+                session: statement.read::<StampId, _>("session")?,
+                service: statement.read::<String, _>("service")?,
+                issue: statement.read::<String, _>("issue")?,
+                seconds: statement.read::<i64, _>("seconds")?,
+                remote_id: statement.read::<Option<String>, _>("remote_id")?,
                 date: DateTime::parse_from_rfc3339(&statement.read::<String, _>("datetime")?)?
                     .into(),
-                in_out: InOut::from_str(&statement.read::<String, _>("in_out")?).unwrap(),
-            }),
+                share: statement.read::<Option<String>, _>("share")?,
+            });
+        }
+
+        Ok(worklogs)
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Worklog (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                session INTEGER NOT NULL,
+                service TEXT NOT NULL,
+                issue TEXT NOT NULL,
+                seconds INTEGER NOT NULL,
+                remote_id TEXT,
+                datetime TEXT,
+                share TEXT
+            );
+            CREATE INDEX IF NOT EXISTS Worklog_session ON Worklog (session);";
+
+        do_simple_query(conn, query.into())
+    }
+}
+
+/// Person owning stamps, when several share the work-log
+///
+/// Stamps are only read and written for the user selected on the
+/// connection, the [DEFAULT_USER] unless [User::select] is called.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    /// User unique ID (primary-key in database)
+    pub id: i64,
+    /// Name of the user, unique
+    pub name: String,
+}
+
+impl User {
+    /// Select the user of the following stamp queries, creating it if needed
+    ///
+    /// The selection is only kept by this connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `name` - Name of the user
+    ///
+    /// # Return
+    ///
+    /// The selected user
+    pub fn select(conn: &sqlite::Connection, name: &str) -> Result<User, DbError> {
+        let user = match Self::get_by_name(conn, name)? {
+            Some(user) => user,
+            None => {
+                let mut statement = conn.prepare("INSERT INTO User (name) VALUES(?);")?;
+                statement.bind((1, name))?;
+                statement.next()?;
+                let user = User {
+                    id: last_insert_id(conn)?,
+                    name: name.to_string(),
+                };
+                Audit::record(conn, Operation::Insert, "User", user.id, None, Some(name))?;
+                info!(name, "New user");
+                user
+            }
+        };
+
+        let mut statement = conn.prepare("UPDATE temp.UserContext SET user_id = ?;")?;
+        statement.bind((1, user.id))?;
+        statement.next()?;
+        Ok(user)
+    }
+
+    /// Get the user selected on this connection
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn current(conn: &sqlite::Connection) -> Result<User, DbError> {
+        let mut statement = conn.prepare(
+            "SELECT id, name FROM User WHERE id = (SELECT user_id FROM temp.UserContext);",
+        )?;
+
+        match statement.next()? {
+            sqlite::State::Row => Self::read(&statement),
             sqlite::State::Done => Err(DbError::NoSuchEntry),
         }
     }
 
-    /// Delete current stamp from database
+    /// Get all the users, in creation order
     ///
     /// # Arguments
     ///
     /// * `conn` - reference to a open SQLITE database connection
-    pub fn delete(self: &Stamp, conn: &sqlite::Connection) -> Result<(), DbError> {
-        do_simple_query(conn, format!("DELETE FROM Stamp WHERE ID = {};", self.id))
+    pub fn all(conn: &sqlite::Connection) -> Result<Vec<User>, DbError> {
+        let mut statement = conn.prepare("SELECT id, name FROM User ORDER BY id;")?;
+
+        let mut users = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            users.push(Self::read(&statement)?);
+        }
+        Ok(users)
+    }
+
+    fn get_by_name(conn: &sqlite::Connection, name: &str) -> Result<Option<User>, DbError> {
+        let mut statement = conn.prepare("SELECT id, name FROM User WHERE name = ?;")?;
+        statement.bind((1, name))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Self::read(&statement).map(Some),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    fn read(statement: &sqlite::Statement) -> Result<Self, DbError> {
+        Ok(Self {
+            id: statement.read::<i64, _>("id")?,
+            name: statement.read::<String, _>("name")?,
+        })
     }
 
     /// Create database table (Static method)
@@ -281,84 +3724,236 @@ impl Stamp {
     ///
     /// * `conn` - reference to a open SQLITE database connection
     pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
-        let query = "CREATE TABLE IF NOT EXISTS Stamp (
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS User (
                 id INTEGER NOT NULL PRIMARY KEY ASC,
-                datetime TEXT,
-                in_out TEXT
-            );";
+                name TEXT NOT NULL UNIQUE
+            );
+            INSERT OR IGNORE INTO User (id, name) VALUES(1, '{}');",
+            DEFAULT_USER
+        );
 
-        do_simple_query(conn, query.into())
+        do_simple_query(conn, query)
     }
 
-    /// Get an iterator, staring from current stamp
+    /// Create the connection private table holding the selected user
+    fn create_context(conn: &sqlite::Connection) -> Result<(), DbError> {
+        do_simple_query(
+            conn,
+            "CREATE TEMP TABLE IF NOT EXISTS UserContext (user_id INTEGER NOT NULL);
+            INSERT INTO temp.UserContext (user_id)
+                SELECT 1 WHERE NOT EXISTS (SELECT 1 FROM temp.UserContext);"
+                .into(),
+        )
+    }
+}
+
+/// Persistent key-value settings of the work-log
+///
+/// Settings belong to the selected [User], e.g. the sync cursors, but for
+/// the shared ones of the whole work-log, e.g. the hash chain.
+pub struct Setting;
+
+impl Setting {
+    /// Get the value of a setting of the selected [User]
     ///
     /// # Arguments
     ///
     /// * `conn` - reference to a open SQLITE database connection
-    pub fn iter<'a>(&self, conn: &'a sqlite::Connection) -> StampIterator<'a> {
-        StampIterator::new(conn, self.id)
+    /// * `key` - Name of the setting
+    ///
+    /// # Return
+    ///
+    /// Value into Some, None if the setting was never set.
+    pub fn get(conn: &sqlite::Connection, key: &str) -> Result<Option<String>, DbError> {
+        Self::get_of(conn, CURRENT_USER_ID, key)
     }
 
-    /// Delete table as a whole
+    /// Set the value of a setting of the selected [User], replacing any previous value
     ///
-    /// This is mainly used for testing purpose
-    pub fn drop(conn: &sqlite::Connection) -> Result<(), DbError> {
-        let query = "DROP TABLE Stamp";
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    /// * `key` - Name of the setting
+    /// * `value` - New value
+    pub fn set(conn: &sqlite::Connection, key: &str, value: &str) -> Result<(), DbError> {
+        Self::set_of(conn, CURRENT_USER_ID, key, value)
+    }
 
-        do_simple_query(conn, query.into())
+    /// Get the value of a setting of the whole work-log, see [Setting::get]
+    pub fn get_shared(conn: &sqlite::Connection, key: &str) -> Result<Option<String>, DbError> {
+        Self::get_of(conn, SHARED_USER_ID, key)
     }
 
-    /// Compute the time delta from this stamp to another.
-    ///
-    /// * `other` - Stamp to compute time delta from
-    pub fn delta(self: &Stamp, other: &Stamp) -> Duration {
-        if other.date > self.date {
-            other.date - self.date
-        } else {
-            self.date - other.date
+    /// Set the value of a setting of the whole work-log, see [Setting::set]
+    pub fn set_shared(conn: &sqlite::Connection, key: &str, value: &str) -> Result<(), DbError> {
+        Self::set_of(conn, SHARED_USER_ID, key, value)
+    }
+
+    /// Get the value of a setting of the user with given id, an SQL expression
+    fn get_of(
+        conn: &sqlite::Connection,
+        user_id: &str,
+        key: &str,
+    ) -> Result<Option<String>, DbError> {
+        let mut statement = conn.prepare(format!(
+            "SELECT value FROM Setting WHERE user_id = {} AND key = ?;",
+            user_id
+        ))?;
+        statement.bind((1, key))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(statement.read::<String, _>(0)?)),
+            sqlite::State::Done => Ok(None),
         }
     }
+
+    /// Set the value of a setting of the user with given id, an SQL expression
+    fn set_of(
+        conn: &sqlite::Connection,
+        user_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<(), DbError> {
+        let old = Self::get_of(conn, user_id, key)?;
+
+        // Upsert keeps the rowid, recorded as the audited row id
+        let mut statement = conn.prepare(format!(
+            "INSERT INTO Setting (user_id, key, value) VALUES({}, ?, ?)
+            ON CONFLICT (user_id, key) DO UPDATE SET value = excluded.value;",
+            user_id
+        ))?;
+        statement.bind((1, key))?;
+        statement.bind((2, value))?;
+        statement.next()?;
+
+        let mut statement = conn.prepare(format!(
+            "SELECT rowid FROM Setting WHERE user_id = {} AND key = ?;",
+            user_id
+        ))?;
+        statement.bind((1, key))?;
+        statement.next()?;
+        let row_id = statement.read::<i64, _>(0)?;
+
+        Audit::record(
+            conn,
+            if old.is_some() {
+                Operation::Update
+            } else {
+                Operation::Insert
+            },
+            "Setting",
+            row_id,
+            old.map(|old| format!("{}={}", key, old)).as_deref(),
+            Some(&format!("{}={}", key, value)),
+        )
+    }
+
+    /// Create database table (Static method)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - reference to a open SQLITE database connection
+    pub fn create(conn: &sqlite::Connection) -> Result<(), DbError> {
+        let query = "CREATE TABLE IF NOT EXISTS Setting (
+                user_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT,
+                PRIMARY KEY (user_id, key)
+            );";
+
+        do_simple_query(conn, query.into())
+    }
 }
 
-/// Iterator over stamps objects
+/// Iterator over stamps objects, in id order (or reverse id order)
+///
+/// All the stamps are read from a single prepared statement. Iteration stops
+/// after the first error.
 pub struct StampIterator<'a> {
-    current_index: i64,
-    db_conn: &'a sqlite::Connection,
+    statement: sqlite::Statement<'a>,
+    done: bool,
 }
 
 impl<'a> StampIterator<'a> {
-    fn new(conn: &'a sqlite::Connection, start_index: i64) -> Self {
-        Self {
-            db_conn: conn,
-            current_index: start_index,
+    fn new(
+        conn: &'a sqlite::Connection,
+        archived: bool,
+        start_index: Option<StampId>,
+        reverse: bool,
+        from: Option<&DateTime<Utc>>,
+        until: Option<&DateTime<Utc>>,
+    ) -> Result<Self, DbError> {
+        let mut conditions = vec![CURRENT_USER];
+        if start_index.is_some() {
+            conditions.push(if reverse { "id <= :id" } else { "id >= :id" });
+        }
+        if from.is_some() {
+            conditions.push("datetime >= :from");
+        }
+        if until.is_some() {
+            conditions.push("datetime < :until");
+        }
+
+        let mut statement = conn.prepare(format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY id {};",
+            STAMP_COLUMNS,
+            if archived { "AllStamps" } else { "Stamp" },
+            conditions.join(" AND "),
+            if reverse { "DESC" } else { "ASC" }
+        ))?;
+        if let Some(id) = start_index {
+            statement.bind((":id", id))?;
+        }
+        if let Some(from) = from {
+            statement.bind((":from", from.timestamp()))?;
+        }
+        if let Some(until) = until {
+            statement.bind((":until", until.timestamp()))?;
         }
+
+        Ok(Self {
+            statement,
+            done: false,
+        })
     }
 }
 
 impl<'a> Iterator for StampIterator<'a> {
-    type Item = Stamp;
+    type Item = Result<Stamp, DbError>;
 
-    fn next(&mut self) -> Option<Stamp> {
-        if let Ok(s) = Stamp::get(self.db_conn, self.current_index) {
-            self.current_index += 1;
-            Some(s)
-        } else {
-            None
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+
+        let item = match self.statement.next() {
+            Ok(sqlite::State::Row) => Stamp::read(&self.statement),
+            Ok(sqlite::State::Done) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        self.done = item.is_err();
+        Some(item)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{DbError, InOut, ParseInOutError, Stamp};
-    use chrono::{DateTime, Duration, Utc};
+    use super::{
+        Adjustment, Allocation, Audit, Closeout, DbError, Estimate, InOut, Operation,
+        ParseInOutError, Project, Reference, Setting, Source, Stamp, StampFilter, StampId, Tag,
+        User, DEFAULT_USER, SCHEMA_VERSION,
+    };
+    use chrono::{DateTime, Duration, SubsecRound, Utc};
     use sqlite;
-    use std::{fs, path::Path, str::FromStr};
-
-    const DB_FILE: &str = "unit-test.sqlite";
+    use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
 
     fn open_db(file_name: &str) -> sqlite::Connection {
-        sqlite::open(Path::new(file_name)).unwrap()
+        super::open(Path::new(file_name)).unwrap()
     }
 
     struct TestFixture {
@@ -367,18 +3962,12 @@ mod test {
 
     impl TestFixture {
         fn init() -> Self {
-            let conn = open_db(DB_FILE);
-            Stamp::create(&conn).unwrap();
+            let conn = super::open_memory().unwrap();
+            super::init(&conn).unwrap();
             TestFixture { c: conn }
         }
     }
 
-    impl Drop for TestFixture {
-        fn drop(&mut self) {
-            fs::remove_file(DB_FILE).unwrap();
-        }
-    }
-
     #[test]
     fn inout() {
         assert_eq!(InOut::from_str("In"), Ok(InOut::In));
@@ -408,45 +3997,311 @@ mod test {
 
         s_in.insert(&f.c).unwrap();
 
-        assert_ne!(s_in.id, 0);
+        assert_ne!(s_in.id, StampId(0));
         assert!(matches!(s_in.in_out, InOut::In));
 
         let mut s_out = Stamp::check_out();
         s_out.insert(&f.c).unwrap();
 
-        assert_eq!(s_out.id, s_in.id + 1);
-        assert!(matches!(s_out.in_out, InOut::Out));
+        assert_eq!(s_out.id, StampId(s_in.id.0 + 1));
+        assert!(matches!(s_out.in_out, InOut::Out));
+    }
+
+    #[test]
+    fn get() {
+        let f = TestFixture::init();
+
+        // Get a non-existent stamp
+        assert!(matches!(
+            Stamp::get(&f.c, StampId(1)),
+            Err(DbError::NoSuchEntry)
+        ));
+
+        // Create a stamp
+        Stamp::check_in().insert(&f.c).unwrap();
+
+        // Check we can get it now
+        assert!(matches!(Stamp::get(&f.c, StampId(1)), Ok(x) if x.id == StampId(1)));
+    }
+
+    #[test]
+    fn update() {
+        let f = TestFixture::init();
+
+        // Create a stamp
+        let mut s = Stamp::check_in();
+        s.insert(&f.c).unwrap();
+
+        s.in_out = InOut::Out;
+
+        let result = s.update(&f.c);
+
+        println!("{:?}", &result);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn search() {
+        let f = TestFixture::init();
+        let start = Utc::now().trunc_subsecs(0) - Duration::days(10);
+        let mut stamps: Vec<Stamp> = (0..6)
+            .map(|hour| {
+                let in_out = if hour % 2 == 0 { InOut::In } else { InOut::Out };
+                Stamp::new(StampId(0), start + Duration::hours(hour), in_out)
+            })
+            .collect();
+        stamps[4].source = Some(Source::Daemon);
+        Stamp::insert_many(&f.c, &mut stamps).unwrap();
+        Allocation::new(stamps[2].id, "acme", 1.0)
+            .insert(&f.c)
+            .unwrap();
+        Tag::new(stamps[4].id, "Code review").insert(&f.c).unwrap();
+
+        let search = |filter: StampFilter| Stamp::search(&f.c, &filter).unwrap();
+        assert_eq!(search(StampFilter::default()), stamps);
+        assert_eq!(
+            search(StampFilter {
+                from: Some(start + Duration::hours(1)),
+                to: Some(start + Duration::hours(4)),
+                direction: Some(InOut::Out),
+                ..StampFilter::default()
+            }),
+            [stamps[1].clone(), stamps[3].clone()]
+        );
+        // Both stamps of the session
+        assert_eq!(
+            search(StampFilter {
+                project: Some("acme".into()),
+                ..StampFilter::default()
+            }),
+            stamps[2..4]
+        );
+        assert_eq!(
+            search(StampFilter {
+                note_contains: Some("REVIEW".into()),
+                direction: Some(InOut::Out),
+                ..StampFilter::default()
+            }),
+            [stamps[5].clone()]
+        );
+        assert!(search(StampFilter {
+            project: Some("acme".into()),
+            note_contains: Some("review".into()),
+            ..StampFilter::default()
+        })
+        .is_empty());
+        assert_eq!(
+            search(StampFilter {
+                source: Some(Source::Daemon),
+                ..StampFilter::default()
+            }),
+            [stamps[4].clone()]
+        );
+
+        // Pages of the matching stamps
+        let page = |limit, offset| StampFilter {
+            direction: Some(InOut::In),
+            limit,
+            offset,
+            ..StampFilter::default()
+        };
+        assert_eq!(
+            search(page(Some(2), 0)),
+            [stamps[0].clone(), stamps[2].clone()]
+        );
+        assert_eq!(search(page(Some(2), 2)), [stamps[4].clone()]);
+        assert_eq!(
+            search(page(None, 1)),
+            stamps[2..].iter().step_by(2).cloned().collect::<Vec<_>>()
+        );
+        assert!(search(page(Some(0), 0)).is_empty());
+    }
+
+    #[test]
+    fn delete_range() {
+        let f = TestFixture::init();
+        let start = Utc::now().trunc_subsecs(0) - Duration::days(10);
+        let mut stamps: Vec<Stamp> = (0..6)
+            .map(|day| {
+                let in_out = if day % 2 == 0 { InOut::In } else { InOut::Out };
+                Stamp::new(StampId(0), start + Duration::days(day), in_out)
+            })
+            .collect();
+        Stamp::insert_many(&f.c, &mut stamps).unwrap();
+        Allocation::new(stamps[0].id, "acme", 1.0)
+            .insert(&f.c)
+            .unwrap();
+        Tag::new(stamps[2].id, "meeting").insert(&f.c).unwrap();
+
+        // Sessions would be cut
+        let day = |n: i64| start + Duration::days(n);
+        assert!(matches!(
+            Stamp::delete_range(&f.c, &day(0), &day(3)),
+            Err(DbError::OpenSession(id)) if id == stamps[2].id
+        ));
+        assert!(matches!(
+            Stamp::delete_range(&f.c, &day(1), &day(4)),
+            Err(DbError::OpenSession(id)) if id == stamps[0].id
+        ));
+
+        assert_eq!(Stamp::delete_range(&f.c, &day(0), &day(4)).unwrap(), 4);
+        assert_eq!(Stamp::first(&f.c).unwrap(), stamps[4]);
+        assert!(Allocation::of_session(&f.c, stamps[0].id)
+            .unwrap()
+            .is_empty());
+        assert!(Tag::of_session_range(&f.c, stamps[0].id, stamps[3].id)
+            .unwrap()
+            .is_empty());
+        assert_eq!(Stamp::delete_range(&f.c, &day(0), &day(4)).unwrap(), 0);
+
+        Stamp::enable_chain(&f.c).unwrap();
+        assert!(matches!(
+            Stamp::delete_range(&f.c, &day(0), &day(6)),
+            Err(DbError::ChainedStamp)
+        ));
+    }
+
+    #[test]
+    fn insert_many() {
+        let f = TestFixture::init();
+        Stamp::check_in().insert(&f.c).unwrap();
+        Stamp::enable_chain(&f.c).unwrap();
+
+        let start = Utc::now().trunc_subsecs(0) - Duration::days(400);
+        let mut stamps: Vec<Stamp> = (0..1000)
+            .map(|i| {
+                let in_out = if i % 2 == 0 { InOut::Out } else { InOut::In };
+                Stamp::new(StampId(0), start + Duration::hours(i), in_out)
+            })
+            .collect();
+        Stamp::insert_many(&f.c, &mut stamps).unwrap();
+
+        assert_eq!(stamps[0].id, StampId(2));
+        assert_eq!(stamps[999].id, StampId(1001));
+        assert!(stamps.iter().all(|s| s.uid.is_some()));
+        assert_eq!(Stamp::get(&f.c, StampId(500)).unwrap(), stamps[498]);
+        assert_eq!(Stamp::verify_chain(&f.c).unwrap(), Ok(1001));
+        let audited = Audit::all(&f.c).unwrap();
+        assert_eq!(audited.iter().filter(|a| a.table == "Stamp").count(), 1001);
+
+        // All or none
+        Closeout::new(start + Duration::days(1))
+            .insert(&f.c)
+            .unwrap();
+        let mut stamps = vec![
+            Stamp::new(StampId(0), Utc::now(), InOut::Out),
+            Stamp::new(StampId(0), start, InOut::In),
+        ];
+        assert!(matches!(
+            Stamp::insert_many(&f.c, &mut stamps),
+            Err(DbError::ClosedPeriod(_))
+        ));
+        assert_eq!(Stamp::last(&f.c).unwrap().id, StampId(1001));
+        Stamp::insert_many(&f.c, &mut []).unwrap();
+    }
+
+    #[test]
+    fn transactions() {
+        let f = TestFixture::init();
+        let result: Result<(), DbError> = super::transaction(&f.c, |tx| {
+            Stamp::check_in().insert(tx)?;
+            Stamp::get(tx, StampId(42)).map(|_| ())
+        });
+        assert!(result.is_err());
+        assert!(Stamp::last(&f.c).is_none());
+
+        // The failed inner operation only rolls back its own changes
+        let id = super::transaction(&f.c, |tx| {
+            let mut checkin = Stamp::check_in();
+            checkin.insert(tx)?;
+            let inner: Result<(), DbError> = super::transaction(tx, |tx| {
+                Stamp::check_out().insert(tx)?;
+                Err(DbError::ChainedStamp)
+            });
+            assert!(inner.is_err());
+            Ok::<_, DbError>(checkin.id)
+        })
+        .unwrap();
+        assert_eq!(Stamp::last(&f.c).unwrap().id, id);
+        assert_eq!(Stamp::first(&f.c).unwrap().id, id);
+
+        // Stamping after the last one is part of the operation
+        let result: Result<(), DbError> = super::transaction(&f.c, |tx| {
+            Stamp::check_out().insert_next(tx)?;
+            Stamp::check_out().insert_next(tx).map(|_| ())
+        });
+        assert!(matches!(result, Err(DbError::NotAlternating(InOut::Out))));
+        assert_eq!(Stamp::last(&f.c).unwrap().id, id);
+    }
+
+    #[test]
+    fn conflict() {
+        let f = TestFixture::init();
+        let mut s = Stamp::check_in();
+        s.insert(&f.c).unwrap();
+
+        // Read by two editors
+        let mut first = Stamp::get(&f.c, s.id).unwrap();
+        let mut second = first.clone();
+        first.date -= Duration::minutes(5);
+        first.update(&f.c).unwrap();
+        second.in_out = InOut::Out;
+        assert!(matches!(second.update(&f.c), Err(DbError::Conflict(id)) if id == s.id));
+        assert_eq!(Stamp::get(&f.c, s.id).unwrap(), first);
+
+        // Updated again within the same second
+        first.date -= Duration::minutes(5);
+        first.update(&f.c).unwrap();
+        assert!(first.updated_at > s.updated_at);
+        assert_eq!(Stamp::get(&f.c, s.id).unwrap(), first);
+
+        // Built in memory, not checked
+        let mut built = Stamp::new(s.id, s.date, InOut::Out);
+        built.update(&f.c).unwrap();
     }
 
     #[test]
-    fn get() {
+    fn metadata() {
         let f = TestFixture::init();
 
-        // Get a non-existent stamp
-        assert!(matches!(Stamp::get(&f.c, 1), Err(DbError::NoSuchEntry)));
+        // Backdated stamp, written now
+        let mut s = Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
+            InOut::In,
+        );
+        assert_eq!(s.created_at, None);
+        s.insert(&f.c).unwrap();
+        assert!(s.created_at.is_some());
+        assert!(s.backdated_by().unwrap() > Duration::days(365));
 
-        // Create a stamp
-        Stamp::check_in().insert(&f.c).unwrap();
+        let read = Stamp::get(&f.c, s.id).unwrap();
+        assert_eq!(read.created_at, s.created_at);
+        assert_eq!(read.updated_at, s.created_at);
 
-        // Check we can get it now
-        assert!(matches!(Stamp::get(&f.c, 1), Ok(x) if x.id == 1));
+        // Stamped now, so not backdated
+        let mut s = Stamp::check_out();
+        s.insert(&f.c).unwrap();
+        assert_eq!(s.backdated_by(), None);
     }
 
     #[test]
-    fn update() {
+    fn serde() {
         let f = TestFixture::init();
 
-        // Create a stamp
-        let mut s = Stamp::check_in();
+        let mut s = Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
+            InOut::In,
+        );
         s.insert(&f.c).unwrap();
 
-        s.in_out = InOut::Out;
-
-        let result = s.update(&f.c);
-
-        println!("{:?}", &result);
-
-        assert!(result.is_ok());
+        let json = serde_json::to_value(&s).unwrap();
+        assert_eq!(json["id"], 1);
+        assert_eq!(json["date"], "2020-01-01T08:00:00Z");
+        assert_eq!(json["in_out"], "In");
+        assert_eq!(serde_json::from_value::<Stamp>(json).unwrap(), s);
     }
 
     #[test]
@@ -454,7 +4309,8 @@ mod test {
         let f = TestFixture::init();
 
         // Get a non-existent stamp
-        assert!(matches!(Stamp::first(&f.c), None));
+        assert!(Stamp::first(&f.c).is_none());
+        assert_eq!(Stamp::count(&f.c).unwrap(), 0);
 
         // Create a stamp
         let mut first = Stamp::check_in();
@@ -464,6 +4320,7 @@ mod test {
         Stamp::check_in().insert(&f.c).unwrap();
 
         assert!(matches!(Stamp::first(&f.c), Some( x) if x.id == first.id));
+        assert_eq!(Stamp::count(&f.c).unwrap(), 2);
     }
 
     #[test]
@@ -483,13 +4340,17 @@ mod test {
 
         let mut last_iterated: Option<Stamp> = None;
         let mut count = 0;
-        for s in first_stamp.iter(&f.c) {
-            last_iterated = Some(s);
+        for s in first_stamp.iter(&f.c).unwrap() {
+            last_iterated = Some(s.unwrap());
             count += 1;
         }
 
         assert_eq!(last_iterated.unwrap().id, last_inserted.unwrap().id);
         assert_eq!(20, count);
+
+        // Deleted stamps do not stop the iteration
+        Stamp::get(&f.c, StampId(5)).unwrap().delete(&f.c).unwrap();
+        assert_eq!(first_stamp.iter(&f.c).unwrap().count(), 19);
     }
 
     #[test]
@@ -498,7 +4359,7 @@ mod test {
 
         // Check that last() return None on an empty DB
         let res = Stamp::last(&f.c);
-        assert!(matches!(res, None));
+        assert!(res.is_none());
 
         // Create some stamp
         let mut last_inserted = None;
@@ -515,12 +4376,12 @@ mod test {
     #[test]
     fn delta() {
         let t1 = Stamp::new(
-            0,
+            StampId(0),
             DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
             InOut::In,
         );
         let t2 = Stamp::new(
-            0,
+            StampId(0),
             DateTime::<Utc>::from_str("2020-01-01T10:15:20Z").unwrap(),
             InOut::In,
         );
@@ -535,7 +4396,7 @@ mod test {
         let f = TestFixture::init();
 
         let mut t1 = Stamp::new(
-            0,
+            StampId(0),
             DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
             InOut::In,
         );
@@ -553,4 +4414,918 @@ mod test {
         );
         assert!(matches!(does_not_exists, Err(DbError::NoSuchEntry)));
     }
+
+    #[test]
+    fn total_from() {
+        let f = TestFixture::init();
+
+        let start_date = DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap();
+
+        // No stamp at all
+        assert_eq!(
+            Stamp::total_from(&f.c, &start_date).unwrap(),
+            Duration::zero()
+        );
+
+        for (date, in_out) in [
+            ("2019-12-31T08:00:00Z", InOut::In),
+            ("2019-12-31T12:00:00Z", InOut::Out),
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T12:00:00Z", InOut::Out),
+            ("2020-01-01T13:00:00Z", InOut::In),
+            ("2020-01-01T17:30:15Z", InOut::Out),
+            // Still checked-in, not counted
+            ("2020-01-02T08:00:00Z", InOut::In),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&f.c)
+                .unwrap();
+        }
+
+        let exp_total = Duration::hours(8) + Duration::minutes(30) + Duration::seconds(15);
+        assert_eq!(Stamp::total_from(&f.c, &start_date).unwrap(), exp_total);
+    }
+
+    #[test]
+    fn closeout() {
+        let f = TestFixture::init();
+
+        let mut s = Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
+            InOut::In,
+        );
+        s.insert(&f.c).unwrap();
+
+        let until = DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap();
+        Closeout::new(until).insert(&f.c).unwrap();
+
+        // Closed stamps can't be modified anymore
+        s.in_out = InOut::Out;
+        assert!(matches!(s.update(&f.c), Err(DbError::ClosedPeriod(d)) if d == until));
+        assert!(matches!(s.delete(&f.c), Err(DbError::ClosedPeriod(_))));
+        let mut late = Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-01T17:00:00Z").unwrap(),
+            InOut::Out,
+        );
+        assert!(matches!(late.insert(&f.c), Err(DbError::ClosedPeriod(_))));
+
+        // Closed period can't be reopened by an earlier closeout
+        let earlier = DateTime::<Utc>::from_str("2020-01-01T12:00:00Z").unwrap();
+        assert!(matches!(
+            Closeout::new(earlier).insert(&f.c),
+            Err(DbError::ClosedPeriod(_))
+        ));
+
+        // Open period is still writable
+        Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-02T08:00:00Z").unwrap(),
+            InOut::Out,
+        )
+        .insert(&f.c)
+        .unwrap();
+
+        // Removing the latest closeout reopens its period only
+        let later = DateTime::<Utc>::from_str("2020-01-03T00:00:00Z").unwrap();
+        Closeout::new(later).insert(&f.c).unwrap();
+        let reopened = Closeout::delete_last(&f.c).unwrap().unwrap();
+        assert_eq!(reopened.until, later);
+        assert_eq!(Closeout::last(&f.c).unwrap().unwrap().until, until);
+        assert!(matches!(late.insert(&f.c), Err(DbError::ClosedPeriod(_))));
+        let audit = Audit::all(&f.c).unwrap().pop().unwrap();
+        assert_eq!(audit.operation, Operation::Delete);
+        assert_eq!(audit.table, "Closeout");
+        assert_eq!(audit.row_id, reopened.id);
+        assert_eq!(audit.old_value, Some(later.to_rfc3339()));
+
+        assert!(Closeout::delete_last(&f.c).unwrap().is_some());
+        late.insert(&f.c).unwrap();
+        assert!(Closeout::delete_last(&f.c).unwrap().is_none());
+    }
+
+    #[test]
+    fn adjustment() {
+        let f = TestFixture::init();
+
+        let start_date = Utc::now();
+        Adjustment::new(Duration::minutes(90), "Forgot to check-in", None)
+            .insert(&f.c)
+            .unwrap();
+        Adjustment::new(Duration::minutes(-30), "Lunch not stamped", None)
+            .insert(&f.c)
+            .unwrap();
+
+        assert_eq!(
+            Adjustment::total_from(&f.c, &start_date).unwrap(),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            Adjustment::total_from(&f.c, &(Utc::now() + Duration::hours(1))).unwrap(),
+            Duration::zero()
+        );
+
+        let adjustments =
+            Adjustment::range(&f.c, &start_date, &(Utc::now() + Duration::hours(1))).unwrap();
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(adjustments[1].reason, "Lunch not stamped");
+        assert_eq!(adjustments[1].amount, Duration::minutes(-30));
+        assert!(
+            Adjustment::range(&f.c, &(start_date - Duration::days(1)), &start_date)
+                .unwrap()
+                .is_empty()
+        );
+
+        // A back-dated correction counts toward the day it corrects
+        let target = start_date - Duration::days(3);
+        Adjustment::new(Duration::hours(2), "Trip not stamped", Some(target))
+            .insert(&f.c)
+            .unwrap();
+        assert_eq!(
+            Adjustment::total_from(&f.c, &start_date).unwrap(),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            Adjustment::total_from(&f.c, &target).unwrap(),
+            Duration::hours(3)
+        );
+        assert_eq!(
+            Adjustment::total_from(&f.c, &(target + Duration::seconds(1))).unwrap(),
+            Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn audit() {
+        let f = TestFixture::init();
+
+        let mut s = Stamp::new(
+            StampId(0),
+            DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
+            InOut::In,
+        );
+        s.insert(&f.c).unwrap();
+        s.in_out = InOut::Out;
+        s.update(&f.c).unwrap();
+        s.delete(&f.c).unwrap();
+
+        let records = Audit::all(&f.c).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records
+            .iter()
+            .all(|r| r.table == "Stamp" && r.row_id == s.id.0));
+
+        assert_eq!(records[0].operation, Operation::Insert);
+        assert_eq!(records[0].old_value, None);
+        assert_eq!(
+            records[0].new_value.as_deref(),
+            Some("2020-01-01T08:00:00+00:00 In")
+        );
+
+        assert_eq!(records[1].operation, Operation::Update);
+        assert_eq!(records[1].old_value, records[0].new_value);
+        assert_eq!(
+            records[1].new_value.as_deref(),
+            Some("2020-01-01T08:00:00+00:00 Out")
+        );
+
+        assert_eq!(records[2].operation, Operation::Delete);
+        assert_eq!(records[2].old_value, records[1].new_value);
+        assert_eq!(records[2].new_value, None);
+        assert_eq!(records[2].command, None);
+
+        // Other tables are audited too, with the current command
+        Audit::set_command(&f.c, Some("close 2020-01-02")).unwrap();
+        Closeout::new(DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap())
+            .insert(&f.c)
+            .unwrap();
+        Audit::set_command(&f.c, Some("chain enable")).unwrap();
+        Stamp::enable_chain(&f.c).unwrap();
+
+        let records = Audit::all(&f.c).unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[3].table, "Closeout");
+        assert_eq!(records[3].command.as_deref(), Some("close 2020-01-02"));
+        assert_eq!(records[4].table, "Setting");
+        assert_eq!(records[4].operation, Operation::Insert);
+        assert_eq!(records[4].new_value.as_deref(), Some("hash_chain=1"));
+        assert_eq!(records[4].command.as_deref(), Some("chain enable"));
+
+        // Records are hash-chained when written, modifying or removing one breaks the chain
+        assert_eq!(Audit::verify(&records), Ok(5));
+        f.c.execute("UPDATE Audit SET new_value = 'hash_chain=0' WHERE id = 5;")
+            .unwrap();
+        assert_eq!(Audit::verify(&Audit::all(&f.c).unwrap()), Err(5));
+        f.c.execute("DELETE FROM Audit WHERE id = 2;").unwrap();
+        assert_eq!(Audit::verify(&Audit::all(&f.c).unwrap()), Err(3));
+    }
+
+    #[test]
+    fn schema_version() {
+        let f = TestFixture::init();
+
+        assert_eq!(super::schema_version(&f.c).unwrap(), SCHEMA_VERSION);
+
+        // Migrating again is a no-op
+        super::init(&f.c).unwrap();
+        assert_eq!(super::schema_version(&f.c).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn hash_chain() {
+        let f = TestFixture::init();
+
+        Stamp::check_in().insert(&f.c).unwrap();
+        Stamp::check_out().insert(&f.c).unwrap();
+
+        assert!(!Stamp::is_chained(&f.c).unwrap());
+        Stamp::enable_chain(&f.c).unwrap();
+        assert!(Stamp::is_chained(&f.c).unwrap());
+
+        Stamp::check_in().insert(&f.c).unwrap();
+        assert_eq!(Stamp::verify_chain(&f.c).unwrap(), Ok(3));
+
+        // Modification through the application are refused
+        let mut s = Stamp::get(&f.c, StampId(2)).unwrap();
+        s.in_out = InOut::In;
+        assert!(matches!(s.update(&f.c), Err(DbError::ChainedStamp)));
+        assert!(matches!(s.delete(&f.c), Err(DbError::ChainedStamp)));
+
+        // ... and detected when done behind its back
+        f.c.execute("UPDATE Stamp SET in_out = 'In' WHERE id = 2;")
+            .unwrap();
+        assert_eq!(Stamp::verify_chain(&f.c).unwrap(), Err(StampId(2)));
+    }
+
+    #[test]
+    fn reverse_iterator() {
+        let f = TestFixture::init();
+
+        for _ in 0..5 {
+            Stamp::check_in().insert(&f.c).unwrap();
+            Stamp::check_out().insert(&f.c).unwrap();
+        }
+
+        let ids: Vec<i64> = Stamp::last(&f.c)
+            .unwrap()
+            .iter_rev(&f.c)
+            .unwrap()
+            .map(|s| s.unwrap().id.0)
+            .collect();
+        assert_eq!(ids, (1..=10).rev().collect::<Vec<i64>>());
+
+        let ids: Vec<i64> = Stamp::get(&f.c, StampId(3))
+            .unwrap()
+            .iter_rev(&f.c)
+            .unwrap()
+            .map(|s| s.unwrap().id.0)
+            .collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn bounded_iterator() {
+        let f = TestFixture::init();
+
+        for date in [
+            "2020-01-01T08:00:00Z",
+            "2020-01-01T12:00:00Z",
+            "2020-01-02T08:00:00Z",
+            "2020-01-02T12:00:00Z",
+            "2020-01-03T08:00:00Z",
+        ] {
+            Stamp::new(
+                StampId(0),
+                DateTime::<Utc>::from_str(date).unwrap(),
+                InOut::In,
+            )
+            .insert(&f.c)
+            .unwrap();
+        }
+
+        let day2 = DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap();
+        let day3 = DateTime::<Utc>::from_str("2020-01-03T00:00:00Z").unwrap();
+
+        let first = Stamp::first(&f.c).unwrap();
+        assert_eq!(first.iter_until(&f.c, &day2).unwrap().count(), 2);
+        assert_eq!(first.iter_until(&f.c, &day3).unwrap().count(), 4);
+
+        let ids: Vec<i64> = Stamp::iter_range(&f.c, &day2, &day3)
+            .unwrap()
+            .map(|s| s.unwrap().id.0)
+            .collect();
+        assert_eq!(ids, vec![3, 4]);
+    }
+
+    #[test]
+    fn archive() {
+        let f = TestFixture::init();
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+
+        for (d, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T12:00:00Z", InOut::Out),
+            ("2020-01-02T08:00:00Z", InOut::In),
+        ] {
+            Stamp::new(StampId(0), date(d), in_out)
+                .insert(&f.c)
+                .unwrap();
+        }
+        Stamp::enable_chain(&f.c).unwrap();
+
+        // The open session can not be cut
+        assert!(matches!(
+            Stamp::archive(&f.c, &date("2020-01-03T00:00:00Z")),
+            Err(DbError::OpenSession(StampId(3)))
+        ));
+
+        assert_eq!(
+            Stamp::archive(&f.c, &date("2020-01-02T00:00:00Z")).unwrap(),
+            2
+        );
+        assert_eq!(Stamp::first(&f.c).unwrap().id, StampId(3));
+        assert_eq!(
+            Stamp::iter_range_archived(
+                &f.c,
+                &date("2020-01-01T00:00:00Z"),
+                &date("2020-01-03T00:00:00Z")
+            )
+            .unwrap()
+            .count(),
+            3
+        );
+
+        // Ids and hash chain go on after the archived stamps
+        Stamp::new(StampId(0), date("2020-01-02T12:00:00Z"), InOut::Out)
+            .insert(&f.c)
+            .unwrap();
+        assert_eq!(
+            Stamp::archive(&f.c, &date("2020-01-03T00:00:00Z")).unwrap(),
+            2
+        );
+        let mut checkin = Stamp::check_in();
+        checkin.insert(&f.c).unwrap();
+        assert_eq!(checkin.id, StampId(5));
+        assert_eq!(Stamp::verify_chain(&f.c).unwrap(), Ok(5));
+    }
+
+    #[test]
+    fn migration() {
+        let file = "unit-test-migration.sqlite";
+        let c = open_db(file);
+
+        // Database as created by the first versions
+        c.execute(
+            "CREATE TABLE Stamp (
+                id INTEGER NOT NULL PRIMARY KEY ASC,
+                datetime TEXT,
+                in_out TEXT
+            );
+            INSERT INTO Stamp (datetime, in_out) VALUES
+                ('2020-01-01T08:00:00.123456+00:00', 'In'),
+                ('2020-01-01T12:00:00+00:00', 'Out');",
+        )
+        .unwrap();
+
+        super::init(&c).unwrap();
+        assert_eq!(super::schema_version(&c).unwrap(), SCHEMA_VERSION);
+
+        let first = Stamp::first(&c).unwrap();
+        assert_eq!(
+            first.date,
+            DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap()
+        );
+        assert_eq!(
+            Stamp::total_from(&c, &first.date).unwrap(),
+            Duration::hours(4)
+        );
+        // Stamps get distinct uids
+        let last = Stamp::last(&c).unwrap();
+        assert!(first.uid.is_some());
+        assert_ne!(first.uid, last.uid);
+        // The audit records written before are hash-chained as they are
+        c.execute(
+            "INSERT INTO Audit (datetime, operation, tbl, row_id) VALUES
+                ('2020-01-01T08:00:00+00:00', 'Insert', 'Stamp', 1);",
+        )
+        .unwrap();
+        c.execute("ALTER TABLE Audit DROP COLUMN hash; PRAGMA user_version = 17;")
+            .unwrap();
+        super::init(&c).unwrap();
+        assert_eq!(Audit::verify(&Audit::all(&c).unwrap()), Ok(1));
+
+        drop(c);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn chained_migration() {
+        let file = "unit-test-chained-migration.sqlite";
+        // Hash-chained database storing the dates as text
+        let legacy = |tamper: bool| {
+            let c = open_db(file);
+            c.execute(
+                "CREATE TABLE Stamp (
+                    id INTEGER NOT NULL PRIMARY KEY ASC,
+                    datetime TEXT,
+                    in_out TEXT
+                );",
+            )
+            .unwrap();
+            super::migrate(&c, 0).unwrap();
+            let mut prev_hash = super::GENESIS_HASH.to_string();
+            for (id, date, in_out) in [
+                (1, "2020-01-01T08:00:00+00:00", "In"),
+                (2, "2020-01-01T12:00:00+00:00", "Out"),
+            ] {
+                prev_hash = super::legacy_chain_hash(&prev_hash, id, date, in_out);
+                c.execute(format!(
+                    "INSERT INTO Stamp VALUES ({}, '{}', '{}', '{}');",
+                    id, date, in_out, prev_hash
+                ))
+                .unwrap();
+            }
+            c.execute(format!(
+                "INSERT INTO Setting VALUES ('{}', '1'); PRAGMA user_version = 1;",
+                super::HASH_CHAIN_SETTING
+            ))
+            .unwrap();
+            if tamper {
+                c.execute("UPDATE Stamp SET datetime = '2020-01-01T13:00:00+00:00' WHERE id = 2;")
+                    .unwrap();
+            }
+            c
+        };
+
+        // An intact chain is hashed again in the new format
+        let c = legacy(false);
+        super::init(&c).unwrap();
+        assert!(Stamp::is_chained(&c).unwrap());
+        assert_eq!(Stamp::verify_chain(&c).unwrap(), Ok(2));
+        drop(c);
+        fs::remove_file(file).unwrap();
+
+        // A tampered one is migrated, but not covered by the new hashes
+        let c = legacy(true);
+        super::init(&c).unwrap();
+        assert_eq!(super::schema_version(&c).unwrap(), SCHEMA_VERSION);
+        assert_eq!(Stamp::verify_chain(&c).unwrap(), Err(StampId(2)));
+        assert_eq!(Stamp::last(&c).unwrap().id, StampId(2));
+        drop(c);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn users() {
+        let f = TestFixture::init();
+        let date = |s| DateTime::<Utc>::from_str(s).unwrap();
+        let stamp = |s, in_out| {
+            Stamp::new(StampId::default(), date(s), in_out)
+                .insert(&f.c)
+                .unwrap()
+                .clone()
+        };
+
+        assert_eq!(User::current(&f.c).unwrap().name, DEFAULT_USER);
+        let default_in = stamp("2020-01-01T08:00:00Z", InOut::In);
+        let alice = User::select(&f.c, "alice").unwrap();
+        let alice_in = stamp("2020-01-01T09:00:00Z", InOut::In);
+        User::select(&f.c, DEFAULT_USER).unwrap();
+        stamp("2020-01-01T12:00:00Z", InOut::Out);
+
+        // Sessions are paired per user, skipping the other users' ids
+        assert_eq!(
+            Stamp::total_from(&f.c, &date("2020-01-01T00:00:00Z")).unwrap(),
+            Duration::hours(4)
+        );
+        assert_eq!(Stamp::last(&f.c).unwrap().previous(&f.c), Some(default_in));
+        assert!(matches!(
+            Stamp::get(&f.c, alice_in.id),
+            Err(DbError::NoSuchEntry)
+        ));
+
+        assert_eq!(User::select(&f.c, "alice").unwrap(), alice);
+        assert_eq!(Stamp::first(&f.c), Some(alice_in.clone()));
+        assert_eq!(Stamp::last(&f.c), Some(alice_in));
+        assert_eq!(User::all(&f.c).unwrap().len(), 2);
+
+        // Archiving would cut the session of alice
+        assert!(matches!(
+            Stamp::archive(&f.c, &date("2020-01-02T00:00:00Z")),
+            Err(DbError::OpenSession(_))
+        ));
+        stamp("2020-01-01T17:00:00Z", InOut::Out);
+        // Only the stamps of alice are archived
+        assert_eq!(
+            Stamp::archive(&f.c, &date("2020-01-02T00:00:00Z")).unwrap(),
+            2
+        );
+        assert_eq!(
+            Stamp::archived_until(&f.c).unwrap(),
+            Some(date("2020-01-01T17:00:00Z"))
+        );
+
+        // Corrections, closings and settings are kept per user
+        Adjustment::new(Duration::hours(1), "forgot", None)
+            .insert(&f.c)
+            .unwrap();
+        Closeout::new(date("2020-01-03T00:00:00Z"))
+            .insert(&f.c)
+            .unwrap();
+        Setting::set(&f.c, "cursor", "alice").unwrap();
+        Setting::set_shared(&f.c, "shared", "1").unwrap();
+        User::select(&f.c, DEFAULT_USER).unwrap();
+        assert_eq!(
+            Adjustment::total_from(&f.c, &date("2020-01-01T00:00:00Z")).unwrap(),
+            Duration::zero()
+        );
+        assert!(Closeout::last(&f.c).unwrap().is_none());
+        assert_eq!(Setting::get(&f.c, "cursor").unwrap(), None);
+        assert_eq!(
+            Setting::get_shared(&f.c, "shared").unwrap().as_deref(),
+            Some("1")
+        );
+        assert_eq!(Stamp::archived_until(&f.c).unwrap(), None);
+        assert_eq!(Stamp::count(&f.c).unwrap(), 2);
+    }
+
+    #[test]
+    fn open() {
+        let file = "unit-test-open.sqlite";
+        let c = open_db(file);
+
+        let mut statement = c.prepare("PRAGMA journal_mode;").unwrap();
+        statement.next().unwrap();
+        assert_eq!(statement.read::<String, _>(0).unwrap(), "wal");
+
+        drop(statement);
+        drop(c);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn insert_next() {
+        let file = "unit-test-insert-next.sqlite";
+        super::init(&open_db(file)).unwrap();
+
+        // Double check-in by concurrent processes
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let c = open_db(file);
+                    super::init(&c).unwrap();
+                    Stamp::check_in().insert_next(&c).map(|_| ())
+                })
+            })
+            .collect();
+        let results: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Ok(_) | Err(DbError::NotAlternating(InOut::In)))));
+
+        let c = open_db(file);
+        super::init(&c).unwrap();
+        let checkin = Stamp::last(&c).unwrap();
+        assert_eq!(checkin.id, StampId(1));
+        let mut checkout = Stamp::check_out();
+        checkout.insert_next(&c).unwrap();
+        assert_eq!(Stamp::last(&c), Some(checkout));
+        assert_eq!(
+            DbError::NotAlternating(InOut::Out).to_string(),
+            "Already checked out"
+        );
+
+        drop(c);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn allocation() {
+        let f = TestFixture::init();
+
+        let mut checkin = Stamp::check_in();
+        checkin.insert(&f.c).unwrap();
+
+        Allocation::new(checkin.id, "PROJ-1", 0.6)
+            .insert(&f.c)
+            .unwrap();
+        Allocation::new(checkin.id, "PROJ-7", 0.4)
+            .insert(&f.c)
+            .unwrap();
+
+        let allocations = Allocation::of_session(&f.c, checkin.id).unwrap();
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[0].target, "PROJ-1");
+        assert_eq!(allocations[1].fraction, 0.4);
+
+        assert!(Allocation::of_session(&f.c, StampId(checkin.id.0 + 1))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn tag() {
+        let f = TestFixture::init();
+
+        let mut first = Stamp::check_in();
+        first.insert(&f.c).unwrap();
+        let mut second = Stamp::check_in();
+        second.insert(&f.c).unwrap();
+
+        Tag::new(second.id, "meeting").insert(&f.c).unwrap();
+        Tag::new(first.id, "billable").insert(&f.c).unwrap();
+        Tag::new(first.id, "meeting").insert(&f.c).unwrap();
+
+        let tags = Tag::of_session_range(&f.c, first.id, second.id).unwrap();
+        let names: Vec<(StampId, &str)> =
+            tags.iter().map(|t| (t.session, t.name.as_str())).collect();
+        assert_eq!(
+            names,
+            [
+                (first.id, "billable"),
+                (first.id, "meeting"),
+                (second.id, "meeting")
+            ]
+        );
+        assert_eq!(
+            Tag::of_session_range(&f.c, second.id, second.id)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn reference() {
+        let f = TestFixture::init();
+
+        let mut first = Stamp::check_in();
+        first.insert(&f.c).unwrap();
+        let mut checkout = Stamp::check_out();
+        checkout.insert(&f.c).unwrap();
+        let mut second = Stamp::check_in();
+        second.insert(&f.c).unwrap();
+
+        Reference::new(first.id, "ABC-123").insert(&f.c).unwrap();
+        Reference::new(second.id, "group/project#42")
+            .insert(&f.c)
+            .unwrap();
+
+        let references = Reference::of_session_range(&f.c, first.id, second.id).unwrap();
+        let names: Vec<(StampId, &str)> = references
+            .iter()
+            .map(|r| (r.session, r.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [(first.id, "ABC-123"), (second.id, "group/project#42")]
+        );
+
+        // Deleted along with the session
+        Stamp::delete_session_data(&f.c, first.id).unwrap();
+        assert_eq!(
+            Reference::of_session_range(&f.c, first.id, second.id)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn estimate() {
+        let f = TestFixture::init();
+
+        Estimate::new(true, "refactor", Duration::hours(6))
+            .save(&f.c)
+            .unwrap();
+        let mut acme = Estimate::new(false, "acme", Duration::hours(40));
+        acme.save(&f.c).unwrap();
+        let mut again = Estimate::new(false, "acme", Duration::hours(50));
+        again.save(&f.c).unwrap();
+        assert_eq!(again.id, acme.id);
+
+        let estimates = Estimate::all(&f.c).unwrap();
+        assert_eq!(estimates.len(), 2);
+        assert_eq!(estimates[0], again);
+        assert!(estimates[1].tag);
+        assert_eq!(estimates[1].estimate, Duration::hours(6));
+        assert_eq!(Estimate::get(&f.c, true, "acme").unwrap(), None);
+
+        let records = Audit::all(&f.c).unwrap();
+        let last = records.last().unwrap();
+        assert_eq!(last.operation, Operation::Update);
+        assert_eq!(last.old_value.as_deref(), Some("project acme 144000"));
+
+        assert!(Estimate::delete(&f.c, false, "acme").unwrap());
+        assert!(!Estimate::delete(&f.c, false, "acme").unwrap());
+        assert_eq!(Estimate::all(&f.c).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn project() {
+        let f = TestFixture::init();
+
+        let api = Project::register(&f.c, "acme/backend/api").unwrap();
+        let backend = Project::get(&f.c, "acme/backend").unwrap().unwrap();
+        let acme = Project::get(&f.c, "acme").unwrap().unwrap();
+        assert_eq!(api.parent, Some(backend.id));
+        assert_eq!(backend.parent, Some(acme.id));
+        assert_eq!(acme.parent, None);
+        assert_eq!(Project::register(&f.c, "acme/backend").unwrap(), backend);
+
+        let mut checkin = Stamp::check_in();
+        checkin.insert(&f.c).unwrap();
+        Allocation::new(checkin.id, "acme/web", 1.0)
+            .insert(&f.c)
+            .unwrap();
+        let names: Vec<String> = Project::all(&f.c)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(
+            names,
+            ["acme", "acme/backend", "acme/backend/api", "acme/web"]
+        );
+
+        assert_eq!(
+            Project::parent_name("acme/backend/api"),
+            Some("acme/backend")
+        );
+        assert_eq!(Project::parent_name("acme"), None);
+        assert_eq!(Project::parent_name("/acme"), None);
+    }
+
+    #[test]
+    fn split() {
+        let f = TestFixture::init();
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        let mut stamps = [
+            Stamp::new(StampId(0), date("2020-01-01T22:00:00Z"), InOut::In),
+            Stamp::new(StampId(0), date("2020-01-03T02:00:00Z"), InOut::Out),
+            Stamp::new(StampId(0), date("2020-01-03T08:00:00Z"), InOut::In),
+            Stamp::new(StampId(0), date("2020-01-03T12:00:00Z"), InOut::Out),
+        ];
+        for stamp in &mut stamps {
+            stamp.insert(&f.c).unwrap();
+        }
+        Allocation::new(stamps[0].id, "ACME", 1.0)
+            .insert(&f.c)
+            .unwrap();
+        Tag::new(stamps[0].id, "night").insert(&f.c).unwrap();
+        Tag::new(stamps[2].id, "meeting").insert(&f.c).unwrap();
+
+        let midnights = vec![date("2020-01-02T00:00:00Z"), date("2020-01-03T00:00:00Z")];
+        assert_eq!(
+            Stamp::split(&f.c, &[(stamps[0].clone(), midnights)]).unwrap(),
+            1
+        );
+
+        // Sessions of each day, in date order
+        let split: Vec<(StampId, DateTime<Utc>, InOut)> = Stamp::iter_range(
+            &f.c,
+            &date("2020-01-01T00:00:00Z"),
+            &date("2020-01-04T00:00:00Z"),
+        )
+        .unwrap()
+        .map(|s| s.unwrap())
+        .map(|s| (s.id, s.date, s.in_out))
+        .collect();
+        assert_eq!(
+            split,
+            [
+                (StampId(1), date("2020-01-01T22:00:00Z"), InOut::In),
+                (StampId(2), date("2020-01-02T00:00:00Z"), InOut::Out),
+                (StampId(3), date("2020-01-02T00:00:00Z"), InOut::In),
+                (StampId(4), date("2020-01-03T00:00:00Z"), InOut::Out),
+                (StampId(5), date("2020-01-03T00:00:00Z"), InOut::In),
+                (StampId(6), date("2020-01-03T02:00:00Z"), InOut::Out),
+                (StampId(7), date("2020-01-03T08:00:00Z"), InOut::In),
+                (StampId(8), date("2020-01-03T12:00:00Z"), InOut::Out),
+            ]
+        );
+        let allocations = Allocation::of_session_range(&f.c, StampId(1), StampId(7)).unwrap();
+        assert_eq!(
+            allocations.iter().map(|a| a.session).collect::<Vec<_>>(),
+            [StampId(1), StampId(3), StampId(5)]
+        );
+        let tags = Tag::of_session_range(&f.c, StampId(1), StampId(7)).unwrap();
+        assert_eq!(
+            tags.iter()
+                .map(|t| (t.session, t.name.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (StampId(1), "night"),
+                (StampId(3), "night"),
+                (StampId(5), "night"),
+                (StampId(7), "meeting")
+            ]
+        );
+
+        // Past audit records are untouched, the renumbering is recorded once
+        // and resolves them to the current ids
+        let audits: Vec<Audit> = Audit::all(&f.c)
+            .unwrap()
+            .into_iter()
+            .filter(|a| a.table == "Stamp")
+            .collect();
+        let renumbered = audits.last().unwrap();
+        assert_eq!(renumbered.operation, Operation::Update);
+        assert_eq!(renumbered.row_id, 2);
+        assert_eq!(renumbered.old_value, None);
+        assert_eq!(
+            renumbered.new_value.as_deref(),
+            Some("5->2,6->3,7->4,8->5,2->6,3->7,4->8")
+        );
+        assert_eq!(
+            audits.iter().filter(|a| a.renumbering().is_some()).count(),
+            1
+        );
+        let written: BTreeMap<i64, String> = audits
+            .iter()
+            .filter(|a| a.operation == Operation::Insert)
+            .map(|a| (a.row_id, a.new_value.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            written[&2],
+            format!("{} Out", date("2020-01-03T02:00:00Z").to_rfc3339())
+        );
+        let current: BTreeMap<i64, String> = audits
+            .iter()
+            .zip(Audit::current_row_ids(&audits))
+            .filter(|(a, _)| a.operation == Operation::Insert)
+            .map(|(a, id)| (id, a.new_value.clone().unwrap()))
+            .collect();
+        let expected: BTreeMap<i64, String> = split
+            .iter()
+            .map(|(id, date, in_out)| (id.0, format!("{} {}", date.to_rfc3339(), in_out)))
+            .collect();
+        assert_eq!(current, expected);
+    }
+
+    #[test]
+    fn audit_current_row_ids() {
+        let record = |table: &str, row_id: i64, old: Option<&str>, new: &str| Audit {
+            id: 0,
+            date: Utc::now(),
+            operation: match old {
+                Some(_) => Operation::Update,
+                None if new.contains("->") => Operation::Update,
+                None => Operation::Insert,
+            },
+            table: table.into(),
+            row_id,
+            old_value: old.map(String::from),
+            new_value: Some(new.into()),
+            command: None,
+            hash: String::new(),
+        };
+        let records = [
+            record("Stamp", 1, None, "2020-01-01T08:00:00+00:00 In"),
+            record("Stamp", 2, None, "2020-01-01T07:00:00+00:00 In"),
+            record("Allocation", 1, None, "1 2 ISSUE-1"),
+            record("Stamp", 1, None, "2->1,1->2"),
+            record("Stamp", 3, None, "2020-01-01T06:00:00+00:00 In"),
+            record("Stamp", 1, Some("a"), "b"),
+            record("Stamp", 1, None, "3->1,1->2,2->3"),
+        ];
+
+        assert_eq!(records[0].renumbering(), None);
+        assert_eq!(records[3].renumbering(), Some(vec![(2, 1), (1, 2)]));
+        assert_eq!(records[5].renumbering(), None);
+        assert_eq!(Audit::current_row_ids(&records), [3, 2, 1, 2, 1, 2, 1]);
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn encrypted() {
+        let file = "unit-test-encrypted.sqlite";
+        let _ = fs::remove_file(file);
+
+        let c = super::open_encrypted(Path::new(file), "it's secret").unwrap();
+        super::init(&c).unwrap();
+        let mut checkin = Stamp::check_in();
+        checkin.insert(&c).unwrap();
+        drop(c);
+
+        // Nothing readable without the passphrase
+        let plain = sqlite::open(file).unwrap();
+        assert!(plain
+            .execute("SELECT count(*) FROM sqlite_master;")
+            .is_err());
+        drop(plain);
+        assert!(matches!(
+            super::open_encrypted(Path::new(file), "wrong"),
+            Err(DbError::WrongPassphrase)
+        ));
+
+        let c = super::open_encrypted(Path::new(file), "it's secret").unwrap();
+        super::init(&c).unwrap();
+        assert_eq!(Stamp::last(&c), Some(checkin));
+
+        drop(c);
+        fs::remove_file(file).unwrap();
+    }
 }
@@ -0,0 +1,159 @@
+//! D-Bus service of the work-log
+//!
+//! Enabled with the `dbus` cargo feature, `wtime dbus` owns the
+//! `org.wtime.Tracker` name on the session bus, so that desktop applets and
+//! scripts can stamp without spawning the command:
+//!
+//! ```text
+//! busctl --user call org.wtime.Tracker /org/wtime/Tracker org.wtime.Tracker CheckIn
+//! ```
+//!
+//! | Method     | Arguments  | Returns                                         |
+//! |------------|------------|-------------------------------------------------|
+//! | `CheckIn`  |            | Check-in date (Unix seconds)                    |
+//! | `CheckOut` | split (s)  | Seconds worked in the finished session          |
+//! | `Status`   |            | Checked in, last stamp date, day and week totals (seconds) |
+//!
+//! The `StateChanged(b checked_in)` signal is emitted after every stamp. An
+//! empty split does not split the session.
+
+use crate::app::{App, Status};
+use crate::storage::Storage;
+use anyhow::{anyhow, Context, Result};
+use std::sync::mpsc::{self, Sender};
+use tracing::info;
+
+/// Well-known name of the service
+pub const NAME: &str = "org.wtime.Tracker";
+
+/// Path of the tracker object
+pub const PATH: &str = "/org/wtime/Tracker";
+
+/// Request forwarded from the bus to the thread owning the [App]
+enum Call {
+    CheckIn,
+    CheckOut(String),
+    Status,
+}
+
+/// Answer to a [Call]
+enum Answer {
+    Stamped(i64),
+    Worked(i64),
+    Status(Status),
+}
+
+/// Where a [Call] is answered, the error being a message
+type Reply = Sender<Result<Answer, String>>;
+
+/// D-Bus object, forwarding the method calls to the [App]
+///
+/// The application is not shared between threads, so the calls are run by
+/// the thread owning it.
+struct Tracker {
+    calls: Sender<(Call, Reply)>,
+}
+
+impl Tracker {
+    fn call(&self, call: Call) -> zbus::fdo::Result<Answer> {
+        let (reply, answer) = mpsc::channel();
+        self.calls
+            .send((call, reply))
+            .map_err(|_| zbus::fdo::Error::Failed("Service is stopping".into()))?;
+
+        answer
+            .recv()
+            .map_err(|_| zbus::fdo::Error::Failed("Service is stopping".into()))?
+            .map_err(zbus::fdo::Error::Failed)
+    }
+}
+
+#[zbus::interface(name = "org.wtime.Tracker")]
+impl Tracker {
+    /// Check-in now, returning the check-in date
+    fn check_in(&self) -> zbus::fdo::Result<i64> {
+        match self.call(Call::CheckIn)? {
+            Answer::Stamped(date) => Ok(date),
+            _ => unreachable!("CheckIn answers the stamp date"),
+        }
+    }
+
+    /// Check-out now, returning the worked time of the session
+    fn check_out(&self, split: &str) -> zbus::fdo::Result<i64> {
+        match self.call(Call::CheckOut(split.to_string()))? {
+            Answer::Worked(seconds) => Ok(seconds),
+            _ => unreachable!("CheckOut answers the worked time"),
+        }
+    }
+
+    /// Get whether checked in, the last stamp date and the day and week totals
+    fn status(&self) -> zbus::fdo::Result<(bool, i64, i64, i64)> {
+        match self.call(Call::Status)? {
+            Answer::Status(status) => Ok((
+                status.checked_in,
+                status.last_stamp.map_or(0, |s| s.date.timestamp()),
+                status.resume.day_total.num_seconds(),
+                status.resume.week_total.num_seconds(),
+            )),
+            _ => unreachable!("Status answers the status"),
+        }
+    }
+
+    /// Emitted after a check-in or check-out
+    #[zbus(signal)]
+    async fn state_changed(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        checked_in: bool,
+    ) -> zbus::Result<()>;
+}
+
+/// Run a call on the application
+fn answer<S: Storage>(app: &App<S>, call: Call) -> Result<Answer> {
+    match call {
+        Call::CheckIn => {
+            app.set_command("dbus: CheckIn")?;
+            Ok(Answer::Stamped(app.checkin(false, None)?.date.timestamp()))
+        }
+        Call::CheckOut(split) => {
+            app.set_command("dbus: CheckOut")?;
+            let split = Some(split.as_str()).filter(|s| !s.trim().is_empty());
+            let summary = app.checkout(split)?;
+            Ok(Answer::Worked(
+                summary.session.map_or(0, |s| s.duration().num_seconds()),
+            ))
+        }
+        Call::Status => Ok(Answer::Status(app.status()?)),
+    }
+}
+
+/// Serve the tracker on the session bus, only returning on failure
+///
+/// # Arguments
+///
+/// * `app` - Application the calls are run on
+pub fn run<S: Storage>(app: &App<S>) -> Result<()> {
+    let (sender, calls) = mpsc::channel();
+    let connection = zbus::blocking::connection::Builder::session()
+        .and_then(|builder| builder.serve_at(PATH, Tracker { calls: sender }))
+        .and_then(|builder| builder.name(NAME))
+        .and_then(|builder| builder.build())
+        .context("Registering on the session bus")?;
+    info!("Serving {} on the session bus", NAME);
+
+    for (call, reply) in calls {
+        let stamps = !matches!(call, Call::Status);
+        let answer = answer(app, call).map_err(|e| format!("{:#}", e));
+        let succeeded = answer.is_ok();
+        // The caller may have given up waiting
+        let _ = reply.send(answer);
+
+        if stamps && succeeded {
+            let checked_in = app.status()?.checked_in;
+            connection
+                .emit_signal(None::<()>, PATH, NAME, "StateChanged", &(checked_in,))
+                .context("Emitting StateChanged")?;
+        }
+    }
+
+    Err(anyhow!("Session bus connection lost"))
+}
@@ -0,0 +1,481 @@
+//! Diagnosis of the work-log consistency
+//!
+//! Forgotten stamps leave the work-log with sessions that can not be
+//! counted. The doctor finds them and suggests the commands that book the
+//! missing time, so that the log can be repaired by copy-paste.
+//!
+//! A session left open too long can also be fixed interactively with
+//! `wtime doctor --interactive`, see [Resolution]. Sessions running past
+//! midnight are counted on the day they start, `wtime doctor --fix` splits
+//! them into a session per day.
+
+use crate::config::Config;
+use crate::db::{DbError, InOut, Stamp, StampId};
+use crate::storage::Storage;
+use crate::tr;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt::{self, Display, Formatter};
+
+/// Longest time one can stay checked in before it is reported as forgotten
+pub const MAX_OPEN_HOURS: i64 = 24;
+
+/// Inconsistency found in the work-log
+#[derive(Debug)]
+pub enum Problem {
+    /// Check-in followed by another check-in, i.e. a check-out was forgotten
+    MissingCheckout {
+        /// The check-in whose session has no end
+        checkin: Stamp,
+        /// Date of the following check-in
+        next: DateTime<Utc>,
+    },
+
+    /// Check-out following another check-out, i.e. a check-in was forgotten
+    MissingCheckin {
+        /// Date of the previous check-out
+        previous: DateTime<Utc>,
+        /// The check-out whose session has no start
+        checkout: Stamp,
+    },
+
+    /// Still checked in since more than [MAX_OPEN_HOURS]
+    OpenSession {
+        /// The last stamp, a check-in
+        checkin: Stamp,
+    },
+
+    /// Session running past midnight, all counted on its first day
+    OverMidnight {
+        /// The check-in of the session
+        checkin: Stamp,
+        /// Date of its check-out, on a later day
+        checkout: DateTime<Utc>,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingCheckout { checkin, next } => write!(
+                f,
+                "{}",
+                tr!(
+                    "problem-missing-checkout",
+                    id = checkin.id.0,
+                    checkin = checkin.date.format("%Y-%m-%d %H:%M").to_string(),
+                    next = next.format("%Y-%m-%d %H:%M").to_string()
+                )
+            ),
+            Problem::MissingCheckin { previous, checkout } => write!(
+                f,
+                "{}",
+                tr!(
+                    "problem-missing-checkin",
+                    id = checkout.id.0,
+                    checkout = checkout.date.format("%Y-%m-%d %H:%M").to_string(),
+                    previous = previous.format("%Y-%m-%d %H:%M").to_string()
+                )
+            ),
+            Problem::OpenSession { checkin } => write!(
+                f,
+                "{}",
+                tr!(
+                    "problem-open-session",
+                    id = checkin.id.0,
+                    checkin = checkin.date.format("%Y-%m-%d %H:%M").to_string()
+                )
+            ),
+            Problem::OverMidnight { checkin, checkout } => write!(
+                f,
+                "{}",
+                tr!(
+                    "problem-over-midnight",
+                    id = checkin.id.0,
+                    checkin = checkin.date.format("%Y-%m-%d %H:%M").to_string(),
+                    checkout = checkout.format("%Y-%m-%d %H:%M").to_string()
+                )
+            ),
+        }
+    }
+}
+
+/// Command line booking `minutes` to the day of `date`
+fn adjust_command(minutes: i64, date: &DateTime<Utc>, reason: &str) -> String {
+    format!(
+        "wtime adjust {} --for {} --reason \"{}\"",
+        minutes,
+        date.format("%Y-%m-%d"),
+        reason
+    )
+}
+
+impl Problem {
+    /// Commands repairing the problem, to be run in order
+    ///
+    /// The uncounted session is assumed to last `session` at most, the
+    /// suggested times should be reviewed before running the commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - Suggested duration of the uncounted session, e.g. the daily target
+    pub fn fixes(&self, session: Duration) -> Vec<String> {
+        match self {
+            Problem::MissingCheckout { checkin, next } => {
+                let end = (*next).min(checkin.date + session);
+                vec![adjust_command(
+                    (end - checkin.date).num_minutes(),
+                    &checkin.date,
+                    &format!(
+                        "Forgotten check-out of stamp {}, assumed at {}",
+                        checkin.id,
+                        end.format("%H:%M")
+                    ),
+                )]
+            }
+            Problem::MissingCheckin { previous, checkout } => {
+                let start = (*previous).max(checkout.date - session);
+                vec![adjust_command(
+                    (checkout.date - start).num_minutes(),
+                    &start,
+                    &format!(
+                        "Forgotten check-in before stamp {}, assumed at {}",
+                        checkout.id,
+                        start.format("%H:%M")
+                    ),
+                )]
+            }
+            Problem::OpenSession { checkin } => {
+                // The check-out counts the whole period, remove the excess
+                let excess = Utc::now() - (checkin.date + session);
+                vec![
+                    "wtime checkout".to_string(),
+                    adjust_command(
+                        -excess.num_minutes(),
+                        &checkin.date,
+                        &format!(
+                            "Forgotten check-out of stamp {}, assumed at {}",
+                            checkin.id,
+                            (checkin.date + session).format("%H:%M")
+                        ),
+                    ),
+                ]
+            }
+            Problem::OverMidnight { .. } => vec!["wtime doctor --fix".to_string()],
+        }
+    }
+}
+
+/// Midnights between two dates, excluded, in the configured timezone
+///
+/// # Arguments
+///
+/// * `start` - Start of the period, e.g. a check-in
+/// * `end` - End of the period, e.g. a check-out
+/// * `config` - User settings, giving the timezone, see [Config::start_of_day]
+pub fn midnights(
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    config: &Config,
+) -> Vec<DateTime<Utc>> {
+    config
+        .day_of(start)
+        .iter_days()
+        .skip(1)
+        .map(|day| config.start_of_day(day))
+        .take_while(|midnight| midnight < end)
+        .collect()
+}
+
+/// Fix of a session left open, see [Problem::OpenSession]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// The session really is still going on
+    Keep,
+    /// The check-out was forgotten, the session ended at this time
+    Cap(DateTime<Utc>),
+    /// A break was not stamped, from the first to the second time
+    Break(DateTime<Utc>, DateTime<Utc>),
+}
+
+impl Resolution {
+    /// Stamps fixing the session, to insert after its check-in
+    ///
+    /// # Arguments
+    ///
+    /// * `checkin` - The check-in of the open session
+    /// * `now` - Current Date/Time, the times must be before
+    ///
+    /// # Return
+    ///
+    /// The stamps in date order, None if the times are not within the session.
+    pub fn stamps(&self, checkin: &Stamp, now: &DateTime<Utc>) -> Option<Vec<Stamp>> {
+        let within = |date: &DateTime<Utc>| checkin.date < *date && date <= now;
+        match self {
+            Resolution::Keep => Some(Vec::new()),
+            Resolution::Cap(end) => {
+                within(end).then(|| vec![Stamp::new(StampId(0), *end, InOut::Out)])
+            }
+            Resolution::Break(start, end) => {
+                (within(start) && within(end) && start < end).then(|| {
+                    vec![
+                        Stamp::new(StampId(0), *start, InOut::Out),
+                        Stamp::new(StampId(0), *end, InOut::In),
+                    ]
+                })
+            }
+        }
+    }
+}
+
+/// Parse a time given when fixing a session
+///
+/// A local time skipped by a daylight saving time shift is invalid, a
+/// repeated one is the earliest.
+///
+/// # Arguments
+///
+/// * `s` - `YYYY-MM-DD HH:MM`, or `HH:MM` on `day`, local time
+/// * `day` - Day of a time given alone, in `tz`
+/// * `tz` - Timezone of the given time
+pub fn parse_time(s: &str, day: NaiveDate, tz: &Tz) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    let date = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M").map(|time| day.and_time(time)))
+        .ok()?;
+    Some(
+        tz.from_local_datetime(&date)
+            .earliest()?
+            .with_timezone(&Utc),
+    )
+}
+
+/// Find the inconsistencies of the work-log
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `now` - Current Date/Time, to detect forgotten check-outs
+/// * `config` - User settings, giving the timezone of the midnights
+pub fn diagnose<S: Storage + ?Sized>(
+    storage: &S,
+    now: &DateTime<Utc>,
+    config: &Config,
+) -> Result<Vec<Problem>, DbError> {
+    let mut problems = Vec::new();
+    let first = match storage.first_stamp()? {
+        Some(first) => first,
+        None => return Ok(problems),
+    };
+
+    let mut possible_last: Option<Stamp> = None;
+    for stamp in storage.stamp_range(&first.date, &(*now + Duration::days(1)))? {
+        let stamp = stamp?;
+        match possible_last {
+            Some(last) if last.in_out == InOut::In && stamp.in_out == InOut::In => {
+                problems.push(Problem::MissingCheckout {
+                    checkin: last,
+                    next: stamp.date,
+                });
+            }
+            Some(last) if last.in_out == InOut::Out && stamp.in_out == InOut::Out => {
+                problems.push(Problem::MissingCheckin {
+                    previous: last.date,
+                    checkout: stamp.clone(),
+                });
+            }
+            Some(last)
+                if last.in_out == InOut::In
+                    && stamp.in_out == InOut::Out
+                    && !midnights(&last.date, &stamp.date, config).is_empty() =>
+            {
+                problems.push(Problem::OverMidnight {
+                    checkin: last,
+                    checkout: stamp.date,
+                });
+            }
+            _ => (),
+        }
+        possible_last = Some(stamp);
+    }
+
+    if let Some(last) = possible_last {
+        if last.in_out == InOut::In && *now - last.date > Duration::hours(MAX_OPEN_HOURS) {
+            problems.push(Problem::OpenSession { checkin: last });
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diagnose, midnights, parse_time, Problem, Resolution};
+    use crate::config::Config;
+    use crate::db::{self, InOut, Stamp, StampId};
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn problems() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-02T08:00:00Z", InOut::In),
+            ("2020-01-02T12:00:00Z", InOut::Out),
+            ("2020-01-02T17:00:00Z", InOut::Out),
+            ("2020-01-03T08:00:00Z", InOut::In),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+
+        let now = DateTime::<Utc>::from_str("2020-01-03T10:00:00Z").unwrap();
+        let problems = diagnose(&conn, &now, &Config::default()).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            &problems[0],
+            Problem::MissingCheckout { checkin, .. } if checkin.id == StampId(1)
+        ));
+        assert_eq!(
+            problems[0].fixes(Duration::hours(8)),
+            vec!["wtime adjust 480 --for 2020-01-01 --reason \"Forgotten check-out of stamp 1, assumed at 16:00\""]
+        );
+        assert!(matches!(
+            &problems[1],
+            Problem::MissingCheckin { checkout, .. } if checkout.id == StampId(4)
+        ));
+        assert_eq!(
+            problems[1].fixes(Duration::hours(8)),
+            vec!["wtime adjust 300 --for 2020-01-02 --reason \"Forgotten check-in before stamp 4, assumed at 12:00\""]
+        );
+
+        // Last check-in is forgotten a day later
+        let problems = diagnose(&conn, &(now + Duration::days(1)), &Config::default()).unwrap();
+        assert_eq!(problems.len(), 3);
+        assert!(
+            matches!(&problems[2], Problem::OpenSession { checkin } if checkin.id == StampId(5))
+        );
+    }
+
+    #[test]
+    fn over_midnight() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+
+        for (day, in_out) in [
+            ("2020-01-01T22:00:00Z", InOut::In),
+            ("2020-01-02T01:00:00Z", InOut::Out),
+            // Ending at midnight is no problem
+            ("2020-01-02T20:00:00Z", InOut::In),
+            ("2020-01-03T00:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), date(day), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+
+        let problems = diagnose(&conn, &date("2020-01-03T10:00:00Z"), &Config::default()).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            &problems[0],
+            Problem::OverMidnight { checkin, checkout }
+                if checkin.id == StampId(1) && *checkout == date("2020-01-02T01:00:00Z")
+        ));
+        assert_eq!(
+            problems[0].fixes(Duration::hours(8)),
+            vec!["wtime doctor --fix"]
+        );
+
+        let config = Config::default();
+        assert_eq!(
+            midnights(
+                &date("2020-01-01T22:00:00Z"),
+                &date("2020-01-03T00:00:01Z"),
+                &config
+            ),
+            vec![date("2020-01-02T00:00:00Z"), date("2020-01-03T00:00:00Z")]
+        );
+        assert!(midnights(
+            &date("2020-01-01T00:00:00Z"),
+            &date("2020-01-02T00:00:00Z"),
+            &config
+        )
+        .is_empty());
+
+        // 22:00 to 01:30 in Zurich, the UTC midnight is not crossed
+        let zurich = Config {
+            timezone: chrono_tz::Europe::Zurich,
+            ..Config::default()
+        };
+        assert_eq!(
+            midnights(
+                &date("2020-01-01T21:00:00Z"),
+                &date("2020-01-02T00:30:00Z"),
+                &zurich
+            ),
+            vec![date("2020-01-01T23:00:00Z")]
+        );
+        assert!(midnights(
+            &date("2020-01-02T00:00:00Z"),
+            &date("2020-01-02T20:00:00Z"),
+            &zurich
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn resolutions() {
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        let checkin = Stamp::new(StampId(5), date("2020-01-03T08:00:00Z"), InOut::In);
+        let now = date("2020-01-04T10:00:00Z");
+        let day = NaiveDate::from_ymd_opt(2020, 1, 3).unwrap();
+        let zurich = chrono_tz::Europe::Zurich;
+
+        // Local times, one hour ahead of UTC in winter
+        assert_eq!(
+            parse_time("17:30", day, &zurich),
+            Some(date("2020-01-03T16:30:00Z"))
+        );
+        assert_eq!(
+            parse_time(" 2020-01-04 09:00 ", day, &zurich),
+            Some(date("2020-01-04T08:00:00Z"))
+        );
+        assert_eq!(parse_time("5pm", day, &zurich), None);
+        // Skipped by the shift to summer time
+        let shift = NaiveDate::from_ymd_opt(2020, 3, 29).unwrap();
+        assert_eq!(parse_time("02:30", shift, &zurich), None);
+
+        assert_eq!(Resolution::Keep.stamps(&checkin, &now), Some(Vec::new()));
+        let stamps = Resolution::Cap(date("2020-01-03T17:30:00Z"))
+            .stamps(&checkin, &now)
+            .unwrap();
+        assert_eq!(stamps.len(), 1);
+        assert_eq!(stamps[0].in_out, InOut::Out);
+        assert_eq!(stamps[0].date, date("2020-01-03T17:30:00Z"));
+        // Before the check-in or after now
+        assert!(Resolution::Cap(date("2020-01-03T07:00:00Z"))
+            .stamps(&checkin, &now)
+            .is_none());
+        assert!(Resolution::Cap(date("2020-01-04T11:00:00Z"))
+            .stamps(&checkin, &now)
+            .is_none());
+
+        let stamps = Resolution::Break(date("2020-01-03T17:00:00Z"), date("2020-01-04T08:00:00Z"))
+            .stamps(&checkin, &now)
+            .unwrap();
+        assert_eq!(
+            stamps.iter().map(|s| s.in_out).collect::<Vec<_>>(),
+            vec![InOut::Out, InOut::In]
+        );
+        assert!(
+            Resolution::Break(date("2020-01-04T08:00:00Z"), date("2020-01-03T17:00:00Z"))
+                .stamps(&checkin, &now)
+                .is_none()
+        );
+    }
+}
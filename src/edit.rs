@@ -0,0 +1,494 @@
+//! Edit stamps in a text editor
+//!
+//! `wtime edit --range today` writes the stamps of the range to a temporary
+//! file, a stamp per line, and opens `$EDITOR` on it, like `git rebase -i`:
+//!
+//! ```text
+//! 12 2024-02-12 08:00 in
+//! 13 2024-02-12 12:00 out
+//! new 2024-02-12 13:00 in
+//! ```
+//!
+//! Changing a line updates the stamp, removing it deletes the stamp and a
+//! line starting with `new` inserts one. The changes are checked first, then
+//! applied all at once.
+
+use crate::config::Config;
+use crate::db::{InOut, Stamp, StampId};
+use crate::export::ParseFormatError;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Stamps edited at once
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Range {
+    /// Stamps of the current day
+    Today,
+    /// Stamps of the previous day
+    Yesterday,
+    /// Stamps of the current week, from Monday
+    Week,
+    /// Stamps of the current month
+    Month,
+    /// Stamps of the day of a date, e.g. of a picked stamp
+    DayOf(DateTime<Utc>),
+}
+
+impl FromStr for Range {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "today" => Ok(Self::Today),
+            "yesterday" => Ok(Self::Yesterday),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+impl Range {
+    /// Get the date window of the range, in the configured timezone
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration, giving the timezone
+    /// * `now` - Current date/time, the ranges but [Range::DayOf] are around
+    ///
+    /// # Return
+    ///
+    /// The lower (included) and upper (excluded) bounds of the window.
+    pub fn window(&self, config: &Config, now: &DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let today = config.day_of(now);
+        let (first, last) = match self {
+            Range::DayOf(date) => {
+                let day = config.day_of(date);
+                (day, day + Duration::days(1))
+            }
+            Range::Today => (today, today + Duration::days(1)),
+            Range::Yesterday => (today - Duration::days(1), today),
+            Range::Week => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                (monday, monday + Duration::days(7))
+            }
+            Range::Month => {
+                let first = today.with_day(1).unwrap();
+                (first, first + Months::new(1))
+            }
+        };
+        (config.start_of_day(first), config.start_of_day(last))
+    }
+}
+
+/// Invalid edited stamps
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EditError {
+    /// Line not in the `<id|new> YYYY-MM-DD HH:MM <in|out>` format
+    #[error("Line {0}: expected `<id|new> YYYY-MM-DD HH:MM <in|out>`")]
+    Syntax(usize),
+
+    /// Id of a stamp not in the edited range
+    #[error("Line {0}: no stamp {1} in the edited range")]
+    UnknownStamp(usize, StampId),
+
+    /// Same stamp on two lines
+    #[error("Line {0}: stamp {1} is already listed")]
+    Duplicate(usize, StampId),
+
+    /// Stamp moved out of the edited range
+    #[error("Line {0}: the date is outside the edited range")]
+    OutOfRange(usize),
+
+    /// Check-ins and check-outs would no longer alternate
+    #[error("Two check-{}s in a row at {}", .0.to_string().to_lowercase(), .1.format("%Y-%m-%d %H:%M"))]
+    NotAlternating(InOut, DateTime<Utc>),
+}
+
+/// Line of the edited file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Number of the line, from 1
+    pub number: usize,
+    /// Stamp of the line, None for a new one
+    pub id: Option<StampId>,
+    /// Date/Time of the stamp
+    pub date: DateTime<Utc>,
+    /// Direction of the stamp
+    pub in_out: InOut,
+}
+
+/// Write stamps in the edited format
+///
+/// # Arguments
+///
+/// * `stamps` - Stamps of the range
+/// * `window` - Bounds of the range
+/// * `tz` - Timezone the dates are written in
+pub fn dump(stamps: &[Stamp], window: (&DateTime<Utc>, &DateTime<Utc>), tz: &Tz) -> String {
+    let local = |date: &DateTime<Utc>| date.with_timezone(tz).format("%Y-%m-%d %H:%M");
+    let mut text = format!(
+        "# Stamps from {} to {} ({} time)\n\
+        #\n\
+        # <id> <YYYY-MM-DD HH:MM> <in|out>\n\
+        #\n\
+        # Change a line to update the stamp, remove it to delete the stamp, and\n\
+        # write `new` instead of an id to insert one. Lines starting with # are\n\
+        # ignored, an unchanged file changes nothing.\n\n",
+        local(window.0),
+        local(window.1),
+        tz.name()
+    );
+    for stamp in stamps {
+        let _ = writeln!(
+            text,
+            "{} {} {}",
+            stamp.id,
+            local(&stamp.date),
+            stamp.in_out.to_string().to_lowercase()
+        );
+    }
+    text
+}
+
+/// Read the edited stamps
+///
+/// # Arguments
+///
+/// * `text` - Content of the edited file
+/// * `tz` - Timezone the dates are written in
+pub fn parse(text: &str, tz: &Tz) -> Result<Vec<Line>, EditError> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [id, day, time, in_out] = fields[..] else {
+            return Err(EditError::Syntax(number));
+        };
+        let id = match id {
+            "new" => None,
+            id => Some(StampId(id.parse().map_err(|_| EditError::Syntax(number))?)),
+        };
+        let date = NaiveDateTime::parse_from_str(&format!("{} {}", day, time), "%Y-%m-%d %H:%M")
+            .ok()
+            .and_then(|date| tz.from_local_datetime(&date).earliest())
+            .ok_or(EditError::Syntax(number))?;
+        let in_out = InOut::from_str(in_out).map_err(|_| EditError::Syntax(number))?;
+
+        lines.push(Line {
+            number,
+            id,
+            date: date.with_timezone(&Utc),
+            in_out,
+        });
+    }
+    Ok(lines)
+}
+
+/// Changes to apply to the work-log
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// New stamps, in date order
+    pub inserted: Vec<Stamp>,
+    /// Modified stamps
+    pub updated: Vec<Stamp>,
+    /// Removed stamps
+    pub deleted: Vec<Stamp>,
+}
+
+impl Changes {
+    /// Whether there is nothing to change
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Compare the edited stamps with the original ones
+///
+/// Dates are written to the minute, so a stamp whose line is unchanged keeps
+/// its seconds.
+///
+/// # Arguments
+///
+/// * `stamps` - Stamps of the range, as read
+/// * `lines` - Edited lines, see [parse]
+/// * `window` - Bounds of the range, the edited dates must stay within
+/// * `before` - Direction of the last stamp before the range, if any
+/// * `after` - Direction of the first stamp after the range, if any
+///
+/// # Return
+///
+/// The changes, or why they are refused, e.g. check-ins and check-outs no
+/// longer alternating.
+pub fn changes(
+    stamps: &[Stamp],
+    lines: &[Line],
+    window: (&DateTime<Utc>, &DateTime<Utc>),
+    before: Option<InOut>,
+    after: Option<InOut>,
+) -> Result<Changes, EditError> {
+    let mut remaining: BTreeMap<StampId, &Stamp> = stamps.iter().map(|s| (s.id, s)).collect();
+    let mut changes = Changes::default();
+    let mut result = Vec::new();
+
+    for line in lines {
+        if line.date < *window.0 || line.date >= *window.1 {
+            return Err(EditError::OutOfRange(line.number));
+        }
+        let Some(id) = line.id else {
+            changes
+                .inserted
+                .push(Stamp::new(StampId::default(), line.date, line.in_out));
+            result.push((line.date, line.in_out));
+            continue;
+        };
+
+        let stamp = match remaining.remove(&id) {
+            Some(stamp) => stamp,
+            None if stamps.iter().any(|s| s.id == id) => {
+                return Err(EditError::Duplicate(line.number, id))
+            }
+            None => return Err(EditError::UnknownStamp(line.number, id)),
+        };
+        let minute = stamp.date.with_second(0).and_then(|d| d.with_nanosecond(0));
+        if Some(line.date) == minute && line.in_out == stamp.in_out {
+            result.push((stamp.date, stamp.in_out));
+        } else {
+            let mut stamp = stamp.clone();
+            stamp.date = line.date;
+            stamp.in_out = line.in_out;
+            result.push((stamp.date, stamp.in_out));
+            changes.updated.push(stamp);
+        }
+    }
+    changes.deleted = remaining.into_values().cloned().collect();
+    changes.inserted.sort_by_key(|s| s.date);
+
+    // Out before In at the same date, as renumbered
+    result.sort_by_key(|(date, in_out)| (*date, *in_out == InOut::In));
+    let mut previous = before;
+    for (date, in_out) in result {
+        if previous == Some(in_out) {
+            return Err(EditError::NotAlternating(in_out, date));
+        }
+        previous = Some(in_out);
+    }
+    if let (Some(last), Some(after)) = (previous, after) {
+        if last == after {
+            return Err(EditError::NotAlternating(after, *window.1));
+        }
+    }
+    Ok(changes)
+}
+
+/// Temporary file only readable by the user, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Create a new file in the temporary directory, failing if it exists
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Content of the file
+    fn create(text: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("wtime-edit-{}.txt", Uuid::new_v4()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&path)
+            .with_context(|| format!("Creating {:?}", path))?;
+        // Removed from now on, even if the write fails
+        let temp = Self(path);
+        file.write_all(text.as_bytes())
+            .with_context(|| format!("Writing {:?}", temp.0))?;
+        Ok(temp)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Let the user edit a text in `$VISUAL` or `$EDITOR`
+///
+/// The text is written to a new temporary file only the user can read,
+/// removed on return whatever happens.
+///
+/// # Arguments
+///
+/// * `text` - Text to edit
+///
+/// # Return
+///
+/// The edited text, an error if the editor failed.
+pub fn open_editor(text: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from(if cfg!(windows) { "notepad" } else { "vi" }));
+    let temp = TempFile::create(text)?;
+    let file = &temp.0;
+
+    // The editor may come with arguments, e.g. `code --wait`
+    #[cfg(windows)]
+    let status = Command::new("cmd")
+        .arg("/C")
+        .arg(format!("{} \"{}\"", editor, file.display()))
+        .status();
+    #[cfg(not(windows))]
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(file)
+        .status();
+
+    match status.with_context(|| format!("Running {}", editor))? {
+        status if status.success() => {
+            fs::read_to_string(file).with_context(|| format!("Reading {:?}", file))
+        }
+        status => Err(anyhow!("{} failed with {}", editor, status)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{changes, dump, parse, EditError, Range, TempFile};
+    use crate::config::Config;
+    use crate::db::{InOut, Stamp, StampId};
+    use chrono::{DateTime, Utc};
+    use chrono_tz::Tz;
+    use std::str::FromStr;
+
+    fn date(s: &str) -> DateTime<Utc> {
+        DateTime::<Utc>::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn ranges() {
+        let config = Config {
+            timezone: Tz::Europe__Zurich,
+            ..Config::default()
+        };
+        let now = date("2024-02-14T23:30:00Z");
+        assert_eq!(
+            Range::Today.window(&config, &now),
+            (date("2024-02-14T23:00:00Z"), date("2024-02-15T23:00:00Z"))
+        );
+        assert_eq!(
+            Range::Week.window(&config, &now),
+            (date("2024-02-11T23:00:00Z"), date("2024-02-18T23:00:00Z"))
+        );
+        assert_eq!(
+            Range::Month.window(&config, &now),
+            (date("2024-01-31T23:00:00Z"), date("2024-02-29T23:00:00Z"))
+        );
+        assert_eq!(
+            Range::DayOf(date("2024-01-01T12:00:00Z")).window(&config, &now),
+            (date("2023-12-31T23:00:00Z"), date("2024-01-01T23:00:00Z"))
+        );
+        assert_eq!(Range::from_str("Yesterday"), Ok(Range::Yesterday));
+        assert!(Range::from_str("year").is_err());
+    }
+
+    #[test]
+    fn edit() {
+        let tz = Tz::Europe__Zurich;
+        let window = (date("2024-02-11T23:00:00Z"), date("2024-02-12T23:00:00Z"));
+        let window = (&window.0, &window.1);
+        let stamps = [
+            Stamp::new(StampId(12), date("2024-02-12T07:00:42Z"), InOut::In),
+            Stamp::new(StampId(13), date("2024-02-12T11:00:00Z"), InOut::Out),
+            Stamp::new(StampId(14), date("2024-02-12T12:00:00Z"), InOut::In),
+        ];
+
+        let text = dump(&stamps, window, &tz);
+        assert!(text.starts_with("# Stamps from 2024-02-12 00:00 to 2024-02-13 00:00"));
+        assert!(text.ends_with(
+            "\n12 2024-02-12 08:00 in\n13 2024-02-12 12:00 out\n14 2024-02-12 13:00 in\n"
+        ));
+
+        // Unchanged, the seconds are kept
+        let lines = parse(&text, &tz).unwrap();
+        assert!(changes(&stamps, &lines, window, Some(InOut::Out), None)
+            .unwrap()
+            .is_empty());
+
+        let text = "12 2024-02-12 08:00 in\n\
+            # Lunch\n\
+            new 2024-02-12 12:30 out\n\
+            14 2024-02-12 13:15 in\n";
+        let lines = parse(text, &tz).unwrap();
+        assert_eq!(lines[1].number, 3);
+        let edited = changes(&stamps, &lines, window, None, None).unwrap();
+        assert_eq!(edited.inserted.len(), 1);
+        assert_eq!(edited.inserted[0].date, date("2024-02-12T11:30:00Z"));
+        assert_eq!(edited.updated.len(), 1);
+        assert_eq!(edited.updated[0].date, date("2024-02-12T12:15:00Z"));
+        assert_eq!(edited.deleted, [stamps[1].clone()]);
+
+        assert_eq!(parse("12 08:00 in", &tz), Err(EditError::Syntax(1)));
+        assert_eq!(
+            parse("\n12 2024-02-12 08:00 maybe", &tz),
+            Err(EditError::Syntax(2))
+        );
+        let refused = |text: &str| {
+            let lines = parse(text, &tz).unwrap();
+            changes(&stamps, &lines, window, Some(InOut::Out), Some(InOut::In)).unwrap_err()
+        };
+        assert_eq!(
+            refused("15 2024-02-12 08:00 in"),
+            EditError::UnknownStamp(1, StampId(15))
+        );
+        assert_eq!(
+            refused("12 2024-02-12 08:00 in\n12 2024-02-12 09:00 in"),
+            EditError::Duplicate(2, StampId(12))
+        );
+        assert_eq!(refused("12 2024-02-13 08:00 in"), EditError::OutOfRange(1));
+        assert_eq!(
+            refused("12 2024-02-12 08:00 in\n14 2024-02-12 13:00 in"),
+            EditError::NotAlternating(InOut::In, date("2024-02-12T12:00:00Z"))
+        );
+        // The stamp after the range is a check-in
+        assert_eq!(
+            refused("12 2024-02-12 08:00 in"),
+            EditError::NotAlternating(InOut::In, *window.1)
+        );
+    }
+
+    #[test]
+    fn temp_file() {
+        let temp = TempFile::create("1 2024-02-12 08:00 in\n").unwrap();
+        let path = temp.0.clone();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "1 2024-02-12 08:00 in\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        drop(temp);
+        assert!(!path.exists());
+    }
+}
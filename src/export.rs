@@ -0,0 +1,716 @@
+//! Export of the work-log data
+//!
+//! This module contains functions writing the database content in formats
+//! meant to be consumed by other tools.
+
+use crate::config::Config;
+use crate::db::{Allocation, Audit, StampId, GENESIS_HASH};
+use crate::format::{self, DurationFormat};
+use crate::import::TIMEWARRIOR_DATE;
+use crate::report::{self, DaySpan, Session};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Timelike, Utc};
+use rust_xlsxwriter::{ExcelDateTime, Format as XlsxFormat, Workbook};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Output format of an export
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// Comma separated values, with a header line
+    Csv,
+    /// JSON array of objects
+    Json,
+}
+
+/// Unit-struct for parsing error on Format enum
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseFormatError;
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Output format of a session export
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SessionFormat {
+    /// Intervals of Timewarrior data files, tagged with the allocations
+    Timewarrior,
+    /// Harvest bulk import CSV, with the hours of every project per day
+    Harvest,
+    /// Kimai timesheet import CSV, with a record per session and project
+    Kimai,
+    /// Emacs org-mode CLOCK entries, under a headline per project
+    Org,
+    /// Excel workbook, with a sheet of the worked days per month
+    Xlsx,
+}
+
+impl FromStr for SessionFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "timewarrior" | "timew" => Ok(Self::Timewarrior),
+            "harvest" => Ok(Self::Harvest),
+            "kimai" => Ok(Self::Kimai),
+            "org" => Ok(Self::Org),
+            "xlsx" => Ok(Self::Xlsx),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Quote a Timewarrior tag when needed
+fn timewarrior_tag(tag: &str) -> String {
+    if !tag.is_empty() && !tag.contains(|c: char| c.is_whitespace() || c == '"' || c == '#') {
+        return tag.to_string();
+    }
+    format!("\"{}\"", tag.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Activity of the exported Kimai records, which wtime does not track
+pub const KIMAI_ACTIVITY: &str = "Work";
+
+/// Split an allocation target into client and project
+///
+/// The client is the first part of a `client/project` target, none otherwise.
+fn client_project(target: &str) -> (&str, &str) {
+    match target.split_once('/') {
+        Some((client, project)) if !client.is_empty() && !project.is_empty() => (client, project),
+        _ => ("", target),
+    }
+}
+
+/// Worked interval, from and to dates
+type Interval = (DateTime<Utc>, DateTime<Utc>);
+
+/// Split a session into consecutive intervals, one per allocation share
+///
+/// The intervals follow each other, so that they do not overlap.
+fn share_intervals(
+    session: &Session,
+    allocations: &BTreeMap<StampId, Vec<Allocation>>,
+) -> Vec<(String, DateTime<Utc>, DateTime<Utc>)> {
+    let session_allocations = allocations.get(&session.id).map_or(&[][..], Vec::as_slice);
+    let mut from = session.start;
+    report::shares(session, session_allocations)
+        .into_iter()
+        .map(|(target, share)| {
+            let interval = (target, from, from + share);
+            from += share;
+            interval
+        })
+        .collect()
+}
+
+/// Build an Excel workbook of the worked days, with a sheet per month
+///
+/// Every day has its first check-in, last check-out, breaks and worked
+/// hours, summed by formulas on the last row of the sheet.
+///
+/// # Arguments
+///
+/// * `sessions` - Sessions to export
+/// * `config` - User settings, giving the timezone of the days and times
+///
+/// # Return
+///
+/// The content of the xlsx file.
+fn workbook(sessions: &[Session], config: &Config) -> Result<Vec<u8>> {
+    let mut months: BTreeMap<NaiveDate, BTreeMap<NaiveDate, DaySpan>> = BTreeMap::new();
+    for (day, span) in report::day_spans(sessions, config) {
+        months
+            .entry(day.with_day(1).unwrap())
+            .or_default()
+            .insert(day, span);
+    }
+
+    let bold = XlsxFormat::new().set_bold();
+    let date = XlsxFormat::new().set_num_format("yyyy-mm-dd");
+    let time = XlsxFormat::new().set_num_format("hh:mm");
+    let digits = format::durations().precision;
+    let hours_format = if digits == 0 {
+        "0".to_string()
+    } else {
+        format!("0.{}", "0".repeat(digits))
+    };
+    let hours = XlsxFormat::new().set_num_format(&hours_format);
+    let total = hours.clone().set_bold();
+    let excel_time = |d: &DateTime<Utc>| {
+        let d = d.with_timezone(&config.timezone);
+        ExcelDateTime::from_hms(d.hour() as u16, d.minute() as u8, 0)
+    };
+    let decimal_hours = |d: &Duration| d.num_seconds() as f64 / 3600.0;
+
+    let mut workbook = Workbook::new();
+    for (month, days) in months {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(month.format("%Y-%m").to_string())?;
+        sheet.set_column_width(0, 12)?;
+        for (column, title) in ["Date", "Start", "End", "Breaks", "Worked"]
+            .into_iter()
+            .enumerate()
+        {
+            sheet.write_string_with_format(0, column as u16, title, &bold)?;
+        }
+
+        let mut row = 1;
+        for (day, span) in &days {
+            let excel_day =
+                ExcelDateTime::from_ymd(day.year() as u16, day.month() as u8, day.day() as u8)?;
+            sheet.write_datetime_with_format(row, 0, &excel_day, &date)?;
+            sheet.write_datetime_with_format(row, 1, &excel_time(&span.start)?, &time)?;
+            sheet.write_datetime_with_format(row, 2, &excel_time(&span.end)?, &time)?;
+            sheet.write_number_with_format(row, 3, decimal_hours(&span.breaks()), &hours)?;
+            sheet.write_number_with_format(row, 4, decimal_hours(&span.worked), &hours)?;
+            row += 1;
+        }
+
+        // Excel computes the totals when opening the file
+        sheet.write_string_with_format(row, 0, "Total", &bold)?;
+        for (column, letter) in [(3, 'D'), (4, 'E')] {
+            sheet.write_formula_with_format(
+                row,
+                column,
+                format!("=SUM({letter}2:{letter}{row})").as_str(),
+                &total,
+            )?;
+        }
+    }
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Export worked sessions in given format.
+///
+/// # Arguments
+///
+/// * `out` - Where to write the export
+/// * `sessions` - Sessions to export
+/// * `allocations` - Allocations of the sessions, by session ID
+/// * `user` - Name of the user who worked the sessions
+/// * `config` - User settings, giving the timezone of the days
+/// * `format` - Output format
+pub fn sessions(
+    out: &mut dyn Write,
+    sessions: &[Session],
+    allocations: &BTreeMap<StampId, Vec<Allocation>>,
+    user: &str,
+    config: &Config,
+    format: SessionFormat,
+) -> Result<()> {
+    match format {
+        SessionFormat::Timewarrior => {
+            for session in sessions {
+                let mut line = format!(
+                    "inc {} - {}",
+                    session.start.format(TIMEWARRIOR_DATE),
+                    session.end.format(TIMEWARRIOR_DATE)
+                );
+                let tags: Vec<String> = allocations
+                    .get(&session.id)
+                    .into_iter()
+                    .flatten()
+                    .map(|a| timewarrior_tag(&a.target))
+                    .collect();
+                if !tags.is_empty() {
+                    line = format!("{} # {}", line, tags.join(" "));
+                }
+                writeln!(out, "{}", line)?;
+            }
+        }
+        SessionFormat::Harvest => {
+            let mut hours: BTreeMap<(NaiveDate, String), Duration> = BTreeMap::new();
+            for session in sessions {
+                let session_allocations =
+                    allocations.get(&session.id).map_or(&[][..], Vec::as_slice);
+                for (target, share) in report::shares(session, session_allocations) {
+                    let total = hours
+                        .entry((session.day(config), target))
+                        .or_insert_with(Duration::zero);
+                    *total = *total + share;
+                }
+            }
+
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(["Date", "Client", "Project", "Hours", "Notes"])?;
+            for ((day, target), duration) in hours {
+                let (client, project) = client_project(&target);
+                writer.write_record([
+                    day.format("%Y-%m-%d").to_string().as_str(),
+                    client,
+                    project,
+                    format::hours(&duration).as_str(),
+                    "",
+                ])?;
+            }
+            writer.flush()?;
+        }
+        SessionFormat::Kimai => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record([
+                "Date",
+                "From",
+                "To",
+                "Duration",
+                "User",
+                "Customer",
+                "Project",
+                "Activity",
+                "Description",
+                "Tags",
+            ])?;
+            for session in sessions {
+                for (target, from, to) in share_intervals(session, allocations) {
+                    let (local_from, local_to) =
+                        (from.with_timezone(&Local), to.with_timezone(&Local));
+                    let (customer, project) = client_project(&target);
+                    // Seconds, unless decimal hours are asked for billing
+                    let duration = if format::durations().format == DurationFormat::Decimal {
+                        format::hours(&(to - from))
+                    } else {
+                        (to - from).num_seconds().to_string()
+                    };
+                    writer.write_record([
+                        local_from.format("%Y-%m-%d").to_string().as_str(),
+                        local_from.format("%H:%M").to_string().as_str(),
+                        local_to.format("%H:%M").to_string().as_str(),
+                        duration.as_str(),
+                        user,
+                        customer,
+                        project,
+                        KIMAI_ACTIVITY,
+                        "",
+                        "",
+                    ])?;
+                }
+            }
+            writer.flush()?;
+        }
+        SessionFormat::Org => {
+            let mut clocks: BTreeMap<String, Vec<Interval>> = BTreeMap::new();
+            for session in sessions {
+                for (target, from, to) in share_intervals(session, allocations) {
+                    clocks.entry(target).or_default().push((from, to));
+                }
+            }
+
+            let org_date = |date: DateTime<Utc>| {
+                date.with_timezone(&Local)
+                    .format("[%Y-%m-%d %a %H:%M]")
+                    .to_string()
+            };
+            for (target, intervals) in clocks {
+                writeln!(out, "* {}", target)?;
+                writeln!(out, "  :LOGBOOK:")?;
+                // Org keeps the latest clock on top
+                for (from, to) in intervals.into_iter().rev() {
+                    let minutes = (to - from).num_minutes();
+                    writeln!(
+                        out,
+                        "  CLOCK: {}--{} => {:>2}:{:02}",
+                        org_date(from),
+                        org_date(to),
+                        minutes / 60,
+                        minutes % 60
+                    )?;
+                }
+                writeln!(out, "  :END:")?;
+            }
+        }
+        SessionFormat::Xlsx => out.write_all(&workbook(sessions, config)?)?,
+    }
+
+    Ok(())
+}
+
+/// Export the audit log, hash-chained, in given format.
+///
+/// The hashes are the ones stored when each record was written, see
+/// [crate::db::audit_hash], so that the records modified since are detected
+/// when verifying the export. The row ids are
+/// exported as written, along with the current stamp ids, see
+/// [Audit::current_row_ids].
+///
+/// # Arguments
+///
+/// * `out` - Where to write the export
+/// * `records` - Complete audit log, oldest first
+/// * `from` - Only export records at or after this date
+/// * `to` - Only export records before this date
+/// * `format` - Output format
+pub fn audit(
+    out: &mut dyn Write,
+    records: &[Audit],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    format: Format,
+) -> Result<()> {
+    let mut prev_hash = GENESIS_HASH;
+    let mut chained = Vec::new();
+
+    for (record, current_id) in records.iter().zip(Audit::current_row_ids(records)) {
+        let in_range = from.is_none_or(|f| record.date >= f) && to.is_none_or(|t| record.date < t);
+        if in_range {
+            chained.push((record, current_id, prev_hash, record.hash.as_str()));
+        }
+        prev_hash = &record.hash;
+    }
+
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record([
+                "id",
+                "datetime",
+                "operation",
+                "table",
+                "row_id",
+                "current_row_id",
+                "old_value",
+                "new_value",
+                "command",
+                "prev_hash",
+                "hash",
+            ])?;
+            for (record, current_id, prev_hash, hash) in chained {
+                writer.write_record([
+                    record.id.to_string().as_str(),
+                    record.date.to_rfc3339().as_str(),
+                    record.operation.to_string().as_str(),
+                    record.table.as_str(),
+                    record.row_id.to_string().as_str(),
+                    current_id.to_string().as_str(),
+                    record.old_value.as_deref().unwrap_or_default(),
+                    record.new_value.as_deref().unwrap_or_default(),
+                    record.command.as_deref().unwrap_or_default(),
+                    prev_hash,
+                    hash,
+                ])?;
+            }
+            writer.flush()?;
+        }
+        Format::Json => {
+            let entries: Vec<_> = chained
+                .into_iter()
+                .map(|(record, current_id, prev_hash, hash)| {
+                    json!({
+                        "id": record.id,
+                        "datetime": record.date.to_rfc3339(),
+                        "operation": record.operation.to_string(),
+                        "table": record.table,
+                        "row_id": record.row_id,
+                        "current_row_id": current_id,
+                        "old_value": record.old_value,
+                        "new_value": record.new_value,
+                        "command": record.command,
+                        "prev_hash": prev_hash,
+                        "hash": hash,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *out, &entries)?;
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{audit, sessions, Format, SessionFormat};
+    use crate::config::Config;
+    use crate::db::{audit_hash, Allocation, Audit, Operation, StampId, GENESIS_HASH};
+    use crate::import;
+    use crate::report::Session;
+    use chrono::{DateTime, Duration, Local, Utc};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    fn record(prev_hash: &str, id: i64, date: &str) -> Audit {
+        let mut record = Audit {
+            id,
+            date: DateTime::<Utc>::from_str(date).unwrap(),
+            operation: Operation::Insert,
+            table: "Stamp".into(),
+            row_id: id,
+            old_value: None,
+            new_value: Some(format!("{} In", date)),
+            command: None,
+            hash: String::new(),
+        };
+        record.hash = audit_hash(prev_hash, &record);
+        record
+    }
+
+    #[test]
+    fn audit_chain() {
+        let first = record(GENESIS_HASH, 1, "2020-01-01T08:00:00Z");
+        let second = record(&first.hash, 2, "2020-01-02T08:00:00Z");
+        let third = record(&second.hash, 3, "2020-01-03T08:00:00Z");
+        let mut records = vec![first, second, third];
+
+        let mut out = Vec::new();
+        audit(
+            &mut out,
+            &records,
+            Some(DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap()),
+            None,
+            Format::Json,
+        )
+        .unwrap();
+        let exported: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let exported = exported.as_array().unwrap();
+
+        // Partial export is still chained from the first record
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0]["id"], 2);
+        assert_eq!(exported[0]["prev_hash"], records[0].hash);
+        assert_eq!(exported[0]["hash"], records[1].hash);
+        assert_eq!(exported[1]["prev_hash"], exported[0]["hash"]);
+
+        let mut out = Vec::new();
+        audit(&mut out, &records, None, None, Format::Csv).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().nth(1).unwrap().ends_with(&records[0].hash));
+
+        // The stored hashes are exported, a modified record keeps its hash
+        records[1].new_value = Some("2020-01-02T09:00:00+00:00 In".into());
+        let mut out = Vec::new();
+        audit(&mut out, &records, None, None, Format::Json).unwrap();
+        let exported: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(exported[1]["hash"], records[1].hash);
+        assert_eq!(Audit::verify(&records), Err(2));
+
+        // The command is only hashed when recorded
+        let mut with_command = record(GENESIS_HASH, 1, "2020-01-01T08:00:00Z");
+        with_command.command = Some("checkin".into());
+        assert_ne!(audit_hash(GENESIS_HASH, &with_command), records[0].hash);
+    }
+
+    #[test]
+    fn timewarrior_round_trip() {
+        let date = |d: &str| DateTime::<Utc>::from_str(d).unwrap();
+        let worked = vec![
+            Session {
+                id: StampId(1),
+                start: date("2020-01-01T08:00:00Z"),
+                end: date("2020-01-01T12:00:00Z"),
+            },
+            Session {
+                id: StampId(3),
+                start: date("2020-01-01T13:00:00Z"),
+                end: date("2020-01-01T15:00:00Z"),
+            },
+        ];
+        let mut allocations = BTreeMap::new();
+        allocations.insert(
+            StampId(1),
+            vec![
+                Allocation::new(StampId(1), "big \"web\" site", 0.5),
+                Allocation::new(StampId(1), "PROJ-1", 0.5),
+            ],
+        );
+
+        let mut out = Vec::new();
+        sessions(
+            &mut out,
+            &worked,
+            &allocations,
+            "me",
+            &Config::default(),
+            SessionFormat::Timewarrior,
+        )
+        .unwrap();
+        let data = String::from_utf8(out).unwrap();
+        assert_eq!(
+            data,
+            "inc 20200101T080000Z - 20200101T120000Z # \"big \\\"web\\\" site\" PROJ-1\n\
+             inc 20200101T130000Z - 20200101T150000Z\n"
+        );
+
+        let records = import::timewarrior(&data).unwrap();
+        assert_eq!(records[0].project.as_deref(), Some("big \"web\" site"));
+        assert_eq!(records[1].start, worked[1].start);
+    }
+
+    #[test]
+    fn harvest_hours() {
+        let date = |d: &str| DateTime::<Utc>::from_str(d).unwrap();
+        let worked = vec![
+            Session {
+                id: StampId(1),
+                start: date("2020-01-01T08:00:00Z"),
+                end: date("2020-01-01T12:00:00Z"),
+            },
+            Session {
+                id: StampId(3),
+                start: date("2020-01-01T13:00:00Z"),
+                end: date("2020-01-01T14:30:00Z"),
+            },
+        ];
+        let mut allocations = BTreeMap::new();
+        allocations.insert(
+            StampId(1),
+            vec![
+                Allocation::new(StampId(1), "ACME/website", 0.75),
+                Allocation::new(StampId(1), "internal", 0.25),
+            ],
+        );
+        allocations.insert(
+            StampId(3),
+            vec![Allocation::new(StampId(3), "ACME/website", 1.0)],
+        );
+
+        let mut out = Vec::new();
+        sessions(
+            &mut out,
+            &worked,
+            &allocations,
+            "me",
+            &Config::default(),
+            SessionFormat::Harvest,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Date,Client,Project,Hours,Notes\n\
+             2020-01-01,ACME,website,4.50,\n\
+             2020-01-01,,internal,1.00,\n"
+        );
+    }
+
+    #[test]
+    fn kimai_records() {
+        let start = DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap();
+        let worked = vec![Session {
+            id: StampId(1),
+            start,
+            end: start + Duration::hours(4),
+        }];
+        let mut allocations = BTreeMap::new();
+        allocations.insert(
+            StampId(1),
+            vec![Allocation::new(StampId(1), "ACME/website", 0.75)],
+        );
+
+        let mut out = Vec::new();
+        sessions(
+            &mut out,
+            &worked,
+            &allocations,
+            "me",
+            &Config::default(),
+            SessionFormat::Kimai,
+        )
+        .unwrap();
+        let mut reader = csv::Reader::from_reader(out.as_slice());
+        let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+        let fields = |record: &csv::StringRecord| -> Vec<String> {
+            record.iter().skip(3).take(5).map(str::to_string).collect()
+        };
+        assert_eq!(
+            fields(&records[0]),
+            ["10800", "me", "ACME", "website", "Work"]
+        );
+        assert_eq!(
+            fields(&records[1]),
+            ["3600", "me", "", "(unallocated)", "Work"]
+        );
+
+        // The second share starts when the first ends
+        let local = (start + Duration::hours(3)).with_timezone(&Local);
+        assert_eq!(records[0][2], local.format("%H:%M").to_string());
+        assert_eq!(records[1][1], records[0][2]);
+    }
+
+    #[test]
+    fn org_clocks() {
+        let start = DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap();
+        let worked = vec![
+            Session {
+                id: StampId(1),
+                start,
+                end: start + Duration::hours(4),
+            },
+            Session {
+                id: StampId(3),
+                start: start + Duration::hours(5),
+                end: start + Duration::minutes(5 * 60 + 30),
+            },
+        ];
+        let mut allocations = BTreeMap::new();
+        allocations.insert(StampId(1), vec![Allocation::new(StampId(1), "PROJ-1", 0.5)]);
+        allocations.insert(StampId(3), vec![Allocation::new(StampId(3), "PROJ-1", 1.0)]);
+
+        let mut out = Vec::new();
+        sessions(
+            &mut out,
+            &worked,
+            &allocations,
+            "me",
+            &Config::default(),
+            SessionFormat::Org,
+        )
+        .unwrap();
+        let org = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = org.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "* (unallocated)");
+        assert_eq!(lines[4], "* PROJ-1");
+        assert!(lines[6].ends_with("=>  0:30"));
+        assert!(lines[7].ends_with("=>  2:00"));
+
+        let local = start.with_timezone(&Local);
+        assert!(lines[7].starts_with(&format!(
+            "  CLOCK: {}--",
+            local.format("[%Y-%m-%d %a %H:%M]")
+        )));
+    }
+
+    #[test]
+    fn xlsx_workbook() {
+        let date = |d: &str| DateTime::<Utc>::from_str(d).unwrap();
+        let worked = vec![
+            Session {
+                id: StampId(1),
+                start: date("2020-01-31T08:00:00Z"),
+                end: date("2020-01-31T12:00:00Z"),
+            },
+            Session {
+                id: StampId(3),
+                start: date("2020-02-03T08:00:00Z"),
+                end: date("2020-02-03T12:00:00Z"),
+            },
+        ];
+
+        let mut out = Vec::new();
+        sessions(
+            &mut out,
+            &worked,
+            &BTreeMap::new(),
+            "me",
+            &Config::default(),
+            SessionFormat::Xlsx,
+        )
+        .unwrap();
+        // A zip archive, with a sheet per month
+        assert!(out.starts_with(b"PK"));
+        let names = String::from_utf8_lossy(&out);
+        assert!(names.contains("xl/worksheets/sheet2.xml"));
+        assert!(!names.contains("xl/worksheets/sheet3.xml"));
+    }
+}
@@ -0,0 +1,193 @@
+//! Formatting of the printed durations
+//!
+//! The `duration_format` of the configuration selects how the durations
+//! printed by the check-out, the status and the reports read:
+//!
+//! - `hm` (default): hours and minutes, e.g. `7h 32m`
+//! - `clock`: hours and minutes as on a clock, e.g. `7:32`
+//! - `decimal`: decimal hours, e.g. `7.53h`, for billing
+//!
+//! ```toml
+//! duration_format = "decimal"
+//! # Digits of the decimal hours
+//! hours_precision = 2
+//! ```
+//!
+//! The HTML and Markdown reports, the timesheets and the Harvest exports
+//! always give decimal hours, with these digits. The decimal format, or the
+//! `--decimal-hours` option of `wtime report` and `wtime export`, also gives
+//! the Kimai exports in decimal hours rather than seconds.
+
+use crate::export::ParseFormatError;
+use chrono::Duration;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Digits of the decimal hours, unless configured
+pub const DEFAULT_PRECISION: usize = 2;
+
+/// How the durations are printed
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub enum DurationFormat {
+    /// Hours and minutes, e.g. `7h 32m`
+    #[default]
+    HoursMinutes,
+    /// Hours and minutes as on a clock, e.g. `7:32`
+    Clock,
+    /// Decimal hours, e.g. `7.53h`
+    Decimal,
+}
+
+impl FromStr for DurationFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hm" => Ok(Self::HoursMinutes),
+            "clock" => Ok(Self::Clock),
+            "decimal" => Ok(Self::Decimal),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Format of the durations, with the digits of the decimal hours
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Durations {
+    /// How the durations are printed
+    pub format: DurationFormat,
+    /// Digits of the decimal hours
+    pub precision: usize,
+}
+
+impl Default for Durations {
+    fn default() -> Self {
+        Self {
+            format: DurationFormat::default(),
+            precision: DEFAULT_PRECISION,
+        }
+    }
+}
+
+impl Durations {
+    /// Format a duration, the seconds being dropped unless decimal
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - Duration to format, negative ones get a `-` sign
+    pub fn duration(&self, d: &Duration) -> String {
+        if *d < Duration::zero() {
+            return format!("-{}", self.duration(&-*d));
+        }
+        match self.format {
+            DurationFormat::HoursMinutes => {
+                format!("{}h {:02}m", d.num_hours(), d.num_minutes() % 60)
+            }
+            DurationFormat::Clock => format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60),
+            DurationFormat::Decimal => format!("{}h", self.hours(d)),
+        }
+    }
+
+    /// Format a signed duration difference, e.g. `+0h 30m` or `-1h 05m`
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - Difference to format
+    pub fn delta(&self, d: &Duration) -> String {
+        if *d < Duration::zero() {
+            self.duration(d)
+        } else {
+            format!("+{}", self.duration(d))
+        }
+    }
+
+    /// Format a duration in decimal hours, without unit, e.g. `7.53`
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - Duration to format
+    pub fn hours(&self, d: &Duration) -> String {
+        format!("{:.*}", self.precision, d.num_seconds() as f64 / 3600.0)
+    }
+}
+
+/// Format a running time as a stopwatch does, seconds included, e.g. `1:02:03`
+///
+/// # Arguments
+///
+/// * `d` - Running time, not negative
+pub fn timer(d: &Duration) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        d.num_hours(),
+        d.num_minutes() % 60,
+        d.num_seconds() % 60
+    )
+}
+
+/// Format of the printed durations, hours and minutes until [init] is called
+static FORMAT: OnceLock<Durations> = OnceLock::new();
+
+/// Select the format of the printed durations, once at start
+///
+/// # Arguments
+///
+/// * `durations` - Format of the durations
+pub fn init(durations: Durations) {
+    let _ = FORMAT.set(durations);
+}
+
+/// Format of the printed durations
+pub fn durations() -> &'static Durations {
+    FORMAT.get_or_init(Durations::default)
+}
+
+/// Format a printed duration, see [Durations::duration]
+pub fn duration(d: &Duration) -> String {
+    durations().duration(d)
+}
+
+/// Format a printed duration difference, see [Durations::delta]
+pub fn delta(d: &Duration) -> String {
+    durations().delta(d)
+}
+
+/// Format a duration in decimal hours, see [Durations::hours]
+pub fn hours(d: &Duration) -> String {
+    durations().hours(d)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{timer, DurationFormat, Durations};
+    use chrono::Duration;
+
+    #[test]
+    fn durations() {
+        let format = |format, precision| Durations { format, precision };
+        let hm = Durations::default();
+        let clock = format(DurationFormat::Clock, 2);
+        let decimal = format(DurationFormat::Decimal, 2);
+
+        let d = Duration::minutes(7 * 60 + 32) + Duration::seconds(20);
+        assert_eq!(hm.duration(&d), "7h 32m");
+        assert_eq!(clock.duration(&d), "7:32");
+        assert_eq!(decimal.duration(&d), "7.54h");
+        assert_eq!(decimal.duration(&Duration::minutes(30)), "0.50h");
+        assert_eq!(format(DurationFormat::Decimal, 1).duration(&d), "7.5h");
+        assert_eq!(format(DurationFormat::Decimal, 0).hours(&d), "8");
+        assert_eq!(format(DurationFormat::HoursMinutes, 3).hours(&d), "7.539");
+
+        let late = -Duration::minutes(65);
+        assert_eq!(hm.duration(&late), "-1h 05m");
+        assert_eq!(clock.delta(&late), "-1:05");
+        assert_eq!(hm.delta(&Duration::minutes(30)), "+0h 30m");
+        assert_eq!(decimal.delta(&Duration::zero()), "+0.00h");
+        assert_eq!(timer(&d), "7:32:20");
+        assert_eq!(timer(&Duration::seconds(65)), "0:01:05");
+
+        assert_eq!("clock".parse(), Ok(DurationFormat::Clock));
+        assert_eq!(" Decimal".parse(), Ok(DurationFormat::Decimal));
+        assert!("minutes".parse::<DurationFormat>().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Shell hooks run on the work-log events
+//!
+//! Commands given in the configuration are run through the shell when
+//! checking in, checking out or when a command fails:
+//!
+//! ```toml
+//! on_checkin = "notify-send wtime \"Working on $WTIME_PROJECT\""
+//! on_checkout = "echo $WTIME_DATE $WTIME_DURATION >> ~/sessions.log"
+//! on_error = "logger -t wtime \"$WTIME_ERROR\""
+//! ```
+//!
+//! The event data is given in environment variables:
+//!
+//! * `WTIME_EVENT` - `checkin`, `checkout` or `error`
+//! * `WTIME_DATE` - Date/time of the stamp, RFC 3339
+//! * `WTIME_PROJECT` - Projects of the session, comma separated, if any
+//! * `WTIME_START` - Start of the session on check-out, RFC 3339
+//! * `WTIME_DURATION` - Worked seconds of the session on check-out
+//! * `WTIME_ERROR` - Error message on error
+//! * `WTIME_COMMAND` - Arguments of the failed command on error
+
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::process::Command;
+
+/// Event a hook is run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// After a check-in
+    CheckIn,
+    /// After a check-out
+    CheckOut,
+    /// After a failed command
+    Error,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Event::CheckIn => "checkin",
+            Event::CheckOut => "checkout",
+            Event::Error => "error",
+        })
+    }
+}
+
+/// Run a hook command through the shell and wait for it
+///
+/// # Arguments
+///
+/// * `command` - Shell command of the hook
+/// * `event` - Event the hook is run on, given in `WTIME_EVENT`
+/// * `vars` - Other environment variables of the event
+///
+/// # Return
+///
+/// An error if the command could not be run or exited with a failure.
+pub fn run(command: &str, event: Event, vars: &[(&str, String)]) -> Result<()> {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    let status = shell
+        .arg(command)
+        .env("WTIME_EVENT", event.to_string())
+        .envs(vars.iter().map(|(key, value)| (key, value)))
+        .status()
+        .with_context(|| format!("Running the {} hook", event))?;
+    if !status.success() {
+        return Err(anyhow!("The {} hook failed with {}", event, status));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::{run, Event};
+    use std::fs;
+
+    #[test]
+    fn environment() {
+        let file = std::env::temp_dir().join("wtime-hooks-test.txt");
+        let _ = fs::remove_file(&file);
+
+        let command = format!(
+            "echo \"$WTIME_EVENT $WTIME_PROJECT\" > {}",
+            file.to_string_lossy()
+        );
+        run(
+            &command,
+            Event::CheckIn,
+            &[("WTIME_PROJECT", "acme".into())],
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "checkin acme\n");
+        fs::remove_file(&file).unwrap();
+
+        let e = run("exit 3", Event::CheckOut, &[]).unwrap_err();
+        assert!(e.to_string().starts_with("The checkout hook failed"));
+    }
+}
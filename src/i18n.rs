@@ -0,0 +1,258 @@
+//! Translation of the printed messages
+//!
+//! The messages printed by the commands are looked up by id in the
+//! [Fluent](https://projectfluent.org) files built in from `src/locales`,
+//! English messages standing in for the ones missing in a language. The
+//! language is the `language` of the configuration, or else the one of the
+//! `LC_ALL`, `LC_MESSAGES` or `LANG` environment variable:
+//!
+//! ```toml
+//! language = "fr"
+//! ```
+//!
+//! Error messages, logs and exported files stay in English.
+
+use crate::export::ParseFormatError;
+use chrono::Datelike;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::FluentResource;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+/// Language of the printed messages
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+}
+
+impl Language {
+    /// Language of the environment, from the `LC_ALL`, `LC_MESSAGES` or
+    /// `LANG` variable, the first one set
+    ///
+    /// # Return
+    ///
+    /// None if no variable is set, or not to a supported language.
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// Identifier of the language, e.g. `en`
+    fn id(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+        }
+    }
+
+    /// Messages of the language, in the Fluent syntax
+    fn messages(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("locales/en.ftl"),
+            Language::French => include_str!("locales/fr.ftl"),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = ParseFormatError;
+
+    /// Parse a language or a locale, e.g. `fr`, `fr-CH` or `fr_CH.UTF-8`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.trim().split(['_', '-', '.', '@']).next().unwrap_or("");
+        match language.to_lowercase().as_str() {
+            // The POSIX locale is English
+            "en" | "c" | "posix" => Ok(Self::English),
+            "fr" => Ok(Self::French),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Messages of a language
+pub struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+}
+
+impl Catalog {
+    /// Load the messages of a language
+    ///
+    /// # Arguments
+    ///
+    /// * `language` - Language of the messages
+    pub fn new(language: Language) -> Self {
+        let id: LanguageIdentifier = language.id().parse().unwrap();
+        let mut bundle = FluentBundle::new_concurrent(vec![id]);
+        // Placeables are not isolated, the terminal would print the marks
+        bundle.set_use_isolating(false);
+
+        for language in [Language::English, language] {
+            let resource = FluentResource::try_new(language.messages().to_string()).unwrap_or_else(
+                |(_, errors)| panic!("Invalid {:?} messages: {:?}", language, errors),
+            );
+            bundle.add_resource_overriding(resource);
+        }
+        Self { bundle }
+    }
+
+    /// Format a message
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the message
+    /// * `args` - Values of the variables of the message
+    ///
+    /// # Return
+    ///
+    /// The formatted message, or its identifier if unknown.
+    pub fn message(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        let Some(pattern) = self.bundle.get_message(id).and_then(|m| m.value()) else {
+            return id.to_string();
+        };
+        let mut errors = Vec::new();
+        self.bundle
+            .format_pattern(pattern, args, &mut errors)
+            .into_owned()
+    }
+
+    /// Translate the names of the `%a`, `%A`, `%b` and `%B` specifiers of a
+    /// date format, for the weekday and month of a date
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Format of the date, see [chrono::format::strftime]
+    /// * `date` - Date the format is used for
+    ///
+    /// # Return
+    ///
+    /// The format with these specifiers replaced by the names.
+    pub fn localize(&self, format: &str, date: &impl Datelike) -> String {
+        let name = |id: &str, number: u32| {
+            let mut args = FluentArgs::new();
+            args.set("number", number);
+            // A literal % in a name would be taken as a specifier
+            self.message(id, Some(&args)).replace('%', "%%")
+        };
+        let weekday = date.weekday().number_from_monday();
+
+        let mut localized = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('a') => localized.push_str(&name("weekday-short", weekday)),
+                Some('A') => localized.push_str(&name("weekday", weekday)),
+                Some('b') => localized.push_str(&name("month-short", date.month())),
+                Some('B') => localized.push_str(&name("month", date.month())),
+                Some(other) => {
+                    localized.push('%');
+                    localized.push(other);
+                }
+                None => localized.push('%'),
+            }
+        }
+        localized
+    }
+}
+
+/// Catalog of the printed messages, English until [init] is called
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Select the language of the printed messages, once at start
+///
+/// # Arguments
+///
+/// * `language` - Language of the messages
+pub fn init(language: Language) {
+    let _ = CATALOG.set(Catalog::new(language));
+}
+
+/// Catalog of the printed messages
+pub fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog::new(Language::English))
+}
+
+/// Translate the names of a date format, see [Catalog::localize]
+pub fn localize(format: &str, date: &impl Datelike) -> String {
+    catalog().localize(format, date)
+}
+
+/// Format a printed message, e.g. `tr!("checked-in", time = "08:00")`
+///
+/// The values are converted into Fluent values: strings and numbers.
+#[macro_export]
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::catalog().message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::catalog().message($id, Some(&args))
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Catalog, FluentArgs, Language};
+    use chrono::NaiveDate;
+    use std::collections::BTreeSet;
+
+    /// Identifiers of the messages of a language
+    fn ids(language: Language) -> BTreeSet<&'static str> {
+        language
+            .messages()
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+            .filter_map(|line| line.split_once(" ="))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn languages() {
+        assert_eq!("fr".parse(), Ok(Language::French));
+        assert_eq!("fr_CH.UTF-8".parse(), Ok(Language::French));
+        assert_eq!("en-US".parse(), Ok(Language::English));
+        assert_eq!("C".parse(), Ok(Language::English));
+        assert!("de_CH.UTF-8".parse::<Language>().is_err());
+
+        // Every message is translated
+        assert_eq!(ids(Language::French), ids(Language::English));
+    }
+
+    #[test]
+    fn messages() {
+        let english = Catalog::new(Language::English);
+        let french = Catalog::new(Language::French);
+
+        let mut args = FluentArgs::new();
+        args.set("time", "08:00");
+        assert_eq!(
+            english.message("checked-in", Some(&args)),
+            "Checked in at 08:00"
+        );
+        assert_eq!(french.message("checked-in", Some(&args)), "Arrivée à 08:00");
+        assert_eq!(english.message("no-such-message", None), "no-such-message");
+
+        let day = NaiveDate::from_ymd_opt(2024, 2, 12).unwrap();
+        assert_eq!(english.localize("%a %Y-%m-%d", &day), "Mon %Y-%m-%d");
+        assert_eq!(
+            day.format(&french.localize("%A %d %B %Y", &day))
+                .to_string(),
+            "lundi 12 février 2024"
+        );
+        assert_eq!(french.localize("%b %%a", &day), "fév %%a");
+    }
+}
@@ -2,6 +2,71 @@
 //!
 //! The central object is the [db::Stamp] structure that enables to create and query check-in or check-out
 //! entries and forms the work-log.
+//!
+//! The supported types are gathered in the [prelude].
 
+#[doc(hidden)]
+pub mod activity;
+#[doc(hidden)]
+pub mod agent;
+#[doc(hidden)]
+pub mod alias;
+#[doc(hidden)]
+pub mod allocation;
 pub mod app;
+#[doc(hidden)]
+pub mod chart;
+#[doc(hidden)]
+pub mod clipboard;
+#[doc(hidden)]
+pub mod compliance;
+pub mod config;
 pub mod db;
+#[cfg(feature = "dbus")]
+#[doc(hidden)]
+pub mod dbus;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod edit;
+#[doc(hidden)]
+pub mod export;
+pub mod format;
+#[doc(hidden)]
+pub mod hooks;
+pub mod i18n;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod ntp;
+#[cfg(feature = "postgres")]
+pub mod pg;
+#[doc(hidden)]
+pub mod pick;
+pub mod prelude;
+#[doc(hidden)]
+pub mod project;
+#[doc(hidden)]
+pub mod push;
+#[doc(hidden)]
+pub mod render;
+pub mod report;
+#[doc(hidden)]
+pub mod serve;
+#[doc(hidden)]
+pub mod site;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod statusbar;
+pub mod storage;
+#[doc(hidden)]
+pub mod sync;
+#[doc(hidden)]
+pub mod taskwarrior;
+#[doc(hidden)]
+pub mod timesheet;
+#[doc(hidden)]
+pub mod wifi;
@@ -0,0 +1,236 @@
+# Messages printed by wtime, see the i18n module
+#
+# The other languages translate these messages, the missing ones are printed
+# in English.
+
+## Names
+
+weekday = { $number ->
+    [1] Monday
+    [2] Tuesday
+    [3] Wednesday
+    [4] Thursday
+    [5] Friday
+    [6] Saturday
+   *[7] Sunday
+}
+weekday-short = { $number ->
+    [1] Mon
+    [2] Tue
+    [3] Wed
+    [4] Thu
+    [5] Fri
+    [6] Sat
+   *[7] Sun
+}
+month = { $number ->
+    [1] January
+    [2] February
+    [3] March
+    [4] April
+    [5] May
+    [6] June
+    [7] July
+    [8] August
+    [9] September
+    [10] October
+    [11] November
+   *[12] December
+}
+month-short = { $number ->
+    [1] Jan
+    [2] Feb
+    [3] Mar
+    [4] Apr
+    [5] May
+    [6] Jun
+    [7] Jul
+    [8] Aug
+    [9] Sep
+    [10] Oct
+    [11] Nov
+   *[12] Dec
+}
+direction-in = In
+direction-out = Out
+
+## Check-in and check-out
+
+checked-in = Checked in at { $time }
+checked-in-on = Checked in at { $time } on { $project }
+previous-closed = Closed the previous session at { $date }
+active-since = System active since { $time }, use `checkin --since-active` to check-in from then
+checked-out = Checked out at { $time }
+worked-session = You worked { $worked }
+allocated = Allocated { $worked } to { $target } ({ $percent }%)
+checkout-totals = Today: { $day } ({ $day_delta } to target), this week: { $week } ({ $week_delta } to target)
+switched = Checked out after { $worked }, checked in at { $time } on { $project }
+worked-today = You worked { $worked } today (since { $since })
+worked-week = You worked { $worked } this week (since { $since })
+watch-running = Session { $session }, today { $today }
+watch-stopped = Checked out, today { $today }
+bar-today = Today: { $worked }
+bar-week = This week: { $worked }
+bar-no-session = No session today
+bar-check-in = Check in
+bar-check-out = Check out
+bar-refresh = Refresh
+ask-close-previous = Still checked in since { $since }, close that session at [{ $suggested }]:
+ask-split = Split session across issues (e.g. `60% PROJ-1, 40% PROJ-7`):
+
+## Warnings
+
+warning = Warning: { $message }
+warning-clock-backwards = Warning: the new stamp at { $new } is dated before the last one at { $last }, check the system clock
+warning-clock-ahead = Warning: the system clock is { $seconds } seconds ahead of { $server }
+warning-clock-behind = Warning: the system clock is { $seconds } seconds behind { $server }
+clock-error = Checking the system clock: { $error }
+warning-day-overtime = Warning: today exceeds the daily target by { $over }
+warning-week-overtime = Warning: this week exceeds the weekly target by { $over }
+warning-budget = Warning: project { $project } used { $percent }% of its { $budget } budget, { $remaining } left
+warning-budget-exceeded = Warning: project { $project } exceeds its { $budget } budget by { $over }
+violation-long-day = { $worked } worked on { $day }, more than the { $limit } limit
+violation-short-rest = Only { $rest } rest between the check-out at { $checkout } and the check-in at { $checkin }, less than { $limit }
+violation-no-break = { $worked } worked without a break from { $start } to { $end }, more than { $limit }
+
+## Stamps
+
+answer-yes = y
+confirm-delete = Delete stamp { $id } ? [y/N]
+confirm-purge = Delete the stamps before { $before } for good ? [y/N]
+confirm-restore = Replace the work-log with { $file } ? [y/N]
+stamp-deleted = Stamp { $id } of { $date } deleted, see `wtime doctor` if a session is left open
+stamps-archived = { $count } stamp(s) before { $before } archived (see `report --archived`)
+stamps-purged = { $count } stamp(s) before { $before } deleted
+stamps-edited = { $inserted } stamp(s) inserted, { $updated } updated, { $deleted } deleted
+nothing-changed = Nothing changed
+stamps-found = { $count } stamp(s) found
+search-more = There may be more, see `--offset { $offset }`
+pick-stamp = stamp { $id }
+pick-search = Search stamps, e.g. `feb 12 acme` (nothing lists the last ones):
+pick-none = No stamp matches { $query }
+pick-number = Pick a number, search again or nothing to cancel:
+closed = Work-log closed until { $until }
+reopened = Closeout until { $until } removed, the work-log is open again up to the previous one
+adjusted = Adjusted { $day } by { $minutes } minutes ({ $reason })
+estimate-recorded = Estimated { $name } at { $estimate }
+estimate-removed = Removed the estimate of { $name }
+imported = Imported { $count } session(s)
+import-skipped = { $count } session(s) skipped, overlapping worked time or in the future
+chain-enabled = Stamps are now hash-chained, they can no longer be modified
+chain-valid = Hash chain is valid ({ $count } stamps)
+audit-valid = Audit log is valid ({ $count } records)
+
+## Backup and synchronization
+
+backup-saved = Work-log saved to { $file }
+backup-restored = Work-log restored from { $file }
+sync-exported = { $count } stamp(s) exported to { $file }
+sync-pulled = Pulled { $count } stamp(s): { $added } added, { $updated } updated
+sync-pushed = Pushed { $count } stamp(s): { $added } added, { $updated } updated
+sync-merged = { $added } stamp(s) added, { $updated } updated, { $known } already known
+sync-renumbered = { $count } stamp(s) renumbered to stay in date order
+
+## Doctor
+
+problem-missing-checkout = Stamp { $id } (check-in at { $checkin }) has no check-out before the check-in at { $next }
+problem-missing-checkin = Stamp { $id } (check-out at { $checkout }) has no check-in since the check-out at { $previous }
+problem-open-session = Stamp { $id } (check-in at { $checkin }) is still open
+problem-over-midnight = Stamp { $id } (check-in at { $checkin }) runs past midnight until the check-out at { $checkout }
+doctor-ok = No problem found
+doctor-split = { $count } session(s) split at midnight
+doctor-explain = Run `wtime doctor --explain` for the commands fixing them
+doctor-resolution = Keep it, cap it at a time or insert a break? [k/c/b]
+doctor-checkout-time = Check-out time:
+doctor-break-start = Break start time:
+doctor-break-end = Break end time:
+doctor-checked-in = Checked in at { $date } (stamp { $id })
+doctor-checked-out = Checked out at { $date } (stamp { $id })
+doctor-unsolved = Run the commands above to fix the other problems
+
+## Reports
+
+total = Total
+week = Week { $week }
+start = Start
+end = End
+breaks = Breaks
+overtime = Overtime
+calendar-off = off
+calendar-total = Total: { $total } ({ $delta } to target)
+stats-title = Your { $year } in wtime
+stats-total = { $total } worked over { $days } days
+stats-busiest-week = Busiest week: { $week } with { $total }
+stats-average-day = Average day: from { $start } to { $end }
+stats-longest = Longest session: { $worked } on { $day }
+stats-days-off = Days off taken: { $count }
+habits-title = Your habits over the last { $weeks } weeks
+habits-current-streak = Current streak: { $days } day(s) meeting the target
+habits-longest-streak = Longest streak: { $days } day(s), from { $from } to { $to }
+habits-record = That is your best streak, keep it up!
+habits-earliest = Earliest check-in: { $time } on { $day }
+habits-latest = Latest check-in: { $time } on { $day }
+habits-steady-weeks = Steady weeks: { $count } of { $weeks } within 10% of the target
+trend-week-ending = Week ending
+trend-days = { $days } days
+trend-rising = rising
+trend-falling = falling
+trend-steady = steady
+trend-creeping = Creeping overtime: both averages are above the daily target of { $target }
+report-written = Report written to { $file }
+report-copied = Report copied to the clipboard
+budgets = Budgets
+budget-remaining = { $used } of { $budget }, { $remaining } left
+budget-exceeded = { $used } of { $budget }, exceeded by { $over }
+no-estimates = No estimate recorded, add one with `wtime estimate <NAME> <DURATION>`
+estimate-project = Project
+estimate-tag = Tag
+estimate = Estimate
+actual = Actual
+variance = Variance
+chart-written = Chart written to { $file }
+timesheet-written = Timesheet of { $month } ({ $total }) written to { $file }
+site-written = Site with { $count } month(s) written to { $dir }
+
+## Integrations
+
+taskwarrior-checked-out = wtime: checked out, { $worked } worked
+taskwarrior-checked-in = wtime: checked in at { $time } on '{ $task }'
+pushed-jira = Pushed { $count } worklog(s) to { $issue } ({ $total })
+pushed-gitlab = Pushed { $count } spent time(s) to { $issue } ({ $total })
+api-serving = Serving the API on { $url }
+metrics-serving = Serving the metrics on { $url }
+dbus-serving = Serving { $name } on the session bus
+wifi-watching = Watching { $count } Wi-Fi network(s)
+wifi-error = Reading the Wi-Fi network: { $error }
+wifi-open-session = Checked in since { $date }, run `wtime doctor --interactive` to fix it
+idle-watching = Checking out after { $after } without input or once locked
+idle-error = Reading the user input: { $error }
+idle-checked-out = Away, checked out at { $time }
+daemon-installed = Daemon started at every logon: { $location }
+daemon-uninstalled = Daemon not started at logon anymore
+
+## Installation
+
+info-version = version
+info-features = features
+info-database = database
+info-database-server = PostgreSQL server
+info-file-size = file size
+info-bytes = { $bytes } bytes
+info-schema-version = schema version
+info-stamps = stamps
+info-first-stamp = first stamp
+info-last-stamp = last stamp
+info-timezone = timezone
+info-language = language
+info-config-file = config file
+info-config-missing = { $file } (missing, defaults used)
+info-daily-target = daily target
+info-weekly-target = weekly target
+info-month-start-day = month start day
+info-set = set
+info-unset = unset
+info-hooks = hooks
+info-wifi-rules = wifi rules
+info-aliases = aliases
@@ -0,0 +1,263 @@
+# Messages de wtime en français, voir en.ftl
+
+## Noms
+
+weekday = { $number ->
+    [1] lundi
+    [2] mardi
+    [3] mercredi
+    [4] jeudi
+    [5] vendredi
+    [6] samedi
+   *[7] dimanche
+}
+weekday-short = { $number ->
+    [1] lun
+    [2] mar
+    [3] mer
+    [4] jeu
+    [5] ven
+    [6] sam
+   *[7] dim
+}
+month = { $number ->
+    [1] janvier
+    [2] février
+    [3] mars
+    [4] avril
+    [5] mai
+    [6] juin
+    [7] juillet
+    [8] août
+    [9] septembre
+    [10] octobre
+    [11] novembre
+   *[12] décembre
+}
+month-short = { $number ->
+    [1] jan
+    [2] fév
+    [3] mar
+    [4] avr
+    [5] mai
+    [6] jun
+    [7] jul
+    [8] aoû
+    [9] sep
+    [10] oct
+    [11] nov
+   *[12] déc
+}
+direction-in = Arrivée
+direction-out = Départ
+
+## Arrivée et départ
+
+checked-in = Arrivée à { $time }
+checked-in-on = Arrivée à { $time } sur { $project }
+previous-closed = Session précédente fermée à { $date }
+active-since = Système actif depuis { $time }, `checkin --since-active` fait arriver à cette heure
+checked-out = Départ à { $time }
+worked-session = Vous avez travaillé { $worked }
+allocated = { $worked } attribuées à { $target } ({ $percent } %)
+checkout-totals = Aujourd'hui : { $day } ({ $day_delta } sur l'objectif), cette semaine : { $week } ({ $week_delta } sur l'objectif)
+switched = Départ après { $worked }, arrivée à { $time } sur { $project }
+worked-today = Vous avez travaillé { $worked } aujourd'hui (depuis { $since })
+worked-week = Vous avez travaillé { $worked } cette semaine (depuis { $since })
+watch-running = Session { $session }, aujourd'hui { $today }
+watch-stopped = Pas de session, aujourd'hui { $today }
+bar-today = Aujourd'hui : { $worked }
+bar-week = Cette semaine : { $worked }
+bar-no-session = Pas de session aujourd'hui
+bar-check-in = Arrivée
+bar-check-out = Départ
+bar-refresh = Actualiser
+ask-close-previous = Toujours arrivé depuis { $since }, fermer cette session à [{ $suggested }] :
+ask-split = Répartir la session entre tickets (p. ex. `60% PROJ-1, 40% PROJ-7`) :
+
+## Avertissements
+
+warning = Attention : { $message }
+warning-clock-backwards = Attention : le nouveau pointage de { $new } est antérieur au dernier, de { $last }, vérifiez l'horloge du système
+warning-clock-ahead = Attention : l'horloge du système avance de { $seconds } secondes sur { $server }
+warning-clock-behind = Attention : l'horloge du système retarde de { $seconds } secondes sur { $server }
+clock-error = Vérification de l'horloge du système : { $error }
+warning-day-overtime = Attention : aujourd'hui dépasse l'objectif journalier de { $over }
+warning-week-overtime = Attention : cette semaine dépasse l'objectif hebdomadaire de { $over }
+warning-budget = Attention : le projet { $project } a consommé { $percent } % de son budget de { $budget }, il reste { $remaining }
+warning-budget-exceeded = Attention : le projet { $project } dépasse son budget de { $budget } de { $over }
+violation-long-day = { $worked } travaillées le { $day }, plus que la limite de { $limit }
+violation-short-rest = Seulement { $rest } de repos entre le départ de { $checkout } et l'arrivée de { $checkin }, moins que { $limit }
+violation-no-break = { $worked } travaillées sans pause de { $start } à { $end }, plus que { $limit }
+
+## Pointages
+
+answer-yes = o
+confirm-delete = Supprimer le pointage { $id } ? [o/N]
+confirm-purge = Supprimer définitivement les pointages avant le { $before } ? [o/N]
+confirm-restore = Remplacer le journal par { $file } ? [o/N]
+stamp-deleted = Pointage { $id } du { $date } supprimé, voir `wtime doctor` si une session reste ouverte
+stamps-archived = { $count ->
+    [one] { $count } pointage avant le { $before } archivé
+   *[other] { $count } pointages avant le { $before } archivés
+} (voir `report --archived`)
+stamps-purged = { $count ->
+    [one] { $count } pointage avant le { $before } supprimé
+   *[other] { $count } pointages avant le { $before } supprimés
+}
+stamps-edited = Pointages : { $inserted } ajoutés, { $updated } modifiés, { $deleted } supprimés
+nothing-changed = Rien n'a changé
+stamps-found = { $count ->
+    [one] { $count } pointage trouvé
+   *[other] { $count } pointages trouvés
+}
+search-more = Il peut y en avoir d'autres, voir `--offset { $offset }`
+pick-stamp = pointage { $id }
+pick-search = Chercher des pointages, p. ex. `fév 12 acme` (rien liste les derniers) :
+pick-none = Aucun pointage ne correspond à { $query }
+pick-number = Choisir un numéro, chercher à nouveau ou rien pour annuler :
+closed = Journal fermé jusqu'au { $until }
+reopened = Fermeture jusqu'au { $until } supprimée, le journal est rouvert jusqu'à la précédente
+adjusted = { $day } ajusté de { $minutes } minutes ({ $reason })
+estimate-recorded = { $name } estimé à { $estimate }
+estimate-removed = Estimation de { $name } supprimée
+imported = { $count ->
+    [one] { $count } session importée
+   *[other] { $count } sessions importées
+}
+import-skipped = { $count ->
+    [one] { $count } session ignorée, chevauchant du temps travaillé ou dans le futur
+   *[other] { $count } sessions ignorées, chevauchant du temps travaillé ou dans le futur
+}
+chain-enabled = Les pointages sont maintenant chaînés, ils ne peuvent plus être modifiés
+chain-valid = La chaîne est valide ({ $count } pointages)
+audit-valid = Le journal d'audit est valide ({ $count } enregistrements)
+
+## Sauvegarde et synchronisation
+
+backup-saved = Journal sauvegardé dans { $file }
+backup-restored = Journal restauré depuis { $file }
+sync-exported = { $count ->
+    [one] { $count } pointage exporté dans { $file }
+   *[other] { $count } pointages exportés dans { $file }
+}
+sync-pulled = { $count } pointage(s) reçu(s) : { $added } ajouté(s), { $updated } modifié(s)
+sync-pushed = { $count } pointage(s) envoyé(s) : { $added } ajouté(s), { $updated } modifié(s)
+sync-merged = Pointages : { $added } ajouté(s), { $updated } modifié(s), { $known } déjà connu(s)
+sync-renumbered = { $count ->
+    [one] { $count } pointage renuméroté pour rester dans l'ordre des dates
+   *[other] { $count } pointages renumérotés pour rester dans l'ordre des dates
+}
+
+## Diagnostic
+
+problem-missing-checkout = Le pointage { $id } (arrivée à { $checkin }) n'a pas de départ avant l'arrivée à { $next }
+problem-missing-checkin = Le pointage { $id } (départ à { $checkout }) n'a pas d'arrivée depuis le départ à { $previous }
+problem-open-session = Le pointage { $id } (arrivée à { $checkin }) est toujours ouvert
+problem-over-midnight = Le pointage { $id } (arrivée à { $checkin }) dépasse minuit jusqu'au départ à { $checkout }
+doctor-ok = Aucun problème trouvé
+doctor-split = { $count ->
+    [one] { $count } session coupée à minuit
+   *[other] { $count } sessions coupées à minuit
+}
+doctor-explain = Lancez `wtime doctor --explain` pour les commandes qui les corrigent
+doctor-resolution = La garder (k), la couper à une heure (c) ou insérer une pause (b) ? [k/c/b]
+doctor-checkout-time = Heure de départ :
+doctor-break-start = Début de la pause :
+doctor-break-end = Fin de la pause :
+doctor-checked-in = Arrivée à { $date } (pointage { $id })
+doctor-checked-out = Départ à { $date } (pointage { $id })
+doctor-unsolved = Lancez les commandes ci-dessus pour corriger les autres problèmes
+
+## Rapports
+
+total = Total
+week = Sem. { $week }
+start = Début
+end = Fin
+breaks = Pauses
+overtime = Écart
+calendar-off = congé
+calendar-total = Total : { $total } ({ $delta } sur l'objectif)
+stats-title = Votre année { $year } dans wtime
+stats-total = { $total } travaillées sur { $days } jours
+stats-busiest-week = Semaine la plus chargée : { $week } avec { $total }
+stats-average-day = Journée moyenne : de { $start } à { $end }
+stats-longest = Plus longue session : { $worked } le { $day }
+stats-days-off = Jours de congé pris : { $count }
+habits-title = Vos habitudes des { $weeks } dernières semaines
+habits-current-streak = { $days ->
+    [one] Série en cours : { $days } jour atteignant l'objectif
+   *[other] Série en cours : { $days } jours atteignant l'objectif
+}
+habits-longest-streak = { $days ->
+    [one] Plus longue série : { $days } jour, du { $from } au { $to }
+   *[other] Plus longue série : { $days } jours, du { $from } au { $to }
+}
+habits-record = C'est votre meilleure série, continuez !
+habits-earliest = Arrivée la plus matinale : { $time } le { $day }
+habits-latest = Arrivée la plus tardive : { $time } le { $day }
+habits-steady-weeks = Semaines régulières : { $count } sur { $weeks } à moins de 10 % de l'objectif
+trend-week-ending = Semaine au
+trend-days = { $days } jours
+trend-rising = en hausse
+trend-falling = en baisse
+trend-steady = stable
+trend-creeping = Heures supplémentaires rampantes : les deux moyennes dépassent l'objectif journalier de { $target }
+report-written = Rapport écrit dans { $file }
+report-copied = Rapport copié dans le presse-papiers
+budgets = Budgets
+budget-remaining = { $used } sur { $budget }, reste { $remaining }
+budget-exceeded = { $used } sur { $budget }, dépassé de { $over }
+no-estimates = Aucune estimation, ajoutez-en une avec `wtime estimate <NOM> <DURÉE>`
+estimate-project = Projet
+estimate-tag = Étiquette
+estimate = Estimé
+actual = Réel
+variance = Écart
+chart-written = Graphique écrit dans { $file }
+timesheet-written = Feuille de temps de { $month } ({ $total }) écrite dans { $file }
+site-written = Site de { $count } mois écrit dans { $dir }
+
+## Intégrations
+
+taskwarrior-checked-out = wtime : départ, { $worked } travaillées
+taskwarrior-checked-in = wtime : arrivée à { $time } sur '{ $task }'
+pushed-jira = { $count } worklog(s) envoyé(s) à { $issue } ({ $total })
+pushed-gitlab = { $count } temps passé(s) envoyé(s) à { $issue } ({ $total })
+api-serving = API servie sur { $url }
+metrics-serving = Métriques servies sur { $url }
+dbus-serving = { $name } servi sur le bus de session
+wifi-watching = Surveillance de { $count } réseau(x) Wi-Fi
+wifi-error = Lecture du réseau Wi-Fi : { $error }
+wifi-open-session = Arrivé depuis { $date }, lancez `wtime doctor --interactive` pour corriger
+idle-watching = Départ après { $after } sans saisie ou au verrouillage
+idle-error = Lecture de la saisie : { $error }
+idle-checked-out = Absent, départ à { $time }
+daemon-installed = Démon lancé à chaque ouverture de session : { $location }
+daemon-uninstalled = Démon plus lancé à l'ouverture de session
+
+## Installation
+
+info-version = version
+info-features = fonctionnalités
+info-database = base de données
+info-database-server = serveur PostgreSQL
+info-file-size = taille du fichier
+info-bytes = { $bytes } octets
+info-schema-version = version du schéma
+info-stamps = pointages
+info-first-stamp = premier pointage
+info-last-stamp = dernier pointage
+info-timezone = fuseau horaire
+info-language = langue
+info-config-file = configuration
+info-config-missing = { $file } (absent, valeurs par défaut)
+info-daily-target = objectif journalier
+info-weekly-target = objectif hebdomadaire
+info-month-start-day = premier jour du mois
+info-set = défini
+info-unset = non défini
+info-hooks = commandes
+info-wifi-rules = règles Wi-Fi
+info-aliases = alias
@@ -1,14 +1,37 @@
-use anyhow::{Context, Result};
-use clap::{command, Command};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use clap::builder::NonEmptyStringValueParser;
+use clap::{command, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
 
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, Level};
+use tracing_subscriber::fmt::format::FmtSpan;
 
 #[cfg(not(debug_assertions))]
 use directories::ProjectDirs;
-#[cfg(not(debug_assertions))]
-use std::fs;
 
+use wtime::alias;
+use wtime::allocation;
 use wtime::app::App;
+use wtime::chart;
+use wtime::config::Config;
+use wtime::db::{InOut, Source, StampFilter, StampId};
+use wtime::edit;
+use wtime::export;
+use wtime::format::{self, DurationFormat, Durations};
+use wtime::hooks::{self, Event};
+use wtime::i18n::{self, Language};
+use wtime::import;
+#[cfg(feature = "postgres")]
+use wtime::pg::PgStorage;
+use wtime::render;
+use wtime::report;
+use wtime::statusbar::StatusFormat;
+use wtime::storage::Storage;
+use wtime::timesheet;
+use wtime::tr;
 
 #[cfg(not(debug_assertions))]
 fn get_db_file() -> Result<PathBuf> {
@@ -26,22 +49,1542 @@ fn get_db_file() -> Result<PathBuf> {
     Ok(PathBuf::from("test.sqlite"))
 }
 
+#[cfg(not(debug_assertions))]
+fn get_config_file() -> Result<PathBuf> {
+    let dirs =
+        ProjectDirs::from("", "", env!("CARGO_PKG_NAME")).context("Error getting config dir")?;
+    Ok(dirs.config_dir().join("config.toml"))
+}
+
+#[cfg(debug_assertions)]
+fn get_config_file() -> Result<PathBuf> {
+    Ok(PathBuf::from("test.toml"))
+}
+
+/// Exit status of `checkout --exit-code` when a target is exceeded
+const EXIT_OVERTIME: i32 = 3;
+
+/// Parse a `YYYY-MM-DD` date given on the command line
+fn parse_date(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").context("Expected YYYY-MM-DD")
+}
+
+/// Get a date argument, see [parse_date], as the beginning of that day in
+/// the configured timezone
+fn date_arg<S: Storage>(app: &App<S>, sub: &ArgMatches, name: &str) -> Option<DateTime<Utc>> {
+    sub.get_one::<NaiveDate>(name)
+        .map(|day| app.start_of_day(*day))
+}
+
+/// Parse a duration given on the command line, e.g. `40h` or `1h30m`
+fn parse_duration(s: &str) -> Result<Duration> {
+    allocation::parse_duration(s).context("Expected a duration like 40h or 1h30m")
+}
+
+/// Parse a `YYYY-MM` month given on the command line, as its first day
+fn parse_month(s: &str) -> Result<NaiveDate> {
+    report::CALENDAR_MONTH
+        .parse(s)
+        .ok()
+        .context("Expected YYYY-MM")
+}
+
+/// Parse a `YYYY-Www` ISO week given on the command line, as its Monday
+fn parse_week(s: &str) -> Result<NaiveDate> {
+    report::Period::Week
+        .parse(s)
+        .ok()
+        .context("Expected YYYY-Www")
+}
+
+/// Date window of a report, bounds left to the defaults when None
+type Window = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Date window of a report, from the `--week`, `--month` or `--range`
+/// selector, or else the `--from` and `--to` dates
+fn report_window<S: Storage>(app: &App<S>, sub: &ArgMatches) -> Result<Window> {
+    let midnight = |day: NaiveDate| Some(app.start_of_day(day));
+    if let Some(monday) = sub.get_one::<NaiveDate>("week") {
+        return Ok((
+            midnight(*monday),
+            midnight(report::Period::Week.end(*monday)),
+        ));
+    }
+    if let Some(month) = sub.get_one::<NaiveDate>("month") {
+        // Months may start on another day than the 1st
+        let period = app.month();
+        let start = period.of_month(*month);
+        return Ok((midnight(start), midnight(period.end(start))));
+    }
+    if let Some(name) = sub.get_one::<String>("range") {
+        let range = app.range(name)?;
+        return Ok((midnight(range.from), midnight(range.to)));
+    }
+    Ok((date_arg(app, sub, "from"), date_arg(app, sub, "to")))
+}
+
+/// Argument restricting the reported totals to the time allocated to a project
+fn project_filter() -> Arg {
+    Arg::new("project")
+        .long("project")
+        .help("Only count the time allocated to this project")
+}
+
+/// Names of the entry points a stamp can be made through, see [Source]
+const SOURCES: [&str; 5] = ["manual", "daemon", "api", "import", "hook"];
+
+/// Argument restricting the reported sessions to the ones stamped through an
+/// entry point, see [Source]
+fn source_filter() -> Arg {
+    Arg::new("source")
+        .long("source")
+        .help("Only count the sessions stamped this way")
+        .value_parser(SOURCES)
+}
+
+/// Time between two readings of `wtime daemon`
+fn daemon_interval() -> Arg {
+    Arg::new("interval")
+        .long("interval")
+        .value_name("SECONDS")
+        .help("Time between two readings of the network and input")
+        .value_parser(value_parser!(i64).range(1..))
+        .default_value("30")
+}
+
+/// Address `wtime daemon` answers the Prometheus metrics on
+fn daemon_metrics() -> Arg {
+    Arg::new("metrics")
+        .long("metrics")
+        .value_name("ADDRESS")
+        .help("Answer the Prometheus metrics on /metrics of this address, e.g. 127.0.0.1:9184")
+}
+
+/// Only report the sessions ended since the last report or export of the
+/// same format with this flag, see [App::last_export]
+fn since_last_export() -> Arg {
+    Arg::new("since-last-export")
+        .long("since-last-export")
+        .help("Only the sessions since the last time with this flag (and format), e.g. for periodic submissions")
+        .action(ArgAction::SetTrue)
+}
+
+/// Give the durations in decimal hours, see the format module
+fn decimal_hours() -> Arg {
+    Arg::new("decimal-hours")
+        .long("decimal-hours")
+        .value_name("DIGITS")
+        .help("Give the durations in decimal hours, with the digits of `hours_precision` unless given")
+        .num_args(0..=1)
+        .require_equals(true)
+        .value_parser(value_parser!(u8).range(0..=6))
+}
+
+/// Ask a question on the terminal and read the answer line
+fn prompt(question: &str) -> Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Ask a yes/no question on the terminal, no being the default
+fn confirm(question: &str) -> Result<bool> {
+    let answer = prompt(&format!("{} ", question))?.to_lowercase();
+    Ok(answer == "y" || answer == tr!("answer-yes"))
+}
+
+/// Print the log events on stderr, the more `verbose` the more detailed
+fn init_logging(verbose: u8) {
+    let (level, span_events) = match verbose {
+        0 => (Level::WARN, FmtSpan::NONE),
+        1 => (Level::INFO, FmtSpan::NONE),
+        // Closing spans report how long the commands and queries took
+        2 => (Level::DEBUG, FmtSpan::CLOSE),
+        _ => (Level::TRACE, FmtSpan::CLOSE),
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(span_events)
+        .with_writer(io::stderr)
+        .init();
+}
+
 fn main() -> Result<()> {
     // Build argument parser
-    let matches = command!()
-        .subcommand(Command::new("checkin").about("Start counting working time"))
-        .subcommand(Command::new("checkout").about("Stop counting work time and display count"))
-        .get_matches();
+    let cli = command!()
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Log what is done on stderr, repeat for more details (e.g. -vv)")
+                .action(ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .value_name("NAME")
+                .help("Track the time of this person, created on first use")
+                .value_parser(NonEmptyStringValueParser::new())
+                .global(true),
+        )
+        .subcommand(
+            Command::new("checkin")
+                .about("Start counting working time")
+                .arg(
+                    Arg::new("since-active")
+                        .long("since-active")
+                        .help("Check-in from when the system became active today (booted or back from idle)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("project")
+                        .long("project")
+                        .value_name("NAME")
+                        .help("Allocate the session to a project, detected from the git repository if `detect_project` is set"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("NAME")
+                        .help("Tag the session, e.g. meeting or billable (repeatable)")
+                        .value_parser(NonEmptyStringValueParser::new())
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("ref")
+                        .long("ref")
+                        .value_name("TICKET")
+                        .help("Reference a ticket the session is about, e.g. ABC-123, its time being pushed there (repeatable)")
+                        .value_parser(NonEmptyStringValueParser::new())
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Check-in even if the last stamp is dated later, e.g. by a wrong clock")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("close-previous-at")
+                        .long("close-previous-at")
+                        .value_name("TIME")
+                        .help("Close the session left open at HH:MM (or YYYY-MM-DD HH:MM) first, asked if omitted on a terminal"),
+                ),
+        )
+        .subcommand(
+            Command::new("checkout")
+                .about("Stop counting work time and display count")
+                .arg(
+                    Arg::new("split")
+                        .long("split")
+                        .value_name("SPEC")
+                        .num_args(0..=1)
+                        .help("Split the session across issues, e.g. \"60% PROJ-1, 40% PROJ-7\" (prompted if omitted)"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Check-out even if the check-in is dated later, e.g. by a wrong clock")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("exit-code")
+                        .long("exit-code")
+                        .help("Exit with status 3 when the day or week target is exceeded beyond `overtime_threshold`")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("switch")
+                .about("Check-out and check-in on another project at once")
+                .arg(
+                    Arg::new("project")
+                        .required(true)
+                        .help("Project of the new session"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Switch even if the check-in is dated later, e.g. by a wrong clock")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("close")
+                .about("Close the work-log before given date, making it immutable")
+                .arg(
+                    Arg::new("date")
+                        .help("First day that stays open (YYYY-MM-DD)")
+                        .required(true)
+                        .value_parser(parse_date),
+                ),
+        )
+        .subcommand(
+            Command::new("reopen")
+                .about("Remove the latest closeout, reopening its period (audited)"),
+        )
+        .subcommand(
+            Command::new("adjust")
+                .about("Book a correction of the worked time")
+                .arg(
+                    Arg::new("minutes")
+                        .help("Minutes to add, negative to remove")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(i64)),
+                )
+                .arg(
+                    Arg::new("reason")
+                        .short('r')
+                        .long("reason")
+                        .help("Why the correction is needed")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("for")
+                        .long("for")
+                        .help("Day the correction applies to (YYYY-MM-DD)")
+                        .value_parser(parse_date),
+                ),
+        )
+        .subcommand(
+            Command::new("estimate")
+                .about(
+                    "Record the expected time of a project or tagged task, \
+                    or list the estimates against the actual time",
+                )
+                .arg(
+                    Arg::new("name")
+                        .help("Project, or tag with --tag")
+                        .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    Arg::new("duration")
+                        .help("Expected time, e.g. 40h or 1h30m")
+                        .value_parser(parse_duration),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .help("Estimate the sessions tagged NAME rather than a project")
+                        .requires("name")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("remove")
+                        .long("remove")
+                        .help("Remove the estimate")
+                        .requires("name")
+                        .conflicts_with("duration")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Access the log of database modifications")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("export")
+                        .about("Export the hash-chained audit log")
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .help("Only export modifications from this day (YYYY-MM-DD)")
+                                .value_parser(parse_date),
+                        )
+                        .arg(
+                            Arg::new("to")
+                                .long("to")
+                                .help("Only export modifications until this day, included (YYYY-MM-DD)")
+                                .value_parser(parse_date),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .help("Output format")
+                                .value_parser(["csv", "json"])
+                                .default_value("csv"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check the stored hashes of the audit log, reporting the first mismatch"),
+                ),
+        )
+        .subcommand(
+            Command::new("chain")
+                .about("Manage the hash-chained tamper-evident mode")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("enable").about("Hash-chain the stamps, making them immutable"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check the integrity of the work-log")
+                .arg_required_else_help(true)
+                .arg(
+                    Arg::new("chain")
+                        .long("chain")
+                        .help("Check that no stamp was modified since hash-chained")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Import sessions exported by another tool")
+                .arg(
+                    Arg::new("file")
+                        .help("File to import, standard input if omitted")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .alias("from")
+                        .help("Input format")
+                        .value_parser(["json", "toggl", "timewarrior", "hamster", "ics", "xlsx"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::new("ics")
+                        .long("ics")
+                        .value_name("FILE|URL")
+                        .help("Import the events of a calendar file, or of a calendar URL (e.g. CalDAV)")
+                        .conflicts_with_all(["file", "format"]),
+                )
+                .arg(
+                    Arg::new("map")
+                        .long("map")
+                        .help("Where to find the session fields, e.g. '{start: .begin, end: .finish, project: .client}'")
+                        .value_parser(|s: &str| s.parse::<import::Mapping>())
+                        .default_value(import::DEFAULT_MAP),
+                )
+                .arg(
+                    Arg::new("columns")
+                        .long("columns")
+                        .help("Columns of the session fields in a workbook, by letter or header, e.g. '{date: A, start: B, end: C, project: Client}'")
+                        .value_parser(|s: &str| s.parse::<import::Columns>())
+                        .default_value(import::DEFAULT_COLUMNS),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export the worked sessions for another tool")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["timewarrior", "harvest", "kimai", "org", "xlsx"])
+                        .default_value("timewarrior"),
+                )
+                .arg(decimal_hours())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("First day of the sessions (YYYY-MM-DD), defaults to the beginning of the week")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("Export sessions until this day, excluded (YYYY-MM-DD)")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("archived")
+                        .long("archived")
+                        .help("Include the archived stamps")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(since_last_export().conflicts_with("from")),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Find stamps among the whole work-log")
+                .visible_alias("list")
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Only stamps from this day (YYYY-MM-DD)")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("Only stamps before this day (YYYY-MM-DD)")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("direction")
+                        .long("direction")
+                        .help("Only the check-ins or only the check-outs")
+                        .value_parser(["in", "out"]),
+                )
+                .arg(
+                    Arg::new("project")
+                        .long("project")
+                        .help("Only the sessions allocated to this project"),
+                )
+                .arg(
+                    Arg::new("note-contains")
+                        .long("note-contains")
+                        .help("Only the sessions having a tag containing this text"),
+                )
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("Only the stamps made this way")
+                        .value_parser(SOURCES),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .help("List at most this number of stamps")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .help("Skip this number of stamps first")
+                        .value_parser(value_parser!(usize))
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            Command::new("week")
+                .about("Display the timesheet grid of a week")
+                .arg(project_filter())
+                .arg(source_filter())
+                .arg(
+                    Arg::new("week")
+                        .long("week")
+                        .help("ISO week (YYYY-Www), defaults to the current week")
+                        .value_parser(parse_week),
+                ),
+        )
+        .subcommand(
+            Command::new("month")
+                .about("Display the calendar of a month with the worked hours")
+                .arg(project_filter())
+                .arg(source_filter())
+                .arg(
+                    Arg::new("month")
+                        .long("month")
+                        .help("Month (YYYY-MM), defaults to the current month")
+                        .value_parser(parse_month),
+                ),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Show the running session timer and today's total, refreshed every second"),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show the worked time of the day and week, as without command")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Print for a menu or status bar: `xbar` for the xbar and SwiftBar plugins, `waybar` for the Waybar and i3status-rust custom modules")
+                        .value_parser(["text", "xbar", "waybar"])
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Sum up a year of work")
+                .arg(project_filter())
+                .arg(
+                    Arg::new("year")
+                        .long("year")
+                        .help("Year to sum up, defaults to the current year")
+                        .value_parser(value_parser!(i32).range(1970..=9999)),
+                )
+                .arg(
+                    Arg::new("trend")
+                        .long("trend")
+                        .help("Show the 7 and 30 days rolling averages of the daily hours instead")
+                        .conflicts_with("year")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("habits")
+                        .long("habits")
+                        .help("Show the streaks of days meeting the target, the earliest and latest check-ins and the steady weeks instead")
+                        .conflicts_with_all(["year", "trend"])
+                        .action(ArgAction::SetTrue),
+                )
+                .group(ArgGroup::new("recent").args(["trend", "habits"]))
+                .arg(
+                    Arg::new("weeks")
+                        .long("weeks")
+                        .help("Number of weeks of the trend or of the habits")
+                        .requires("recent")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .default_value("8"),
+                ),
+        )
+        .subcommand(
+            Command::new("chart")
+                .about("Draw the worked hours per day as an SVG image")
+                .arg(project_filter())
+                .arg(source_filter())
+                .arg(
+                    Arg::new("range")
+                        .long("range")
+                        .help("Days to draw")
+                        .value_parser(["week", "month", "year"])
+                        .default_value("month"),
+                )
+                .arg(
+                    Arg::new("kind")
+                        .long("kind")
+                        .help("Kind of chart")
+                        .value_parser(["bar", "line"])
+                        .default_value("bar"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("SVG file to write")
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value("hours.svg"),
+                ),
+        )
+        .subcommand(
+            Command::new("timesheet")
+                .about("Write the timesheet of a month, to sign off")
+                .arg(project_filter())
+                .arg(source_filter())
+                .arg(
+                    Arg::new("month")
+                        .long("month")
+                        .help("Month of the timesheet (YYYY-MM), defaults to the current month")
+                        .value_parser(parse_month),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["pdf"])
+                        .default_value("pdf"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("File to write, defaults to timesheet-YYYY-MM.pdf")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Move old stamps out of the current work-log")
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .help("First day that stays in the current work-log (YYYY-MM-DD)")
+                        .required(true)
+                        .value_parser(parse_date),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Edit the stamps of a range in $EDITOR")
+                .arg(
+                    Arg::new("range")
+                        .long("range")
+                        .help("Stamps to edit")
+                        .value_parser(["today", "yesterday", "week", "month"])
+                        .default_value("today"),
+                )
+                .arg(
+                    Arg::new("pick")
+                        .long("pick")
+                        .help("Edit the day of a stamp picked with a fuzzy finder")
+                        .conflicts_with("range")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Delete a stamp")
+                .arg(
+                    Arg::new("id")
+                        .help("ID of the stamp to delete")
+                        .required_unless_present("pick")
+                        .value_parser(value_parser!(i64)),
+                )
+                .arg(
+                    Arg::new("pick")
+                        .long("pick")
+                        .help("Pick the stamp with a fuzzy finder")
+                        .conflicts_with("id")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Do not ask for confirmation")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Delete old stamps for good")
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .help("First day that stays in the work-log (YYYY-MM-DD)")
+                        .required(true)
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Do not ask for confirmation")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Save a consistent snapshot of the work-log")
+                .arg(
+                    Arg::new("file")
+                        .help("Backup file to create")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Replace the work-log by a backup, saving the current one")
+                .arg(
+                    Arg::new("file")
+                        .help("Backup file to restore")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Do not ask for confirmation")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Exchange stamps with the work-log of another device")
+                .arg_required_else_help(true)
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("remote")
+                        .long("remote")
+                        .value_name("URL")
+                        .help("Push and pull the stamps changed since the last sync with a `wtime serve` server"),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Write the stamps to a bundle file")
+                        .arg(
+                            Arg::new("file")
+                                .help("Bundle file to create, standard output if omitted")
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("merge")
+                        .about("Merge the stamps of a bundle made on another device")
+                        .arg(
+                            Arg::new("file")
+                                .help("Bundle file to merge")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Print the database, configuration and build in use, for bug reports"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Find the forgotten stamps in the work-log")
+                .arg(
+                    Arg::new("explain")
+                        .long("explain")
+                        .help("Print the commands fixing each problem")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .help("Split the sessions running past midnight into a session per day")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("interactive")
+                        .long("interactive")
+                        .short('i')
+                        .help("Ask how to fix a session left open")
+                        .conflicts_with_all(["explain", "fix"])
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("site")
+                .about("Publish the work-log as a static web site")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("build").about("Generate the HTML pages").arg(
+                        Arg::new("dir")
+                            .help("Output directory")
+                            .required(true)
+                            .value_parser(value_parser!(PathBuf)),
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Answer check-in, check-out, status and report requests over HTTP")
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .value_name("ADDRESS")
+                        .help("Address and port to listen on")
+                        .default_value("127.0.0.1:8080"),
+                )
+                .arg(
+                    Arg::new("cert")
+                        .long("cert")
+                        .value_name("FILE")
+                        .help("Certificate (PEM) to serve HTTPS, needs the `tls` feature")
+                        .requires("key")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("FILE")
+                        .help("Private key (PEM) of the certificate")
+                        .requires("cert")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("push")
+                .about("Log the worked time on issue trackers")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("jira")
+                        .about("Add the sessions not pushed yet as worklogs of a Jira issue")
+                        .arg(
+                            Arg::new("issue")
+                                .long("issue")
+                                .value_name("KEY")
+                                .help("Issue to log the time on, e.g. PROJ-1")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .help("First day of the sessions (YYYY-MM-DD), defaults to the beginning of the week")
+                                .value_parser(parse_date),
+                        )
+                        .arg(
+                            Arg::new("to")
+                                .long("to")
+                                .help("Push sessions until this day, excluded (YYYY-MM-DD)")
+                                .value_parser(parse_date),
+                        ),
+                )
+                .subcommand(
+                    Command::new("gitlab")
+                        .about("Add the sessions not pushed yet as spent time of a GitLab issue")
+                        .arg(
+                            Arg::new("issue")
+                                .long("issue")
+                                .value_name("URL")
+                                .help("Issue to log the time on, e.g. https://gitlab.com/group/project/-/issues/42")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .help("First day of the sessions (YYYY-MM-DD), defaults to the beginning of the week")
+                                .value_parser(parse_date),
+                        )
+                        .arg(
+                            Arg::new("to")
+                                .long("to")
+                                .help("Push sessions until this day, excluded (YYYY-MM-DD)")
+                                .value_parser(parse_date),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("hook")
+                .about("Check in and out from the hooks of other tools")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("taskwarrior")
+                        .about("Taskwarrior on-add/on-modify hook, reading the tasks on standard input"),
+                ),
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Check in and out as the Wi-Fi network changes or you leave, see `[[wifi]]` and `idle_after` in the configuration")
+                .args_conflicts_with_subcommands(true)
+                .arg(daemon_interval())
+                .arg(daemon_metrics())
+                .subcommand(
+                    Command::new("install")
+                        .about("Start the daemon at every logon, as a scheduled task on Windows or a systemd user service")
+                        .arg(daemon_interval())
+                        .arg(daemon_metrics()),
+                )
+                .subcommand(
+                    Command::new("uninstall").about("Stop starting the daemon at logon"),
+                ),
+        )
+        .subcommand(
+            Command::new("dbus")
+                .about("Answer check-in, check-out and status calls on the session D-Bus"),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("Display the worked time per day")
+                .arg(project_filter())
+                .arg(source_filter())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("First day of the report (YYYY-MM-DD), defaults to the beginning of the week")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("Report until this day, excluded (YYYY-MM-DD)")
+                        .value_parser(parse_date),
+                )
+                .arg(
+                    Arg::new("week")
+                        .long("week")
+                        .help("Report on an ISO week (YYYY-Www)")
+                        .conflicts_with_all(["from", "to"])
+                        .value_parser(parse_week),
+                )
+                .arg(
+                    Arg::new("month")
+                        .long("month")
+                        .help("Report on a month (YYYY-MM), starting on the `month_start_day` of the configuration")
+                        .conflicts_with_all(["from", "to", "week"])
+                        .value_parser(parse_month),
+                )
+                .arg(
+                    Arg::new("range")
+                        .long("range")
+                        .value_name("NAME")
+                        .help("Report on a date window of the `[ranges]` of the configuration")
+                        .conflicts_with_all(["from", "to", "week", "month"]),
+                )
+                .arg(
+                    Arg::new("by-allocation")
+                        .long("by-allocation")
+                        .help("Also show the worked time per allocated issue")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("by-project")
+                        .long("by-project")
+                        .help("Also show the worked time and share of every project per period")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("by-tag")
+                        .long("by-tag")
+                        .help("Also show the worked time and share of every tag")
+                        .conflicts_with("project")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("period")
+                        .long("period")
+                        .help("Period of the worked time per project")
+                        .requires("by-project")
+                        .value_parser(["week", "month"])
+                        .default_value("week"),
+                )
+                .arg(
+                    Arg::new("archived")
+                        .long("archived")
+                        .help("Include the archived stamps")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("bars")
+                        .long("bars")
+                        .help("Draw a bar per day and a sparkline per week")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("ascii")
+                        .long("ascii")
+                        .help("Draw the bars with ASCII characters only")
+                        .requires("bars")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Format of the report")
+                        .value_parser(["text", "html", "markdown"])
+                        .default_value("text"),
+                )
+                .arg(decimal_hours())
+                .arg(since_last_export().conflicts_with_all(["from", "week", "month", "range"]))
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("Write the report to this file instead of printing it")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("copy")
+                        .long("copy")
+                        .help("Copy the report to the clipboard instead of printing it, e.g. for a standup message")
+                        .conflicts_with("out")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("all-users")
+                        .long("all-users")
+                        .help("Show the worked time of every user side by side")
+                        .conflicts_with_all([
+                            "by-allocation",
+                            "by-project",
+                            "by-tag",
+                            "bars",
+                            "format",
+                            "out",
+                            "copy",
+                            "since-last-export",
+                        ])
+                        .action(ArgAction::SetTrue),
+                ),
+        );
+
+    // Expand the aliases of the configuration before parsing
+    let config = Config::load(&get_config_file()?)?;
+    i18n::init(
+        config
+            .language
+            .or_else(Language::from_env)
+            .unwrap_or_default(),
+    );
+    let commands: Vec<String> = cli
+        .get_subcommands()
+        .flat_map(|c| std::iter::once(c.get_name()).chain(c.get_all_aliases()))
+        .map(str::to_string)
+        .collect();
+    let args = alias::expand(&config.aliases, &commands, std::env::args_os().collect())?;
+    let matches = cli.get_matches_from(args);
+
+    // Decimal hours asked for the command take over the configuration
+    let mut durations = Durations {
+        format: config.duration_format,
+        precision: config.hours_precision,
+    };
+    if let Some(("report" | "export", sub)) = matches.subcommand() {
+        if sub.contains_id("decimal-hours") {
+            durations.format = DurationFormat::Decimal;
+            if let Some(precision) = sub.get_one::<u8>("decimal-hours") {
+                durations.precision = (*precision).into();
+            }
+        }
+    }
+    format::init(durations);
 
+    init_logging(matches.get_count("verbose"));
+
+    let on_error = config.on_error.clone();
+    let result = start(&matches, config);
+    if let (Err(e), Some(command)) = (&result, on_error) {
+        let vars = [
+            ("WTIME_ERROR", format!("{:#}", e)),
+            ("WTIME_COMMAND", command_line()),
+        ];
+        if let Err(e) = hooks::run(&command, Event::Error, &vars) {
+            eprintln!("{:#}", e);
+        }
+    }
+    result
+}
+
+/// Open the work-log and run the command given on the command line
+fn start(matches: &ArgMatches, config: Config) -> Result<()> {
     // Create the app object
+    #[cfg(feature = "postgres")]
+    if let Some(url) = &config.database_url {
+        info!("Database is on a PostgreSQL server");
+        let storage = PgStorage::connect(url).context("Connect to database server")?;
+        let app = App::with_storage(storage).with_config(config);
+        if let Some(user) = matches.get_one::<String>("user") {
+            app.select_user(user)?;
+        }
+        app.set_command(&command_line())?;
+        return run(app, matches);
+    }
+    #[cfg(not(feature = "postgres"))]
+    if config.database_url.is_some() {
+        return Err(anyhow!(
+            "A database_url is configured, but this build lacks the `postgres` feature"
+        ));
+    }
+
     let db_file = get_db_file()?;
-    println!("Database file is {:?}", db_file);
-    let app = App::new(db_file.as_path()).context("Open DB file")?;
+    info!("Database file is {:?}", db_file);
+    let app = App::new(db_file.as_path())
+        .context("Open DB file")?
+        .with_config(config);
+    if let Some(user) = matches.get_one::<String>("user") {
+        app.select_user(user)?;
+    }
+    app.set_command(&command_line())?;
+
+    // Commands specific to the sqlite file
+    match matches.subcommand() {
+        Some(("archive", sub)) => app.do_archive(&date_arg(&app, sub, "before").unwrap()),
+        Some(("edit", sub)) => {
+            let range = if sub.get_flag("pick") {
+                edit::Range::DayOf(app.pick_stamp(prompt)?.date)
+            } else {
+                sub.get_one::<String>("range")
+                    .unwrap()
+                    .parse::<edit::Range>()
+                    .unwrap()
+            };
+            app.do_edit(range, edit::open_editor)
+        }
+        Some(("delete", sub)) => {
+            let id = match sub.get_one::<i64>("id") {
+                Some(id) => StampId(*id),
+                None => app.pick_stamp(prompt)?.id,
+            };
+            if !sub.get_flag("yes") && !confirm(&tr!("confirm-delete", id = id.0))? {
+                return Err(anyhow!("Delete cancelled"));
+            }
+            app.do_delete(id)
+        }
+        Some(("purge", sub)) => {
+            let day = sub.get_one::<NaiveDate>("before").unwrap();
+            if !sub.get_flag("yes")
+                && !confirm(&tr!(
+                    "confirm-purge",
+                    before = day.format("%Y-%m-%d").to_string()
+                ))?
+            {
+                return Err(anyhow!("Purge cancelled"));
+            }
+            app.do_purge(&app.start_of_day(*day))
+        }
+        Some(("backup", sub)) => app.do_backup(sub.get_one::<PathBuf>("file").unwrap()),
+        Some(("info", _)) => app.do_info(Some(&db_file), &get_config_file()?),
+        Some(("sync", sub)) => match sub.subcommand() {
+            Some(("export", sub)) => {
+                app.do_sync_export(sub.get_one::<PathBuf>("file").map(PathBuf::as_path))
+            }
+            Some(("merge", sub)) => app.do_sync_merge(sub.get_one::<PathBuf>("file").unwrap()),
+            _ => app.do_sync_remote(sub.get_one::<String>("remote").unwrap()),
+        },
+        Some(("hook", sub)) => match sub.subcommand() {
+            Some(("taskwarrior", _)) => {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input)?;
+                app.with_source(Source::Hook).do_hook_taskwarrior(&input)
+            }
+            _ => unreachable!("Hook subcommand is required"),
+        },
+        Some(("restore", sub)) => {
+            let file = sub.get_one::<PathBuf>("file").unwrap();
+            if !sub.get_flag("yes")
+                && !confirm(&tr!("confirm-restore", file = format!("{:?}", file)))?
+            {
+                return Err(anyhow!("Restore cancelled"));
+            }
+
+            let mut save_to = db_file.into_os_string();
+            save_to.push(format!(".{}.bak", Utc::now().format("%Y%m%d%H%M%S")));
+            app.do_restore(file, Path::new(&save_to))
+        }
+        _ => run(app, matches),
+    }
+}
+
+/// Arguments of the program, as recorded in the audit log
+fn command_line() -> String {
+    std::env::args().skip(1).collect::<Vec<_>>().join(" ")
+}
+
+/// Run the command given on the command line
+fn run<S: Storage>(app: App<S>, matches: &ArgMatches) -> Result<()> {
+    // Restrict the totals to a project or a source, accept stamps before the
+    // last one, or record the stamps as made through an interface
+    let app = match matches.subcommand() {
+        Some(("report" | "week" | "month" | "stats" | "chart" | "timesheet", sub)) => app
+            .with_project(sub.get_one::<String>("project").cloned())
+            .with_source_filter(
+                sub.get_one::<String>("source")
+                    .map(|s| s.parse::<Source>().unwrap()),
+            ),
+        Some(("checkin" | "checkout" | "switch", sub)) => app.with_force(sub.get_flag("force")),
+        Some(("serve" | "dbus", _)) => app.with_source(Source::Api),
+        Some(("daemon", _)) => app.with_source(Source::Daemon),
+        Some(("import", _)) => app.with_source(Source::Import),
+        _ => app,
+    };
 
     // Reacts on command
     match matches.subcommand() {
-        Some(("checkin", _)) => app.do_checkin(),
-        Some(("checkout", _)) => app.do_checkout(),
+        Some(("checkin", sub)) => {
+            let mut close_previous_at = sub.get_one::<String>("close-previous-at").cloned();
+            if close_previous_at.is_none() && io::stdin().is_terminal() {
+                if let Some(checkin) = app.stale_checkin()? {
+                    let suggested = app
+                        .local(&app.suggested_checkout(&checkin))
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string();
+                    let answer = prompt(&format!(
+                        "{} ",
+                        tr!(
+                            "ask-close-previous",
+                            since = app
+                                .local(&checkin.date)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string(),
+                            suggested = suggested.as_str()
+                        )
+                    ))?;
+                    close_previous_at = Some(if answer.is_empty() { suggested } else { answer });
+                }
+            }
+            app.do_checkin(
+                sub.get_flag("since-active"),
+                sub.get_one::<String>("project").map(String::as_str),
+                &sub.get_many::<String>("tag")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                &sub.get_many::<String>("ref")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                close_previous_at.as_deref(),
+            )
+        }
+        Some(("checkout", sub)) => {
+            let split = if sub.contains_id("split") {
+                match sub.get_one::<String>("split") {
+                    Some(spec) => Some(spec.clone()),
+                    None => Some(prompt(&format!("{} ", tr!("ask-split")))?),
+                }
+            } else {
+                None
+            };
+            let overtime = app.do_checkout(split.as_deref())?;
+            if overtime && sub.get_flag("exit-code") {
+                std::process::exit(EXIT_OVERTIME);
+            }
+            Ok(())
+        }
+        Some(("switch", sub)) => app.do_switch(sub.get_one::<String>("project").unwrap()),
+        Some(("close", sub)) => app.do_close(&date_arg(&app, sub, "date").unwrap()),
+        Some(("reopen", _)) => app.do_reopen(),
+        Some(("adjust", sub)) => app.do_adjust(
+            Duration::minutes(*sub.get_one::<i64>("minutes").unwrap()),
+            sub.get_one::<String>("reason").unwrap(),
+            date_arg(&app, sub, "for"),
+        ),
+        Some(("estimate", sub)) => {
+            let tag = sub.get_flag("tag");
+            match (
+                sub.get_one::<String>("name"),
+                sub.get_one::<Duration>("duration"),
+            ) {
+                (Some(name), _) if sub.get_flag("remove") => app.do_estimate_remove(tag, name),
+                (Some(name), Some(duration)) => app.do_estimate(tag, name, *duration),
+                (Some(_), None) => Err(anyhow!("Give the estimated duration, or --remove")),
+                (None, _) => app.do_estimates(),
+            }
+        }
+        Some(("audit", sub)) => match sub.subcommand() {
+            Some(("export", sub)) => app.do_audit_export(
+                date_arg(&app, sub, "from"),
+                // The last day is included
+                sub.get_one::<NaiveDate>("to")
+                    .map(|day| app.start_of_day(*day + Duration::days(1))),
+                sub.get_one::<String>("format")
+                    .unwrap()
+                    .parse::<export::Format>()
+                    .unwrap(),
+            ),
+            Some(("verify", _)) => app.do_audit_verify(),
+            _ => unreachable!("Audit subcommand is required"),
+        },
+        Some(("chain", sub)) => match sub.subcommand() {
+            Some(("enable", _)) => app.do_chain_enable(),
+            _ => unreachable!("Chain subcommand is required"),
+        },
+        Some(("verify", sub)) => {
+            if sub.get_flag("chain") {
+                app.do_verify_chain()?;
+            }
+            Ok(())
+        }
+        Some(("import", sub)) => {
+            let format = sub
+                .get_one::<String>("format")
+                .unwrap()
+                .parse::<import::Format>()
+                .unwrap();
+            let file = sub.get_one::<PathBuf>("file");
+            let records = if let Some(calendar) = sub.get_one::<String>("ics") {
+                let input = if calendar.starts_with("http://") || calendar.starts_with("https://") {
+                    import::fetch(calendar)?
+                } else {
+                    fs::read_to_string(calendar).context("Reading calendar file")?
+                };
+                import::ics(&input)?
+            } else if format == import::Format::Hamster {
+                let file = file.ok_or_else(|| anyhow!("Give the Hamster database file"))?;
+                import::hamster(file)?
+            } else if format == import::Format::Xlsx {
+                let file = file.ok_or_else(|| anyhow!("Give the xlsx workbook file"))?;
+                import::xlsx(file, sub.get_one::<import::Columns>("columns").unwrap())?
+            } else {
+                let input = match file {
+                    Some(file) => fs::read_to_string(file).context("Reading input file")?,
+                    None => {
+                        let mut input = String::new();
+                        io::stdin().read_to_string(&mut input)?;
+                        input
+                    }
+                };
+                import::read(
+                    &input,
+                    format,
+                    sub.get_one::<import::Mapping>("map").unwrap(),
+                )?
+            };
+            app.do_import(records)
+        }
+        Some(("export", sub)) => {
+            let format = sub
+                .get_one::<String>("format")
+                .unwrap()
+                .parse::<export::SessionFormat>()
+                .unwrap();
+            if format == export::SessionFormat::Xlsx && io::stdout().is_terminal() {
+                return Err(anyhow!(
+                    "Redirect the xlsx export to a file, e.g. `> hours.xlsx`"
+                ));
+            }
+            let last_export = sub.get_flag("since-last-export").then(|| {
+                format!("export {}", sub.get_one::<String>("format").unwrap())
+            });
+            let from = match &last_export {
+                Some(key) => Some(app.last_export(key)?),
+                None => date_arg(&app, sub, "from"),
+            };
+            let to = date_arg(&app, sub, "to");
+            app.do_export(from, to, sub.get_flag("archived"), format)?;
+            match (last_export, from) {
+                (Some(key), Some(from)) => {
+                    app.mark_exported(&key, from, to, sub.get_flag("archived"))
+                }
+                _ => Ok(()),
+            }
+        }
+        Some(("search", sub)) => app.do_search(&StampFilter {
+            from: date_arg(&app, sub, "from"),
+            to: date_arg(&app, sub, "to"),
+            direction: sub
+                .get_one::<String>("direction")
+                .map(|d| d.parse::<InOut>().unwrap()),
+            project: sub.get_one::<String>("project").cloned(),
+            note_contains: sub.get_one::<String>("note-contains").cloned(),
+            source: sub.get_one::<String>("source").map(|s| s.parse::<Source>().unwrap()),
+            limit: sub.get_one::<usize>("limit").copied(),
+            offset: *sub.get_one::<usize>("offset").unwrap(),
+        }),
+        Some(("week", sub)) => app.do_week(
+            sub.get_one::<NaiveDate>("week")
+                .copied()
+                .unwrap_or_else(|| {
+                    let today = app.today();
+                    today - Duration::days(today.weekday().num_days_from_monday().into())
+                }),
+        ),
+        Some(("month", sub)) => app.do_month(
+            sub.get_one::<NaiveDate>("month")
+                .copied()
+                .unwrap_or_else(|| app.today().with_day(1).unwrap()),
+            io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        ),
+        Some(("watch", _)) => app.do_watch(io::stdout().is_terminal()),
+        Some(("stats", sub)) if sub.get_flag("habits") => {
+            app.do_stats_habits(*sub.get_one::<u32>("weeks").unwrap())
+        }
+        Some(("stats", sub)) if sub.get_flag("trend") => {
+            app.do_stats_trend(*sub.get_one::<u32>("weeks").unwrap())
+        }
+        Some(("stats", sub)) => app.do_stats_year(
+            sub.get_one::<i32>("year")
+                .copied()
+                .unwrap_or_else(|| app.today().year()),
+        ),
+        Some(("chart", sub)) => app.do_chart(
+            sub.get_one::<String>("range")
+                .unwrap()
+                .parse::<chart::Range>()
+                .unwrap(),
+            sub.get_one::<String>("kind")
+                .unwrap()
+                .parse::<chart::Kind>()
+                .unwrap(),
+            sub.get_one::<PathBuf>("out").unwrap(),
+        ),
+        Some(("timesheet", sub)) => {
+            let month = sub
+                .get_one::<NaiveDate>("month")
+                .copied()
+                .unwrap_or_else(|| app.today().with_day(1).unwrap());
+            let out = sub.get_one::<PathBuf>("out").cloned().unwrap_or_else(|| {
+                PathBuf::from(format!("timesheet-{}.pdf", month.format("%Y-%m")))
+            });
+            app.do_timesheet(
+                month,
+                sub.get_one::<String>("format")
+                    .unwrap()
+                    .parse::<timesheet::Format>()
+                    .unwrap(),
+                &out,
+            )
+        }
+        Some(("archive", _))
+        | Some(("edit", _))
+        | Some(("delete", _))
+        | Some(("purge", _))
+        | Some(("backup", _))
+        | Some(("restore", _))
+        | Some(("sync", _))
+        | Some(("hook", _)) => Err(anyhow!(
+            "Archive, edit, delete, purge, backup, restore, sync and hooks are only available for the sqlite database"
+        )),
+        Some(("info", _)) => app.do_info(None, &get_config_file()?),
+        Some(("doctor", sub)) if sub.get_flag("interactive") => app.do_doctor_interactive(prompt),
+        Some(("doctor", sub)) => app.do_doctor(sub.get_flag("explain"), sub.get_flag("fix")),
+        Some(("site", sub)) => match sub.subcommand() {
+            Some(("build", sub)) => app.do_site_build(sub.get_one::<PathBuf>("dir").unwrap()),
+            _ => unreachable!("Site subcommand is required"),
+        },
+        Some(("serve", sub)) => {
+            let cert = sub.get_one::<PathBuf>("cert");
+            let key = sub.get_one::<PathBuf>("key");
+            app.do_serve(
+                sub.get_one::<String>("listen").unwrap(),
+                cert.zip(key).map(|(c, k)| (c.as_path(), k.as_path())),
+            )
+        }
+        Some(("push", sub)) => match sub.subcommand() {
+            Some(("jira", sub)) => app.do_push_jira(
+                sub.get_one::<String>("issue").unwrap(),
+                date_arg(&app, sub, "from"),
+                date_arg(&app, sub, "to"),
+            ),
+            Some(("gitlab", sub)) => app.do_push_gitlab(
+                sub.get_one::<String>("issue").unwrap(),
+                date_arg(&app, sub, "from"),
+                date_arg(&app, sub, "to"),
+            ),
+            _ => unreachable!("Push subcommand is required"),
+        },
+        Some(("daemon", sub)) => match sub.subcommand() {
+            Some(("install", install)) => app.do_daemon_install(
+                Duration::seconds(*install.get_one::<i64>("interval").unwrap()),
+                install.get_one::<String>("metrics").map(String::as_str),
+                matches.get_one::<String>("user").map(String::as_str),
+            ),
+            Some(("uninstall", _)) => app.do_daemon_uninstall(),
+            _ => app.do_daemon(
+                Duration::seconds(*sub.get_one::<i64>("interval").unwrap()),
+                sub.get_one::<String>("metrics").map(String::as_str),
+            ),
+        },
+        #[cfg(feature = "dbus")]
+        Some(("dbus", _)) => app.do_dbus(),
+        #[cfg(not(feature = "dbus"))]
+        Some(("dbus", _)) => Err(anyhow!("This build lacks the `dbus` feature")),
+        Some(("report", sub)) if sub.get_flag("all-users") => {
+            let (from, to) = report_window(&app, sub)?;
+            app.do_team_report(from, to, sub.get_flag("archived"))
+        }
+        Some(("report", sub)) => {
+            let (mut from, to) = report_window(&app, sub)?;
+            let last_export = sub.get_flag("since-last-export").then(|| {
+                format!("report {}", sub.get_one::<String>("format").unwrap())
+            });
+            if let Some(key) = &last_export {
+                from = Some(app.last_export(key)?);
+            }
+            app.do_report(
+            from,
+            to,
+            sub.get_flag("by-allocation"),
+            sub.get_flag("by-project").then(|| {
+                match sub.get_one::<String>("period").unwrap().parse().unwrap() {
+                    report::Period::Month { .. } => app.month(),
+                    period => period,
+                }
+            }),
+            sub.get_flag("by-tag"),
+            sub.get_flag("archived"),
+            match (sub.get_flag("bars"), sub.get_flag("ascii")) {
+                (false, _) => None,
+                (true, false) => Some(render::Glyphs::Unicode),
+                (true, true) => Some(render::Glyphs::Ascii),
+            },
+            sub.get_one::<String>("format")
+                .unwrap()
+                .parse::<render::ReportFormat>()
+                .unwrap(),
+            sub.get_one::<PathBuf>("out").map(PathBuf::as_path),
+            sub.get_flag("copy"),
+        )?;
+            match (last_export, from) {
+                (Some(key), Some(from)) => {
+                    app.mark_exported(&key, from, to, sub.get_flag("archived"))
+                }
+                _ => Ok(()),
+            }
+        }
+        Some(("status", sub)) => app.do_status(
+            sub.get_one::<String>("format")
+                .unwrap()
+                .parse::<StatusFormat>()
+                .unwrap(),
+        ),
         None => app.do_list(),
         _ => unreachable!("Should never match none"),
     }
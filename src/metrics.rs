@@ -0,0 +1,101 @@
+//! Prometheus metrics of the work-log
+//!
+//! `GET /metrics` of `wtime serve`, and of `wtime daemon --metrics <address>`,
+//! answers the gauges below in the Prometheus text format, e.g. to graph the
+//! worked hours in Grafana:
+//!
+//! | Metric                  | Value                                              |
+//! |-------------------------|----------------------------------------------------|
+//! | `wtime_checked_in`      | 1 while a session is running, 0 otherwise          |
+//! | `wtime_session_seconds` | Time of the running session, 0 when checked out    |
+//! | `wtime_seconds_today`   | Worked time of today, the running session included |
+//! | `wtime_seconds_week`    | Worked time of the week, the running session included |
+
+use crate::statusbar::Bar;
+use chrono::Duration;
+
+/// Path the metrics are answered on
+pub const PATH: &str = "/metrics";
+
+/// Content type of the Prometheus text format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Render the metrics in the Prometheus text format
+///
+/// # Arguments
+///
+/// * `bar` - Status of the work-log, see [App::status_bar](crate::app::App::status_bar)
+pub fn render(bar: &Bar) -> String {
+    let session = bar
+        .running
+        .map_or_else(Duration::zero, |start| bar.now - start);
+    let gauges = [
+        (
+            "wtime_checked_in",
+            "Whether a session is running",
+            i64::from(bar.running.is_some()),
+        ),
+        (
+            "wtime_session_seconds",
+            "Time of the running session, 0 when checked out",
+            session.num_seconds(),
+        ),
+        (
+            "wtime_seconds_today",
+            "Worked time of today, the running session included",
+            bar.today.num_seconds(),
+        ),
+        (
+            "wtime_seconds_week",
+            "Worked time of the week, the running session included",
+            bar.week.num_seconds(),
+        ),
+    ];
+
+    gauges
+        .iter()
+        .map(|(name, help, value)| {
+            format!(
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n",
+                name = name,
+                help = help,
+                value = value
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::render;
+    use crate::statusbar::Bar;
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn gauges() {
+        let now = DateTime::<Utc>::from_str("2020-01-01T14:30:00Z").unwrap();
+        let mut bar = Bar {
+            now,
+            running: Some(now - Duration::minutes(90)),
+            sessions: Vec::new(),
+            today: Duration::minutes(5 * 60 + 30),
+            week: Duration::hours(20),
+        };
+
+        let text = render(&bar);
+        assert!(text.starts_with(
+            "# HELP wtime_checked_in Whether a session is running\n\
+             # TYPE wtime_checked_in gauge\n\
+             wtime_checked_in 1\n"
+        ));
+        assert!(text.contains("\nwtime_session_seconds 5400\n"));
+        assert!(text.contains("\nwtime_seconds_today 19800\n"));
+        assert!(text.ends_with("\nwtime_seconds_week 72000\n"));
+
+        bar.running = None;
+        let text = render(&bar);
+        assert!(text.contains("\nwtime_checked_in 0\n"));
+        assert!(text.contains("\nwtime_session_seconds 0\n"));
+    }
+}
@@ -0,0 +1,156 @@
+//! System clock check against an NTP server
+//!
+//! A wrong system clock silently dates the stamps wrong. When `ntp_server`
+//! is set in the configuration, the check-in and check-out first ask the
+//! server for the time (a single SNTP request) and warn when the system clock
+//! is off by more than `max_clock_skew`:
+//!
+//! ```toml
+//! ntp_server = "pool.ntp.org"
+//! max_clock_skew = "1m"
+//! ```
+//!
+//! Querying the server needs the `ntp` cargo feature.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+/// Size of an NTP packet without extensions
+pub const PACKET_SIZE: usize = 48;
+
+/// Seconds from the NTP era (1900-01-01) to the Unix epoch
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// Convert a date to an NTP timestamp, seconds and fraction
+fn to_ntp(date: &DateTime<Utc>) -> u64 {
+    let seconds = (date.timestamp() + NTP_UNIX_OFFSET) as u64;
+    let fraction = (u64::from(date.timestamp_subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Convert an NTP timestamp to a date
+fn from_ntp(timestamp: u64) -> DateTime<Utc> {
+    let seconds = (timestamp >> 32) as i64 - NTP_UNIX_OFFSET;
+    let nanos = ((timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    Utc.timestamp_opt(seconds, nanos as u32).unwrap()
+}
+
+/// Read the NTP timestamp at an offset of a packet
+fn timestamp_at(packet: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&packet[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Build the request packet of a client
+///
+/// # Arguments
+///
+/// * `sent` - Date/Time the request is sent, echoed back by the server
+pub fn request(sent: &DateTime<Utc>) -> [u8; PACKET_SIZE] {
+    let mut packet = [0; PACKET_SIZE];
+    // No leap indicator, version 4, client mode
+    packet[0] = 0b00_100_011;
+    packet[40..48].copy_from_slice(&to_ntp(sent).to_be_bytes());
+    packet
+}
+
+/// Offset of the system clock from the server clock
+///
+/// # Arguments
+///
+/// * `reply` - Packet answered by the server
+/// * `sent` - Date/Time the request was sent, by the system clock
+/// * `received` - Date/Time the reply was received, by the system clock
+///
+/// # Return
+///
+/// How much the system clock is ahead (negative when behind), None if the
+/// reply is not a valid server answer.
+pub fn offset(reply: &[u8], sent: &DateTime<Utc>, received: &DateTime<Utc>) -> Option<Duration> {
+    // Server mode, and a stratum set (0 is a "kiss-o'-death")
+    if reply.len() < PACKET_SIZE || reply[0] & 0b111 != 4 || reply[1] == 0 {
+        return None;
+    }
+
+    let server_received = from_ntp(timestamp_at(reply, 32));
+    let server_sent = from_ntp(timestamp_at(reply, 40));
+    Some(((*sent - server_received) + (*received - server_sent)) / 2)
+}
+
+/// Ask an NTP server how far off the system clock is, see [offset]
+///
+/// # Arguments
+///
+/// * `server` - Host name of the server, with an optional port (123 by default)
+/// * `timeout` - How long to wait for the answer
+#[cfg(feature = "ntp")]
+pub fn clock_offset(server: &str, timeout: std::time::Duration) -> anyhow::Result<Duration> {
+    use anyhow::{anyhow, Context};
+    use std::net::UdpSocket;
+
+    let address = if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{}:123", server)
+    };
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket
+        .connect(&address)
+        .with_context(|| format!("Connecting to {}", address))?;
+
+    let sent = Utc::now();
+    socket.send(&request(&sent))?;
+    let mut reply = [0; PACKET_SIZE];
+    let size = socket
+        .recv(&mut reply)
+        .with_context(|| format!("Waiting for {}", address))?;
+    let received = Utc::now();
+
+    offset(&reply[..size], &sent, &received)
+        .ok_or_else(|| anyhow!("Invalid answer from {}", address))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_ntp, offset, request, to_ntp, PACKET_SIZE};
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn timestamps() {
+        let date = DateTime::<Utc>::from_str("2024-02-12T08:30:00.5Z").unwrap();
+        assert_eq!(to_ntp(&date) >> 32, 3_916_715_400);
+        assert_eq!(to_ntp(&date) & 0xffff_ffff, 1 << 31);
+        assert_eq!(from_ntp(to_ntp(&date)), date);
+
+        let packet = request(&date);
+        assert_eq!(packet[0], 0x23);
+        assert_eq!(&packet[40..48], &to_ntp(&date).to_be_bytes());
+    }
+
+    #[test]
+    fn clock_offset() {
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        let sent = date("2024-02-12T08:30:10Z");
+        let received = date("2024-02-12T08:30:10.5Z");
+
+        // Server 10 seconds behind, half a second round trip
+        let mut reply = [0; PACKET_SIZE];
+        reply[0] = 0x24;
+        reply[1] = 2;
+        let server = to_ntp(&date("2024-02-12T08:30:00.25Z"));
+        reply[32..40].copy_from_slice(&server.to_be_bytes());
+        reply[40..48].copy_from_slice(&server.to_be_bytes());
+        assert_eq!(
+            offset(&reply, &sent, &received),
+            Some(Duration::seconds(10))
+        );
+
+        // Kiss-o'-death, client packet or truncated
+        reply[1] = 0;
+        assert_eq!(offset(&reply, &sent, &received), None);
+        assert_eq!(offset(&request(&sent), &sent, &received), None);
+        assert_eq!(offset(&reply[..40], &sent, &received), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! Fuzzy finder picking a stamp from a human-readable list
+//!
+//! `wtime edit --pick` and `wtime delete --pick` list the stamps as lines
+//! such as `Mon 2024-02-12 08:00 in acme #review (stamp 12)`. Typed words
+//! narrow the list down: each one must be found in the line, its letters in
+//! order but not necessarily adjacent, e.g. `0212 acm` or `feb in`.
+
+/// Score of a word found in a line, None if not found
+///
+/// Letters following each other, or starting a word of the line, score
+/// more. The case is ignored.
+///
+/// # Arguments
+///
+/// * `word` - Word typed by the user
+/// * `line` - Line to look in
+pub fn score(word: &str, line: &str) -> Option<i64> {
+    let word: Vec<char> = word.to_lowercase().chars().collect();
+    let line: Vec<char> = line.to_lowercase().chars().collect();
+    let Some(first) = word.first() else {
+        return Some(0);
+    };
+
+    // Best of the matches starting at every occurrence of the first letter
+    (0..line.len())
+        .filter(|start| line[*start] == *first)
+        .filter_map(|start| score_from(&word, &line, start))
+        .max()
+}
+
+/// Score of a word matched from a position of a line, its letters taken as
+/// soon as found
+fn score_from(word: &[char], line: &[char], start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut position = start;
+    let mut previous: Option<usize> = None;
+
+    for letter in word {
+        let found = position + line[position..].iter().position(|c| c == letter)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !line[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// Find the lines matching a query, best first
+///
+/// Every word of the query must be found in a line, see [score]. Lines
+/// scoring the same come latest first, so that an empty query lists the
+/// last lines.
+///
+/// # Arguments
+///
+/// * `query` - Words typed by the user
+/// * `lines` - Lines to look in
+///
+/// # Return
+///
+/// The indexes of the matching lines.
+pub fn matches(query: &str, lines: &[String]) -> Vec<usize> {
+    let mut found: Vec<(i64, usize)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            query
+                .split_whitespace()
+                .map(|word| score(word, line))
+                .sum::<Option<i64>>()
+                .map(|score| (score, index))
+        })
+        .collect();
+    found.sort_by(|a, b| b.cmp(a));
+    found.into_iter().map(|(_, index)| index).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{matches, score};
+
+    #[test]
+    fn fuzzy() {
+        let line = "Mon 2024-02-12 08:00 in acme #review (stamp 12)";
+        assert!(score("acm", line).is_some());
+        assert!(score("ACME", line).is_some());
+        assert!(score("0212", line).is_some());
+        assert!(score("emca", line).is_none());
+        // Adjacent letters and word starts score more
+        assert!(score("rev", line) > score("rvw", line));
+        assert!(score("acme", line) > score("cme", line));
+
+        let lines: Vec<String> = [
+            "Mon 2024-02-12 08:00 in acme (stamp 1)",
+            "Mon 2024-02-12 12:00 out (stamp 2)",
+            "Mon 2024-02-12 13:00 in globex (stamp 3)",
+            "Mon 2024-02-12 17:00 out (stamp 4)",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(matches("", &lines), [3, 2, 1, 0]);
+        assert_eq!(matches("in", &lines), [2, 0]);
+        assert_eq!(matches("12:00 out", &lines), [1, 3]);
+        assert_eq!(matches("mon", &lines), [3, 2, 1, 0]);
+        assert!(matches("initech", &lines).is_empty());
+    }
+}
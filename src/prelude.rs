@@ -0,0 +1,27 @@
+//! Supported public types
+//!
+//! Library users should import from here:
+//!
+//! ```
+//! use wtime::prelude::*;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let store = wtime::db::open_memory()?;
+//! wtime::db::init(&store)?;
+//!
+//! let mut stamp = Stamp::check_in();
+//! store.insert_stamp(&mut stamp)?;
+//! assert_eq!(store.get_stamp(StampId(1))?, stamp);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The items re-exported here follow semantic versioning: they are only
+//! changed incompatibly with a new major version. Modules hidden from the
+//! documentation are internal to the `wtime` command and may change in any
+//! release.
+
+pub use crate::app::{AppError, CheckoutSummary, Resume, WorkSummary};
+pub use crate::db::{InOut, Stamp, StampId};
+pub use crate::report::{Report, Session};
+pub use crate::storage::Storage as StampStore;
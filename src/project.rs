@@ -0,0 +1,94 @@
+//! Detection of the project worked on
+//!
+//! Inside a git repository, the project is given by the `project` key of a
+//! `.wtime.toml` file at the root of the repository:
+//!
+//! ```toml
+//! project = "acme/website"
+//! ```
+//!
+//! Without this file, or without this key, the project is the name of the
+//! repository directory.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project file, at the root of a repository
+pub const PROJECT_FILE: &str = ".wtime.toml";
+
+/// Content of the project file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    /// Project the time worked in the repository is allocated to
+    project: Option<String>,
+}
+
+/// Get the root of the git repository containing a directory
+///
+/// # Arguments
+///
+/// * `dir` - Directory inside the repository
+pub fn repository_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|d| d.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Get the project of the git repository containing a directory
+///
+/// # Arguments
+///
+/// * `dir` - Directory inside the repository
+///
+/// # Return
+///
+/// The project into Some, None outside of a repository.
+pub fn detect(dir: &Path) -> Result<Option<String>> {
+    let Some(root) = repository_root(dir) else {
+        return Ok(None);
+    };
+
+    let file = root.join(PROJECT_FILE);
+    let project_file = if file.exists() {
+        let text = fs::read_to_string(&file).with_context(|| format!("Reading {:?}", file))?;
+        toml::from_str(&text).with_context(|| format!("Invalid {:?}", file))?
+    } else {
+        ProjectFile::default()
+    };
+
+    Ok(project_file.project.or_else(|| {
+        root.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect, PROJECT_FILE};
+    use std::fs;
+
+    #[test]
+    fn projects() {
+        let dir = std::env::temp_dir().join("wtime-project-test");
+        let _ = fs::remove_dir_all(&dir);
+        let sources = dir.join("website").join("src");
+        fs::create_dir_all(&sources).unwrap();
+
+        assert_eq!(detect(&sources).unwrap(), None);
+
+        fs::create_dir(dir.join("website").join(".git")).unwrap();
+        assert_eq!(detect(&sources).unwrap().as_deref(), Some("website"));
+
+        let file = dir.join("website").join(PROJECT_FILE);
+        fs::write(&file, "project = \"acme/website\"\n").unwrap();
+        assert_eq!(detect(&sources).unwrap().as_deref(), Some("acme/website"));
+
+        fs::write(&file, "client = \"acme\"\n").unwrap();
+        assert!(detect(&sources).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
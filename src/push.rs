@@ -0,0 +1,582 @@
+//! Push of the worked time to issue trackers
+//!
+//! The time of the sessions is logged on an issue, each session at most once
+//! per issue: every push is recorded as a [Worklog]. Sessions referencing
+//! tickets, see [Reference], are only pushed to them, evenly shared. Sessions
+//! split with allocations only give the share allocated to the issue,
+//! sessions without allocation are pushed whole, to a single issue.
+
+use crate::db::{DbError, Reference, StampId, Worklog};
+use crate::report::{self, Session};
+use crate::storage::Storage;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// [Worklog] service name of Jira
+pub const JIRA: &str = "jira";
+
+/// [Worklog] service name of GitLab
+pub const GITLAB: &str = "gitlab";
+
+/// Type for errors while pushing
+#[derive(Error, Debug)]
+pub enum PushError {
+    /// Error of the database
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    /// Issue tracker could not be reached or refused the request
+    #[error("Remote: {0}")]
+    Remote(String),
+
+    /// Issue URL is not of the expected form
+    #[error("Invalid issue URL `{0}`")]
+    InvalidIssue(String),
+}
+
+/// Time of a session to log on an issue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Session the time is taken from
+    pub session: StampId,
+    /// Check-in time of the session
+    pub started: DateTime<Utc>,
+    /// Time to log
+    pub duration: Duration,
+    /// Allocation target or referenced ticket the time is the share of, None
+    /// for the whole session
+    pub share: Option<String>,
+}
+
+/// Get the tickets referenced by sessions, by session ID
+///
+/// None are with a storage backend not supporting the references.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `sessions` - Sessions to get the references of, in id order
+fn session_references<S: Storage + ?Sized>(
+    storage: &S,
+    sessions: &[Session],
+) -> Result<BTreeMap<StampId, Vec<String>>, DbError> {
+    let mut references: BTreeMap<StampId, Vec<String>> = BTreeMap::new();
+    let (first, last) = match (sessions.first(), sessions.last()) {
+        (Some(first), Some(last)) => (first.id, last.id),
+        _ => return Ok(references),
+    };
+
+    let stored = match storage.reference_range(first, last) {
+        Err(DbError::Unsupported(_)) => Vec::new(),
+        other => other?,
+    };
+    for Reference { session, name, .. } in stored {
+        references.entry(session).or_default().push(name);
+    }
+    Ok(references)
+}
+
+/// Get the time of the sessions not pushed to an issue yet
+///
+/// A session referencing tickets is shared evenly between them, whatever its
+/// allocations, and not pushed to other issues. A session pushed whole to an
+/// issue is not pushed again to any other.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `service` - Issue tracker, e.g. [JIRA]
+/// * `issue` - Issue to log the time on
+/// * `sessions` - Sessions to push, in id order
+pub fn pending<S: Storage + ?Sized>(
+    storage: &S,
+    service: &str,
+    issue: &str,
+    sessions: &[Session],
+) -> Result<Vec<Entry>, DbError> {
+    if sessions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let first = sessions[0].id;
+    let last = sessions[sessions.len() - 1].id;
+    let pushed: BTreeSet<StampId> = storage
+        .session_worklogs(service, first, last)?
+        .into_iter()
+        .filter(|worklog| worklog.share.as_deref().is_none_or(|share| share == issue))
+        .map(|worklog| worklog.session)
+        .collect();
+    let allocations = report::session_allocations(storage, sessions)?;
+    let references = session_references(storage, sessions)?;
+
+    Ok(sessions
+        .iter()
+        .filter(|session| !pushed.contains(&session.id))
+        .map(|session| {
+            let shared =
+                references.contains_key(&session.id) || allocations.contains_key(&session.id);
+            let duration = match (references.get(&session.id), allocations.get(&session.id)) {
+                (Some(references), _) if references.iter().any(|r| r == issue) => {
+                    session.duration() / references.len() as i32
+                }
+                (Some(_), _) => Duration::zero(),
+                (None, None) => session.duration(),
+                (None, Some(allocations)) => {
+                    let fraction: f64 = allocations
+                        .iter()
+                        .filter(|a| a.target == issue)
+                        .map(|a| a.fraction)
+                        .sum();
+                    Duration::seconds(
+                        (session.duration().num_seconds() as f64 * fraction.min(1.0)).round()
+                            as i64,
+                    )
+                }
+            };
+            Entry {
+                session: session.id,
+                started: session.start,
+                duration,
+                share: shared.then(|| issue.to_string()),
+            }
+        })
+        .filter(|entry| entry.duration > Duration::zero())
+        .collect())
+}
+
+/// Client of the Jira REST API
+pub struct Jira {
+    /// Base URL of the server, e.g. `https://acme.atlassian.net`
+    url: String,
+    /// Value of the `Authorization` header
+    authorization: String,
+}
+
+impl Jira {
+    /// Create a client of a Jira server
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Base URL of the server, e.g. `https://acme.atlassian.net`
+    /// * `user` - Account e-mail for an API token (Jira Cloud), None for a
+    ///   personal access token (Jira Server)
+    /// * `token` - API or personal access token
+    pub fn new(url: &str, user: Option<&str>, token: &str) -> Self {
+        let authorization = match user {
+            Some(user) => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, token))
+            ),
+            None => format!("Bearer {}", token),
+        };
+
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            authorization,
+        }
+    }
+
+    /// Log time on an issue
+    ///
+    /// # Arguments
+    ///
+    /// * `issue` - Issue key, e.g. `PROJ-1`
+    /// * `entry` - Time to log
+    ///
+    /// # Return
+    ///
+    /// The ID of the new Jira worklog
+    pub fn add_worklog(&self, issue: &str, entry: &Entry) -> Result<String, PushError> {
+        let remote = |e: ureq::Error| PushError::Remote(e.to_string());
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+
+        let mut response = agent
+            .post(format!("{}/rest/api/2/issue/{}/worklog", self.url, issue))
+            .header("Authorization", &self.authorization)
+            .send_json(json!({
+                "started": entry.started.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string(),
+                "timeSpentSeconds": entry.duration.num_seconds(),
+                "comment": "Logged by wtime",
+            }))
+            .map_err(remote)?;
+        let status = response.status();
+        let body: Value = response.body_mut().read_json().unwrap_or(Value::Null);
+
+        if !status.is_success() {
+            // Jira explains the failure in `errorMessages` and `errors`
+            let mut messages: Vec<String> = body["errorMessages"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|m| m.as_str().map(str::to_string))
+                .collect();
+            if let Some(errors) = body["errors"].as_object() {
+                messages.extend(errors.iter().map(|(field, m)| format!("{}: {}", field, m)));
+            }
+            if messages.is_empty() {
+                messages.push(status.to_string());
+            }
+            return Err(PushError::Remote(messages.join(", ")));
+        }
+
+        body["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| PushError::Remote("No worklog id in the answer".into()))
+    }
+}
+
+/// Client of the time tracking of a GitLab issue
+pub struct GitLab {
+    /// Base URL of the server, e.g. `https://gitlab.com`
+    url: String,
+    /// Path of the project, e.g. `group/project`
+    project: String,
+    /// Number of the issue in the project
+    iid: u64,
+    /// Personal access token, with the `api` scope
+    token: String,
+}
+
+impl GitLab {
+    /// Create a client of the issue at an URL
+    ///
+    /// # Arguments
+    ///
+    /// * `issue_url` - Web URL of the issue, e.g.
+    ///   `https://gitlab.com/group/project/-/issues/42`
+    /// * `token` - Personal access token, with the `api` scope
+    pub fn new(issue_url: &str, token: &str) -> Result<Self, PushError> {
+        let invalid = || PushError::InvalidIssue(issue_url.to_string());
+        let (scheme, rest) = issue_url.split_once("://").ok_or_else(invalid)?;
+        let (host, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let (project, iid) = path
+            .trim_end_matches('/')
+            .split_once("/-/issues/")
+            .ok_or_else(invalid)?;
+        let iid = iid.parse().map_err(|_| invalid())?;
+
+        Ok(Self {
+            url: format!("{}://{}", scheme, host),
+            project: project.to_string(),
+            iid,
+            token: token.to_string(),
+        })
+    }
+
+    /// Reference of the issue, e.g. `group/project#42`
+    ///
+    /// Sessions are allocated to the issue with this reference, e.g. with
+    /// `checkout --split "100% group/project#42"`.
+    pub fn reference(&self) -> String {
+        format!("{}#{}", self.project, self.iid)
+    }
+
+    /// Add spent time to the issue
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Time to add
+    pub fn add_spent_time(&self, entry: &Entry) -> Result<(), PushError> {
+        let remote = |e: ureq::Error| PushError::Remote(e.to_string());
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+
+        let mut response = agent
+            .post(format!(
+                "{}/api/v4/projects/{}/issues/{}/add_spent_time",
+                self.url,
+                self.project.replace('/', "%2F"),
+                self.iid
+            ))
+            .header("PRIVATE-TOKEN", &self.token)
+            .send_json(json!({
+                "duration": format!("{}s", entry.duration.num_seconds()),
+                "summary": "Logged by wtime",
+            }))
+            .map_err(remote)?;
+        let status = response.status();
+
+        if !status.is_success() {
+            // GitLab explains the failure in `message` or `error`
+            let body: Value = response.body_mut().read_json().unwrap_or(Value::Null);
+            let message = [&body["message"], &body["error"]]
+                .into_iter()
+                .find(|m| !m.is_null())
+                .map(|m| m.as_str().map_or_else(|| m.to_string(), str::to_string))
+                .unwrap_or_else(|| status.to_string());
+            return Err(PushError::Remote(message));
+        }
+        Ok(())
+    }
+}
+
+/// Push the pending time of sessions, recording a worklog for each
+///
+/// Every worklog is recorded as soon as the tracker accepted it, so a
+/// failure midway can be resumed without pushing twice.
+fn push_pending<S: Storage + ?Sized>(
+    storage: &S,
+    service: &str,
+    issue: &str,
+    sessions: &[Session],
+    mut log: impl FnMut(&Entry) -> Result<Option<String>, PushError>,
+) -> Result<Vec<Worklog>, PushError> {
+    let mut worklogs = Vec::new();
+    for entry in pending(storage, service, issue, sessions)? {
+        let mut worklog = Worklog::new(entry.session, service, issue, entry.duration);
+        worklog.share = entry.share.clone();
+        worklog.remote_id = log(&entry)?;
+        storage.insert_worklog(&mut worklog)?;
+        worklogs.push(worklog);
+    }
+
+    Ok(worklogs)
+}
+
+/// Push the pending time of sessions to a Jira issue, see [pending]
+///
+/// Every worklog is recorded as soon as Jira accepted it, so a failure
+/// midway can be resumed without pushing twice.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `jira` - Jira server
+/// * `issue` - Issue key, e.g. `PROJ-1`
+/// * `sessions` - Sessions to push, in id order
+///
+/// # Return
+///
+/// The recorded worklogs
+pub fn jira<S: Storage + ?Sized>(
+    storage: &S,
+    jira: &Jira,
+    issue: &str,
+    sessions: &[Session],
+) -> Result<Vec<Worklog>, PushError> {
+    push_pending(storage, JIRA, issue, sessions, |entry| {
+        jira.add_worklog(issue, entry).map(Some)
+    })
+}
+
+/// Push the pending time of sessions to a GitLab issue, see [pending]
+///
+/// The sessions allocated to the issue are matched by its reference, see
+/// [GitLab::reference]. GitLab gives no ID to spent time, so the worklogs
+/// have no remote ID.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `gitlab` - GitLab issue
+/// * `sessions` - Sessions to push, in id order
+///
+/// # Return
+///
+/// The recorded worklogs
+pub fn gitlab<S: Storage + ?Sized>(
+    storage: &S,
+    gitlab: &GitLab,
+    sessions: &[Session],
+) -> Result<Vec<Worklog>, PushError> {
+    push_pending(storage, GITLAB, &gitlab.reference(), sessions, |entry| {
+        gitlab.add_spent_time(entry).map(|_| None)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{gitlab, jira, pending, GitLab, Jira, PushError, JIRA};
+    use crate::db::{self, Allocation, InOut, Reference, Stamp, StampId};
+    use crate::report;
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+    use std::thread;
+
+    fn work_log() -> (sqlite::Connection, Vec<report::Session>) {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T12:00:00Z", InOut::Out),
+            ("2020-01-01T13:00:00Z", InOut::In),
+            ("2020-01-01T15:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+        Allocation::new(StampId(3), "PROJ-1", 0.25)
+            .insert(&conn)
+            .unwrap();
+        Allocation::new(StampId(3), "PROJ-7", 0.75)
+            .insert(&conn)
+            .unwrap();
+
+        let from = DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap();
+        let sessions = report::sessions(&conn, &from, &(from + Duration::days(1))).unwrap();
+        (conn, sessions)
+    }
+
+    #[test]
+    fn shares() {
+        let (conn, sessions) = work_log();
+
+        let entries = pending(&conn, JIRA, "PROJ-1", &sessions).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].duration, Duration::hours(4));
+        assert_eq!(entries[1].duration, Duration::minutes(30));
+
+        // Only the allocated share of split sessions
+        let entries = pending(&conn, JIRA, "PROJ-9", &sessions).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].session, StampId(1));
+    }
+
+    #[test]
+    fn references() {
+        let (conn, sessions) = work_log();
+        Reference::new(StampId(1), "PROJ-1").insert(&conn).unwrap();
+        Reference::new(StampId(1), "PROJ-2").insert(&conn).unwrap();
+        Reference::new(StampId(3), "PROJ-7").insert(&conn).unwrap();
+
+        // Shared evenly between the referenced tickets
+        let entries = pending(&conn, JIRA, "PROJ-2", &sessions).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].duration, Duration::hours(2));
+        let entries = pending(&conn, JIRA, "PROJ-1", &sessions).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].session, StampId(1));
+
+        // The references win over the allocations
+        let entries = pending(&conn, JIRA, "PROJ-7", &sessions).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].duration, Duration::hours(2));
+        assert!(pending(&conn, JIRA, "PROJ-9", &sessions)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn push_once() {
+        let (conn, sessions) = work_log();
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+
+        let jira_server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for (i, mut request) in server.incoming_requests().take(3).enumerate() {
+                assert_eq!(request.url(), "/rest/api/2/issue/PROJ-1/worklog");
+                let authorization = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("Authorization"))
+                    .map(|h| h.value.to_string());
+                assert_eq!(authorization.as_deref(), Some("Basic bWU6c2VjcmV0"));
+
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                bodies.push(body);
+                let response = if i < 2 {
+                    tiny_http::Response::from_string(format!("{{\"id\": \"{}\"}}", 100 + i))
+                } else {
+                    tiny_http::Response::from_string(
+                        r#"{"errorMessages": ["Issue does not exist"], "errors": {}}"#,
+                    )
+                    .with_status_code(404)
+                };
+                request.respond(response).unwrap();
+            }
+            bodies
+        });
+
+        let client = Jira::new(&format!("{}/", url), Some("me"), "secret");
+        let worklogs = jira(&conn, &client, "PROJ-1", &sessions).unwrap();
+        assert_eq!(worklogs.len(), 2);
+        assert_eq!(worklogs[1].remote_id.as_deref(), Some("101"));
+        assert_eq!(worklogs[1].seconds, 30 * 60);
+
+        // Already pushed, nothing left to send
+        assert!(jira(&conn, &client, "PROJ-1", &sessions)
+            .unwrap()
+            .is_empty());
+
+        // The whole session is not pushed to another issue, split ones give their share
+        assert!(pending(&conn, JIRA, "PROJ-9", &sessions)
+            .unwrap()
+            .is_empty());
+        let entries = pending(&conn, JIRA, "PROJ-7", &sessions).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].share.as_deref(), Some("PROJ-7"));
+
+        let entry = &pending(&conn, JIRA, "PROJ-7", &sessions).unwrap()[0];
+        let error = client.add_worklog("PROJ-1", entry).unwrap_err();
+        assert!(matches!(error, PushError::Remote(m) if m == "Issue does not exist"));
+
+        let bodies = jira_server.join().unwrap();
+        let first: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(first["started"], "2020-01-01T08:00:00.000+0000");
+        assert_eq!(first["timeSpentSeconds"], 4 * 3600);
+    }
+
+    #[test]
+    fn gitlab_spent_time() {
+        assert!(matches!(
+            GitLab::new("https://gitlab.com/group/project/issues", "t"),
+            Err(PushError::InvalidIssue(_))
+        ));
+
+        let (conn, sessions) = work_log();
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let issue =
+            GitLab::new(&format!("{}/group/sub/project/-/issues/42", url), "secret").unwrap();
+        assert_eq!(issue.reference(), "group/sub/project#42");
+        Allocation::new(StampId(3), "group/sub/project#42", 0.5)
+            .insert(&conn)
+            .unwrap();
+
+        let gitlab_server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for mut request in server.incoming_requests().take(2) {
+                assert_eq!(
+                    request.url(),
+                    "/api/v4/projects/group%2Fsub%2Fproject/issues/42/add_spent_time"
+                );
+                let token = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("PRIVATE-TOKEN"))
+                    .map(|h| h.value.to_string());
+                assert_eq!(token.as_deref(), Some("secret"));
+
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                bodies.push(body);
+                request
+                    .respond(tiny_http::Response::from_string("{}").with_status_code(201))
+                    .unwrap();
+            }
+            bodies
+        });
+
+        let worklogs = gitlab(&conn, &issue, &sessions).unwrap();
+        assert_eq!(worklogs.len(), 2);
+        assert_eq!(worklogs[1].seconds, 3600);
+        assert_eq!(worklogs[1].remote_id, None);
+        assert!(gitlab(&conn, &issue, &sessions).unwrap().is_empty());
+
+        let bodies = gitlab_server.join().unwrap();
+        let first: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(first["duration"], "14400s");
+    }
+}
@@ -0,0 +1,379 @@
+//! Rendering of the reports as documents
+//!
+//! `wtime report` prints plain text by default. The other formats give a
+//! document to share as is, e.g. with a manager.
+
+use crate::config::Config;
+use crate::db::Adjustment;
+use crate::export::ParseFormatError;
+use crate::format;
+use crate::report::Report;
+use crate::site::{bar_chart, escape, hours, STYLE};
+use chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// Output format of a report
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ReportFormat {
+    /// Plain text, for the terminal
+    Text,
+    /// Self-contained HTML page, with a chart of the days
+    Html,
+    /// Markdown tables per week, to paste into wikis or notes
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" | "txt" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Characters of the bars and sparklines of the text reports
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Glyphs {
+    /// Unicode blocks, with eighths for the fractions
+    Unicode,
+    /// ASCII characters only, for limited terminals
+    Ascii,
+}
+
+impl Glyphs {
+    /// Levels of the sparklines, from the lowest
+    fn levels(&self) -> &'static [char] {
+        match self {
+            Glyphs::Unicode => &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+            Glyphs::Ascii => &['_', '.', '-', '~', '=', '+', '*', '#'],
+        }
+    }
+
+    /// Draw a duration as a bar of a character per hour
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - Worked time to draw
+    pub fn bar(&self, duration: &Duration) -> String {
+        let eighths = (hours(duration) * 8.0).round().max(0.0) as usize;
+        match self {
+            Glyphs::Unicode => {
+                let (full, rest) = (eighths / 8, eighths % 8);
+                let mut bar = "█".repeat(full);
+                if rest > 0 {
+                    bar.push([' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'][rest]);
+                }
+                bar
+            }
+            Glyphs::Ascii => "#".repeat((eighths + 4) / 8),
+        }
+    }
+
+    /// Draw durations as a sparkline of a character each
+    ///
+    /// # Arguments
+    ///
+    /// * `durations` - Worked times to draw, the highest getting the top level
+    pub fn sparkline(&self, durations: &[Duration]) -> String {
+        let levels = self.levels();
+        let max = durations
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or_else(Duration::zero);
+        durations
+            .iter()
+            .map(|d| match d.num_seconds() {
+                0 => ' ',
+                seconds => {
+                    let level = seconds * (levels.len() as i64 - 1) / max.num_seconds();
+                    levels[level as usize]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Render a table of hours per day, with a total line
+fn days_table(html: &mut String, days: &BTreeMap<NaiveDate, Duration>) {
+    html.push_str("<table>\n<tr><th>Day</th><th>Hours</th></tr>\n");
+    let mut total = Duration::zero();
+    for (day, duration) in days {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            day.format("%a %Y-%m-%d"),
+            format::hours(duration)
+        );
+        total = total + *duration;
+    }
+    let _ = writeln!(
+        html,
+        "<tr><th>Total</th><th>{}</th></tr>\n</table>",
+        format::hours(&total)
+    );
+}
+
+/// Render a report as a self-contained HTML page
+///
+/// The page holds a bar chart and a table of the days of the report, then
+/// the totals of the projects and a table of the days of every project.
+///
+/// # Arguments
+///
+/// * `user` - Name of the person the report is about
+/// * `report` - Report to render
+/// * `projects` - Worked time per project and per day, see
+///   [crate::report::allocated_days]
+/// * `config` - User settings, giving the timezone of the days
+pub fn html(
+    user: &str,
+    report: &Report,
+    projects: &BTreeMap<String, BTreeMap<NaiveDate, Duration>>,
+    config: &Config,
+) -> String {
+    let first = config.day_of(&report.from);
+    let last = config.day_of(&report.to) - Duration::days(1);
+    let title = format!("Work-log of {}", escape(user));
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+        <style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    let _ = writeln!(
+        html,
+        "<p>From {} to {}: <strong>{} hours</strong></p>",
+        first.format("%a %Y-%m-%d"),
+        last.format("%a %Y-%m-%d"),
+        format::hours(&report.total)
+    );
+
+    let bars: Vec<(String, Duration)> = first
+        .iter_days()
+        .take_while(|day| *day <= last)
+        .map(|day| {
+            let total = report
+                .days
+                .get(&day)
+                .copied()
+                .unwrap_or_else(Duration::zero);
+            (day.format("%d").to_string(), total)
+        })
+        .collect();
+    html.push_str(&bar_chart(&bars));
+    html.push_str("<h2>Days</h2>\n");
+    days_table(&mut html, &report.days);
+
+    if !projects.is_empty() {
+        html.push_str("<h2>Projects</h2>\n");
+        html.push_str("<table>\n<tr><th>Project</th><th>Hours</th><th>Share</th></tr>\n");
+        for (project, days) in projects {
+            let total = days.values().fold(Duration::zero(), |total, d| total + *d);
+            let share = if report.total.is_zero() {
+                0.0
+            } else {
+                total.num_seconds() as f64 * 100.0 / report.total.num_seconds() as f64
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>",
+                escape(project),
+                format::hours(&total),
+                share
+            );
+        }
+        let _ = writeln!(
+            html,
+            "<tr><th>Total</th><th>{}</th><th></th></tr>\n</table>",
+            format::hours(&report.total)
+        );
+
+        for (project, days) in projects {
+            let _ = writeln!(html, "<h3>{}</h3>", escape(project));
+            days_table(&mut html, days);
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Escape the pipes of a markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Render a report as markdown
+///
+/// The days are given in a table per week, followed by the totals of the
+/// projects and the reasons of the adjustments as notes.
+///
+/// # Arguments
+///
+/// * `user` - Name of the person the report is about
+/// * `report` - Report to render
+/// * `projects` - Worked time per project and per day, see
+///   [crate::report::allocated_days]
+/// * `notes` - Adjustments of the days of the report
+/// * `config` - User settings, giving the timezone of the days
+pub fn markdown(
+    user: &str,
+    report: &Report,
+    projects: &BTreeMap<String, BTreeMap<NaiveDate, Duration>>,
+    notes: &[Adjustment],
+    config: &Config,
+) -> String {
+    let first = config.day_of(&report.from);
+    let last = config.day_of(&report.to) - Duration::days(1);
+
+    let mut md = format!("# Work-log of {}\n\n", user);
+    let _ = writeln!(
+        md,
+        "From {} to {}: **{} hours**",
+        first.format("%a %Y-%m-%d"),
+        last.format("%a %Y-%m-%d"),
+        format::hours(&report.total)
+    );
+
+    let mut weeks: BTreeMap<String, BTreeMap<NaiveDate, Duration>> = BTreeMap::new();
+    for (day, total) in &report.days {
+        weeks
+            .entry(day.format("%G-W%V").to_string())
+            .or_default()
+            .insert(*day, *total);
+    }
+    for (week, days) in &weeks {
+        let _ = writeln!(md, "\n## Week {}\n\n| Day | Hours |\n|---|--:|", week);
+        let mut total = Duration::zero();
+        for (day, duration) in days {
+            let _ = writeln!(
+                md,
+                "| {} | {} |",
+                day.format("%a %Y-%m-%d"),
+                format::hours(duration)
+            );
+            total = total + *duration;
+        }
+        let _ = writeln!(md, "| **Total** | **{}** |", format::hours(&total));
+    }
+
+    if !projects.is_empty() {
+        md.push_str("\n## Projects\n\n| Project | Hours | Share |\n|---|--:|--:|\n");
+        for (project, days) in projects {
+            let total = days.values().fold(Duration::zero(), |total, d| total + *d);
+            let share = if report.total.is_zero() {
+                0.0
+            } else {
+                total.num_seconds() as f64 * 100.0 / report.total.num_seconds() as f64
+            };
+            let _ = writeln!(
+                md,
+                "| {} | {} | {:.0}% |",
+                cell(project),
+                format::hours(&total),
+                share
+            );
+        }
+        let _ = writeln!(md, "| **Total** | **{}** | |", format::hours(&report.total));
+    }
+
+    if !notes.is_empty() {
+        md.push_str("\n## Notes\n\n");
+        for note in notes {
+            let _ = writeln!(
+                md,
+                "- {}: {:+} minutes, {}",
+                note.target.format("%a %Y-%m-%d"),
+                note.amount.num_minutes(),
+                note.reason
+            );
+        }
+    }
+
+    md
+}
+
+#[cfg(test)]
+mod test {
+    use super::{html, markdown, Glyphs, ReportFormat};
+    use crate::config::Config;
+    use crate::db::{Adjustment, StampId};
+    use crate::report::{Report, Session};
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn documents() {
+        let date = |s: &str| DateTime::<Utc>::from_str(s).unwrap();
+        let config = Config::default();
+        let sessions = vec![Session {
+            id: StampId(1),
+            start: date("2020-01-01T08:00:00Z"),
+            end: date("2020-01-01T12:00:00Z"),
+        }];
+        let report = Report::new(
+            date("2020-01-01T00:00:00Z"),
+            date("2020-01-04T00:00:00Z"),
+            sessions,
+            &config,
+        );
+        let day = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let projects = BTreeMap::from([
+            (
+                "R&D".to_string(),
+                BTreeMap::from([(day, Duration::hours(3))]),
+            ),
+            (
+                "acme".to_string(),
+                BTreeMap::from([(day, Duration::hours(1))]),
+            ),
+        ]);
+
+        let page = html("jane", &report, &projects, &config);
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<h1>Work-log of jane</h1>"));
+        assert!(page.contains("From Wed 2020-01-01 to Fri 2020-01-03"));
+        // A bar per day of the window
+        assert_eq!(page.matches("<rect").count(), 3);
+        assert!(page.contains("<tr><td>R&amp;D</td><td>3.00</td><td>75%</td></tr>"));
+        assert!(page.contains("<h3>acme</h3>"));
+        assert!(!page.contains("stylesheet"));
+
+        let notes = [Adjustment::new(
+            Duration::minutes(-30),
+            "Long lunch",
+            Some(date("2020-01-02T12:00:00Z")),
+        )];
+        let md = markdown("jane", &report, &projects, &notes, &config);
+        assert!(md.starts_with("# Work-log of jane\n"));
+        assert!(md.contains("## Week 2020-W01\n\n| Day | Hours |\n|---|--:|\n| Wed 2020-01-01 | 4.00 |\n| **Total** | **4.00** |\n"));
+        assert!(md.contains("| acme | 1.00 | 25% |\n"));
+        assert!(md.contains("- Thu 2020-01-02: -30 minutes, Long lunch\n"));
+
+        assert_eq!(ReportFormat::from_str("HTML"), Ok(ReportFormat::Html));
+        assert_eq!(ReportFormat::from_str("md"), Ok(ReportFormat::Markdown));
+        assert!(ReportFormat::from_str("pdf").is_err());
+    }
+
+    #[test]
+    fn bars() {
+        assert_eq!(Glyphs::Unicode.bar(&Duration::minutes(435)), "███████▎");
+        assert_eq!(Glyphs::Unicode.bar(&Duration::hours(2)), "██");
+        assert_eq!(Glyphs::Unicode.bar(&Duration::zero()), "");
+        assert_eq!(Glyphs::Ascii.bar(&Duration::minutes(435)), "#######");
+        assert_eq!(Glyphs::Ascii.bar(&Duration::minutes(450)), "########");
+
+        let week = [8, 4, 0, 1, 6].map(Duration::hours);
+        assert_eq!(Glyphs::Unicode.sparkline(&week), "█▄ ▁▆");
+        assert_eq!(Glyphs::Ascii.sparkline(&week), "#~ _+");
+        assert_eq!(Glyphs::Unicode.sparkline(&[Duration::zero()]), " ");
+    }
+}
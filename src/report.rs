@@ -0,0 +1,1283 @@
+//! Reporting on the work-log
+//!
+//! This module turns the raw stamps into worked sessions and aggregates them
+//! into totals.
+
+use crate::config::Config;
+use crate::db::{Allocation, DbError, Estimate, InOut, Source, Stamp, StampId, User};
+use crate::export::ParseFormatError;
+use crate::storage::{Stamps, Storage};
+use crate::{i18n, tr};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Label of the time not allocated to any issue or project
+pub const UNALLOCATED: &str = "(unallocated)";
+
+/// Label of the time of the sessions without tag
+pub const UNTAGGED: &str = "(untagged)";
+
+/// A worked period, i.e. a check-in followed by a check-out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Session ID, i.e. the ID of the check-in stamp
+    pub id: StampId,
+    /// Check-in time
+    pub start: DateTime<Utc>,
+    /// Check-out time
+    pub end: DateTime<Utc>,
+}
+
+impl Session {
+    /// Worked time of the session
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    /// Day the session is accounted to, i.e. the check-in day in the
+    /// configured timezone
+    pub fn day(&self, config: &Config) -> NaiveDate {
+        config.day_of(&self.start)
+    }
+}
+
+/// Worked time per day over a date window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Lower bound (included) of the window
+    pub from: DateTime<Utc>,
+    /// Upper bound (excluded) of the window
+    pub to: DateTime<Utc>,
+    /// Sessions starting in the window, in id order
+    pub sessions: Vec<Session>,
+    /// Worked time per day having sessions
+    pub days: BTreeMap<NaiveDate, Duration>,
+    /// Worked time over the whole window
+    pub total: Duration,
+    /// Project the report is restricted to, see [Report::for_project]
+    pub project: Option<String>,
+}
+
+impl Report {
+    /// Build the report of the sessions of a date window
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - Lower bound (included) of the window
+    /// * `to` - Upper bound (excluded) of the window
+    /// * `sessions` - Sessions starting in the window, see [sessions]
+    /// * `config` - User settings, giving the timezone of the days
+    pub fn new(
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        sessions: Vec<Session>,
+        config: &Config,
+    ) -> Self {
+        let days = daily_totals(&sessions, config);
+        let total = days
+            .values()
+            .fold(Duration::zero(), |total, day| total + *day);
+        Self {
+            from,
+            to,
+            sessions,
+            days,
+            total,
+            project: None,
+        }
+    }
+
+    /// Restrict the report to the time allocated to a project
+    ///
+    /// Only the sessions allocated to the project or to its nested projects
+    /// are kept, and the days and the total only count their share. The sessions are kept
+    /// whole, as their check-in and check-out are real times.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Work-log storage backend
+    /// * `project` - Issue or project allocated to, [UNALLOCATED] for the
+    ///   time not allocated
+    /// * `config` - User settings, giving the timezone of the days
+    pub fn for_project<S: Storage + ?Sized>(
+        mut self,
+        storage: &S,
+        project: &str,
+        config: &Config,
+    ) -> Result<Self, DbError> {
+        let allocations = session_allocations(storage, &self.sessions)?;
+        let parents = project_parents(storage)?;
+        let mut days: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+        self.sessions.retain(|session| {
+            let session_allocations = allocations.get(&session.id).map_or(&[][..], Vec::as_slice);
+            let allocated = shares(session, session_allocations)
+                .into_iter()
+                .filter(|(target, _)| is_within(target, project, &parents))
+                .fold(Duration::zero(), |total, (_, d)| total + d);
+            if allocated.is_zero() {
+                return false;
+            }
+            let day = days
+                .entry(session.day(config))
+                .or_insert_with(Duration::zero);
+            *day = *day + allocated;
+            true
+        });
+
+        self.total = days
+            .values()
+            .fold(Duration::zero(), |total, day| total + *day);
+        self.days = days;
+        self.project = Some(project.to_string());
+        Ok(self)
+    }
+
+    /// Worked time per allocated issue or project, see [allocated_totals]
+    pub fn allocated<S: Storage + ?Sized>(
+        &self,
+        storage: &S,
+    ) -> Result<BTreeMap<String, Duration>, DbError> {
+        let mut totals = allocated_totals(storage, &self.sessions)?;
+        if let Some(project) = &self.project {
+            let parents = project_parents(storage)?;
+            totals.retain(|target, _| is_within(target, project, &parents));
+        }
+        Ok(totals)
+    }
+
+    /// Worked time per allocated issue or project and per day, see
+    /// [allocated_days]
+    pub fn allocated_days<S: Storage + ?Sized>(
+        &self,
+        storage: &S,
+        config: &Config,
+    ) -> Result<BTreeMap<String, BTreeMap<NaiveDate, Duration>>, DbError> {
+        let mut days = allocated_days(storage, &self.sessions, config)?;
+        if let Some(project) = &self.project {
+            let parents = project_parents(storage)?;
+            days.retain(|target, _| is_within(target, project, &parents));
+        }
+        Ok(days)
+    }
+}
+
+/// Period the worked time is summed over
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Period {
+    /// ISO week, from Monday
+    Week,
+    /// Month, from a day of the month to the day before in the next month,
+    /// named after the month it ends in
+    Month {
+        /// First day of the month, 1 for calendar months, at most 28
+        start_day: u32,
+    },
+}
+
+/// Calendar month, from the 1st
+pub const CALENDAR_MONTH: Period = Period::Month { start_day: 1 };
+
+impl FromStr for Period {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "week" => Ok(Self::Week),
+            "month" => Ok(CALENDAR_MONTH),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+impl Period {
+    /// First day of the period containing a day
+    pub fn start(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => day - Duration::days(day.weekday().num_days_from_monday().into()),
+            Period::Month { start_day } if day.day() >= *start_day => {
+                day.with_day(*start_day).unwrap()
+            }
+            Period::Month { start_day } => (day - Months::new(1)).with_day(*start_day).unwrap(),
+        }
+    }
+
+    /// First day of the period following the one starting on a day
+    pub fn end(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => start + Duration::days(7),
+            Period::Month { .. } => start + Months::new(1),
+        }
+    }
+
+    /// First day of the period named after a calendar month, e.g. the 26th
+    /// of April for May when months start on the 26th
+    ///
+    /// # Arguments
+    ///
+    /// * `month` - First day of the calendar month
+    pub fn of_month(&self, month: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Month { start_day } if *start_day > 1 => {
+                (month - Months::new(1)).with_day(*start_day).unwrap()
+            }
+            _ => self.start(month),
+        }
+    }
+
+    /// Parse a period given as an ISO week (`2024-W23`) or a month
+    /// (`2024-05`)
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The week or month, depending on the kind of period
+    ///
+    /// # Return
+    ///
+    /// The first day of the period, the Monday of an ISO week, see
+    /// [Period::of_month] for a month.
+    pub fn parse(&self, s: &str) -> Result<NaiveDate, ParseFormatError> {
+        /// Parse a number of a count of digits, without sign
+        fn number<T: FromStr>(
+            s: &str,
+            digits: RangeInclusive<usize>,
+        ) -> Result<T, ParseFormatError> {
+            if digits.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit()) {
+                s.parse().map_err(|_| ParseFormatError)
+            } else {
+                Err(ParseFormatError)
+            }
+        }
+
+        let s = s.trim();
+        match self {
+            Period::Week => {
+                let (year, week) = s
+                    .split_once("-W")
+                    .or_else(|| s.split_once("-w"))
+                    .ok_or(ParseFormatError)?;
+                // Years have 52 or 53 ISO weeks, the other ones are refused
+                NaiveDate::from_isoywd_opt(number(year, 4..=4)?, number(week, 1..=2)?, Weekday::Mon)
+                    .ok_or(ParseFormatError)
+            }
+            Period::Month { .. } => {
+                let (year, month) = s.split_once('-').ok_or(ParseFormatError)?;
+                NaiveDate::from_ymd_opt(number(year, 4..=4)?, number(month, 1..=2)?, 1)
+                    .map(|month| self.of_month(month))
+                    .ok_or(ParseFormatError)
+            }
+        }
+    }
+
+    /// Name of the period starting on a day, e.g. `Week 2024-W07`
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Week => tr!("week", week = start.format("%G-W%V").to_string()),
+            Period::Month { .. } => {
+                let month = self.end(start) - Duration::days(1);
+                month.format(&i18n::localize("%B %Y", &month)).to_string()
+            }
+        }
+    }
+}
+
+/// Sum the worked time per issue or project over periods
+///
+/// # Arguments
+///
+/// * `projects` - Worked time per issue or project and per day, see
+///   [allocated_days]
+/// * `period` - Period to sum over
+///
+/// # Return
+///
+/// The worked time per issue or project, by first day of period.
+pub fn period_totals(
+    projects: &BTreeMap<String, BTreeMap<NaiveDate, Duration>>,
+    period: Period,
+) -> BTreeMap<NaiveDate, BTreeMap<String, Duration>> {
+    let mut totals: BTreeMap<NaiveDate, BTreeMap<String, Duration>> = BTreeMap::new();
+    for (project, days) in projects {
+        for (day, worked) in days {
+            let total = totals
+                .entry(period.start(*day))
+                .or_default()
+                .entry(project.clone())
+                .or_insert_with(Duration::zero);
+            *total = *total + *worked;
+        }
+    }
+    totals
+}
+
+/// Worked time per user and per day over a date window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamReport {
+    /// Report of every user, in creation order
+    pub users: Vec<(User, Report)>,
+}
+
+impl TeamReport {
+    /// Days worked by at least one user
+    pub fn days(&self) -> BTreeSet<NaiveDate> {
+        self.users
+            .iter()
+            .flat_map(|(_, report)| report.days.keys().copied())
+            .collect()
+    }
+
+    /// Worked time of all the users on a day
+    pub fn day_total(&self, day: &NaiveDate) -> Duration {
+        self.users
+            .iter()
+            .filter_map(|(_, report)| report.days.get(day))
+            .fold(Duration::zero(), |total, day| total + *day)
+    }
+
+    /// Worked time of all the users over the whole window
+    pub fn total(&self) -> Duration {
+        self.users
+            .iter()
+            .fold(Duration::zero(), |total, (_, report)| total + report.total)
+    }
+}
+
+/// Get the sessions starting in the given date window
+///
+/// A session is only counted when its check-in is directly followed by a
+/// check-out, the same way totals are computed.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `from` - Lower bound (included) of the stamps dates
+/// * `to` - Upper bound (excluded) of the stamps dates
+pub fn sessions<S: Storage + ?Sized>(
+    storage: &S,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+) -> Result<Vec<Session>, DbError> {
+    pair(storage.stamp_range(from, to)?)
+}
+
+/// Get the sessions starting in the given date window, archived ones included
+///
+/// See [sessions].
+pub fn sessions_with_archive<S: Storage + ?Sized>(
+    storage: &S,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+) -> Result<Vec<Session>, DbError> {
+    pair(storage.stamp_range_with_archive(from, to)?)
+}
+
+/// Pair the check-ins directly followed by a check-out into sessions
+fn pair(stamps: Stamps) -> Result<Vec<Session>, DbError> {
+    pair_if(stamps, |_, _| true)
+}
+
+/// Keep only the sessions with a stamp made through a source
+///
+/// The stamps recorded before the sources were are counted as
+/// [Source::Manual].
+///
+/// # Arguments
+///
+/// * `sessions` - Sessions to filter
+/// * `stamps` - Stamps the sessions were paired from, see [sessions]
+/// * `source` - Entry point of the kept sessions
+pub fn retain_source(
+    sessions: &mut Vec<Session>,
+    stamps: Stamps,
+    source: Source,
+) -> Result<(), DbError> {
+    let sourced: BTreeSet<StampId> = pair_if(stamps, |checkin, checkout| {
+        [checkin, checkout]
+            .iter()
+            .any(|stamp| stamp.source.unwrap_or_default() == source)
+    })?
+    .into_iter()
+    .map(|session| session.id)
+    .collect();
+    sessions.retain(|session| sourced.contains(&session.id));
+    Ok(())
+}
+
+/// Pair the check-ins directly followed by a check-out, for which `keep` is
+/// true, into sessions
+fn pair_if(stamps: Stamps, keep: impl Fn(&Stamp, &Stamp) -> bool) -> Result<Vec<Session>, DbError> {
+    let mut sessions = Vec::new();
+    let mut possible_last: Option<Stamp> = None;
+
+    for stamp in stamps {
+        let stamp = stamp?;
+        if let Some(last) = possible_last {
+            if last.in_out == InOut::In && stamp.in_out == InOut::Out && keep(&last, &stamp) {
+                sessions.push(Session {
+                    id: last.id,
+                    start: last.date,
+                    end: stamp.date,
+                });
+            }
+        }
+        possible_last = Some(stamp);
+    }
+
+    Ok(sessions)
+}
+
+/// Sum the sessions per day, in the configured timezone
+pub fn daily_totals(sessions: &[Session], config: &Config) -> BTreeMap<NaiveDate, Duration> {
+    let mut totals = BTreeMap::new();
+    for session in sessions {
+        let total = totals
+            .entry(session.day(config))
+            .or_insert_with(Duration::zero);
+        *total = *total + session.duration();
+    }
+    totals
+}
+
+/// Worked span of a day, as written on a timesheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaySpan {
+    /// First check-in of the day
+    pub start: DateTime<Utc>,
+    /// Last check-out of the day
+    pub end: DateTime<Utc>,
+    /// Worked time of the day
+    pub worked: Duration,
+}
+
+impl DaySpan {
+    /// Time between the first check-in and the last check-out not worked
+    pub fn breaks(&self) -> Duration {
+        self.end - self.start - self.worked
+    }
+}
+
+/// Get the worked span of every day having sessions
+///
+/// # Arguments
+///
+/// * `sessions` - Sessions to gather, in id order
+/// * `config` - User settings, giving the timezone of the days
+pub fn day_spans(sessions: &[Session], config: &Config) -> BTreeMap<NaiveDate, DaySpan> {
+    let mut spans: BTreeMap<NaiveDate, DaySpan> = BTreeMap::new();
+    for session in sessions {
+        spans
+            .entry(session.day(config))
+            .and_modify(|span| {
+                span.start = span.start.min(session.start);
+                span.end = span.end.max(session.end);
+                span.worked = span.worked + session.duration();
+            })
+            .or_insert_with(|| DaySpan {
+                start: session.start,
+                end: session.end,
+                worked: session.duration(),
+            });
+    }
+    spans
+}
+
+/// Get the allocations of sessions, by session ID
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `sessions` - Sessions to get the allocations of, in id order
+pub fn session_allocations<S: Storage + ?Sized>(
+    storage: &S,
+    sessions: &[Session],
+) -> Result<BTreeMap<StampId, Vec<Allocation>>, DbError> {
+    let mut allocations: BTreeMap<StampId, Vec<Allocation>> = BTreeMap::new();
+    let (first, last) = match (sessions.first(), sessions.last()) {
+        (Some(first), Some(last)) => (first.id, last.id),
+        _ => return Ok(allocations),
+    };
+
+    for allocation in storage.allocation_range(first, last)? {
+        allocations
+            .entry(allocation.session)
+            .or_default()
+            .push(allocation);
+    }
+    Ok(allocations)
+}
+
+/// Sum the sessions per issue or project they are allocated to
+///
+/// Time of sessions without [Allocation], or not fully allocated, is
+/// accounted to [UNALLOCATED].
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `sessions` - Sessions to sum, in id order
+pub fn allocated_totals<S: Storage + ?Sized>(
+    storage: &S,
+    sessions: &[Session],
+) -> Result<BTreeMap<String, Duration>, DbError> {
+    let mut totals: BTreeMap<String, Duration> = BTreeMap::new();
+    let allocations = session_allocations(storage, sessions)?;
+
+    for session in sessions {
+        let session_allocations = allocations.get(&session.id).map_or(&[][..], Vec::as_slice);
+        for (target, allocated) in shares(session, session_allocations) {
+            let total = totals.entry(target).or_insert_with(Duration::zero);
+            *total = *total + allocated;
+        }
+    }
+
+    Ok(totals)
+}
+
+/// Sum the sessions per allocated issue or project, then per day
+///
+/// Like [allocated_totals], time not allocated is accounted to
+/// [UNALLOCATED].
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `sessions` - Sessions to sum, in id order
+/// * `config` - User settings, giving the timezone of the days
+pub fn allocated_days<S: Storage + ?Sized>(
+    storage: &S,
+    sessions: &[Session],
+    config: &Config,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, Duration>>, DbError> {
+    let mut totals: BTreeMap<String, BTreeMap<NaiveDate, Duration>> = BTreeMap::new();
+    let allocations = session_allocations(storage, sessions)?;
+
+    for session in sessions {
+        let session_allocations = allocations.get(&session.id).map_or(&[][..], Vec::as_slice);
+        for (target, allocated) in shares(session, session_allocations) {
+            let total = totals
+                .entry(target)
+                .or_default()
+                .entry(session.day(config))
+                .or_insert_with(Duration::zero);
+            *total = *total + allocated;
+        }
+    }
+
+    Ok(totals)
+}
+
+/// Sum the sessions per tag
+///
+/// A session carrying several tags counts in full for each of them, so the
+/// totals may add up to more than the worked time. Sessions without tag are
+/// accounted to [UNTAGGED].
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `sessions` - Sessions to sum, in id order
+pub fn tagged_totals<S: Storage + ?Sized>(
+    storage: &S,
+    sessions: &[Session],
+) -> Result<BTreeMap<String, Duration>, DbError> {
+    let mut totals: BTreeMap<String, Duration> = BTreeMap::new();
+    let (first, last) = match (sessions.first(), sessions.last()) {
+        (Some(first), Some(last)) => (first.id, last.id),
+        _ => return Ok(totals),
+    };
+
+    let mut tags: BTreeMap<StampId, BTreeSet<String>> = BTreeMap::new();
+    for tag in storage.tag_range(first, last)? {
+        tags.entry(tag.session).or_default().insert(tag.name);
+    }
+
+    for session in sessions {
+        let names = match tags.remove(&session.id) {
+            Some(names) => names,
+            None => BTreeSet::from([UNTAGGED.to_string()]),
+        };
+        for name in names {
+            let total = totals.entry(name).or_insert_with(Duration::zero);
+            *total = *total + session.duration();
+        }
+    }
+
+    Ok(totals)
+}
+
+/// Path of the parent of every nested project, by project path
+pub type Parents = BTreeMap<String, String>;
+
+/// Get the parent of every nested project, see [Project](crate::db::Project)
+///
+/// Storage backends without projects have no nested project.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+pub fn project_parents<S: Storage + ?Sized>(storage: &S) -> Result<Parents, DbError> {
+    let projects = match storage.projects() {
+        Ok(projects) => projects,
+        Err(DbError::Unsupported(_)) => return Ok(Parents::new()),
+        Err(e) => return Err(e),
+    };
+    let names: BTreeMap<i64, &str> = projects.iter().map(|p| (p.id, p.name.as_str())).collect();
+    Ok(projects
+        .iter()
+        .filter_map(|project| {
+            let parent = names.get(&project.parent?)?;
+            Some((project.name.clone(), parent.to_string()))
+        })
+        .collect())
+}
+
+/// Get the ancestors of a project, from its parent to the top of the hierarchy
+///
+/// # Arguments
+///
+/// * `project` - Issue or project path
+/// * `parents` - Parent of every nested project, see [project_parents]
+pub fn ancestors<'a>(project: &str, parents: &'a Parents) -> impl Iterator<Item = &'a str> {
+    std::iter::successors(parents.get(project).map(String::as_str), |parent| {
+        parents.get(*parent).map(String::as_str)
+    })
+}
+
+/// Whether an allocation target is a project or one of its nested projects
+///
+/// # Arguments
+///
+/// * `target` - Issue or project path
+/// * `project` - Project path
+/// * `parents` - Parent of every nested project, see [project_parents]
+pub fn is_within(target: &str, project: &str, parents: &Parents) -> bool {
+    target == project || ancestors(target, parents).any(|ancestor| ancestor == project)
+}
+
+/// Add the worked time of the nested projects to all their ancestors
+///
+/// # Arguments
+///
+/// * `totals` - Worked time per issue or project, see [allocated_totals]
+/// * `parents` - Parent of every nested project, see [project_parents]
+pub fn roll_up(
+    totals: &BTreeMap<String, Duration>,
+    parents: &Parents,
+) -> BTreeMap<String, Duration> {
+    let mut rolled = totals.clone();
+    for (project, total) in totals {
+        for ancestor in ancestors(project, parents) {
+            let rolled = rolled
+                .entry(ancestor.to_string())
+                .or_insert_with(Duration::zero);
+            *rolled = *rolled + *total;
+        }
+    }
+    rolled
+}
+
+/// Order projects as a tree, every project following its parent
+///
+/// # Arguments
+///
+/// * `projects` - Issue or project paths, usually the ancestors of the
+///   nested ones included, see [roll_up]
+/// * `parents` - Parent of every nested project, see [project_parents]
+///
+/// # Return
+///
+/// The depth in the tree, 0 at the top, and the path of every project. Only
+/// the ancestors among `projects` count in the depth.
+pub fn project_tree<'a>(
+    projects: impl IntoIterator<Item = &'a String>,
+    parents: &Parents,
+) -> Vec<(usize, &'a str)> {
+    let projects: BTreeSet<&str> = projects.into_iter().map(String::as_str).collect();
+    let mut tree: Vec<(Vec<&str>, &str)> = projects
+        .iter()
+        .map(|&project| {
+            let mut lineage: Vec<&str> = ancestors(project, parents)
+                .filter(|ancestor| projects.contains(ancestor))
+                .collect();
+            lineage.reverse();
+            lineage.push(project);
+            (lineage, project)
+        })
+        .collect();
+    tree.sort();
+    tree.into_iter()
+        .map(|(lineage, project)| (lineage.len() - 1, project))
+        .collect()
+}
+
+/// Consumed share of a project time budget from which it is warned about
+pub const BUDGET_WARNING: f64 = 0.8;
+
+/// Consumption of a project time budget, see [budget_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetUse {
+    /// Issue or project of the budget
+    pub project: String,
+    /// Budgeted time
+    pub budget: Duration,
+    /// Time allocated to the project and its nested projects, archived
+    /// sessions included
+    pub used: Duration,
+}
+
+impl BudgetUse {
+    /// Budgeted time left, negative once exceeded
+    pub fn remaining(&self) -> Duration {
+        self.budget - self.used
+    }
+
+    /// Consumed share of the budget, above 1 once exceeded
+    pub fn ratio(&self) -> f64 {
+        if self.budget <= Duration::zero() {
+            return f64::INFINITY;
+        }
+        self.used.num_seconds() as f64 / self.budget.num_seconds() as f64
+    }
+
+    /// Whether the consumption reached [BUDGET_WARNING]
+    pub fn warned(&self) -> bool {
+        self.ratio() >= BUDGET_WARNING
+    }
+}
+
+/// Sum the time allocated to the budgeted projects over the whole work-log
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `budgets` - Time budget of each issue or project
+pub fn budget_use<S: Storage + ?Sized>(
+    storage: &S,
+    budgets: &BTreeMap<String, Duration>,
+) -> Result<Vec<BudgetUse>, DbError> {
+    if budgets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sessions = sessions_with_archive(
+        storage,
+        &DateTime::<Utc>::MIN_UTC,
+        &DateTime::<Utc>::MAX_UTC,
+    )?;
+    let totals = roll_up(
+        &allocated_totals(storage, &sessions)?,
+        &project_parents(storage)?,
+    );
+    Ok(budgets
+        .iter()
+        .map(|(project, budget)| BudgetUse {
+            project: project.clone(),
+            budget: *budget,
+            used: totals.get(project).copied().unwrap_or_else(Duration::zero),
+        })
+        .collect())
+}
+
+/// Actual time of an [Estimate], see [estimate_actuals]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actual {
+    /// Estimate of the project or tag
+    pub estimate: Estimate,
+    /// Time allocated to the project and its nested projects, or worked in
+    /// the tagged sessions, archived sessions included
+    pub actual: Duration,
+}
+
+impl Actual {
+    /// Time spent beyond the estimate, negative when below it
+    pub fn variance(&self) -> Duration {
+        self.actual - self.estimate.estimate
+    }
+}
+
+/// Sum the actual time of estimated projects and tags over the whole work-log
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `estimates` - Estimates to compare with, see [Estimate::all]
+pub fn estimate_actuals<S: Storage + ?Sized>(
+    storage: &S,
+    estimates: Vec<Estimate>,
+) -> Result<Vec<Actual>, DbError> {
+    if estimates.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sessions = sessions_with_archive(
+        storage,
+        &DateTime::<Utc>::MIN_UTC,
+        &DateTime::<Utc>::MAX_UTC,
+    )?;
+    let projects = roll_up(
+        &allocated_totals(storage, &sessions)?,
+        &project_parents(storage)?,
+    );
+    let tags = if estimates.iter().any(|estimate| estimate.tag) {
+        tagged_totals(storage, &sessions)?
+    } else {
+        BTreeMap::new()
+    };
+    Ok(estimates
+        .into_iter()
+        .map(|estimate| {
+            let totals = if estimate.tag { &tags } else { &projects };
+            let actual = totals
+                .get(&estimate.name)
+                .copied()
+                .unwrap_or_else(Duration::zero);
+            Actual { estimate, actual }
+        })
+        .collect())
+}
+
+/// Split the worked time of a session between its allocations
+///
+/// Time not allocated is accounted to [UNALLOCATED].
+///
+/// # Arguments
+///
+/// * `session` - Session to split
+/// * `allocations` - Allocations of the session
+pub fn shares(session: &Session, allocations: &[Allocation]) -> Vec<(String, Duration)> {
+    let duration = session.duration();
+    let mut remaining = duration;
+    let mut shares = Vec::new();
+
+    for allocation in allocations {
+        let allocated =
+            Duration::seconds((duration.num_seconds() as f64 * allocation.fraction).round() as i64)
+                .min(remaining);
+        shares.push((allocation.target.clone(), allocated));
+        remaining = remaining - allocated;
+    }
+
+    if remaining > Duration::zero() {
+        shares.push((UNALLOCATED.to_string(), remaining));
+    }
+    shares
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        allocated_days, allocated_totals, budget_use, daily_totals, day_spans, estimate_actuals,
+        period_totals, project_parents, project_tree, retain_source, roll_up, sessions,
+        tagged_totals, Period, Report, Session, CALENDAR_MONTH, UNALLOCATED, UNTAGGED,
+    };
+    use crate::config::Config;
+    use crate::db::{self, Allocation, Estimate, InOut, Source, Stamp, StampId, Tag};
+    use crate::storage::Storage;
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    fn session(start: &str, end: &str) -> Session {
+        Session {
+            id: StampId(0),
+            start: DateTime::<Utc>::from_str(start).unwrap(),
+            end: DateTime::<Utc>::from_str(end).unwrap(),
+        }
+    }
+
+    #[test]
+    fn daily() {
+        let totals = daily_totals(
+            &[
+                session("2020-01-01T08:00:00Z", "2020-01-01T12:00:00Z"),
+                session("2020-01-01T13:00:00Z", "2020-01-01T17:30:00Z"),
+                session("2020-01-03T08:00:00Z", "2020-01-03T09:00:00Z"),
+            ],
+            &Config::default(),
+        );
+
+        assert_eq!(totals.len(), 2);
+        assert_eq!(
+            totals[&NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()],
+            Duration::minutes(8 * 60 + 30)
+        );
+        assert_eq!(
+            totals[&NaiveDate::from_ymd_opt(2020, 1, 3).unwrap()],
+            Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let spans = day_spans(
+            &[
+                session("2020-01-01T08:00:00Z", "2020-01-01T12:00:00Z"),
+                session("2020-01-01T13:00:00Z", "2020-01-01T17:30:00Z"),
+                session("2020-01-03T08:00:00Z", "2020-01-03T09:00:00Z"),
+            ],
+            &Config::default(),
+        );
+
+        let first = &spans[&NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()];
+        assert_eq!(first.start.to_rfc3339(), "2020-01-01T08:00:00+00:00");
+        assert_eq!(first.end.to_rfc3339(), "2020-01-01T17:30:00+00:00");
+        assert_eq!(first.worked, Duration::minutes(8 * 60 + 30));
+        assert_eq!(first.breaks(), Duration::hours(1));
+        assert_eq!(
+            spans[&NaiveDate::from_ymd_opt(2020, 1, 3).unwrap()].breaks(),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn zoned_days() {
+        // 00:30 to 02:00 in Zurich, 23:00 to 01:00 in New York
+        let late = [
+            session("2020-01-01T23:30:00Z", "2020-01-02T01:00:00Z"),
+            session("2020-01-02T04:00:00Z", "2020-01-02T06:00:00Z"),
+        ];
+        let day = |d| NaiveDate::from_ymd_opt(2020, 1, d).unwrap();
+
+        let zurich = Config {
+            timezone: chrono_tz::Europe::Zurich,
+            ..Config::default()
+        };
+        let report = Report::new(
+            zurich.start_of_day(day(2)),
+            zurich.start_of_day(day(3)),
+            late.to_vec(),
+            &zurich,
+        );
+        assert_eq!(report.from.to_rfc3339(), "2020-01-01T23:00:00+00:00");
+        assert_eq!(report.days.len(), 1);
+        assert_eq!(
+            report.days[&day(2)],
+            Duration::hours(3) + Duration::minutes(30)
+        );
+
+        let new_york = Config {
+            timezone: chrono_tz::America::New_York,
+            ..Config::default()
+        };
+        let totals = daily_totals(&late, &new_york);
+        assert_eq!(totals[&day(1)], Duration::hours(3) + Duration::minutes(30));
+        assert!(!totals.contains_key(&day(2)));
+        let spans = day_spans(&late, &new_york);
+        assert_eq!(spans[&day(1)].breaks(), Duration::hours(3));
+    }
+
+    #[test]
+    fn allocated() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T12:00:00Z", InOut::Out),
+            ("2020-01-01T13:00:00Z", InOut::In),
+            ("2020-01-01T15:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+        Allocation::new(StampId(1), "PROJ-1", 0.75)
+            .insert(&conn)
+            .unwrap();
+        Allocation::new(StampId(3), "PROJ-1", 0.5)
+            .insert(&conn)
+            .unwrap();
+        Allocation::new(StampId(3), "PROJ-7", 0.5)
+            .insert(&conn)
+            .unwrap();
+
+        let sessions = sessions(
+            &conn,
+            &DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap(),
+            &DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap(),
+        )
+        .unwrap();
+        let totals = allocated_totals(&conn, &sessions).unwrap();
+
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals["PROJ-1"], Duration::hours(4));
+        assert_eq!(totals["PROJ-7"], Duration::hours(1));
+        assert_eq!(totals[UNALLOCATED], Duration::hours(1));
+
+        let report = Report::new(
+            DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap(),
+            DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap(),
+            sessions,
+            &Config::default(),
+        );
+        let report = report
+            .for_project(&conn, "PROJ-7", &Config::default())
+            .unwrap();
+        assert_eq!(report.sessions.len(), 1);
+        assert_eq!(report.sessions[0].id, StampId(3));
+        assert_eq!(report.total, Duration::hours(1));
+        let totals = report.allocated(&conn).unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals["PROJ-7"], Duration::hours(1));
+    }
+
+    #[test]
+    fn budgets_and_estimates() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T12:00:00Z", InOut::Out),
+            ("2020-01-02T08:00:00Z", InOut::In),
+            ("2020-01-02T10:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+        Allocation::new(StampId(1), "acme", 1.0)
+            .insert(&conn)
+            .unwrap();
+        Allocation::new(StampId(3), "acme", 0.5)
+            .insert(&conn)
+            .unwrap();
+
+        let budgets = BTreeMap::from([
+            ("acme".to_string(), Duration::hours(5)),
+            ("globex".to_string(), Duration::hours(10)),
+        ]);
+        let uses = budget_use(&conn, &budgets).unwrap();
+        assert_eq!(uses.len(), 2);
+        assert_eq!(uses[0].project, "acme");
+        assert_eq!(uses[0].used, Duration::hours(5));
+        assert_eq!(uses[0].remaining(), Duration::zero());
+        assert_eq!(uses[0].ratio(), 1.0);
+        assert!(uses[0].warned());
+        assert_eq!(uses[1].used, Duration::zero());
+        assert!(!uses[1].warned());
+        assert!(budget_use(&conn, &BTreeMap::new()).unwrap().is_empty());
+
+        Tag::new(StampId(3), "review").insert(&conn).unwrap();
+        let actuals = estimate_actuals(
+            &conn,
+            vec![
+                Estimate::new(false, "acme", Duration::hours(8)),
+                Estimate::new(true, "review", Duration::hours(1)),
+                Estimate::new(true, "acme", Duration::hours(1)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(actuals[0].actual, Duration::hours(5));
+        assert_eq!(actuals[0].variance(), Duration::hours(-3));
+        assert_eq!(actuals[1].actual, Duration::hours(2));
+        assert_eq!(actuals[1].variance(), Duration::hours(1));
+        assert_eq!(actuals[2].actual, Duration::zero());
+    }
+
+    #[test]
+    fn nested_projects() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T10:00:00Z", InOut::Out),
+            ("2020-01-01T13:00:00Z", InOut::In),
+            ("2020-01-01T14:00:00Z", InOut::Out),
+            ("2020-01-01T15:00:00Z", InOut::In),
+            ("2020-01-01T18:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+        for (session, target) in [(1, "acme/backend/api"), (3, "acme/web"), (5, "acme-labs")] {
+            Allocation::new(StampId(session), target, 1.0)
+                .insert(&conn)
+                .unwrap();
+        }
+
+        let parents = project_parents(&conn).unwrap();
+        assert_eq!(parents.len(), 3);
+        assert_eq!(parents["acme/backend/api"], "acme/backend");
+
+        let sessions = sessions(
+            &conn,
+            &DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap(),
+            &DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap(),
+        )
+        .unwrap();
+        let totals = roll_up(&allocated_totals(&conn, &sessions).unwrap(), &parents);
+        assert_eq!(totals["acme"], Duration::hours(3));
+        assert_eq!(totals["acme/backend"], Duration::hours(2));
+        assert_eq!(totals["acme-labs"], Duration::hours(3));
+        assert_eq!(
+            project_tree(totals.keys(), &parents),
+            [
+                (0, "acme"),
+                (1, "acme/backend"),
+                (2, "acme/backend/api"),
+                (1, "acme/web"),
+                (0, "acme-labs")
+            ]
+        );
+
+        let report = Report::new(
+            DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap(),
+            DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap(),
+            sessions,
+            &Config::default(),
+        )
+        .for_project(&conn, "acme", &Config::default())
+        .unwrap();
+        assert_eq!(report.sessions.len(), 2);
+        assert_eq!(report.total, Duration::hours(3));
+        assert_eq!(report.allocated(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn tagged() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out) in [
+            ("2020-01-01T08:00:00Z", InOut::In),
+            ("2020-01-01T10:00:00Z", InOut::Out),
+            ("2020-01-01T13:00:00Z", InOut::In),
+            ("2020-01-01T14:00:00Z", InOut::Out),
+            ("2020-01-01T15:00:00Z", InOut::In),
+            ("2020-01-01T18:00:00Z", InOut::Out),
+        ] {
+            Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out)
+                .insert(&conn)
+                .unwrap();
+        }
+        Tag::new(StampId(1), "billable").insert(&conn).unwrap();
+        Tag::new(StampId(1), "meeting").insert(&conn).unwrap();
+        Tag::new(StampId(3), "meeting").insert(&conn).unwrap();
+
+        let sessions = sessions(
+            &conn,
+            &DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap(),
+            &DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap(),
+        )
+        .unwrap();
+        let totals = tagged_totals(&conn, &sessions).unwrap();
+
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals["billable"], Duration::hours(2));
+        assert_eq!(totals["meeting"], Duration::hours(3));
+        assert_eq!(totals[UNTAGGED], Duration::hours(3));
+        assert!(tagged_totals(&conn, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sourced() {
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+
+        for (date, in_out, source) in [
+            ("2020-01-01T08:00:00Z", InOut::In, None),
+            ("2020-01-01T10:00:00Z", InOut::Out, Some(Source::Manual)),
+            ("2020-01-01T13:00:00Z", InOut::In, Some(Source::Manual)),
+            ("2020-01-01T14:00:00Z", InOut::Out, Some(Source::Daemon)),
+            ("2020-01-01T15:00:00Z", InOut::In, Some(Source::Import)),
+            ("2020-01-01T18:00:00Z", InOut::Out, Some(Source::Import)),
+        ] {
+            let mut stamp =
+                Stamp::new(StampId(0), DateTime::<Utc>::from_str(date).unwrap(), in_out);
+            stamp.source = source;
+            stamp.insert(&conn).unwrap();
+        }
+
+        let from = DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap();
+        let to = DateTime::<Utc>::from_str("2020-01-02T00:00:00Z").unwrap();
+        let retained = |source| {
+            let mut sessions = sessions(&conn, &from, &to).unwrap();
+            retain_source(&mut sessions, conn.stamp_range(&from, &to).unwrap(), source).unwrap();
+            sessions.iter().map(|s| s.id.0).collect::<Vec<_>>()
+        };
+
+        // Unknown sources count as manual, a session has both its sources
+        assert_eq!(retained(Source::Manual), [1, 3]);
+        assert_eq!(retained(Source::Daemon), [3]);
+        assert_eq!(retained(Source::Import), [5]);
+        assert!(retained(Source::Api).is_empty());
+    }
+
+    #[test]
+    fn periods() {
+        let sessions = [
+            session("2024-01-31T08:00:00Z", "2024-01-31T12:00:00Z"),
+            session("2024-02-01T08:00:00Z", "2024-02-01T10:00:00Z"),
+            session("2024-02-05T08:00:00Z", "2024-02-05T09:00:00Z"),
+        ];
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+        let projects = allocated_days(&conn, &sessions, &Config::default()).unwrap();
+
+        let weeks = period_totals(&projects, Period::Week);
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 29).unwrap();
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[&monday][UNALLOCATED], Duration::hours(6));
+        assert_eq!(Period::Week.label(monday), "Week 2024-W05");
+
+        let months = period_totals(&projects, CALENDAR_MONTH);
+        let february = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        assert_eq!(months[&february][UNALLOCATED], Duration::hours(3));
+        assert_eq!(CALENDAR_MONTH.label(february), "February 2024");
+        assert_eq!(Period::from_str("Month"), Ok(CALENDAR_MONTH));
+    }
+
+    #[test]
+    fn period_selectors() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(Period::Week.parse("2024-W23"), Ok(day(2024, 6, 3)));
+        assert_eq!(Period::Week.parse("2024-w5"), Ok(day(2024, 1, 29)));
+        // ISO weeks straddle the years
+        assert_eq!(Period::Week.parse("2021-W01"), Ok(day(2021, 1, 4)));
+        assert_eq!(Period::Week.parse("2026-W01"), Ok(day(2025, 12, 29)));
+        assert_eq!(Period::Week.parse("2020-W53"), Ok(day(2020, 12, 28)));
+        assert_eq!(Period::Week.end(day(2020, 12, 28)), day(2021, 1, 4));
+        assert!(Period::Week.parse("2021-W53").is_err());
+        assert!(Period::Week.parse("2024-W00").is_err());
+        assert!(Period::Week.parse("2024-W+1").is_err());
+        assert!(Period::Week.parse("2024-05").is_err());
+
+        assert_eq!(CALENDAR_MONTH.parse("2024-05"), Ok(day(2024, 5, 1)));
+        assert_eq!(CALENDAR_MONTH.parse("2024-2"), Ok(day(2024, 2, 1)));
+        assert_eq!(CALENDAR_MONTH.end(day(2024, 2, 1)), day(2024, 3, 1));
+        assert_eq!(CALENDAR_MONTH.end(day(2024, 12, 1)), day(2025, 1, 1));
+        assert!(CALENDAR_MONTH.parse("2024-13").is_err());
+        assert!(CALENDAR_MONTH.parse("24-05").is_err());
+        assert!(CALENDAR_MONTH.parse("2024-W23").is_err());
+    }
+
+    #[test]
+    fn billing_months() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let billing = Period::Month { start_day: 26 };
+
+        // May runs from the 26th of April to the 25th of May
+        assert_eq!(billing.parse("2024-05"), Ok(day(2024, 4, 26)));
+        assert_eq!(billing.end(day(2024, 4, 26)), day(2024, 5, 26));
+        assert_eq!(billing.label(day(2024, 4, 26)), "May 2024");
+        assert_eq!(billing.start(day(2024, 5, 25)), day(2024, 4, 26));
+        assert_eq!(billing.start(day(2024, 5, 26)), day(2024, 5, 26));
+        assert_eq!(billing.parse("2025-01"), Ok(day(2024, 12, 26)));
+        assert_eq!(billing.start(day(2025, 1, 1)), day(2024, 12, 26));
+
+        let sessions = [
+            session("2024-05-25T08:00:00Z", "2024-05-25T12:00:00Z"),
+            session("2024-05-26T08:00:00Z", "2024-05-26T10:00:00Z"),
+        ];
+        let conn = db::open_memory().unwrap();
+        db::init(&conn).unwrap();
+        let projects = allocated_days(&conn, &sessions, &Config::default()).unwrap();
+        let months = period_totals(&projects, billing);
+        assert_eq!(months[&day(2024, 4, 26)][UNALLOCATED], Duration::hours(4));
+        assert_eq!(months[&day(2024, 5, 26)][UNALLOCATED], Duration::hours(2));
+    }
+}
@@ -0,0 +1,603 @@
+//! HTTP API of the work-log
+//!
+//! `wtime serve` answers JSON requests so that stamping can be done from a
+//! phone or another tool on the network:
+//!
+//! | Method | Path        | Body                      | Answer                               |
+//! |--------|-------------|---------------------------|--------------------------------------|
+//! | GET    | `/status`   |                           | [Status]                             |
+//! | POST   | `/checkin`  |                           | The check-in [Stamp](crate::db::Stamp) |
+//! | POST   | `/checkout` | `{"split": "60% PROJ-1"}` | [CheckoutSummary](crate::app::CheckoutSummary) |
+//! | GET    | `/report`   |                           | Worked time per day, see [report_json] |
+//! | GET    | `/stamps`   |                           | The stamps of the window             |
+//! | PUT    | `/stamps/<id>` | `{"date": "2020-01-01T08:00:00Z", "in_out": "In"}` | The changed stamp |
+//! | GET    | `/sync`     |                           | [Pull](crate::sync::Pull)            |
+//! | POST   | `/sync`     | [Bundle](crate::sync::Bundle) | [Merge](crate::sync::Merge)      |
+//!
+//! The report and stamps window is given by the `from` and `to` query
+//! parameters (`YYYY-MM-DD`). `GET /sync` only answers the stamps updated
+//! since the `since` query parameter (Unix seconds), see [crate::sync]. Every request must carry the configured token
+//! in an `Authorization: Bearer <token>` header, and a body of at most
+//! [MAX_BODY_BYTES]. Errors are answered as `{"error": "<message>"}`.
+//!
+//! `GET /` answers the [DASHBOARD] page, a web UI built on this API, and
+//! `GET /metrics` the Prometheus metrics, see [crate::metrics].
+
+use crate::app::{App, Status};
+use crate::db::{InOut, StampId};
+use crate::report::Report;
+use crate::storage::Storage;
+use crate::{metrics, sync};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use tracing::{info, warn};
+
+/// Dashboard page showing the current session, charts and the stamps
+///
+/// It asks for the API token and keeps it in the browser local storage.
+pub const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Days listed by `GET /stamps` when no window is given
+const DEFAULT_STAMPS_DAYS: i64 = 7;
+
+/// Shortest `api_token` accepted, see [check_token]
+pub const MIN_TOKEN_LENGTH: usize = 16;
+
+/// Largest request body accepted, a sync bundle of years of stamps fits
+pub const MAX_BODY_BYTES: usize = 4 << 20;
+
+/// Check that the token is not too easy to guess
+///
+/// # Arguments
+///
+/// * `token` - The `api_token` of the configuration
+pub fn check_token(token: &str) -> Result<()> {
+    if token.trim().chars().count() < MIN_TOKEN_LENGTH {
+        return Err(anyhow!(
+            "`api_token` must have at least {} characters, e.g. from `openssl rand -hex 32`",
+            MIN_TOKEN_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// Request received by the API, independent of the HTTP server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request {
+    /// HTTP method, e.g. `GET`
+    pub method: String,
+    /// Path and query, e.g. `/report?from=2020-01-01`
+    pub url: String,
+    /// Value of the `Authorization` header, if any
+    pub authorization: Option<String>,
+    /// Request body, empty if none
+    pub body: String,
+}
+
+/// Answer to a [Request]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    /// HTTP status code
+    pub status: u16,
+    /// JSON body
+    pub body: Value,
+}
+
+impl Reply {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+/// Body of a `POST /checkout`, every field being optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CheckoutBody {
+    /// Split specification, see [crate::allocation::parse_split]
+    split: Option<String>,
+}
+
+/// Body of a `PUT /stamps/<id>`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StampBody {
+    /// New date of the stamp
+    date: DateTime<Utc>,
+    /// New direction of the stamp
+    in_out: InOut,
+}
+
+/// Compare two secrets in a time independent of where they differ
+fn same_secret(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Get the value of a query parameter
+fn query_value<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Get the value of a `YYYY-MM-DD` query parameter, as the beginning of that
+/// day in the configured timezone
+fn query_date<S: Storage>(app: &App<S>, query: &str, name: &str) -> Result<Option<DateTime<Utc>>> {
+    match query_value(query, name) {
+        None => Ok(None),
+        Some(value) => {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .with_context(|| format!("Invalid `{}` date, expected YYYY-MM-DD", name))?;
+            Ok(Some(app.start_of_day(date)))
+        }
+    }
+}
+
+/// Get the value of a Unix seconds query parameter
+fn query_timestamp(query: &str, name: &str) -> Result<Option<DateTime<Utc>>> {
+    query_value(query, name)
+        .map(|value| {
+            value
+                .parse::<i64>()
+                .ok()
+                .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+                .ok_or_else(|| anyhow!("Invalid `{}` date, expected Unix seconds", name))
+        })
+        .transpose()
+}
+
+/// Represent a report as JSON, durations being in seconds
+pub fn report_json(report: &Report) -> Value {
+    json!({
+        "from": report.from,
+        "to": report.to,
+        "sessions": report.sessions,
+        "days": report
+            .days
+            .iter()
+            .map(|(day, total)| (day.to_string(), json!(total.num_seconds())))
+            .collect::<serde_json::Map<_, _>>(),
+        "total": report.total.num_seconds(),
+    })
+}
+
+/// Answer an API request
+///
+/// # Arguments
+///
+/// * `app` - Application the requests are run on
+/// * `token` - Token the requests must carry
+/// * `request` - Request to answer
+pub fn handle<S: Storage>(app: &App<S>, token: &str, request: &Request) -> Reply {
+    if !authorized(request, token) {
+        return Reply::error(401, "Missing or invalid bearer token");
+    }
+    if request.body.len() > MAX_BODY_BYTES {
+        return Reply::error(
+            413,
+            format!("Request body larger than {} bytes", MAX_BODY_BYTES),
+        );
+    }
+
+    let (path, query) = request
+        .url
+        .split_once('?')
+        .unwrap_or((request.url.as_str(), ""));
+    let result = match (request.method.as_str(), path) {
+        ("GET", "/status") => app.status().map(|status: Status| json!(status)),
+        ("POST", "/checkin") => {
+            // Recorded in the audit log as the origin of the modifications
+            app.set_command(&format!("serve: {} {}", request.method, path))
+                .and_then(|_| app.checkin(false, None))
+                .map(|stamp| json!(stamp))
+        }
+        ("POST", "/checkout") => {
+            let body = if request.body.trim().is_empty() {
+                Ok(CheckoutBody::default())
+            } else {
+                serde_json::from_str::<CheckoutBody>(&request.body)
+                    .map_err(|e| anyhow!("Invalid checkout body: {}", e))
+            };
+            body.and_then(|body| {
+                app.set_command(&format!("serve: {} {}", request.method, path))?;
+                app.checkout(body.split.as_deref())
+            })
+            .map(|summary| json!(summary))
+        }
+        ("GET", "/report") => query_date(app, query, "from")
+            .and_then(|from| Ok((from, query_date(app, query, "to")?)))
+            .and_then(|(from, to)| app.report(from, to, false))
+            .map(|report| report_json(&report)),
+        ("GET", "/stamps") => query_date(app, query, "from")
+            .and_then(|from| Ok((from, query_date(app, query, "to")?)))
+            .and_then(|(from, to)| {
+                let now = Utc::now();
+                app.stamps(
+                    &from.unwrap_or(now - Duration::days(DEFAULT_STAMPS_DAYS)),
+                    &to.unwrap_or(now + Duration::days(1)),
+                )
+            })
+            .map(|stamps| json!(stamps)),
+        ("PUT", _) if path.starts_with("/stamps/") => {
+            let id = path["/stamps/".len()..]
+                .parse::<i64>()
+                .map_err(|_| anyhow!("Invalid stamp id"));
+            let body = serde_json::from_str::<StampBody>(&request.body)
+                .map_err(|e| anyhow!("Invalid stamp body: {}", e));
+            id.and_then(|id| Ok((id, body?)))
+                .and_then(|(id, body)| {
+                    app.set_command(&format!("serve: {} {}", request.method, path))?;
+                    app.edit_stamp(StampId(id), body.date, body.in_out)
+                })
+                .map(|stamp| json!(stamp))
+        }
+        ("GET", "/sync") => query_timestamp(query, "since")
+            .and_then(|since| app.sync_pull(since.as_ref()))
+            .map(|pull| json!(pull)),
+        ("POST", "/sync") => serde_json::from_str::<sync::Bundle>(&request.body)
+            .map_err(|e| anyhow!("Invalid sync bundle: {}", e))
+            .and_then(|bundle| {
+                app.set_command(&format!("serve: {} {}", request.method, path))?;
+                app.sync_push(&bundle)
+            })
+            .map(|merge| json!(merge)),
+        (_, "/status" | "/checkin" | "/checkout" | "/report" | "/stamps" | "/sync") => {
+            return Reply::error(
+                405,
+                format!("{} is not allowed on {}", request.method, path),
+            )
+        }
+        _ if path.starts_with("/stamps/") => {
+            return Reply::error(
+                405,
+                format!("{} is not allowed on {}", request.method, path),
+            )
+        }
+        _ => return Reply::error(404, format!("No such endpoint {}", path)),
+    };
+
+    match result {
+        Ok(body) => Reply::ok(body),
+        Err(e) => Reply::error(400, format!("{:#}", e)),
+    }
+}
+
+/// Whether a request carries the token as bearer token
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| same_secret(given.trim(), token))
+}
+
+/// Read a request received by the HTTP server
+///
+/// The body is read up to one byte over [MAX_BODY_BYTES], for [handle] to
+/// refuse it.
+fn read_request(http: &mut tiny_http::Request) -> Request {
+    let mut bytes = Vec::new();
+    if let Err(e) = http
+        .as_reader()
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut bytes)
+    {
+        warn!("Unreadable request body: {}", e);
+    }
+    let body = match String::from_utf8(bytes) {
+        Ok(body) => body,
+        // Cut in the middle of a character, refused anyway
+        Err(e) if e.as_bytes().len() > MAX_BODY_BYTES => {
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+        Err(e) => {
+            warn!("Unreadable request body: {}", e);
+            String::new()
+        }
+    };
+    Request {
+        method: http.method().as_str().to_uppercase(),
+        url: http.url().to_string(),
+        authorization: http
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .map(|h| h.value.to_string()),
+        body,
+    }
+}
+
+/// Answer the body of a reply
+fn respond(
+    http: tiny_http::Request,
+    request: &Request,
+    status: u16,
+    body: String,
+    content_type: &str,
+) {
+    let response = tiny_http::Response::from_string(body)
+        .with_status_code(status)
+        .with_header(
+            format!("Content-Type: {}", content_type)
+                .parse::<tiny_http::Header>()
+                .unwrap(),
+        );
+    if let Err(e) = http.respond(response) {
+        warn!("Failed to answer {} {}: {}", request.method, request.url, e);
+    }
+}
+
+/// Answer a request of the Prometheus metrics, see [crate::metrics]
+///
+/// Any other request is answered as not found.
+///
+/// # Arguments
+///
+/// * `app` - Application the metrics are read from
+/// * `token` - Token the requests must carry, any request being accepted if None
+/// * `http` - Request to answer
+pub fn answer_metrics<S: Storage>(app: &App<S>, token: Option<&str>, mut http: tiny_http::Request) {
+    let request = read_request(&mut http);
+    let reply = match (request.method.as_str(), request.url.as_str()) {
+        (_, _) if token.is_some_and(|token| !authorized(&request, token)) => {
+            Reply::error(401, "Missing or invalid bearer token")
+        }
+        ("GET", metrics::PATH) => match app.status_bar(&Utc::now()) {
+            Ok(bar) => {
+                info!(status = 200, "Metrics answered");
+                return respond(
+                    http,
+                    &request,
+                    200,
+                    metrics::render(&bar),
+                    metrics::CONTENT_TYPE,
+                );
+            }
+            Err(e) => Reply::error(500, format!("{:#}", e)),
+        },
+        (_, metrics::PATH) => Reply::error(
+            405,
+            format!("{} is not allowed on {}", request.method, metrics::PATH),
+        ),
+        (_, path) => Reply::error(404, format!("No such endpoint {}", path)),
+    };
+    info!(
+        method = request.method.as_str(),
+        url = request.url.as_str(),
+        status = reply.status,
+        "Request answered"
+    );
+    respond(
+        http,
+        &request,
+        reply.status,
+        reply.body.to_string(),
+        "application/json",
+    );
+}
+
+/// Answer the requests received by `server` until it fails
+///
+/// The requests are handled one at a time, the application not being shared
+/// between threads.
+///
+/// # Arguments
+///
+/// * `app` - Application the requests are run on
+/// * `token` - Token the requests must carry
+/// * `server` - HTTP(S) server listening for the requests
+pub fn run<S: Storage>(app: &App<S>, token: &str, server: &tiny_http::Server) -> Result<()> {
+    loop {
+        let http = server.recv().context("Receiving request")?;
+        if http.url() == metrics::PATH {
+            answer_metrics(app, Some(token), http);
+            continue;
+        }
+
+        let mut http = http;
+        let request = read_request(&mut http);
+        if request.method == "GET" && request.url == "/" {
+            let response = tiny_http::Response::from_string(DASHBOARD).with_header(
+                "Content-Type: text/html; charset=utf-8"
+                    .parse::<tiny_http::Header>()
+                    .unwrap(),
+            );
+            if let Err(e) = http.respond(response) {
+                warn!("Failed to answer the dashboard: {}", e);
+            }
+            continue;
+        }
+
+        let reply = handle(app, token, &request);
+        info!(
+            method = request.method.as_str(),
+            url = request.url.as_str(),
+            status = reply.status,
+            "Request answered"
+        );
+
+        respond(
+            http,
+            &request,
+            reply.status,
+            reply.body.to_string(),
+            "application/json",
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_token, handle, Request, MAX_BODY_BYTES};
+    use crate::app::App;
+    use crate::sync::{Bundle, Pull};
+
+    fn request(method: &str, url: &str, body: &str) -> Request {
+        Request {
+            method: method.into(),
+            url: url.into(),
+            authorization: Some("Bearer secret".into()),
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn endpoints() {
+        let app = App::in_memory().unwrap();
+
+        let reply = handle(&app, "secret", &request("GET", "/status", ""));
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["checked_in"], false);
+
+        let reply = handle(&app, "secret", &request("POST", "/checkin", ""));
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["in_out"], "In");
+        let reply = handle(&app, "secret", &request("POST", "/checkin", ""));
+        assert_eq!(reply.status, 400);
+        assert!(reply.body["error"].is_string());
+
+        let reply = handle(&app, "secret", &request("GET", "/status", ""));
+        assert_eq!(reply.body["checked_in"], true);
+
+        let reply = handle(
+            &app,
+            "secret",
+            &request("POST", "/checkout", r#"{"split": "100% PROJ-1"}"#),
+        );
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["allocations"][0]["target"], "PROJ-1");
+
+        let reply = handle(&app, "secret", &request("GET", "/report", ""));
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["sessions"].as_array().unwrap().len(), 1);
+        let reply = handle(
+            &app,
+            "secret",
+            &request("GET", "/report?from=2020-01-01&to=2020-01-02", ""),
+        );
+        assert_eq!(reply.body["total"], 0);
+        let reply = handle(&app, "secret", &request("GET", "/report?from=soon", ""));
+        assert_eq!(reply.status, 400);
+
+        let reply = handle(&app, "secret", &request("GET", "/stamps", ""));
+        assert_eq!(reply.body.as_array().unwrap().len(), 2);
+        let reply = handle(
+            &app,
+            "secret",
+            &request(
+                "PUT",
+                "/stamps/1",
+                r#"{"date": "2020-01-01T08:00:00Z", "in_out": "Out"}"#,
+            ),
+        );
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["date"], "2020-01-01T08:00:00Z");
+        assert_eq!(reply.body["in_out"], "Out");
+        let reply = handle(
+            &app,
+            "secret",
+            &request("GET", "/stamps?from=2020-01-01&to=2020-01-02", ""),
+        );
+        assert_eq!(reply.body[0]["id"], 1);
+        assert_eq!(
+            handle(&app, "secret", &request("PUT", "/stamps/9", r#"{}"#)).status,
+            400
+        );
+
+        assert_eq!(
+            handle(&app, "secret", &request("GET", "/checkin", "")).status,
+            405
+        );
+        assert_eq!(
+            handle(&app, "secret", &request("DELETE", "/stamps/1", "")).status,
+            405
+        );
+        assert_eq!(
+            handle(&app, "secret", &request("GET", "/nowhere", "")).status,
+            404
+        );
+    }
+
+    #[test]
+    fn sync() {
+        let app = App::in_memory().unwrap();
+        handle(&app, "secret", &request("POST", "/checkin", ""));
+
+        let reply = handle(&app, "secret", &request("GET", "/sync", ""));
+        assert_eq!(reply.status, 200);
+        let pull: Pull = serde_json::from_value(reply.body).unwrap();
+        assert_eq!(pull.bundle.stamps.len(), 1);
+
+        // Nothing changed since the cursor
+        let url = format!("/sync?since={}", pull.cursor.timestamp() + 1);
+        let reply = handle(&app, "secret", &request("GET", &url, ""));
+        assert_eq!(reply.body["bundle"]["stamps"], serde_json::json!([]));
+
+        let mut stamp = pull.bundle.stamps[0].clone();
+        stamp.uid = Some(uuid::Uuid::new_v4());
+        stamp.date -= chrono::Duration::hours(1);
+        let bundle = Bundle {
+            version: crate::sync::BUNDLE_VERSION,
+            stamps: vec![stamp],
+        };
+        let reply = handle(
+            &app,
+            "secret",
+            &request("POST", "/sync", &serde_json::to_string(&bundle).unwrap()),
+        );
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["added"], 1);
+
+        assert_eq!(
+            handle(&app, "secret", &request("GET", "/sync?since=soon", "")).status,
+            400
+        );
+        assert_eq!(
+            handle(&app, "secret", &request("POST", "/sync", "{}")).status,
+            400
+        );
+    }
+
+    #[test]
+    fn token() {
+        let app = App::in_memory().unwrap();
+
+        let mut request = request("GET", "/status", "");
+        assert_eq!(handle(&app, "secret", &request).status, 200);
+        assert_eq!(handle(&app, "secrets", &request).status, 401);
+        request.authorization = Some("secret".into());
+        assert_eq!(handle(&app, "secret", &request).status, 401);
+        request.authorization = None;
+        assert_eq!(handle(&app, "secret", &request).status, 401);
+
+        assert!(check_token("").is_err());
+        assert!(check_token("                    ").is_err());
+        assert!(check_token("secret").is_err());
+        assert!(check_token("4f1c9e7a2b8d3065").is_ok());
+    }
+
+    #[test]
+    fn body_size() {
+        let app = App::in_memory().unwrap();
+
+        let body = " ".repeat(MAX_BODY_BYTES + 1);
+        let reply = handle(&app, "secret", &request("POST", "/checkout", &body));
+        assert_eq!(reply.status, 413);
+        assert!(!app.status().unwrap().checked_in);
+    }
+}
@@ -0,0 +1,303 @@
+//! Static web site generation
+//!
+//! Renders the work-log as a set of read-only HTML pages: an index listing the
+//! months and one page per month with the daily worked hours. Both break the
+//! hours down per allocated project.
+
+use crate::config::Config;
+use crate::format;
+use crate::report::{self, Session};
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+pub(crate) const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; }
+table { border-collapse: collapse; }
+td, th { padding: 0.2em 1em; border-bottom: 1px solid #ddd; text-align: right; }
+td:first-child, th:first-child { text-align: left; }
+svg rect { fill: #4a7ab5; }
+svg text { font-size: 10px; fill: #333; }
+";
+
+/// Escape the HTML special characters of a text
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn hours(d: &Duration) -> f64 {
+    d.num_seconds() as f64 / 3600.0
+}
+
+/// Render a bar chart of hours as inline SVG
+pub(crate) fn bar_chart(bars: &[(String, Duration)]) -> String {
+    const HEIGHT: f64 = 150.0;
+    const BAR_WIDTH: f64 = 20.0;
+
+    let max = bars.iter().map(|(_, d)| hours(d)).fold(1.0, f64::max);
+
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        bars.len() as f64 * BAR_WIDTH,
+        HEIGHT + 15.0
+    );
+    for (i, (label, duration)) in bars.iter().enumerate() {
+        let height = hours(duration) / max * HEIGHT;
+        let x = i as f64 * BAR_WIDTH;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{} {:.2}h</title></rect>",
+            x + 2.0,
+            HEIGHT - height,
+            BAR_WIDTH - 4.0,
+            height,
+            label,
+            hours(duration)
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+            x + 2.0,
+            HEIGHT + 12.0,
+            label
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+        <link rel=\"stylesheet\" href=\"style.css\">\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n"
+    )
+}
+
+/// Render a table of hours per project, with their share of the total
+fn projects_table(body: &mut String, projects: &BTreeMap<&str, Duration>) {
+    if projects.is_empty() {
+        return;
+    }
+    let total = projects
+        .values()
+        .fold(Duration::zero(), |total, d| total + *d);
+    body.push_str("<h2>Projects</h2>\n");
+    body.push_str("<table>\n<tr><th>Project</th><th>Hours</th><th>Share</th></tr>\n");
+    for (project, duration) in projects {
+        let share = if total.is_zero() {
+            0.0
+        } else {
+            duration.num_seconds() as f64 * 100.0 / total.num_seconds() as f64
+        };
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>",
+            escape(project),
+            format::hours(duration),
+            share
+        );
+    }
+    body.push_str("</table>\n");
+}
+
+/// Sum the hours of every project over the days matching a filter
+fn project_totals(
+    projects: &BTreeMap<String, BTreeMap<NaiveDate, Duration>>,
+    within: impl Fn(&NaiveDate) -> bool,
+) -> BTreeMap<&str, Duration> {
+    projects
+        .iter()
+        .filter_map(|(project, days)| {
+            let total = days
+                .iter()
+                .filter(|(day, _)| within(day))
+                .fold(Duration::zero(), |total, (_, d)| total + *d);
+            (!total.is_zero()).then_some((project.as_str(), total))
+        })
+        .collect()
+}
+
+fn month_page(
+    month: &NaiveDate,
+    days: &BTreeMap<NaiveDate, Duration>,
+    projects: &BTreeMap<&str, Duration>,
+) -> String {
+    let mut bars = Vec::new();
+    let mut day = *month;
+    while day.month() == month.month() {
+        let total = days.get(&day).copied().unwrap_or_else(Duration::zero);
+        bars.push((day.day().to_string(), total));
+        day = day.succ_opt().unwrap();
+    }
+
+    let mut body = String::from("<p><a href=\"index.html\">All months</a></p>\n");
+    body.push_str(&bar_chart(&bars));
+    body.push_str("<table>\n<tr><th>Day</th><th>Hours</th></tr>\n");
+    let mut total = Duration::zero();
+    for (day, duration) in days {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            day.format("%a %Y-%m-%d"),
+            format::hours(duration)
+        );
+        total = total + *duration;
+    }
+    let _ = writeln!(
+        body,
+        "<tr><th>Total</th><th>{}</th></tr>\n</table>",
+        format::hours(&total)
+    );
+    projects_table(&mut body, projects);
+
+    page(&month.format("%B %Y").to_string(), &body)
+}
+
+fn index_page(
+    months: &BTreeMap<NaiveDate, BTreeMap<NaiveDate, Duration>>,
+    projects: &BTreeMap<&str, Duration>,
+) -> String {
+    let totals: Vec<(NaiveDate, Duration, usize)> = months
+        .iter()
+        .map(|(month, days)| {
+            let total = days.values().fold(Duration::zero(), |acc, d| acc + *d);
+            (*month, total, days.len())
+        })
+        .collect();
+
+    let bars: Vec<(String, Duration)> = totals
+        .iter()
+        .map(|(month, total, _)| (month.format("%m").to_string(), *total))
+        .collect();
+
+    let mut body = bar_chart(&bars);
+    body.push_str("<table>\n<tr><th>Month</th><th>Days worked</th><th>Hours</th></tr>\n");
+    for (month, total, days) in totals.iter().rev() {
+        let _ = writeln!(
+            body,
+            "<tr><td><a href=\"{}.html\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            month.format("%Y-%m"),
+            month.format("%B %Y"),
+            days,
+            format::hours(total)
+        );
+    }
+    body.push_str("</table>\n");
+    projects_table(&mut body, projects);
+
+    page("Work-log", &body)
+}
+
+/// Generate the static web site from given sessions into `out_dir`
+///
+/// # Arguments
+///
+/// * `sessions` - Sessions to publish
+/// * `projects` - Worked time per project and per day, see
+///   [report::allocated_days]
+/// * `config` - User settings, giving the timezone of the days
+/// * `out_dir` - Directory to write the pages to, created if needed
+///
+/// # Return
+///
+/// Number of written month pages
+pub fn build(
+    sessions: &[Session],
+    projects: &BTreeMap<String, BTreeMap<NaiveDate, Duration>>,
+    config: &Config,
+    out_dir: &Path,
+) -> Result<usize> {
+    let mut months: BTreeMap<NaiveDate, BTreeMap<NaiveDate, Duration>> = BTreeMap::new();
+    for (day, total) in report::daily_totals(sessions, config) {
+        let month = day.with_day(1).unwrap();
+        months.entry(month).or_default().insert(day, total);
+    }
+
+    fs::create_dir_all(out_dir).context("Creating output directory")?;
+    fs::write(out_dir.join("style.css"), STYLE).context("Writing style sheet")?;
+    fs::write(
+        out_dir.join("index.html"),
+        index_page(&months, &project_totals(projects, |_| true)),
+    )
+    .context("Writing index")?;
+    for (month, days) in &months {
+        let in_month = project_totals(projects, |day| day.with_day(1).as_ref() == Some(month));
+        fs::write(
+            out_dir.join(format!("{}.html", month.format("%Y-%m"))),
+            month_page(month, days, &in_month),
+        )
+        .context("Writing month page")?;
+    }
+
+    Ok(months.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::build;
+    use crate::config::Config;
+    use crate::db::StampId;
+    use crate::report::Session;
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::str::FromStr;
+
+    #[test]
+    fn pages() {
+        let out_dir = std::env::temp_dir().join("wtime-site-test");
+        let sessions = [
+            Session {
+                id: StampId(1),
+                start: DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap(),
+                end: DateTime::<Utc>::from_str("2020-01-01T12:00:00Z").unwrap(),
+            },
+            Session {
+                id: StampId(3),
+                start: DateTime::<Utc>::from_str("2020-02-03T08:00:00Z").unwrap(),
+                end: DateTime::<Utc>::from_str("2020-02-03T09:30:00Z").unwrap(),
+            },
+        ];
+
+        let day = |m, d| NaiveDate::from_ymd_opt(2020, m, d).unwrap();
+        let projects = BTreeMap::from([
+            (
+                "R&D".to_string(),
+                BTreeMap::from([(day(1, 1), Duration::hours(3))]),
+            ),
+            (
+                "acme".to_string(),
+                BTreeMap::from([
+                    (day(1, 1), Duration::hours(1)),
+                    (day(2, 3), Duration::minutes(90)),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(
+            build(&sessions, &projects, &Config::default(), &out_dir).unwrap(),
+            2
+        );
+
+        let index = fs::read_to_string(out_dir.join("index.html")).unwrap();
+        assert!(index.contains("href=\"2020-01.html\""));
+        assert!(index.contains("href=\"2020-02.html\""));
+        assert!(index.contains("<h2>Projects</h2>"));
+        assert!(index.contains("<tr><td>R&amp;D</td><td>3.00</td><td>55%</td></tr>"));
+        assert!(index.contains("<tr><td>acme</td><td>2.50</td><td>45%</td></tr>"));
+        let january = fs::read_to_string(out_dir.join("2020-01.html")).unwrap();
+        assert!(january.contains("<tr><td>R&amp;D</td><td>3.00</td><td>75%</td></tr>"));
+        assert!(january.contains("<tr><td>acme</td><td>1.00</td><td>25%</td></tr>"));
+        let february = fs::read_to_string(out_dir.join("2020-02.html")).unwrap();
+        assert!(february.contains("<td>1.50</td>"));
+        assert!(february.contains("<tr><td>acme</td><td>1.50</td><td>100%</td></tr>"));
+        assert!(!february.contains("R&amp;D"));
+
+        fs::remove_dir_all(out_dir).unwrap();
+    }
+}
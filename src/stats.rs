@@ -0,0 +1,403 @@
+//! Statistics over the work-log
+//!
+//! `wtime stats --year 2024` sums up a year: worked time, busiest week,
+//! average day, longest session and days off. `wtime stats --trend` follows
+//! the 7 and 30 days rolling averages of the daily hours, to notice creeping
+//! overtime early. `wtime stats --habits` encourages regular hours: streaks
+//! of days meeting the target, earliest and latest check-ins, and the weeks
+//! close to their target.
+
+use crate::config::Config;
+use crate::report::{self, Report, Session};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Utc};
+use std::collections::BTreeMap;
+
+/// Summary of a year of work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YearStats {
+    /// Year summed up
+    pub year: i32,
+    /// Worked time over the year
+    pub total: Duration,
+    /// Number of days having sessions
+    pub days: usize,
+    /// ISO week (`YYYY-Www`) having the most worked time, and its total
+    pub busiest_week: Option<(String, Duration)>,
+    /// Average time of the first check-in of the days
+    pub average_start: Option<NaiveTime>,
+    /// Average time of the last check-out of the days
+    pub average_end: Option<NaiveTime>,
+    /// Longest session of the year
+    pub longest: Option<Session>,
+    /// Holidays falling on working days, i.e. days off taken
+    pub days_off: usize,
+}
+
+/// Average of times of day
+fn average_time(times: impl Iterator<Item = NaiveTime>) -> Option<NaiveTime> {
+    let seconds: Vec<u32> = times.map(|t| t.num_seconds_from_midnight()).collect();
+    let count = u32::try_from(seconds.len()).ok().filter(|n| *n > 0)?;
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds.iter().sum::<u32>() / count, 0)
+}
+
+/// Sum up a year of work
+///
+/// # Arguments
+///
+/// * `year` - Year to sum up
+/// * `report` - Report of the year
+/// * `config` - User settings, giving the days off of any year and the
+///   timezone of the days and times
+pub fn year(year: i32, report: &Report, config: &Config) -> YearStats {
+    let spans = report::day_spans(&report.sessions, config);
+    let local_time = |date: &DateTime<Utc>| date.with_timezone(&config.timezone).time();
+
+    let mut weeks: BTreeMap<String, Duration> = BTreeMap::new();
+    for (day, worked) in &report.days {
+        let week = weeks
+            .entry(day.format("%G-W%V").to_string())
+            .or_insert_with(Duration::zero);
+        *week = *week + *worked;
+    }
+    // The first week of the most worked ones
+    let busiest_week = weeks.into_iter().rev().max_by_key(|(_, total)| *total);
+
+    YearStats {
+        year,
+        total: report.total,
+        days: report.days.len(),
+        busiest_week,
+        average_start: average_time(spans.values().map(|s| local_time(&s.start))),
+        average_end: average_time(spans.values().map(|s| local_time(&s.end))),
+        longest: report
+            .sessions
+            .iter()
+            .rev()
+            .max_by_key(|s| s.duration())
+            .cloned(),
+        days_off: config
+            .holidays
+            .iter()
+            .filter(|day| day.year() == year && day.weekday().number_from_monday() <= 5)
+            .count(),
+    }
+}
+
+/// Relative difference of the averages beyond which a trend is flagged
+const TREND_THRESHOLD: f64 = 0.1;
+
+/// Direction of the short term average against the long term one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    /// Short term average more than 10% above the long term one
+    Rising,
+    /// Short term average more than 10% below the long term one
+    Falling,
+    /// Averages within 10% of each other
+    Steady,
+}
+
+impl Trend {
+    /// Compare a short term average to a long term one
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - Short term average, e.g. over 7 days
+    /// * `long` - Long term average, e.g. over 30 days
+    pub fn of(short: Duration, long: Duration) -> Self {
+        let (short, long) = (short.num_seconds() as f64, long.num_seconds() as f64);
+        if short > long * (1.0 + TREND_THRESHOLD) {
+            Trend::Rising
+        } else if short < long * (1.0 - TREND_THRESHOLD) {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+/// Average worked time per working day over the days ending at a day
+///
+/// Time worked on days off counts, but these days do not, so that the
+/// average compares to the daily target.
+///
+/// # Arguments
+///
+/// * `days` - Worked time per day
+/// * `last` - Last day of the window, included
+/// * `window` - Number of days of the window
+/// * `working` - Whether a day is a working day, see [crate::config::Config::day_target]
+pub fn rolling_average(
+    days: &BTreeMap<NaiveDate, Duration>,
+    last: NaiveDate,
+    window: i64,
+    working: impl Fn(NaiveDate) -> bool,
+) -> Duration {
+    let first = last - Duration::days(window - 1);
+    let total = days
+        .range(first..=last)
+        .fold(Duration::zero(), |total, (_, d)| total + *d);
+    let working_days = first
+        .iter_days()
+        .take_while(|day| *day <= last)
+        .filter(|day| working(*day))
+        .count()
+        .max(1);
+    total / working_days as i32
+}
+
+/// Habits over the last weeks, see [habits]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Habits {
+    /// Working days in a row meeting their target, up to the last day
+    pub current_streak: usize,
+    /// Longest run of working days meeting their target, its first and last
+    /// day and its length
+    pub longest_streak: Option<(NaiveDate, NaiveDate, usize)>,
+    /// Earliest first check-in of a day, and that day
+    pub earliest_checkin: Option<(NaiveDate, NaiveTime)>,
+    /// Latest first check-in of a day, and that day
+    pub latest_checkin: Option<(NaiveDate, NaiveTime)>,
+    /// Weeks whose worked time is within 10% of their target
+    pub steady_weeks: usize,
+    /// Whole weeks having a target
+    pub weeks: usize,
+}
+
+/// Sum up the habits of the days of a window: streaks of days meeting the
+/// target, first check-ins and steady weeks
+///
+/// Days without target neither break nor extend a streak, and the last day,
+/// still running, only extends it. Only the whole weeks from Monday are
+/// compared to their target.
+///
+/// # Arguments
+///
+/// * `report` - Report of the window
+/// * `first` - First day of the window
+/// * `last` - Last day of the window, included, e.g. today
+/// * `config` - User settings, giving the target of a day (see
+///   [Config::day_target]) and the timezone of the check-in times
+pub fn habits(report: &Report, first: NaiveDate, last: NaiveDate, config: &Config) -> Habits {
+    let target = |day: NaiveDate| config.day_target(day);
+    let worked = |day: NaiveDate| {
+        report
+            .days
+            .get(&day)
+            .copied()
+            .unwrap_or_else(Duration::zero)
+    };
+
+    let mut streak: Option<(NaiveDate, usize)> = None;
+    let mut longest_streak: Option<(NaiveDate, NaiveDate, usize)> = None;
+    for day in first.iter_days().take_while(|day| *day <= last) {
+        let day_target = target(day);
+        if day_target.is_zero() {
+            continue;
+        }
+        if worked(day) < day_target {
+            if day < last {
+                streak = None;
+            }
+            continue;
+        }
+        let (start, length) = streak.map_or((day, 1), |(start, length)| (start, length + 1));
+        streak = Some((start, length));
+        if longest_streak.is_none_or(|(_, _, longest)| length > longest) {
+            longest_streak = Some((start, day, length));
+        }
+    }
+
+    let checkins: Vec<(NaiveDate, NaiveTime)> = report::day_spans(&report.sessions, config)
+        .into_iter()
+        .map(|(day, span)| (day, span.start.with_timezone(&config.timezone).time()))
+        .collect();
+
+    let mut steady_weeks = 0;
+    let mut weeks = 0;
+    let first_monday =
+        first + Duration::days(((7 - first.weekday().num_days_from_monday()) % 7).into());
+    for monday in first_monday
+        .iter_weeks()
+        .take_while(|monday| *monday + Duration::days(6) <= last)
+    {
+        let days = || monday.iter_days().take(7);
+        let week_target = days().fold(Duration::zero(), |total, day| total + target(day));
+        if week_target.is_zero() {
+            continue;
+        }
+        weeks += 1;
+        let week_worked = days().fold(Duration::zero(), |total, day| total + worked(day));
+        let gap = (week_worked - week_target).num_seconds().abs() as f64;
+        if gap <= week_target.num_seconds() as f64 * TREND_THRESHOLD {
+            steady_weeks += 1;
+        }
+    }
+
+    Habits {
+        current_streak: streak.map_or(0, |(_, length)| length),
+        longest_streak,
+        // The first day of the earliest and of the latest ones
+        earliest_checkin: checkins.iter().min_by_key(|(_, time)| *time).copied(),
+        latest_checkin: checkins.iter().rev().max_by_key(|(_, time)| *time).copied(),
+        steady_weeks,
+        weeks,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{habits, rolling_average, year, Trend};
+    use crate::config::Config;
+    use crate::db::StampId;
+    use crate::report::{Report, Session};
+    use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    fn session(id: i64, start: &str, end: &str) -> Session {
+        Session {
+            id: StampId(id),
+            start: DateTime::<Utc>::from_str(start).unwrap(),
+            end: DateTime::<Utc>::from_str(end).unwrap(),
+        }
+    }
+
+    #[test]
+    fn year_stats() {
+        let sessions = [
+            session(1, "2024-01-02T08:00:00Z", "2024-01-02T12:00:00Z"),
+            session(3, "2024-01-02T13:00:00Z", "2024-01-02T17:00:00Z"),
+            session(5, "2024-03-12T09:00:00Z", "2024-03-12T15:30:00Z"),
+            session(7, "2024-03-13T09:00:00Z", "2024-03-13T15:30:00Z"),
+        ];
+        let mut config = Config {
+            holidays: vec![
+                NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(),
+                // Saturday
+                NaiveDate::from_ymd_opt(2024, 12, 28).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            ],
+            ..Config::default()
+        };
+
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let report = Report::new(from, to, sessions.to_vec(), &config);
+        let stats = year(2024, &report, &config);
+        assert_eq!(stats.total, Duration::hours(21));
+        assert_eq!(stats.days, 3);
+        assert_eq!(
+            stats.busiest_week,
+            Some(("2024-W11".to_string(), Duration::hours(13)))
+        );
+        assert_eq!(stats.average_start, NaiveTime::from_hms_opt(8, 40, 0));
+        assert_eq!(stats.average_end, NaiveTime::from_hms_opt(16, 0, 0));
+        // The first of the longest ones
+        assert_eq!(stats.longest.unwrap().id, StampId(5));
+        assert_eq!(stats.days_off, 1);
+
+        let empty = year(2023, &Report::new(from, to, Vec::new(), &config), &config);
+        assert_eq!(empty.busiest_week, None);
+        assert_eq!(empty.average_start, None);
+        assert_eq!(empty.longest, None);
+
+        // Local times of the day
+        config.timezone = chrono_tz::Europe::Zurich;
+        let stats = year(2024, &report, &config);
+        assert_eq!(stats.average_start, NaiveTime::from_hms_opt(9, 40, 0));
+    }
+
+    #[test]
+    fn habit_stats() {
+        let sessions = [
+            session(1, "2024-01-01T09:00:00Z", "2024-01-01T17:00:00Z"),
+            session(3, "2024-01-02T08:00:00Z", "2024-01-02T16:00:00Z"),
+            session(5, "2024-01-03T08:00:00Z", "2024-01-03T12:00:00Z"),
+            session(7, "2024-01-04T07:30:00Z", "2024-01-04T15:30:00Z"),
+            session(9, "2024-01-05T10:00:00Z", "2024-01-05T19:00:00Z"),
+            session(11, "2024-01-08T08:00:00Z", "2024-01-08T16:00:00Z"),
+            // Today, still running
+            session(13, "2024-01-09T07:30:00Z", "2024-01-09T09:30:00Z"),
+        ];
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let config = Config::default();
+
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap();
+        let report = Report::new(from, to, sessions.to_vec(), &config);
+        let stats = habits(&report, day(1), day(9), &config);
+        // The weekend does not break the streak, nor the day running
+        assert_eq!(stats.current_streak, 3);
+        assert_eq!(stats.longest_streak, Some((day(4), day(8), 3)));
+        assert_eq!(
+            stats.earliest_checkin,
+            Some((day(4), NaiveTime::from_hms_opt(7, 30, 0).unwrap()))
+        );
+        assert_eq!(
+            stats.latest_checkin,
+            Some((day(5), NaiveTime::from_hms_opt(10, 0, 0).unwrap()))
+        );
+        // 37h for 40h
+        assert_eq!((stats.steady_weeks, stats.weeks), (1, 1));
+
+        // A day missed breaks the streak, the second week is not over
+        let stats = habits(&report, day(3), day(10), &config);
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.longest_streak, Some((day(4), day(8), 3)));
+        assert_eq!(stats.weeks, 0);
+
+        let empty = habits(
+            &Report::new(from, to, Vec::new(), &config),
+            day(1),
+            day(9),
+            &config,
+        );
+        assert_eq!(empty.current_streak, 0);
+        assert_eq!(empty.longest_streak, None);
+        assert_eq!(empty.earliest_checkin, None);
+        assert_eq!((empty.steady_weeks, empty.weeks), (0, 1));
+    }
+
+    #[test]
+    fn rolling() {
+        // Monday 2024-01-01 to Sunday 2024-01-14: 8h on weekdays, then 10h
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let working = |day: NaiveDate| day.weekday().number_from_monday() <= 5;
+        let days: BTreeMap<NaiveDate, Duration> = monday
+            .iter_days()
+            .take(14)
+            .filter(|day| working(*day))
+            .map(|day| {
+                let hours = if day.day() < 8 { 8 } else { 10 };
+                (day, Duration::hours(hours))
+            })
+            .collect();
+
+        let sunday = monday + Duration::days(13);
+        let week = rolling_average(&days, sunday, 7, working);
+        let fortnight = rolling_average(&days, sunday, 14, working);
+        assert_eq!(week, Duration::hours(10));
+        assert_eq!(fortnight, Duration::hours(9));
+        assert_eq!(Trend::of(week, fortnight), Trend::Rising);
+        assert_eq!(Trend::of(fortnight, week), Trend::Steady);
+        assert_eq!(Trend::of(Duration::hours(7), fortnight), Trend::Falling);
+        // Days not worked count
+        assert_eq!(
+            rolling_average(&days, sunday, 28, working),
+            Duration::minutes(270)
+        );
+
+        // Weekend work counts, on the working days
+        let saturday = monday + Duration::days(5);
+        let days = BTreeMap::from([(saturday, Duration::hours(5))]);
+        assert_eq!(
+            rolling_average(&days, saturday + Duration::days(1), 7, working),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            rolling_average(&BTreeMap::new(), saturday, 1, working),
+            Duration::zero()
+        );
+    }
+}
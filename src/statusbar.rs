@@ -0,0 +1,228 @@
+//! Status of the work-log for the menu and status bars
+//!
+//! `wtime status --format xbar` prints the text of an
+//! [xbar](https://xbarapp.com) or SwiftBar plugin: the running time as the
+//! title, then a menu with the totals, the sessions of today and the
+//! actions. The plugin is a script calling it, named after its refresh
+//! interval, e.g. `wtime.1m.sh`.
+//!
+//! `wtime status --format waybar` prints the JSON of a Waybar or
+//! i3status-rust custom module, see [waybar].
+
+use crate::export::ParseFormatError;
+use crate::report::Session;
+use crate::{format, tr};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde_json::json;
+use std::str::FromStr;
+
+/// Format of `wtime status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFormat {
+    /// Worked time of the day and week, as printed by `wtime`
+    Text,
+    /// xbar and SwiftBar plugin text, see [xbar]
+    Xbar,
+    /// Waybar and i3status-rust module JSON, see [waybar]
+    Waybar,
+}
+
+impl FromStr for StatusFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "xbar" | "swiftbar" => Ok(Self::Xbar),
+            "waybar" | "i3status-rs" => Ok(Self::Waybar),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// What a bar shows of the work-log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bar {
+    /// Date/Time the times run until
+    pub now: DateTime<Utc>,
+    /// Check-in time of the running session, None when checked out
+    pub running: Option<DateTime<Utc>>,
+    /// Sessions of today, in id order, the running one excluded
+    pub sessions: Vec<Session>,
+    /// Worked time of today, the running session included
+    pub today: Duration,
+    /// Worked time of the week, the running session included
+    pub week: Duration,
+}
+
+/// Text of an xbar or SwiftBar plugin
+///
+/// The actions run `exe` and refresh the plugin.
+///
+/// # Arguments
+///
+/// * `bar` - What to show
+/// * `timezone` - Timezone the times are shown in
+/// * `exe` - Path of the wtime executable
+pub fn xbar(bar: &Bar, timezone: &Tz, exe: &str) -> String {
+    let mut lines = vec![
+        match bar.running {
+            Some(start) => format!("▶ {}", format::duration(&(bar.now - start))),
+            None => format!("⏸ {}", format::duration(&bar.today)),
+        },
+        "---".to_string(),
+        tr!("bar-today", worked = format::duration(&bar.today)),
+        tr!("bar-week", worked = format::duration(&bar.week)),
+        "---".to_string(),
+    ];
+    lines.extend(
+        session_lines(bar, timezone)
+            .into_iter()
+            .map(|line| format!("{} | font=Menlo", line)),
+    );
+
+    let (label, command) = match bar.running {
+        Some(_) => (tr!("bar-check-out"), "checkout"),
+        None => (tr!("bar-check-in"), "checkin"),
+    };
+    lines.extend([
+        "---".to_string(),
+        format!(
+            "{} | shell=\"{}\" param1={} terminal=false refresh=true",
+            label, exe, command
+        ),
+        format!("{} | refresh=true", tr!("bar-refresh")),
+    ]);
+
+    lines.join("\n")
+}
+
+/// JSON of a Waybar or i3status-rust custom module
+///
+/// The text is the running time, or today's once checked out. The class
+/// (Waybar) is `checked-in` or `checked-out`, and so is the state
+/// (i3status-rust) `Good` or `Idle`, for styling. The tooltip gives the
+/// totals and the sessions of today.
+///
+/// # Arguments
+///
+/// * `bar` - What to show
+/// * `timezone` - Timezone the times are shown in
+pub fn waybar(bar: &Bar, timezone: &Tz) -> String {
+    let (text, class, state) = match bar.running {
+        Some(start) => (format::duration(&(bar.now - start)), "checked-in", "Good"),
+        None => (format::duration(&bar.today), "checked-out", "Idle"),
+    };
+    let mut tooltip = vec![
+        tr!("bar-today", worked = format::duration(&bar.today)),
+        tr!("bar-week", worked = format::duration(&bar.week)),
+    ];
+    tooltip.extend(session_lines(bar, timezone));
+
+    json!({
+        "text": text,
+        "tooltip": tooltip.join("\n"),
+        "class": class,
+        "alt": class,
+        "state": state,
+    })
+    .to_string()
+}
+
+/// Lines of the sessions of today, the running one last
+fn session_lines(bar: &Bar, timezone: &Tz) -> Vec<String> {
+    let time = |date: &DateTime<Utc>| date.with_timezone(timezone).format("%H:%M").to_string();
+    let mut lines: Vec<String> = bar
+        .sessions
+        .iter()
+        .map(|session| {
+            format!(
+                "{} – {}  {}",
+                time(&session.start),
+                time(&session.end),
+                format::duration(&session.duration())
+            )
+        })
+        .collect();
+    if let Some(start) = bar.running {
+        lines.push(format!(
+            "{} – …  {}",
+            time(&start),
+            format::duration(&(bar.now - start))
+        ));
+    }
+    if lines.is_empty() {
+        lines.push(tr!("bar-no-session"));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::{waybar, xbar, Bar, StatusFormat};
+    use crate::db::StampId;
+    use crate::report::Session;
+    use chrono::{DateTime, Duration, Utc};
+    use chrono_tz::Tz;
+    use std::str::FromStr;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::<Utc>::from_str(&format!("2020-01-01T{}:00Z", time)).unwrap()
+    }
+
+    #[test]
+    fn running() {
+        let bar = Bar {
+            now: at("14:30"),
+            running: Some(at("13:00")),
+            sessions: vec![Session {
+                id: StampId(1),
+                start: at("08:00"),
+                end: at("12:00"),
+            }],
+            today: Duration::minutes(5 * 60 + 30),
+            week: Duration::hours(20),
+        };
+
+        let text = xbar(&bar, &Tz::Europe__Zurich, "/usr/bin/wtime");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "▶ 1h 30m");
+        assert_eq!(lines[1], "---");
+        assert!(lines.contains(&"09:00 – 13:00  4h 00m | font=Menlo"));
+        assert!(lines.contains(&"14:00 – …  1h 30m | font=Menlo"));
+        assert!(lines[lines.len() - 2]
+            .ends_with("| shell=\"/usr/bin/wtime\" param1=checkout terminal=false refresh=true"));
+        assert!(lines[lines.len() - 1].ends_with("| refresh=true"));
+
+        let module: serde_json::Value =
+            serde_json::from_str(&waybar(&bar, &Tz::Europe__Zurich)).unwrap();
+        assert_eq!(module["text"], "1h 30m");
+        assert_eq!(module["class"], "checked-in");
+        assert_eq!(module["state"], "Good");
+        let tooltip = module["tooltip"].as_str().unwrap();
+        assert!(tooltip.ends_with("\n09:00 – 13:00  4h 00m\n14:00 – …  1h 30m"));
+    }
+
+    #[test]
+    fn stopped() {
+        let bar = Bar {
+            now: at("14:30"),
+            running: None,
+            sessions: Vec::new(),
+            today: Duration::zero(),
+            week: Duration::hours(20),
+        };
+
+        let text = xbar(&bar, &Tz::UTC, "wtime");
+        assert!(text.starts_with("⏸ 0h 00m\n---\n"));
+        assert!(text.contains("param1=checkin"));
+
+        let module: serde_json::Value = serde_json::from_str(&waybar(&bar, &Tz::UTC)).unwrap();
+        assert_eq!(module["text"], "0h 00m");
+        assert_eq!(module["class"], "checked-out");
+        assert_eq!(StatusFormat::from_str("SwiftBar"), Ok(StatusFormat::Xbar));
+        assert_eq!(StatusFormat::from_str("waybar"), Ok(StatusFormat::Waybar));
+        assert!(StatusFormat::from_str("json").is_err());
+    }
+}
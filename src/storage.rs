@@ -0,0 +1,483 @@
+//! Storage backend abstraction
+//!
+//! The application accesses the work-log through the [Storage] trait, so that
+//! it does not depend on a particular database. The sqlite backend implements
+//! it on top of the [db](crate::db) module.
+
+use crate::db::{
+    self, Adjustment, Allocation, Audit, Closeout, DbError, Estimate, Project, Reference, Setting,
+    Stamp, StampFilter, StampId, StampIterator, Tag, User, Worklog,
+};
+use crate::sync::{self, Merge};
+use chrono::{DateTime, Duration, Utc};
+
+/// Iterator over stamps returned by a [Storage]
+pub type Stamps<'a> = Box<dyn Iterator<Item = Result<Stamp, DbError>> + 'a>;
+
+/// Operations a work-log storage backend must provide
+pub trait Storage {
+    /// Insert a new stamp, setting its id
+    fn insert_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError>;
+
+    /// Insert a new stamp after the last one, setting its id
+    ///
+    /// Check-ins and check-outs must alternate, [DbError::NotAlternating]
+    /// otherwise. Backends without locking check the last stamp, then insert.
+    fn insert_next_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError> {
+        match self.last_stamp()? {
+            Some(last) if last.in_out == stamp.in_out => Err(DbError::NotAlternating(stamp.in_out)),
+            _ => self.insert_stamp(stamp),
+        }
+    }
+
+    /// Insert new stamps at once, setting their ids
+    ///
+    /// Either all of them are inserted or none. Backends without
+    /// transactions refuse it.
+    fn insert_stamps(&self, _stamps: &mut [Stamp]) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Inserting several stamps at once"))
+    }
+
+    /// Run a multi-step operation atomically, see [db::transaction]
+    ///
+    /// Either all the changes of the operation are written or none, when it
+    /// fails. Backends without transactions run the operation as is.
+    fn transaction<T, E: From<DbError>>(
+        &self,
+        operation: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        Self: Sized,
+    {
+        operation()
+    }
+
+    /// Change the date and direction of an existing stamp
+    ///
+    /// Its modification time is refreshed, [DbError::Conflict] if the stamp
+    /// was modified since read.
+    fn update_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError>;
+
+    /// Get the stamp with given id, or [DbError::NoSuchEntry]
+    fn get_stamp(&self, id: StampId) -> Result<Stamp, DbError>;
+
+    /// Get the stamp preceding the given one, None if it is the first
+    fn previous_stamp(&self, stamp: &Stamp) -> Result<Option<Stamp>, DbError>;
+
+    /// Get the very first stamp, None if there is no stamp at all
+    fn first_stamp(&self) -> Result<Option<Stamp>, DbError>;
+
+    /// Get the very last stamp, None if there is no stamp at all
+    fn last_stamp(&self) -> Result<Option<Stamp>, DbError>;
+
+    /// Count the stamps, archived ones excluded
+    fn stamp_count(&self) -> Result<i64, DbError>;
+
+    /// Version of the database schema
+    ///
+    /// Backends migrating their schema otherwise have no version.
+    fn schema_version(&self) -> Result<i64, DbError> {
+        Err(DbError::Unsupported("Schema versions"))
+    }
+
+    /// Iterate over the stamps dated in `[from, to)`, in id order
+    fn stamp_range<'a>(
+        &'a self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Stamps<'a>, DbError>;
+
+    /// Iterate over the stamps dated in `[from, to)`, archived ones included
+    ///
+    /// Backends without archive only have the current stamps.
+    fn stamp_range_with_archive<'a>(
+        &'a self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Stamps<'a>, DbError> {
+        self.stamp_range(from, to)
+    }
+
+    /// Total worked time of the stamps at or after `from`
+    fn stamp_total_from(&self, from: &DateTime<Utc>) -> Result<Duration, DbError>;
+
+    /// Insert a new adjustment, setting its id
+    fn insert_adjustment(&self, adjustment: &mut Adjustment) -> Result<(), DbError>;
+
+    /// Sum of the adjustments applying at or after `from`, by target date
+    fn adjustment_total_from(&self, from: &DateTime<Utc>) -> Result<Duration, DbError>;
+
+    /// Adjustments applying to the days of a date window, by target date
+    fn adjustment_range(
+        &self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Adjustment>, DbError>;
+
+    /// Insert a new closeout, setting its id
+    fn insert_closeout(&self, closeout: &mut Closeout) -> Result<(), DbError>;
+
+    /// Remove the latest closeout, see [Closeout::delete_last]
+    fn delete_last_closeout(&self) -> Result<Option<Closeout>, DbError>;
+
+    /// Insert a new allocation, setting its id
+    fn insert_allocation(&self, allocation: &mut Allocation) -> Result<(), DbError>;
+
+    /// Delete the allocations of a session
+    fn delete_allocations(&self, session: StampId) -> Result<(), DbError>;
+
+    /// Get the allocations of the sessions with ids in `[first, last]`
+    fn allocation_range(&self, first: StampId, last: StampId) -> Result<Vec<Allocation>, DbError>;
+
+    /// Get the complete audit log, oldest first
+    fn audit_log(&self) -> Result<Vec<Audit>, DbError>;
+
+    /// Set the command line recorded in the audit log with the following modifications
+    fn set_audit_command(&self, command: Option<&str>) -> Result<(), DbError>;
+
+    /// Tell if the stamps are hash-chained
+    fn is_chained(&self) -> Result<bool, DbError>;
+
+    /// Enable the hash-chained mode
+    fn enable_chain(&self) -> Result<(), DbError>;
+
+    /// Check the hash chain, see [Stamp::verify_chain]
+    fn verify_chain(&self) -> Result<Result<usize, StampId>, DbError>;
+
+    /// Get the stamps updated at or after `since`, all of them if None
+    ///
+    /// Backends not supporting [sync] refuse it.
+    fn stamps_updated_since(&self, _since: Option<&DateTime<Utc>>) -> Result<Vec<Stamp>, DbError> {
+        Err(DbError::Unsupported("Sync"))
+    }
+
+    /// Find the stamps matching a filter, see [Stamp::search]
+    fn search_stamps(&self, _filter: &StampFilter) -> Result<Vec<Stamp>, DbError> {
+        Err(DbError::Unsupported("Searching stamps"))
+    }
+
+    /// Split sessions running past midnight, see [Stamp::split]
+    fn split_sessions(&self, _sessions: &[(Stamp, Vec<DateTime<Utc>>)]) -> Result<usize, DbError> {
+        Err(DbError::Unsupported("Splitting sessions"))
+    }
+
+    /// Renumber the stamps in date order, see [Stamp::renumber]
+    ///
+    /// Backends not supporting it refuse the insertion of stamps before the
+    /// last one.
+    fn renumber_stamps(&self) -> Result<usize, DbError> {
+        Err(DbError::Unsupported("Inserting stamps in the past"))
+    }
+
+    /// Merge the stamps of another device, see [sync::merge]
+    ///
+    /// Backends not supporting [sync] refuse it.
+    fn merge_stamps(&self, _stamps: &[Stamp]) -> Result<Merge, DbError> {
+        Err(DbError::Unsupported("Sync"))
+    }
+
+    /// Select the user whose stamps are read and written, creating it if needed
+    ///
+    /// Backends without [User] table only track one person.
+    fn select_user(&self, _name: &str) -> Result<User, DbError> {
+        Err(DbError::Unsupported("Multiple users"))
+    }
+
+    /// Get the user whose stamps are read and written
+    fn current_user(&self) -> Result<User, DbError> {
+        Err(DbError::Unsupported("Multiple users"))
+    }
+
+    /// Get all the users, in creation order
+    fn users(&self) -> Result<Vec<User>, DbError> {
+        Err(DbError::Unsupported("Multiple users"))
+    }
+
+    /// Record time pushed to an issue tracker, setting its id
+    fn insert_worklog(&self, _worklog: &mut Worklog) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Pushing worklogs"))
+    }
+
+    /// Get the worklogs pushed from the sessions with ids in `[first, last]`,
+    /// see [Worklog::of_sessions]
+    fn session_worklogs(
+        &self,
+        _service: &str,
+        _first: StampId,
+        _last: StampId,
+    ) -> Result<Vec<Worklog>, DbError> {
+        Err(DbError::Unsupported("Pushing worklogs"))
+    }
+
+    /// Insert a new tag of a session, setting its id
+    fn insert_tag(&self, _tag: &mut Tag) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Tagging sessions"))
+    }
+
+    /// Get the tags of the sessions with ids in `[first, last]`
+    fn tag_range(&self, _first: StampId, _last: StampId) -> Result<Vec<Tag>, DbError> {
+        Err(DbError::Unsupported("Tagging sessions"))
+    }
+
+    /// Insert a new reference of a session, setting its id
+    fn insert_reference(&self, _reference: &mut Reference) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Referencing tickets"))
+    }
+
+    /// Get the references of the sessions with ids in `[first, last]`
+    fn reference_range(&self, _first: StampId, _last: StampId) -> Result<Vec<Reference>, DbError> {
+        Err(DbError::Unsupported("Referencing tickets"))
+    }
+
+    /// Get the value of a setting, see [Setting::get]
+    fn setting(&self, _key: &str) -> Result<Option<String>, DbError> {
+        Err(DbError::Unsupported("Settings"))
+    }
+
+    /// Set the value of a setting, see [Setting::set]
+    fn set_setting(&self, _key: &str, _value: &str) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Settings"))
+    }
+
+    /// Record an estimate, replacing the previous one, see [Estimate::save]
+    fn save_estimate(&self, _estimate: &mut Estimate) -> Result<(), DbError> {
+        Err(DbError::Unsupported("Estimates"))
+    }
+
+    /// Remove the estimate of a project or tag, see [Estimate::delete]
+    fn delete_estimate(&self, _tag: bool, _name: &str) -> Result<bool, DbError> {
+        Err(DbError::Unsupported("Estimates"))
+    }
+
+    /// Get all the estimates, see [Estimate::all]
+    fn estimates(&self) -> Result<Vec<Estimate>, DbError> {
+        Err(DbError::Unsupported("Estimates"))
+    }
+
+    /// Get all the projects, see [Project::all]
+    fn projects(&self) -> Result<Vec<Project>, DbError> {
+        Err(DbError::Unsupported("Nested projects"))
+    }
+}
+
+impl Storage for sqlite::Connection {
+    fn insert_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError> {
+        stamp.insert(self)?;
+        Ok(())
+    }
+
+    fn insert_next_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError> {
+        stamp.insert_next(self)?;
+        Ok(())
+    }
+
+    fn insert_stamps(&self, stamps: &mut [Stamp]) -> Result<(), DbError> {
+        Stamp::insert_many(self, stamps)
+    }
+
+    fn transaction<T, E: From<DbError>>(
+        &self,
+        operation: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        db::transaction(self, |_| operation())
+    }
+
+    fn update_stamp(&self, stamp: &mut Stamp) -> Result<(), DbError> {
+        stamp.update(self)?;
+        Ok(())
+    }
+
+    fn get_stamp(&self, id: StampId) -> Result<Stamp, DbError> {
+        Stamp::get(self, id)
+    }
+
+    fn previous_stamp(&self, stamp: &Stamp) -> Result<Option<Stamp>, DbError> {
+        Ok(stamp.previous(self))
+    }
+
+    fn first_stamp(&self) -> Result<Option<Stamp>, DbError> {
+        Ok(Stamp::first(self))
+    }
+
+    fn last_stamp(&self) -> Result<Option<Stamp>, DbError> {
+        Ok(Stamp::last(self))
+    }
+
+    fn stamp_count(&self) -> Result<i64, DbError> {
+        Stamp::count(self)
+    }
+
+    fn schema_version(&self) -> Result<i64, DbError> {
+        db::schema_version(self)
+    }
+
+    fn stamp_range<'a>(
+        &'a self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Stamps<'a>, DbError> {
+        let iter: StampIterator<'a> = Stamp::iter_range(self, from, to)?;
+        Ok(Box::new(iter))
+    }
+
+    fn stamp_range_with_archive<'a>(
+        &'a self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Stamps<'a>, DbError> {
+        Ok(Box::new(Stamp::iter_range_archived(self, from, to)?))
+    }
+
+    fn stamp_total_from(&self, from: &DateTime<Utc>) -> Result<Duration, DbError> {
+        Stamp::total_from(self, from)
+    }
+
+    fn insert_adjustment(&self, adjustment: &mut Adjustment) -> Result<(), DbError> {
+        adjustment.insert(self)?;
+        Ok(())
+    }
+
+    fn adjustment_total_from(&self, from: &DateTime<Utc>) -> Result<Duration, DbError> {
+        Adjustment::total_from(self, from)
+    }
+
+    fn adjustment_range(
+        &self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Adjustment>, DbError> {
+        Adjustment::range(self, from, to)
+    }
+
+    fn insert_closeout(&self, closeout: &mut Closeout) -> Result<(), DbError> {
+        closeout.insert(self)?;
+        Ok(())
+    }
+
+    fn delete_last_closeout(&self) -> Result<Option<Closeout>, DbError> {
+        Closeout::delete_last(self)
+    }
+
+    fn insert_allocation(&self, allocation: &mut Allocation) -> Result<(), DbError> {
+        allocation.insert(self)?;
+        Ok(())
+    }
+
+    fn delete_allocations(&self, session: StampId) -> Result<(), DbError> {
+        Allocation::delete_of_session(self, session)?;
+        Ok(())
+    }
+
+    fn allocation_range(&self, first: StampId, last: StampId) -> Result<Vec<Allocation>, DbError> {
+        Allocation::of_session_range(self, first, last)
+    }
+
+    fn audit_log(&self) -> Result<Vec<Audit>, DbError> {
+        Audit::all(self)
+    }
+
+    fn set_audit_command(&self, command: Option<&str>) -> Result<(), DbError> {
+        Audit::set_command(self, command)
+    }
+
+    fn is_chained(&self) -> Result<bool, DbError> {
+        Stamp::is_chained(self)
+    }
+
+    fn enable_chain(&self) -> Result<(), DbError> {
+        Stamp::enable_chain(self)
+    }
+
+    fn verify_chain(&self) -> Result<Result<usize, StampId>, DbError> {
+        Stamp::verify_chain(self)
+    }
+
+    fn stamps_updated_since(&self, since: Option<&DateTime<Utc>>) -> Result<Vec<Stamp>, DbError> {
+        Stamp::updated_since(self, since)
+    }
+
+    fn search_stamps(&self, filter: &StampFilter) -> Result<Vec<Stamp>, DbError> {
+        Stamp::search(self, filter)
+    }
+
+    fn split_sessions(&self, sessions: &[(Stamp, Vec<DateTime<Utc>>)]) -> Result<usize, DbError> {
+        Stamp::split(self, sessions)
+    }
+
+    fn renumber_stamps(&self) -> Result<usize, DbError> {
+        Stamp::renumber(self)
+    }
+
+    fn merge_stamps(&self, stamps: &[Stamp]) -> Result<Merge, DbError> {
+        sync::merge_sqlite(self, stamps)
+    }
+
+    fn select_user(&self, name: &str) -> Result<User, DbError> {
+        User::select(self, name)
+    }
+
+    fn current_user(&self) -> Result<User, DbError> {
+        User::current(self)
+    }
+
+    fn users(&self) -> Result<Vec<User>, DbError> {
+        User::all(self)
+    }
+
+    fn insert_worklog(&self, worklog: &mut Worklog) -> Result<(), DbError> {
+        worklog.insert(self)?;
+        Ok(())
+    }
+
+    fn session_worklogs(
+        &self,
+        service: &str,
+        first: StampId,
+        last: StampId,
+    ) -> Result<Vec<Worklog>, DbError> {
+        Worklog::of_sessions(self, service, first, last)
+    }
+
+    fn insert_tag(&self, tag: &mut Tag) -> Result<(), DbError> {
+        tag.insert(self)?;
+        Ok(())
+    }
+
+    fn tag_range(&self, first: StampId, last: StampId) -> Result<Vec<Tag>, DbError> {
+        Tag::of_session_range(self, first, last)
+    }
+
+    fn insert_reference(&self, reference: &mut Reference) -> Result<(), DbError> {
+        reference.insert(self)?;
+        Ok(())
+    }
+
+    fn reference_range(&self, first: StampId, last: StampId) -> Result<Vec<Reference>, DbError> {
+        Reference::of_session_range(self, first, last)
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>, DbError> {
+        Setting::get(self, key)
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> Result<(), DbError> {
+        Setting::set(self, key, value)
+    }
+
+    fn save_estimate(&self, estimate: &mut Estimate) -> Result<(), DbError> {
+        estimate.save(self)?;
+        Ok(())
+    }
+
+    fn delete_estimate(&self, tag: bool, name: &str) -> Result<bool, DbError> {
+        Estimate::delete(self, tag, name)
+    }
+
+    fn estimates(&self) -> Result<Vec<Estimate>, DbError> {
+        Estimate::all(self)
+    }
+
+    fn projects(&self) -> Result<Vec<Project>, DbError> {
+        Project::all(self)
+    }
+}
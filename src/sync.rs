@@ -0,0 +1,337 @@
+//! Synchronization of the work-log between devices
+//!
+//! Every stamp bears a globally unique uid, so that the stamps of a device
+//! can be exported to a file and merged into the work-log of another device
+//! without being duplicated. Merging both ways gives the same work-log on
+//! both devices:
+//!
+//! * Stamps with an unknown uid are added, then all the stamps are
+//!   renumbered in date order (see [Stamp::renumber]).
+//! * Stamps with a known uid but a different date or direction were edited
+//!   on one device: the most recently updated version wins, the content
+//!   breaking ties.
+//!
+//! Instead of files, the bundles can be exchanged with `wtime serve` (see
+//! [crate::serve]): only the stamps updated since the previous
+//! synchronization are then pulled with [pull] and pushed with [push].
+
+use crate::db::{self, DbError, Stamp, StampId};
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Version of the [Bundle] format
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Type for errors while synchronizing
+#[derive(Error, Debug)]
+pub enum SyncError {
+    /// Error of the database
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    /// Bundle was made by another version of wtime
+    #[error("Unsupported bundle version {0}, expected {BUNDLE_VERSION}")]
+    UnsupportedVersion(u32),
+
+    /// Bundle stamp has no uid, so it can not be matched
+    #[error("Stamp {0} of the bundle has no uid")]
+    MissingUid(StampId),
+
+    /// Remote server could not be reached or refused the request
+    #[error("Remote: {0}")]
+    Remote(String),
+}
+
+/// Stamps exchanged between devices
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bundle {
+    /// Format version, see [BUNDLE_VERSION]
+    pub version: u32,
+    /// The current (not archived) stamps, in id order
+    pub stamps: Vec<Stamp>,
+}
+
+/// Stamps pulled from a server, see [pull]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pull {
+    /// Server time before reading the stamps, to pull from next time
+    pub cursor: DateTime<Utc>,
+    /// Stamps updated since the requested cursor
+    pub bundle: Bundle,
+}
+
+/// Outcome of a [merge]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Merge {
+    /// Stamps added to the work-log
+    pub added: usize,
+    /// Stamps replaced by a more recently updated version
+    pub updated: usize,
+    /// Stamps already in the work-log
+    pub known: usize,
+    /// Stamps whose id changed to keep the work-log in date order
+    pub renumbered: usize,
+}
+
+/// Get the stamps to send to another device
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `since` - Only export the stamps updated at or after this date, all if None
+pub fn export<S: Storage + ?Sized>(
+    storage: &S,
+    since: Option<&DateTime<Utc>>,
+) -> Result<Bundle, SyncError> {
+    Ok(Bundle {
+        version: BUNDLE_VERSION,
+        stamps: storage.stamps_updated_since(since)?,
+    })
+}
+
+/// Tell which of two versions of a stamp wins, the same on every device
+fn compare_versions(a: &Stamp, b: &Stamp) -> Ordering {
+    let key = |s: &Stamp| (s.updated_at, s.date, s.in_out.to_string());
+    key(a).cmp(&key(b))
+}
+
+/// Merge the stamps received from another device
+///
+/// The merge is atomic: on error, the work-log is left unchanged.
+///
+/// # Arguments
+///
+/// * `storage` - Work-log storage backend
+/// * `bundle` - Stamps exported by the other device
+pub fn merge<S: Storage + ?Sized>(storage: &S, bundle: &Bundle) -> Result<Merge, SyncError> {
+    if bundle.version != BUNDLE_VERSION {
+        return Err(SyncError::UnsupportedVersion(bundle.version));
+    }
+    if let Some(stamp) = bundle.stamps.iter().find(|s| s.uid.is_none()) {
+        return Err(SyncError::MissingUid(stamp.id));
+    }
+
+    Ok(storage.merge_stamps(&bundle.stamps)?)
+}
+
+/// Merge stamps into a sqlite work-log, in a transaction
+///
+/// Stamps without uid are taken as new ones.
+///
+/// # Arguments
+///
+/// * `conn` - reference to a open SQLITE database connection
+/// * `stamps` - Stamps exported by the other device
+pub(crate) fn merge_sqlite(conn: &sqlite::Connection, stamps: &[Stamp]) -> Result<Merge, DbError> {
+    db::transaction(conn, |tx| merge_stamps(tx, stamps))
+}
+
+fn merge_stamps(conn: &sqlite::Connection, stamps: &[Stamp]) -> Result<Merge, DbError> {
+    let mut merge = Merge::default();
+    let archived_until = Stamp::archived_until(conn)?;
+
+    for remote in stamps {
+        let uid = remote.uid.unwrap_or_else(Uuid::new_v4);
+
+        match Stamp::get_by_uid(conn, &uid, false)? {
+            Some(local) if local.date == remote.date && local.in_out == remote.in_out => {
+                merge.known += 1;
+            }
+            Some(mut local) => {
+                if compare_versions(remote, &local) == Ordering::Greater {
+                    local.date = remote.date;
+                    local.in_out = remote.in_out;
+                    local.update(conn)?;
+                    merge.updated += 1;
+                } else {
+                    merge.known += 1;
+                }
+            }
+            None if Stamp::get_by_uid(conn, &uid, true)?.is_some() => merge.known += 1,
+            None => {
+                if let Some(until) = archived_until.filter(|until| remote.date <= *until) {
+                    return Err(DbError::ArchivedPeriod(until));
+                }
+
+                let mut stamp = Stamp::new(StampId::default(), remote.date, remote.in_out);
+                stamp.uid = Some(uid);
+                stamp.insert(conn)?;
+                merge.added += 1;
+            }
+        }
+    }
+
+    if merge.added > 0 || merge.updated > 0 {
+        merge.renumbered = Stamp::renumber(conn)?;
+    }
+    Ok(merge)
+}
+
+/// Client of the sync endpoint of a wtime server
+fn agent() -> ureq::Agent {
+    // Error answers carry a message
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into()
+}
+
+/// Get the result of a request, or the error message of the server
+fn answer<T: for<'de> Deserialize<'de>>(
+    response: Result<ureq::http::Response<ureq::Body>, ureq::Error>,
+) -> Result<T, SyncError> {
+    let remote = |e: ureq::Error| SyncError::Remote(e.to_string());
+    let mut response = response.map_err(remote)?;
+
+    if response.status().is_success() {
+        return response.body_mut().read_json().map_err(remote);
+    }
+    let message = response
+        .body_mut()
+        .read_json::<serde_json::Value>()
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or_else(|| response.status().to_string());
+    Err(SyncError::Remote(message))
+}
+
+/// Get the stamps updated on a server since the previous pull
+///
+/// # Arguments
+///
+/// * `url` - Base URL of the server, e.g. `https://host:8080`
+/// * `token` - API token of the server
+/// * `since` - Cursor returned by the previous pull, None to get all the stamps
+pub fn pull(url: &str, token: &str, since: Option<&DateTime<Utc>>) -> Result<Pull, SyncError> {
+    let mut request = agent()
+        .get(format!("{}/sync", url.trim_end_matches('/')))
+        .header("Authorization", format!("Bearer {}", token));
+    if let Some(since) = since {
+        request = request.query("since", since.timestamp().to_string());
+    }
+
+    answer(request.call())
+}
+
+/// Merge stamps into the work-log of a server
+///
+/// # Arguments
+///
+/// * `url` - Base URL of the server, e.g. `https://host:8080`
+/// * `token` - API token of the server
+/// * `bundle` - Stamps updated since the previous push, see [export]
+pub fn push(url: &str, token: &str, bundle: &Bundle) -> Result<Merge, SyncError> {
+    answer(
+        agent()
+            .post(format!("{}/sync", url.trim_end_matches('/')))
+            .header("Authorization", format!("Bearer {}", token))
+            .send_json(bundle),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, merge, Merge, SyncError};
+    use crate::db::{self, InOut, Stamp, StampId};
+    use crate::report;
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+
+    fn stamp(conn: &sqlite::Connection, date: &str, in_out: InOut) -> Stamp {
+        let mut stamp = Stamp::new(
+            StampId::default(),
+            DateTime::<Utc>::from_str(date).unwrap(),
+            in_out,
+        );
+        stamp.insert(conn).unwrap();
+        stamp
+    }
+
+    #[test]
+    fn merge_both_ways() {
+        let laptop = db::open_memory().unwrap();
+        db::init(&laptop).unwrap();
+        let desktop = db::open_memory().unwrap();
+        db::init(&desktop).unwrap();
+
+        stamp(&laptop, "2020-01-01T08:00:00Z", InOut::In);
+        stamp(&laptop, "2020-01-01T12:00:00Z", InOut::Out);
+        stamp(&desktop, "2020-01-01T13:00:00Z", InOut::In);
+        stamp(&desktop, "2020-01-01T17:00:00Z", InOut::Out);
+        stamp(&laptop, "2020-01-02T08:00:00Z", InOut::In);
+        stamp(&laptop, "2020-01-02T09:00:00Z", InOut::Out);
+
+        let outcome = merge(&laptop, &export(&desktop, None).unwrap()).unwrap();
+        assert_eq!(
+            outcome,
+            Merge {
+                added: 2,
+                updated: 0,
+                known: 0,
+                renumbered: 4
+            }
+        );
+        merge(&desktop, &export(&laptop, None).unwrap()).unwrap();
+
+        // Same stamps in the same order on both devices
+        let laptop_stamps = export(&laptop, None).unwrap().stamps;
+        let desktop_stamps = export(&desktop, None).unwrap().stamps;
+        let content =
+            |stamps: &[Stamp]| -> Vec<_> { stamps.iter().map(|s| (s.id, s.uid, s.date)).collect() };
+        assert_eq!(content(&laptop_stamps), content(&desktop_stamps));
+
+        let from = DateTime::<Utc>::from_str("2020-01-01T00:00:00Z").unwrap();
+        let sessions = report::sessions(&laptop, &from, &(from + Duration::days(2))).unwrap();
+        assert_eq!(sessions.len(), 3);
+
+        // Merging again changes nothing
+        let outcome = merge(&laptop, &export(&desktop, None).unwrap()).unwrap();
+        assert_eq!(outcome.known, 6);
+        assert_eq!(outcome.added + outcome.updated, 0);
+    }
+
+    #[test]
+    fn conflicts() {
+        let laptop = db::open_memory().unwrap();
+        db::init(&laptop).unwrap();
+        let desktop = db::open_memory().unwrap();
+        db::init(&desktop).unwrap();
+
+        stamp(&laptop, "2020-01-01T08:00:00Z", InOut::In);
+        merge(&desktop, &export(&laptop, None).unwrap()).unwrap();
+
+        // Edited later on the desktop
+        let mut edited = export(&desktop, None).unwrap();
+        edited.stamps[0].date += Duration::minutes(30);
+        edited.stamps[0].updated_at = edited.stamps[0].updated_at.map(|d| d + Duration::hours(1));
+        let outcome = merge(&laptop, &edited).unwrap();
+        assert_eq!(outcome.updated, 1);
+        assert_eq!(
+            Stamp::get(&laptop, StampId(1)).unwrap().date,
+            DateTime::<Utc>::from_str("2020-01-01T08:30:00Z").unwrap()
+        );
+
+        // An older version is ignored
+        let mut stale = export(&desktop, None).unwrap();
+        stale.stamps[0].date -= Duration::minutes(30);
+        stale.stamps[0].updated_at = None;
+        assert_eq!(merge(&laptop, &stale).unwrap().known, 1);
+
+        let mut invalid = export(&desktop, None).unwrap();
+        invalid.version = 0;
+        assert!(matches!(
+            merge(&laptop, &invalid),
+            Err(SyncError::UnsupportedVersion(0))
+        ));
+        invalid.version = super::BUNDLE_VERSION;
+        invalid.stamps[0].uid = None;
+        assert!(matches!(
+            merge(&laptop, &invalid),
+            Err(SyncError::MissingUid(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+//! Taskwarrior hook
+//!
+//! `wtime hook taskwarrior` is installed as an `on-modify` (and `on-add`)
+//! hook of Taskwarrior, which gives it the task before and after the
+//! modification as JSON lines on its standard input. Starting a task checks
+//! in, stopping or completing it checks out and allocates the session to the
+//! project of the task. The modified task is echoed back unchanged, as
+//! Taskwarrior expects.
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// Type for errors while reading the hook input
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TaskwarriorError {
+    /// Input lacks the modified task
+    #[error("No task given on the standard input")]
+    MissingTask,
+
+    /// A line is not a JSON task
+    #[error("Invalid task: {0}")]
+    InvalidTask(String),
+}
+
+/// Task the hook is called for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    /// Unique ID of the task
+    pub uuid: String,
+    /// Project of the task, if any
+    pub project: Option<String>,
+    /// Description of the task
+    pub description: String,
+}
+
+/// What the modification means for the work-log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Task was started
+    Started(Task),
+    /// Task was stopped, completed or deleted while started
+    Stopped(Task),
+    /// Task modification unrelated to time tracking
+    Other,
+}
+
+/// Read a task line
+fn task(line: &str) -> Result<Value, TaskwarriorError> {
+    let task: Value =
+        serde_json::from_str(line).map_err(|e| TaskwarriorError::InvalidTask(e.to_string()))?;
+    if !task.is_object() {
+        return Err(TaskwarriorError::InvalidTask(line.to_string()));
+    }
+    Ok(task)
+}
+
+/// Tell how a task was modified
+///
+/// # Arguments
+///
+/// * `input` - Standard input of the hook: the original task then the
+///   modified one for `on-modify`, only the added task for `on-add`
+///
+/// # Return
+///
+/// The change and the modified task line, to write back to Taskwarrior.
+pub fn change(input: &str) -> Result<(Change, String), TaskwarriorError> {
+    let lines: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+    let (original, modified) = match lines[..] {
+        [added] => (None, added),
+        [original, modified, ..] => (Some(task(original)?), modified),
+        [] => return Err(TaskwarriorError::MissingTask),
+    };
+    let task_value = task(modified)?;
+
+    let started = |task: Option<&Value>| task.is_some_and(|t| t.get("start").is_some());
+    let task = Task {
+        uuid: task_value["uuid"].as_str().unwrap_or_default().to_string(),
+        project: task_value["project"].as_str().map(str::to_string),
+        description: task_value["description"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let change = match (started(original.as_ref()), started(Some(&task_value))) {
+        (false, true) => Change::Started(task),
+        (true, false) => Change::Stopped(task),
+        _ => Change::Other,
+    };
+
+    Ok((change, modified.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{change, Change, TaskwarriorError};
+
+    const PENDING: &str =
+        r#"{"uuid":"a1","description":"Write docs","project":"wtime","status":"pending"}"#;
+    const STARTED: &str = r#"{"uuid":"a1","description":"Write docs","project":"wtime","status":"pending","start":"20200101T080000Z"}"#;
+    const DONE: &str = r#"{"uuid":"a1","description":"Write docs","project":"wtime","status":"completed","end":"20200101T120000Z"}"#;
+
+    #[test]
+    fn changes() {
+        let (started, line) = change(&format!("{}\n{}\n", PENDING, STARTED)).unwrap();
+        assert_eq!(line, STARTED);
+        match started {
+            Change::Started(task) => {
+                assert_eq!(task.uuid, "a1");
+                assert_eq!(task.project.as_deref(), Some("wtime"));
+            }
+            other => panic!("Expected a start, got {:?}", other),
+        }
+
+        assert!(matches!(
+            change(&format!("{}\n{}\n", STARTED, DONE)).unwrap().0,
+            Change::Stopped(_)
+        ));
+        assert_eq!(
+            change(&format!("{}\n{}\n", PENDING, DONE)).unwrap().0,
+            Change::Other
+        );
+        // Added already started
+        assert!(matches!(change(STARTED).unwrap().0, Change::Started(_)));
+
+        assert_eq!(change("\n"), Err(TaskwarriorError::MissingTask));
+        assert!(matches!(
+            change("[1, 2]"),
+            Err(TaskwarriorError::InvalidTask(_))
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+//! Monthly timesheet to sign off
+//!
+//! `wtime timesheet --month 2024-05` writes a one page A4 PDF, with the name
+//! of the person, the period, the worked hours of every day of the month,
+//! their total and signature lines for the employee and the manager.
+
+use crate::export::ParseFormatError;
+use crate::format;
+use chrono::{Datelike, Duration, NaiveDate};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Output format of a timesheet
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// Printable PDF document
+    Pdf,
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pdf" => Ok(Self::Pdf),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+/// Width of an A4 page, in points
+const PAGE_WIDTH: f32 = 595.0;
+/// Height of an A4 page, in points
+const PAGE_HEIGHT: f32 = 842.0;
+/// Left margin and start of the first column
+const LEFT: f32 = 60.0;
+/// Height of a line of the table
+const ROW: f32 = 14.0;
+
+/// Encode text for the WinAnsi encoding of the standard fonts
+///
+/// Characters out of Latin-1 are replaced by `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
+
+/// Write a line of text
+fn text(content: &mut Content, font: Name, size: f32, x: f32, y: f32, text: &str) {
+    content
+        .begin_text()
+        .set_font(font, size)
+        .next_line(x, y)
+        .show(Str(&win_ansi(text)))
+        .end_text();
+}
+
+/// Draw a horizontal line
+fn rule(content: &mut Content, from: f32, to: f32, y: f32) {
+    content.move_to(from, y).line_to(to, y).stroke();
+}
+
+/// Render the timesheet of a month as a PDF document
+///
+/// # Arguments
+///
+/// * `user` - Name of the person the timesheet is about
+/// * `month` - First day of the month
+/// * `days` - Worked time per day, days without work may be missing
+pub fn pdf(user: &str, month: NaiveDate, days: &BTreeMap<NaiveDate, Duration>) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let regular_id = Ref::new(4);
+    let bold_id = Ref::new(5);
+    let content_id = Ref::new(6);
+    let info_id = Ref::new(7);
+    let regular = Name(b"F1");
+    let bold = Name(b"F2");
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+        .parent(page_tree_id)
+        .contents(content_id);
+    page.resources()
+        .fonts()
+        .pair(regular, regular_id)
+        .pair(bold, bold_id);
+    page.finish();
+    for (id, font) in [(regular_id, "Helvetica"), (bold_id, "Helvetica-Bold")] {
+        pdf.type1_font(id)
+            .base_font(Name(font.as_bytes()))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    let period = month.format("%B %Y").to_string();
+    pdf.document_info(info_id)
+        .title(TextStr(&format!("Timesheet of {}, {}", user, period)))
+        .creator(TextStr(env!("CARGO_PKG_NAME")));
+
+    let mut content = Content::new();
+    let mut y = PAGE_HEIGHT - 70.0;
+    text(&mut content, bold, 20.0, LEFT, y, "Timesheet");
+    y -= 30.0;
+    text(
+        &mut content,
+        regular,
+        11.0,
+        LEFT,
+        y,
+        &format!("Name: {}", user),
+    );
+    y -= 16.0;
+    text(
+        &mut content,
+        regular,
+        11.0,
+        LEFT,
+        y,
+        &format!("Period: {}", period),
+    );
+
+    y -= 34.0;
+    text(&mut content, bold, 10.0, LEFT, y, "Date");
+    text(&mut content, bold, 10.0, LEFT + 120.0, y, "Day");
+    text(&mut content, bold, 10.0, LEFT + 240.0, y, "Hours");
+    content.set_line_width(0.5);
+    rule(&mut content, LEFT, PAGE_WIDTH - LEFT, y - 4.0);
+
+    let mut total = Duration::zero();
+    for day in month
+        .iter_days()
+        .take_while(|day| day.month() == month.month())
+    {
+        y -= ROW;
+        let worked = days.get(&day).copied().unwrap_or_else(Duration::zero);
+        total = total + worked;
+        text(
+            &mut content,
+            regular,
+            10.0,
+            LEFT,
+            y,
+            &day.format("%Y-%m-%d").to_string(),
+        );
+        text(
+            &mut content,
+            regular,
+            10.0,
+            LEFT + 120.0,
+            y,
+            &day.format("%A").to_string(),
+        );
+        let worked = if worked.is_zero() {
+            "-".to_string()
+        } else {
+            format::hours(&worked)
+        };
+        text(&mut content, regular, 10.0, LEFT + 240.0, y, &worked);
+    }
+
+    rule(&mut content, LEFT, PAGE_WIDTH - LEFT, y - 6.0);
+    y -= ROW + 6.0;
+    text(&mut content, bold, 10.0, LEFT, y, "Total");
+    text(
+        &mut content,
+        bold,
+        10.0,
+        LEFT + 240.0,
+        y,
+        &format::hours(&total),
+    );
+
+    // Signature lines, at the bottom of the page
+    let middle = PAGE_WIDTH / 2.0;
+    for (x, label) in [(LEFT, "Employee"), (middle + 20.0, "Manager")] {
+        rule(&mut content, x, x + middle - LEFT - 20.0, 130.0);
+        text(
+            &mut content,
+            regular,
+            9.0,
+            x,
+            118.0,
+            &format!("{}, date and signature", label),
+        );
+    }
+    pdf.stream(content_id, &content.finish());
+
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pdf, Format};
+    use chrono::{Duration, NaiveDate};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn month() {
+        let month = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let days = BTreeMap::from([
+            (
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                Duration::hours(8),
+            ),
+            (
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                Duration::minutes(450),
+            ),
+        ]);
+
+        let document = pdf("Zoë", month, &days);
+        assert!(document.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&document);
+        // Content is not compressed
+        assert!(text.contains("(Period: February 2024)"));
+        assert!(text.contains("(2024-02-29)"));
+        assert!(!text.contains("(2024-03-01)"));
+        assert!(text.contains("(7.50)"));
+        assert!(text.contains("(15.50)"));
+        assert!(text.contains("(Manager, date and signature)"));
+        // Latin-1 name in WinAnsi, as hexadecimal string
+        let name: String = "Name: Zo\u{eb}"
+            .chars()
+            .map(|c| format!("{:02X}", c as u8))
+            .collect();
+        assert!(text.to_uppercase().contains(&format!("<{}>", name)));
+
+        assert_eq!(Format::from_str("PDF"), Ok(Format::Pdf));
+        assert!(Format::from_str("docx").is_err());
+    }
+}
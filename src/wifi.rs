@@ -0,0 +1,223 @@
+//! Wi-Fi based check-in and check-out
+//!
+//! `wtime daemon` watches the Wi-Fi network the computer is connected to and
+//! applies the `[[wifi]]` rules of the configuration:
+//!
+//! ```toml
+//! [[wifi]]
+//! ssid = "OfficeNet"
+//! checkout_after = "10m"
+//! project = "acme"
+//! ```
+//!
+//! Connecting to the network checks in (allocated to the project, if any),
+//! being disconnected from it for `checkout_after` checks out. Reading the
+//! network needs the `wifi` cargo feature, and NetworkManager or the
+//! wireless tools on Linux, `netsh` on Windows.
+
+use crate::config::WifiRule;
+use chrono::{DateTime, Utc};
+
+/// Stamp to make, as decided by a [Watcher]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Check-in, allocated to a project if any
+    CheckIn(Option<String>),
+    /// Check-out
+    CheckOut,
+}
+
+/// State of the watched network, turning its changes into [Action]s
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    /// Rules of the networks to watch
+    rules: Vec<WifiRule>,
+    /// Index of the rule of the network connected to
+    connected: Option<usize>,
+    /// Since when the network of the rule is not seen anymore
+    lost_since: Option<DateTime<Utc>>,
+}
+
+impl Watcher {
+    /// Create a watcher, as if not connected
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - Rules of the networks to watch
+    pub fn new(rules: Vec<WifiRule>) -> Self {
+        Self {
+            rules,
+            connected: None,
+            lost_since: None,
+        }
+    }
+
+    /// Take a new reading of the network into account
+    ///
+    /// Only the changes of network lead to an action, so that stamps made
+    /// by hand in between are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `ssid` - Network connected to, None if disconnected
+    /// * `checked_in` - Whether the work-log is checked in
+    /// * `now` - Date/time of the reading
+    pub fn update(
+        &mut self,
+        ssid: Option<&str>,
+        checked_in: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Action> {
+        let matched = ssid.and_then(|ssid| self.rules.iter().position(|r| r.ssid == ssid));
+
+        match (self.connected, matched) {
+            (_, Some(rule)) => {
+                let newly = self.connected.is_none();
+                self.connected = Some(rule);
+                self.lost_since = None;
+                (newly && !checked_in).then(|| Action::CheckIn(self.rules[rule].project.clone()))
+            }
+            (Some(rule), None) => {
+                let lost_since = *self.lost_since.get_or_insert(now);
+                if now - lost_since < self.rules[rule].checkout_after {
+                    return None;
+                }
+                self.connected = None;
+                self.lost_since = None;
+                checked_in.then_some(Action::CheckOut)
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// Read the network name out of `nmcli -t -f active,ssid dev wifi`
+///
+/// # Arguments
+///
+/// * `output` - Output of nmcli, a `yes:` or `no:` line per network seen
+pub fn parse_nmcli(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("yes:"))
+        // Colons of the name are escaped
+        .map(|ssid| ssid.replace("\\:", ":"))
+        .filter(|ssid| !ssid.is_empty())
+}
+
+/// Read the network name out of `netsh wlan show interfaces`
+///
+/// # Arguments
+///
+/// * `output` - Output of netsh, a `Name : value` line per property
+pub fn parse_netsh(output: &str) -> Option<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        // Not the BSSID, i.e. the access point address
+        .find(|(name, _)| name.trim() == "SSID")
+        .map(|(_, ssid)| ssid.trim().to_string())
+        .filter(|ssid| !ssid.is_empty())
+}
+
+/// Get the name of the Wi-Fi network connected to
+///
+/// NetworkManager is asked first, then the wireless tools. Windows is asked
+/// with netsh.
+#[cfg(feature = "wifi")]
+pub fn current_ssid() -> anyhow::Result<Option<String>> {
+    use anyhow::anyhow;
+    use std::process::Command;
+
+    if cfg!(windows) {
+        let output = Command::new("netsh")
+            .args(["wlan", "show", "interfaces"])
+            .output()
+            .map_err(|e| anyhow!("netsh cannot be run: {}", e))?;
+        return Ok(parse_netsh(&String::from_utf8_lossy(&output.stdout)));
+    }
+
+    if let Ok(output) = Command::new("nmcli")
+        .args(["-t", "-f", "active,ssid", "dev", "wifi"])
+        .output()
+    {
+        if output.status.success() {
+            return Ok(parse_nmcli(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+
+    // Fails when not connected
+    let output = Command::new("iwgetid")
+        .arg("-r")
+        .output()
+        .map_err(|e| anyhow!("Neither nmcli nor iwgetid can be run: {}", e))?;
+    let ssid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(ssid).filter(|ssid| output.status.success() && !ssid.is_empty()))
+}
+
+/// Get the name of the Wi-Fi network connected to, which this build cannot
+#[cfg(not(feature = "wifi"))]
+pub fn current_ssid() -> anyhow::Result<Option<String>> {
+    Err(anyhow::anyhow!("This build lacks the `wifi` feature"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_netsh, parse_nmcli, Action, Watcher};
+    use crate::config::WifiRule;
+    use chrono::{DateTime, Duration, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn office_rule() {
+        let mut watcher = Watcher::new(vec![WifiRule {
+            ssid: "OfficeNet".into(),
+            checkout_after: Duration::minutes(10),
+            project: Some("acme".into()),
+        }]);
+        let start = DateTime::<Utc>::from_str("2020-01-01T08:00:00Z").unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+
+        assert_eq!(watcher.update(Some("HomeNet"), false, at(0)), None);
+        assert_eq!(
+            watcher.update(Some("OfficeNet"), false, at(1)),
+            Some(Action::CheckIn(Some("acme".into())))
+        );
+        assert_eq!(watcher.update(Some("OfficeNet"), true, at(2)), None);
+
+        // Short disconnections are ignored
+        assert_eq!(watcher.update(None, true, at(3)), None);
+        assert_eq!(watcher.update(Some("OfficeNet"), true, at(5)), None);
+        assert_eq!(watcher.update(None, true, at(6)), None);
+        assert_eq!(watcher.update(None, true, at(15)), None);
+        assert_eq!(watcher.update(None, true, at(16)), Some(Action::CheckOut));
+        assert_eq!(watcher.update(None, false, at(30)), None);
+
+        // Already checked in by hand
+        assert_eq!(watcher.update(Some("OfficeNet"), true, at(40)), None);
+    }
+
+    #[test]
+    fn nmcli() {
+        assert_eq!(
+            parse_nmcli("no:HomeNet\nyes:Office\\:Net\n").as_deref(),
+            Some("Office:Net")
+        );
+        assert_eq!(parse_nmcli("no:HomeNet\n"), None);
+    }
+
+    #[test]
+    fn netsh() {
+        let output = "
+There is 1 interface on the system:
+
+    Name                   : Wi-Fi
+    State                  : connected
+    SSID                   : Office Net
+    BSSID                  : 00:11:22:33:44:55
+    Network type           : Infrastructure
+";
+        assert_eq!(parse_netsh(output).as_deref(), Some("Office Net"));
+        assert_eq!(parse_netsh("    State : disconnected\n"), None);
+    }
+}
@@ -1,84 +1,239 @@
 use assert_cmd::*;
-use std::fs;
+use predicates::prelude::PredicateBooleanExt;
+use tempfile::{tempdir, TempDir};
 
-const TEST_FILE: &str = "test.sqlite";
-fn teardown() {
-    fs::remove_file(TEST_FILE).unwrap();
+/// Run the program in `dir`, where debug builds open `test.sqlite` and `test.toml`
+fn wtime(dir: &TempDir) -> Command {
+    let mut command = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    command.current_dir(dir.path());
+    command
 }
+
 #[test]
 fn test_default() {
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
+    let dir = tempdir().unwrap();
+    wtime(&dir).assert().success();
+}
+
+#[test]
+fn test_checkin_checkout() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir).arg("checkout").assert().success();
+}
+
+#[test]
+fn test_double_checkin() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir).arg("checkin").assert().failure();
+}
+
+#[test]
+fn test_first_checkout() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkout").assert().success();
+}
+
+#[test]
+fn test_double_checkout() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir).arg("checkout").assert().success();
+
+    wtime(&dir).arg("checkout").assert().failure();
+}
+
+#[test]
+fn test_adjust() {
+    let dir = tempdir().unwrap();
+    wtime(&dir)
+        .args(["adjust", "-30", "--reason", "Lunch not stamped"])
         .assert()
         .success();
 
-    teardown();
+    wtime(&dir).args(["adjust", "30"]).assert().failure();
 }
 
 #[test]
-fn test_checkin_checkout() {
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkin")
+fn test_chain() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).args(["verify", "--chain"]).assert().failure();
+
+    wtime(&dir).args(["chain", "enable"]).assert().success();
+
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir).args(["verify", "--chain"]).assert().success();
+}
+
+#[test]
+fn test_checkout_split() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir)
+        .args(["checkout", "--split", "60% PROJ-1, 40% PROJ-7"])
+        .env("LC_ALL", "C")
         .assert()
-        .success();
+        .success()
+        .stdout(predicates::str::contains("to PROJ-7 (40%)"));
+}
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkout")
+#[test]
+fn test_report() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir)
+        .args(["checkout", "--split", "100% PROJ-1"])
         .assert()
         .success();
 
-    teardown();
+    wtime(&dir)
+        .args(["report", "--by-allocation"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Total"));
 }
 
 #[test]
-fn test_double_checkin() {
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
+fn test_language() {
+    let dir = tempdir().unwrap();
+    wtime(&dir)
         .arg("checkin")
+        .env("LC_ALL", "fr_CH.UTF-8")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Arrivée à"));
+}
+
+#[test]
+fn test_decimal_hours() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    wtime(&dir)
+        .args(["checkout", "--split", "100% PROJ-1"])
         .assert()
         .success();
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkin")
+    wtime(&dir)
+        .args(["report", "--decimal-hours=3"])
+        .env("LC_ALL", "C")
         .assert()
-        .failure();
+        .success()
+        .stdout(predicates::str::contains("0.000h"));
 
-    teardown();
+    wtime(&dir)
+        .args(["export", "--format", "harvest", "--decimal-hours=1"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(",0.0,"));
 }
 
 #[test]
-fn test_first_checkout() {
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkout")
+fn test_since_last_export() {
+    let dir = tempdir().unwrap();
+    wtime(&dir)
+        .arg("import")
+        .write_stdin(r#"[{"start": "2020-01-06 08:00:00", "end": "2020-01-06 12:00:00"}]"#)
         .assert()
         .success();
 
-    teardown();
+    wtime(&dir)
+        .args(["export", "--format", "harvest", "--since-last-export"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2020-01-06,"));
+
+    // Nothing new since
+    wtime(&dir)
+        .args(["export", "--format", "harvest", "--since-last-export"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2020-01-06,").not());
+
+    // Every format has its own cursor
+    wtime(&dir)
+        .args(["export", "--format", "kimai", "--since-last-export"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2020-01-06"));
 }
 
 #[test]
-fn test_double_checkout() {
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkin")
+fn test_estimate() {
+    let dir = tempdir().unwrap();
+    wtime(&dir)
+        .arg("import")
+        .write_stdin(
+            r#"[{"start": "2020-01-06 08:00:00", "end": "2020-01-06 12:00:00", "project": "acme"}]"#,
+        )
         .assert()
         .success();
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkout")
+    wtime(&dir)
+        .args(["estimate", "acme", "3h"])
+        .env("LC_ALL", "C")
         .assert()
-        .success();
+        .success()
+        .stdout(predicates::str::contains("Estimated acme at 3h 00m"));
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg("checkout")
+    wtime(&dir)
+        .arg("estimate")
+        .env("LC_ALL", "C")
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"acme\s+3h 00m\s+4h 00m\s+\+1h 00m").unwrap());
+
+    wtime(&dir)
+        .args(["estimate", "--remove", "--tag", "acme"])
         .assert()
         .failure();
+}
+
+#[test]
+fn test_info() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
 
-    teardown();
+    wtime(&dir)
+        .arg("info")
+        .env("LC_ALL", "fr_CH.UTF-8")
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::is_match(r"base de données:\s+test.sqlite")
+                .unwrap()
+                .and(predicates::str::is_match(r"version du schéma:\s+\d+").unwrap())
+                .and(predicates::str::is_match(r"pointages:\s+1\n").unwrap())
+                .and(predicates::str::is_match(r"langue:\s+French").unwrap())
+                .and(predicates::str::is_match(r"api_token:\s+non défini").unwrap()),
+        );
+}
+
+#[test]
+fn test_audit_export() {
+    let dir = tempdir().unwrap();
+    wtime(&dir).arg("checkin").assert().success();
+
+    // The day given with --to is included
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    wtime(&dir)
+        .args(["audit", "export", "--to", &today, "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Insert,Stamp"));
+
+    // The hashes stored when writing still match
+    wtime(&dir)
+        .args(["audit", "verify"])
+        .env("LC_ALL", "C")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Audit log is valid (1 records)"));
 }